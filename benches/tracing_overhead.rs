@@ -0,0 +1,186 @@
+//! Cost of the tracing pipeline around a tool call.
+//!
+//! - `tool_dispatch`: a full `tools/call` round trip over an in-memory MCP
+//!   transport, with no subscriber, with a bare registry, and with the
+//!   OpenTelemetry layer installed (spans are recorded but not exported).
+//! - `io_capture`: recording tool input and output as span attributes, by
+//!   hand with `trace_rmcp_setup`/`trace_rmcp_result` and through the
+//!   `#[trace_io]` macro, against plain argument/result handling.
+//!
+//! Run with `cargo bench --bench tracing_overhead`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use rmcp::handler::server::wrapper::Parameters;
+use rmcp::model::{CallToolRequestParam, CallToolResult};
+use rmcp::service::RunningService;
+use rmcp::{ErrorData as McpError, RoleClient, ServiceExt};
+use rmcp_trace_macro::trace_io;
+use serde_json::json;
+use tokio::runtime::Runtime;
+use tracing::{field::Empty, Dispatch, Instrument};
+use tracing_subscriber::layer::SubscriberExt;
+
+use rmcp_demo::app_state::AppState;
+use rmcp_demo::config::Config;
+use rmcp_demo::trace_utils::{trace_rmcp_result, trace_rmcp_setup};
+use rmcp_demo::weather_tools::{
+    Forecast, ForecastResponse, GetForecastArgs, Units, WeatherService,
+};
+
+/// What `#[trace_io]` expands against: the server's helpers, except the
+/// request context, which an in-memory call has none of.
+mod trace_utils {
+    pub use rmcp_demo::trace_utils::{is_recorded, InputArgs, MaskNothing, MaskSensitive};
+
+    pub fn call_trace_context<C>(_: &C) -> Option<opentelemetry::Context> {
+        None
+    }
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime")
+}
+
+fn subscribers() -> Vec<(&'static str, Option<Dispatch>)> {
+    let provider = SdkTracerProvider::builder().build();
+    let otel = tracing_opentelemetry::layer().with_tracer(provider.tracer("bench"));
+    vec![
+        ("no_subscriber", None),
+        (
+            "registry",
+            Some(Dispatch::new(tracing_subscriber::registry())),
+        ),
+        (
+            "otel",
+            Some(Dispatch::new(tracing_subscriber::registry().with(otel))),
+        ),
+    ]
+}
+
+/// Serve a `WeatherService` (mock provider) and connect a client to it.
+async fn connect() -> RunningService<RoleClient, ()> {
+    let config = Config::from_env().expect("config");
+    let service = WeatherService::new(AppState::new(config).expect("app state"));
+    let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        let server = service.serve(server_io).await.expect("server");
+        let _ = server.waiting().await;
+    });
+    ().serve(client_io).await.expect("client")
+}
+
+fn forecast_request() -> CallToolRequestParam {
+    CallToolRequestParam {
+        name: "get_forecast".into(),
+        arguments: json!({ "location": "Brussels", "days": 7 })
+            .as_object()
+            .cloned(),
+    }
+}
+
+fn tool_dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tool_dispatch");
+    for (name, dispatch) in subscribers() {
+        // Spans are created on the bench thread, so a thread-local default
+        // subscriber with a current-thread runtime covers client and server.
+        let _default = dispatch.as_ref().map(tracing::dispatcher::set_default);
+        let rt = runtime();
+        let client = rt.block_on(connect());
+        group.bench_function(BenchmarkId::new("get_forecast", name), |b| {
+            b.to_async(&rt).iter(|| async {
+                client
+                    .call_tool(forecast_request())
+                    .await
+                    .expect("tool call")
+            })
+        });
+        rt.block_on(client.cancel()).expect("close client");
+    }
+    group.finish();
+}
+
+fn forecast_args() -> GetForecastArgs {
+    GetForecastArgs {
+        location: "Brussels".into(),
+        days: 7,
+        lang: None,
+        long_range: false,
+    }
+}
+
+fn forecast() -> ForecastResponse {
+    ForecastResponse {
+        items: (1..=7)
+            .map(|day| Forecast {
+                day,
+                date: format!("2026-06-{:02}", 20 + day),
+                sunrise: Some(format!("2026-06-{:02}T05:46:00+02:00", 20 + day)),
+                sunset: Some(format!("2026-06-{:02}T21:58:00+02:00", 20 + day)),
+                high: 24,
+                low: 14,
+                condition: "Partly Cloudy".into(),
+                precipitation_chance: 40,
+            })
+            .collect(),
+        units: Units::Metric,
+    }
+}
+
+struct Tools;
+
+impl Tools {
+    #[trace_io]
+    async fn get_forecast(
+        &self,
+        _request_context: (),
+        params: Parameters<GetForecastArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        std::hint::black_box(args);
+        Ok(CallToolResult::structured(json!(forecast())))
+    }
+}
+
+fn io_capture(c: &mut Criterion) {
+    let mut group = c.benchmark_group("io_capture");
+    let provider = SdkTracerProvider::builder().build();
+    let otel = tracing_opentelemetry::layer().with_tracer(provider.tracer("bench"));
+    let _default =
+        tracing::dispatcher::set_default(&Dispatch::new(tracing_subscriber::registry().with(otel)));
+    let rt = runtime();
+
+    group.bench_function("plain", |b| {
+        b.to_async(&rt).iter(|| {
+            let span = tracing::info_span!("get_forecast", input = Empty, output = Empty);
+            async {
+                let Parameters(args) = Parameters(forecast_args());
+                std::hint::black_box(args);
+                CallToolResult::structured(json!(forecast()))
+            }
+            .instrument(span)
+        })
+    });
+    group.bench_function("captured", |b| {
+        b.to_async(&rt).iter(|| {
+            let span = tracing::info_span!("get_forecast", input = Empty, output = Empty);
+            async {
+                let args = trace_rmcp_setup(Parameters(forecast_args()));
+                std::hint::black_box(args);
+                trace_rmcp_result(forecast()).expect("structured result")
+            }
+            .instrument(span)
+        })
+    });
+    group.bench_function("trace_io", |b| {
+        b.to_async(&rt)
+            .iter(|| async { Tools.get_forecast((), Parameters(forecast_args())).await })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, tool_dispatch, io_capture);
+criterion_main!(benches);
@@ -0,0 +1,227 @@
+//! Arbitrary requests against the server's router: the MCP endpoint (with
+//! and without a live session), the REST facade and the build info, through
+//! the trace propagation layer and request ids as in `main`.
+//!
+//! Tool spans are sampled, so the `#[trace_io]` input and output recording
+//! runs too. A panic anywhere, including in a session's task, aborts the
+//! run.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use axum::body::Body;
+use axum::http::{HeaderName, HeaderValue, Method, Request};
+use axum::{middleware, Router};
+use http_body_util::BodyExt;
+use libfuzzer_sys::fuzz_target;
+use once_cell::sync::Lazy;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use rmcp::transport::streamable_http_server::StreamableHttpService;
+use rmcp_demo::app_state::AppState;
+use rmcp_demo::config::Config;
+use rmcp_demo::weather_tools::WeatherService;
+use rmcp_demo::{build_info, request_id, rest_api, trace_utils};
+use rmcp_otel::TracePropagationLayer;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// How long a response body is read; SSE streams never end on their own.
+const READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+const INITIALIZE: &str = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05","capabilities":{},"clientInfo":{"name":"fuzz","version":"0"}}}"#;
+
+struct Server {
+    runtime: Runtime,
+    router: Router,
+    /// Initialized session, for requests that reach tool dispatch.
+    session_id: String,
+    tools: Vec<String>,
+}
+
+static SERVER: Lazy<Server> = Lazy::new(|| {
+    let provider = SdkTracerProvider::builder().build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("fuzz")));
+    tracing::subscriber::set_global_default(subscriber).expect("no other subscriber");
+
+    let runtime = Runtime::new().unwrap();
+    let router = runtime.block_on(async {
+        let state = AppState::new(Config::from_env().expect("default config")).unwrap();
+        let mcp_state = state.clone();
+        let service = StreamableHttpService::new(
+            move || Ok(WeatherService::new(mcp_state.clone())),
+            state.sessions.clone(),
+            Default::default(),
+        );
+        Router::new()
+            .nest_service("/weather", service)
+            .merge(rest_api::router(state, None))
+            .merge(build_info::router())
+            .layer(
+                TracePropagationLayer::new()
+                    .on_request(trace_utils::attribute_request)
+                    .with_propagated_headers(vec![HeaderName::from_static("x-correlation-id")]),
+            )
+            .layer(middleware::from_fn(request_id::assign))
+    });
+    let session_id = runtime.block_on(async {
+        let response = send(&router, mcp_request(Method::POST, INITIALIZE, None)).await;
+        let session_id = response["mcp-session-id"].to_str().unwrap().to_string();
+        let initialized = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+        send(
+            &router,
+            mcp_request(Method::POST, initialized, Some(&session_id)),
+        )
+        .await;
+        session_id
+    });
+    let tools = WeatherService::tool_definitions()
+        .into_iter()
+        .map(|tool| tool.name.to_string())
+        .collect();
+    Server {
+        runtime,
+        router,
+        session_id,
+        tools,
+    }
+});
+
+fn mcp_request(method: Method, body: &str, session_id: Option<&str>) -> Request<Body> {
+    let mut request = Request::builder()
+        .method(method)
+        .uri("/weather")
+        .header("content-type", "application/json")
+        .header("accept", "application/json, text/event-stream");
+    if let Some(session_id) = session_id {
+        request = request.header("mcp-session-id", session_id);
+    }
+    request.body(Body::from(body.to_string())).unwrap()
+}
+
+/// Send `request` and read its body for at most [`READ_TIMEOUT`].
+async fn send(router: &Router, request: Request<Body>) -> axum::http::HeaderMap {
+    let response = router.clone().oneshot(request).await.unwrap();
+    let headers = response.headers().clone();
+    let _ = tokio::time::timeout(READ_TIMEOUT, response.into_body().collect()).await;
+    headers
+}
+
+#[derive(Arbitrary, Debug)]
+enum Target {
+    /// The MCP endpoint without a session
+    Mcp,
+    /// The MCP endpoint in the initialized session
+    McpSession,
+    RestWeather(String),
+    RestForecast(String, String),
+    RestExport(String, String),
+    OpenApi,
+    Version,
+    Path(String),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Payload {
+    Raw(Vec<u8>),
+    /// A `tools/call` of a registered tool, with raw (possibly invalid) JSON
+    /// arguments
+    ToolCall {
+        tool: u8,
+        arguments: String,
+    },
+    /// Any method, with raw params
+    JsonRpc {
+        id: Option<i64>,
+        method: String,
+        params: String,
+    },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    method: u8,
+    target: Target,
+    headers: Vec<(String, String)>,
+    payload: Payload,
+}
+
+impl Input {
+    fn uri(&self, session_id: &str) -> (String, Option<String>) {
+        match &self.target {
+            Target::Mcp => ("/weather".to_string(), None),
+            Target::McpSession => ("/weather".to_string(), Some(session_id.to_string())),
+            Target::RestWeather(city) => (format!("/api/weather/{city}"), None),
+            Target::RestForecast(city, days) => (format!("/api/forecast/{city}?days={days}"), None),
+            Target::RestExport(city, query) => {
+                (format!("/api/history/{city}/export?{query}"), None)
+            }
+            Target::OpenApi => ("/api/openapi.json".to_string(), None),
+            Target::Version => ("/version".to_string(), None),
+            Target::Path(path) => (format!("/{path}"), None),
+        }
+    }
+
+    fn body(&self, tools: &[String]) -> Vec<u8> {
+        match &self.payload {
+            Payload::Raw(bytes) => bytes.clone(),
+            Payload::ToolCall { tool, arguments } => {
+                let tool = &tools[usize::from(*tool) % tools.len()];
+                format!(
+                    r#"{{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{{"name":{},"arguments":{arguments}}}}}"#,
+                    serde_json::Value::from(tool.as_str()),
+                )
+                .into_bytes()
+            }
+            Payload::JsonRpc { id, method, params } => {
+                let id = id.map_or("null".to_string(), |id| id.to_string());
+                format!(
+                    r#"{{"jsonrpc":"2.0","id":{id},"method":{},"params":{params}}}"#,
+                    serde_json::Value::from(method.as_str()),
+                )
+                .into_bytes()
+            }
+        }
+    }
+
+    fn request(&self, server: &Server) -> Option<Request<Body>> {
+        let mut method = [
+            Method::GET,
+            Method::POST,
+            Method::DELETE,
+            Method::PUT,
+            Method::OPTIONS,
+        ][usize::from(self.method) % 5]
+            .clone();
+        let (uri, session_id) = self.uri(&server.session_id);
+        // Closing the shared session would end tool dispatch for later inputs
+        if session_id.is_some() && method == Method::DELETE {
+            method = Method::POST;
+        }
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("accept", "application/json, text/event-stream");
+        if let Some(session_id) = session_id {
+            request = request.header("mcp-session-id", session_id);
+        }
+        for (name, value) in &self.headers {
+            let name = HeaderName::try_from(name.as_str()).ok()?;
+            let value = HeaderValue::try_from(value.as_str()).ok()?;
+            request = request.header(name, value);
+        }
+        request.body(Body::from(self.body(&server.tools))).ok()
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let server = &*SERVER;
+    let Some(request) = input.request(server) else {
+        return;
+    };
+    server.runtime.block_on(send(&server.router, request));
+});
@@ -0,0 +1,412 @@
+use axum::body::{Body, HttpBody};
+use axum::extract::Request;
+use axum::http::{HeaderMap, HeaderName, Method, StatusCode};
+use axum::response::Response;
+use http_body_util::BodyExt;
+use opentelemetry::trace::TraceContextExt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tower::{layer::Layer, Service};
+use tracing::{field::Empty, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::propagation::{extract_context, TraceParentContext};
+use crate::store::ContextStore;
+
+/// Header carrying the MCP session id, on the `initialize` response and on
+/// every later request of the session.
+const SESSION_HEADER: &str = "mcp-session-id";
+
+fn session_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// Called with each request and its `http.request` span before the request
+/// reaches the inner service, e.g. to tag the span or add extensions.
+pub type RequestHook = Arc<dyn Fn(&mut Request, &tracing::Span) + Send + Sync>;
+
+/// Tower layer opening an `http.request` server span per request, parented
+/// on the incoming `traceparent`.
+///
+/// Handlers find the request's context in its extensions, as
+/// [`TraceParentContext`]. For work outside a request, a [`ContextStore`]
+/// can also keep the caller's context per MCP session
+/// ([`with_context_store`](Self::with_context_store)):
+/// - on `initialize`, under the session id of the response, before the
+///   response is handed back, so the session's next request always finds it
+///   whichever way the two race;
+/// - on each later request carrying a `traceparent`, under the session id of
+///   the request, before the request reaches the inner service, so its
+///   handlers see the context of the request they serve.
+///
+/// Requests without a `traceparent` leave the stored context as it was.
+///
+/// A session's context is cleared when it is closed (`DELETE`) or unknown to
+/// the server (404).
+#[derive(Clone)]
+pub struct TracePropagationLayer {
+    store: Option<Arc<dyn ContextStore>>,
+    on_request: Option<RequestHook>,
+    propagated_headers: Arc<[HeaderName]>,
+}
+
+impl Default for TracePropagationLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TracePropagationLayer {
+    /// A layer keeping no session contexts.
+    pub fn new() -> Self {
+        Self {
+            store: None,
+            on_request: None,
+            propagated_headers: Arc::new([]),
+        }
+    }
+
+    /// Keep session contexts in `store`, where the server can read them back,
+    /// e.g. an [`InMemoryContextStore`](crate::InMemoryContextStore).
+    pub fn with_context_store(mut self, store: Arc<dyn ContextStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Run `hook` on each request once its span is open.
+    pub fn on_request<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut Request, &tracing::Span) + Send + Sync + 'static,
+    {
+        self.on_request = Some(Arc::new(hook));
+        self
+    }
+
+    /// Record these request headers (e.g. `x-request-id`) on the span as
+    /// `http.request.header.<name>`, and echo them on the response unless it
+    /// sets them itself, so traces can be matched with gateway logs.
+    pub fn with_propagated_headers(
+        mut self,
+        headers: impl IntoIterator<Item = HeaderName>,
+    ) -> Self {
+        self.propagated_headers = headers.into_iter().collect();
+        self
+    }
+}
+
+impl<S> Layer<S> for TracePropagationLayer {
+    type Service = TracePropagationMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TracePropagationMiddleware {
+            inner,
+            store: self.store.clone(),
+            on_request: self.on_request.clone(),
+            propagated_headers: self.propagated_headers.clone(),
+        }
+    }
+}
+
+/// Service built by [`TracePropagationLayer`].
+#[derive(Clone)]
+pub struct TracePropagationMiddleware<S> {
+    inner: S,
+    store: Option<Arc<dyn ContextStore>>,
+    on_request: Option<RequestHook>,
+    propagated_headers: Arc<[HeaderName]>,
+}
+
+impl<S> Service<Request> for TracePropagationMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        // Names only: values may carry credentials (bearer tokens, API keys,
+        // signatures) that must not reach logs
+        tracing::debug!(
+            headers = ?req.headers().keys().map(|name| name.as_str()).collect::<Vec<_>>(),
+            "Incoming request"
+        );
+        match req.headers().get("traceparent") {
+            Some(traceparent) => tracing::debug!(?traceparent, "Received traceparent header"),
+            None => tracing::debug!("No traceparent header found"),
+        }
+
+        // Extract trace context from headers
+        let parent_context = extract_context(req.headers());
+        let has_parent = parent_context.span().span_context().is_valid();
+        let request_session = session_id(req.headers());
+        let closing = req.method() == Method::DELETE;
+
+        // Server span for this exchange. Compression layers sit outside this
+        // middleware, so the sizes recorded here are the decompressed ones.
+        let span = tracing::info_span!(
+            "http.request",
+            otel.name = %format!("{} {}", req.method(), req.uri().path()),
+            otel.kind = "server",
+            http.request.method = %req.method(),
+            url.path = req.uri().path(),
+            http.request.body.size = Empty,
+            http.response.status_code = Empty,
+            http.response.body.size = Empty,
+        );
+        // Ignore failure if the span context is already closed
+        let _ = span.set_parent(parent_context.clone());
+
+        // Correlation headers, recorded here and echoed on the response
+        let mut echoed = HeaderMap::new();
+        for name in self.propagated_headers.iter() {
+            let values: Vec<&str> = req
+                .headers()
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect();
+            if values.is_empty() {
+                continue;
+            }
+            span.set_attribute(format!("http.request.header.{name}"), values.join(","));
+            for value in req.headers().get_all(name) {
+                echoed.append(name.clone(), value.clone());
+            }
+        }
+
+        // Before the span starts, so attributes set here reach the exporter
+        if let Some(hook) = &self.on_request {
+            hook(&mut req, &span);
+        }
+
+        // Handlers parent their spans on this request's span
+        req.extensions_mut()
+            .insert(TraceParentContext(span.context()));
+
+        // Count request body bytes as the handler reads them
+        let request_bytes = Arc::new(AtomicU64::new(0));
+        let counter = request_bytes.clone();
+        let req = req.map(|body| {
+            Body::new(body.map_frame(move |frame| {
+                if let Some(data) = frame.data_ref() {
+                    counter.fetch_add(data.len() as u64, Ordering::Relaxed);
+                }
+                frame
+            }))
+        });
+
+        // Clone what we need for the async block
+        let mut inner = self.inner.clone();
+        let store = self.store.clone();
+
+        Box::pin(
+            async move {
+                if let (Some(store), Some(session_id)) = (&store, &request_session) {
+                    if has_parent && !closing {
+                        store.store(session_id, parent_context.clone()).await;
+                    }
+                }
+
+                // Call the inner service
+                let mut response = inner.call(req).await?;
+                for name in echoed.keys() {
+                    if !response.headers().contains_key(name) {
+                        for value in echoed.get_all(name) {
+                            response.headers_mut().append(name.clone(), value.clone());
+                        }
+                    }
+                }
+
+                let span = tracing::Span::current();
+                span.record(
+                    "http.request.body.size",
+                    request_bytes.load(Ordering::Relaxed),
+                );
+                span.record("http.response.status_code", response.status().as_u16());
+                // Streaming bodies (SSE) have no size up front
+                if let Some(size) = response.body().size_hint().exact() {
+                    span.record("http.response.body.size", size);
+                }
+
+                if let Some(store) = &store {
+                    match request_session {
+                        Some(session_id)
+                            if closing || response.status() == StatusCode::NOT_FOUND =>
+                        {
+                            store.clear(&session_id).await;
+                        }
+                        Some(_) => {}
+                        // A new session: keep its context before the client can
+                        // learn the session id
+                        None if has_parent => {
+                            if let Some(session_id) = session_id(response.headers()) {
+                                store.store(&session_id, parent_context).await;
+                                tracing::info!("Stored trace context for session: {}", session_id);
+                            }
+                        }
+                        None => {}
+                    }
+                }
+
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::InMemoryContextStore;
+    use opentelemetry::trace::TraceId;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use std::convert::Infallible;
+    use tower::{service_fn, ServiceExt};
+
+    const INITIALIZE_TRACE: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+    const CALL_TRACE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    /// A server answering `initialize` (no session header) with a new
+    /// session, 404 for session "gone" and 200 otherwise, after checking
+    /// what its handler can see.
+    fn layered(
+        store: Arc<InMemoryContextStore>,
+    ) -> impl Service<Request, Response = Response, Error = Infallible> + Clone {
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let handler_store = store.clone();
+        let layer = TracePropagationLayer::new()
+            .with_context_store(store)
+            .on_request(|request, _| {
+                request.extensions_mut().insert("hooked");
+            });
+        layer.layer(service_fn(move |request: Request| {
+            let store = handler_store.clone();
+            async move {
+                assert!(request.extensions().get::<TraceParentContext>().is_some());
+                assert_eq!(request.extensions().get::<&str>(), Some(&"hooked"));
+                let response = Response::builder();
+                let response = match session_id(request.headers()) {
+                    None => response.header(SESSION_HEADER, "session-1"),
+                    Some(session) if session == "gone" => response.status(StatusCode::NOT_FOUND),
+                    Some(session) => {
+                        // The request's own context is stored before it is served
+                        if request.headers().contains_key("traceparent") {
+                            let stored = trace_id(store.get(&session).await);
+                            assert_eq!(stored, trace_id_of(CALL_TRACE));
+                        }
+                        response
+                    }
+                };
+                Ok::<_, Infallible>(response.body(Body::empty()).unwrap())
+            }
+        }))
+    }
+
+    fn request(method: Method, session: Option<&str>, traceparent: Option<&str>) -> Request {
+        let mut request = Request::builder().method(method);
+        if let Some(session) = session {
+            request = request.header(SESSION_HEADER, session);
+        }
+        if let Some(traceparent) = traceparent {
+            request = request.header("traceparent", traceparent);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    fn trace_id(context: Option<opentelemetry::Context>) -> Option<TraceId> {
+        context.map(|context| context.span().span_context().trace_id())
+    }
+
+    fn trace_id_of(traceparent: &str) -> Option<TraceId> {
+        TraceId::from_hex(traceparent.split('-').nth(1).unwrap()).ok()
+    }
+
+    #[tokio::test]
+    async fn propagated_headers_are_echoed_unless_the_response_sets_them() {
+        let service = TracePropagationLayer::new()
+            .with_propagated_headers([
+                HeaderName::from_static("x-request-id"),
+                HeaderName::from_static("x-correlation-id"),
+            ])
+            .layer(service_fn(|_: Request| async {
+                let response = Response::builder()
+                    .header("x-correlation-id", "from-server")
+                    .body(Body::empty())
+                    .unwrap();
+                Ok::<_, Infallible>(response)
+            }));
+        let request = Request::builder()
+            .header("x-request-id", "req-1")
+            .header("x-correlation-id", "from-gateway")
+            .header("x-other", "kept-out")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = service.oneshot(request).await.unwrap();
+
+        assert_eq!(response.headers()["x-request-id"], "req-1");
+        assert_eq!(response.headers()["x-correlation-id"], "from-server");
+        assert!(!response.headers().contains_key("x-other"));
+    }
+
+    #[tokio::test]
+    async fn initialize_context_is_stored_before_the_response_is_returned() {
+        let store = Arc::new(InMemoryContextStore::default());
+        let service = layered(store.clone());
+
+        service
+            .oneshot(request(Method::POST, None, Some(INITIALIZE_TRACE)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            trace_id(store.get("session-1").await),
+            trace_id_of(INITIALIZE_TRACE)
+        );
+    }
+
+    #[tokio::test]
+    async fn later_requests_update_the_context_by_their_session_header() {
+        let store = Arc::new(InMemoryContextStore::default());
+        let service = layered(store.clone());
+        let send = |request| service.clone().oneshot(request);
+
+        send(request(Method::POST, None, Some(INITIALIZE_TRACE)))
+            .await
+            .unwrap();
+        send(request(Method::POST, Some("session-1"), Some(CALL_TRACE)))
+            .await
+            .unwrap();
+        send(request(Method::POST, Some("session-1"), None))
+            .await
+            .unwrap();
+        assert_eq!(
+            trace_id(store.get("session-1").await),
+            trace_id_of(CALL_TRACE)
+        );
+
+        send(request(Method::POST, Some("gone"), Some(CALL_TRACE)))
+            .await
+            .unwrap();
+        assert!(store.get("gone").await.is_none());
+        send(request(Method::DELETE, Some("session-1"), Some(CALL_TRACE)))
+            .await
+            .unwrap();
+        assert!(store.get("session-1").await.is_none());
+    }
+}
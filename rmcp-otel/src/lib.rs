@@ -0,0 +1,42 @@
+//! OpenTelemetry trace propagation for MCP servers built on rmcp and axum.
+//!
+//! [`TracePropagationLayer`] continues the caller's trace: it extracts the
+//! incoming `traceparent` with the global propagator, opens an
+//! `http.request` server span under it, and leaves that span's context in
+//! the request extensions as [`TraceParentContext`] for handlers to parent
+//! their own spans on. Optionally, the caller's context is also kept in a
+//! [`ContextStore`] under the MCP session id (`mcp-session-id`), from the
+//! `initialize` response and then from each request of the session, so work
+//! done later for that session outside any request can join the caller's
+//! trace.
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use axum::Router;
+//! use rmcp_otel::{InMemoryContextStore, TracePropagationLayer};
+//! use tracing_opentelemetry::OpenTelemetrySpanExt;
+//!
+//! let store = Arc::new(InMemoryContextStore::default());
+//! let router: Router = Router::new().layer(
+//!     TracePropagationLayer::new()
+//!         .with_context_store(store.clone())
+//!         .on_request(|request, span| {
+//!             if let Some(user) = request.headers().get("x-user-id") {
+//!                 let user = user.to_str().unwrap_or_default().to_string();
+//!                 span.set_attribute("user.id", user);
+//!             }
+//!         }),
+//! );
+//! ```
+//!
+//! Spans are created with `tracing`, so a `tracing-opentelemetry` layer
+//! must be installed for them to be exported.
+
+mod layer;
+mod propagation;
+mod store;
+
+pub use layer::{RequestHook, TracePropagationLayer, TracePropagationMiddleware};
+pub use propagation::{extract_context, inject_headers, spawn_traced, TraceParentContext};
+pub use store::{ContextStore, InMemoryContextStore, StoreFuture};
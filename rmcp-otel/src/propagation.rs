@@ -0,0 +1,40 @@
+use axum::http::HeaderMap;
+use opentelemetry::context::FutureExt;
+use opentelemetry::Context;
+use std::future::Future;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Context of the request's `http.request` span (itself parented on the
+/// incoming `traceparent`), stored in the request extensions.
+#[derive(Clone, Debug)]
+pub struct TraceParentContext(pub Context);
+
+/// The trace context carried by incoming request `headers`, read with the
+/// global propagator. Empty when the caller sent none.
+pub fn extract_context(headers: &HeaderMap) -> Context {
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&opentelemetry_http::HeaderExtractor(headers))
+    })
+}
+
+/// Write the current span's context into outgoing request `headers` with the
+/// global propagator (`traceparent`, plus `baggage` when set), so the
+/// receiving service continues this trace.
+pub fn inject_headers(headers: &mut HeaderMap) {
+    let context = tracing::Span::current().context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut opentelemetry_http::HeaderInjector(headers))
+    });
+}
+
+/// `tokio::spawn` with the current span's OTel context re-attached inside
+/// the task, so spans the task opens join the spawning trace rather than
+/// starting orphan ones. The spawning span itself is not held open.
+pub fn spawn_traced<F>(task: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let context = tracing::Span::current().context();
+    tokio::spawn(task.with_context(context))
+}
@@ -0,0 +1,96 @@
+use opentelemetry::Context;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use tokio::sync::RwLock;
+
+/// Future returned by [`ContextStore`] methods.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Where [`TracePropagationLayer`](crate::TracePropagationLayer) keeps the
+/// trace context each MCP session was opened with. Implement it to share
+/// contexts between replicas, e.g. in Redis.
+pub trait ContextStore: Send + Sync {
+    /// Keep `context` for `session_id`; it also becomes the latest context.
+    fn store<'a>(&'a self, session_id: &'a str, context: Context) -> StoreFuture<'a, ()>;
+
+    /// The context kept for `session_id`, if any.
+    fn get<'a>(&'a self, session_id: &'a str) -> StoreFuture<'a, Option<Context>>;
+
+    /// Forget the context of `session_id`, once the session has ended.
+    fn clear<'a>(&'a self, session_id: &'a str) -> StoreFuture<'a, ()>;
+
+    /// The most recently stored context, for callers that do not know their
+    /// session.
+    fn latest(&self) -> StoreFuture<'_, Option<Context>>;
+}
+
+/// A [`ContextStore`] in process memory.
+#[derive(Default)]
+pub struct InMemoryContextStore {
+    sessions: RwLock<HashMap<String, Context>>,
+    latest: RwLock<Option<Context>>,
+}
+
+impl ContextStore for InMemoryContextStore {
+    fn store<'a>(&'a self, session_id: &'a str, context: Context) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.sessions
+                .write()
+                .await
+                .insert(session_id.to_string(), context.clone());
+            *self.latest.write().await = Some(context);
+            tracing::debug!("Stored trace context for session: {}", session_id);
+        })
+    }
+
+    fn get<'a>(&'a self, session_id: &'a str) -> StoreFuture<'a, Option<Context>> {
+        Box::pin(async move {
+            let context = self.sessions.read().await.get(session_id).cloned();
+            if context.is_some() {
+                tracing::debug!("Retrieved trace context for session: {}", session_id);
+            } else {
+                tracing::debug!("No trace context found for session: {}", session_id);
+            }
+            context
+        })
+    }
+
+    fn clear<'a>(&'a self, session_id: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            if self.sessions.write().await.remove(session_id).is_some() {
+                tracing::debug!("Cleared trace context for session: {}", session_id);
+            }
+        })
+    }
+
+    fn latest(&self) -> StoreFuture<'_, Option<Context>> {
+        Box::pin(async move { self.latest.read().await.clone() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Marker(&'static str);
+
+    #[tokio::test]
+    async fn cleared_sessions_are_forgotten_but_stay_the_latest() {
+        let store = InMemoryContextStore::default();
+        store
+            .store("a", Context::new().with_value(Marker("a")))
+            .await;
+        store
+            .store("b", Context::new().with_value(Marker("b")))
+            .await;
+        store.clear("b").await;
+
+        let a = store.get("a").await.unwrap();
+        assert_eq!(a.get::<Marker>(), Some(&Marker("a")));
+        assert!(store.get("b").await.is_none());
+        let latest = store.latest().await.unwrap();
+        assert_eq!(latest.get::<Marker>(), Some(&Marker("b")));
+    }
+}
@@ -1,6 +1,21 @@
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use syn::parse::Parser;
+use syn::{
+    parse_macro_input, Data, DataStruct, DeriveInput, Fields, FnArg, Ident, ItemFn, LitBool,
+    LitStr, Pat, ReturnType,
+};
+
+/// Arguments of `#[trace_io(...)]`.
+#[derive(Default)]
+struct TraceIoArgs {
+    /// Also emit call metrics (`metrics = true`).
+    metrics: bool,
+    /// Function mapping the result to the recorded output
+    /// (`output_with = "path"`).
+    output_with: Option<syn::Path>,
+}
 
 /// A procedural macro that automatically captures input parameters and output values
 /// as trace span attributes for rmcp tool functions.
@@ -8,10 +23,28 @@ use syn::{parse_macro_input, ItemFn};
 /// This macro will:
 /// 1. Extract all parameters from Parameters<T> and record them as "input"
 /// 2. Capture the return value and record it as "output" before returning
-/// 3. Attach the stored trace context if available
+/// 3. Parent the span on the call's trace context (see
+///    `trace_utils::call_trace_context`) if available
+///
+/// Input and output are only serialized when the span is sampled and enabled
+/// (see `trace_utils::is_recorded`). Fields of the arguments marked
+/// `#[sensitive]` (see [`Sensitive`]) are recorded as `"***"`.
+///
+/// With `#[trace_io(metrics = true)]`, each call also increments the
+/// `tool.calls` counter and records its duration in seconds in the
+/// `tool.duration` histogram, both labeled with `tool.name` and `outcome`
+/// (`ok`, or `error` for an `Err` or an error result). They go through the
+/// global OpenTelemetry meter, whose provider must be installed before the
+/// first call; without one they are no-ops.
+///
+/// The output is the first text content of the result. For results whose
+/// interesting part is elsewhere (images, embedded resources, several
+/// contents), `#[trace_io(output_with = "summarize_output")]` records what
+/// the named function returns instead: any `fn(&CallToolResult) -> T` with
+/// `T: Serialize`, e.g. a list of content types and sizes.
 ///
 /// Usage:
-/// ```rust
+/// ```rust,ignore
 /// #[trace_io]
 /// async fn get_weather(
 ///     &self,
@@ -22,8 +55,65 @@ use syn::{parse_macro_input, ItemFn};
 /// }
 /// ```
 #[proc_macro_attribute]
-pub fn trace_io(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn trace_io(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as ItemFn);
+    expand_trace_io(attr.into(), input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn parse_args(attr: TokenStream2) -> syn::Result<TraceIoArgs> {
+    let mut args = TraceIoArgs::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("metrics") {
+            args.metrics = meta.value()?.parse::<LitBool>()?.value;
+            Ok(())
+        } else if meta.path.is_ident("output_with") {
+            args.output_with = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported trace_io argument, expected `metrics` or `output_with`"))
+        }
+    });
+    parser.parse2(attr)?;
+    Ok(args)
+}
+
+/// Check the shape the expansion relies on: an async method taking
+/// `_request_context` and `params`, which the span skips.
+fn check_signature(sig: &syn::Signature) -> syn::Result<()> {
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            sig.fn_token,
+            "#[trace_io] only supports async fns",
+        ));
+    }
+    if sig.receiver().is_none() {
+        return Err(syn::Error::new_spanned(
+            &sig.ident,
+            "#[trace_io] only supports methods taking `&self`",
+        ));
+    }
+    for name in ["_request_context", "params"] {
+        let found = sig.inputs.iter().any(|input| match input {
+            FnArg::Typed(typed) => {
+                matches!(&*typed.pat, Pat::Ident(pat) if pat.ident == name)
+            }
+            FnArg::Receiver(_) => false,
+        });
+        if !found {
+            return Err(syn::Error::new_spanned(
+                &sig.inputs,
+                format!("#[trace_io] expects an argument named `{name}`"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn expand_trace_io(attr: TokenStream2, input: ItemFn) -> syn::Result<TokenStream2> {
+    let args = parse_args(attr)?;
+    check_signature(&input.sig)?;
 
     let vis = &input.vis;
     let sig = &input.sig;
@@ -36,20 +126,86 @@ pub fn trace_io(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let generics = &sig.generics;
     let where_clause = &sig.generics.where_clause;
 
+    // Call counter and duration histogram, labeled by tool and outcome
+    let (start_timer, record_metrics) = if args.metrics {
+        let start_timer = quote! {
+            let started = std::time::Instant::now();
+        };
+        let record_metrics = quote! {
+            static INSTRUMENTS: std::sync::OnceLock<(
+                opentelemetry::metrics::Counter<u64>,
+                opentelemetry::metrics::Histogram<f64>,
+            )> = std::sync::OnceLock::new();
+            let (calls, duration) = INSTRUMENTS.get_or_init(|| {
+                let meter = opentelemetry::global::meter("rmcp-trace-macro");
+                (
+                    meter
+                        .u64_counter("tool.calls")
+                        .with_description("Tool calls")
+                        .build(),
+                    meter
+                        .f64_histogram("tool.duration")
+                        .with_unit("s")
+                        .with_description("Duration of tool calls")
+                        .build(),
+                )
+            });
+            let outcome = match &result {
+                Ok(call_result) if call_result.is_error != Some(true) => "ok",
+                _ => "error",
+            };
+            let attributes = [
+                opentelemetry::KeyValue::new("tool.name", stringify!(#fn_name)),
+                opentelemetry::KeyValue::new("outcome", outcome),
+            ];
+            calls.add(1, &attributes);
+            duration.record(started.elapsed().as_secs_f64(), &attributes);
+        };
+        (start_timer, record_metrics)
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    let record_output = match &args.output_with {
+        Some(extractor) => quote! {
+            let output_json = serde_json::json!(#extractor(call_result));
+            tracing::Span::current().record("output", tracing::field::display(&output_json.to_string()));
+        },
+        None => quote! {
+            // We need to extract the JSON from CallToolResult
+            // Since CallToolResult::structured() takes a serde_json::Value,
+            // we should capture that value before creating CallToolResult
+            // This is a bit tricky without modifying the original body
+            // For now, we'll just record what we can access
+            if let Some(text) = call_result.content.first().and_then(|content| content.as_text()) {
+                tracing::Span::current().record("output", tracing::field::display(&text.text));
+            }
+        },
+    };
+
     // We'll wrap the entire function body
     let wrapped_body = quote! {
+        #start_timer
+
         // Extract Parameters at the beginning
         let Parameters(args) = params;
 
-        // Try to get stored trace context and attach it
-        let stored_context = crate::trace_store::get_current_trace_context().await;
-        if let Some(ctx) = stored_context {
-            tracing::Span::current().set_parent(ctx);
+        // Parent the span on the call's trace context
+        if let Some(ctx) = crate::trace_utils::call_trace_context(&_request_context) {
+            // Ignore potential failure if the span is already closed
+            let _ = tracing_opentelemetry::OpenTelemetrySpanExt::set_parent(
+                &tracing::Span::current(),
+                ctx,
+            );
         }
 
-        // Record input
-        let input_json = serde_json::json!(&args);
-        tracing::Span::current().record("input", tracing::field::display(&input_json.to_string()));
+        // Record input, skipping serialization when the span is not exported
+        let recorded = crate::trace_utils::is_recorded(&tracing::Span::current());
+        if recorded {
+            use crate::trace_utils::{MaskNothing as _, MaskSensitive as _};
+            let input_json = (&crate::trace_utils::InputArgs(&args)).to_input_json();
+            tracing::Span::current().record("input", tracing::field::display(&input_json.to_string()));
+        }
 
         // Execute the original function body and capture the result
         let execute_body = async move {
@@ -58,23 +214,16 @@ pub fn trace_io(_attr: TokenStream, item: TokenStream) -> TokenStream {
         let result = execute_body.await;
 
         // Record output if successful
-        if let Ok(ref call_result) = result {
-            // We need to extract the JSON from CallToolResult
-            // Since CallToolResult::structured() takes a serde_json::Value,
-            // we should capture that value before creating CallToolResult
-            // This is a bit tricky without modifying the original body
-            // For now, we'll just record what we can access
-            if let Some(content) = call_result.content.first() {
-                if let Some(ref text) = content.text {
-                    tracing::Span::current().record("output", tracing::field::display(&text));
-                }
-            }
+        if let (true, Ok(call_result)) = (recorded, &result) {
+            #record_output
         }
 
+        #record_metrics
+
         result
     };
 
-    let result = quote! {
+    Ok(quote! {
         #(#attrs)*
         #[tracing::instrument(skip(self, _request_context, params), fields(
             input = tracing::field::Empty,
@@ -83,7 +232,291 @@ pub fn trace_io(_attr: TokenStream, item: TokenStream) -> TokenStream {
         #vis #asyncness fn #fn_name #generics(#inputs) #output #where_clause {
             #wrapped_body
         }
+    })
+}
+
+/// Arguments of `#[trace_fn_io(...)]`.
+#[derive(Default)]
+struct TraceFnIoArgs {
+    /// Span name (`name = "..."`), else the function's.
+    name: Option<LitStr>,
+    /// Arguments left out of the input and the span fields (`skip(...)`).
+    skip: Vec<Ident>,
+    /// Extra span fields, passed to `tracing::instrument` (`fields(...)`).
+    fields: Option<TokenStream2>,
+}
+
+/// `#[trace_io]` for any async function returning `Result<T, E>` with
+/// `T: Serialize`, e.g. provider and cache layers.
+///
+/// The function runs in a span like `#[tracing::instrument]`'s, which
+/// records the arguments as fields. On top, when the span is recorded
+/// (see `trace_utils::is_recorded`), the arguments are serialized into the
+/// `input` attribute as a JSON object keyed by argument name, and an `Ok`
+/// value into `output`. Arguments other than `self` must be plain names
+/// and implement `Serialize` and `Debug`, unless skipped.
+///
+/// Usage:
+/// ```rust,ignore
+/// #[trace_fn_io(name = "provider.forecast", skip(client), fields(provider = "open-meteo"))]
+/// async fn forecast(
+///     client: &HttpClient,
+///     location: &str,
+///     days: u32,
+/// ) -> Result<Vec<Forecast>, ProviderError> {
+///     // your implementation
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn trace_fn_io(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as ItemFn);
+    expand_trace_fn_io(attr.into(), input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn parse_fn_args(attr: TokenStream2) -> syn::Result<TraceFnIoArgs> {
+    let mut args = TraceFnIoArgs::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            args.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("skip") {
+            meta.parse_nested_meta(|skipped| {
+                args.skip.push(skipped.path.require_ident()?.clone());
+                Ok(())
+            })
+        } else if meta.path.is_ident("fields") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            args.fields = Some(content.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported trace_fn_io argument, expected `name`, `skip` or `fields`"))
+        }
+    });
+    parser.parse2(attr)?;
+    Ok(args)
+}
+
+fn expand_trace_fn_io(attr: TokenStream2, input: ItemFn) -> syn::Result<TokenStream2> {
+    let args = parse_fn_args(attr)?;
+    let sig = &input.sig;
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            sig.fn_token,
+            "#[trace_fn_io] only supports async fns",
+        ));
+    }
+    if let ReturnType::Default = sig.output {
+        return Err(syn::Error::new_spanned(
+            sig,
+            "#[trace_fn_io] expects a fn returning `Result<T, E>`",
+        ));
+    }
+
+    // Arguments recorded by name
+    let mut names = Vec::new();
+    for input in &sig.inputs {
+        let FnArg::Typed(typed) = input else {
+            continue;
+        };
+        let Pat::Ident(pat) = &*typed.pat else {
+            return Err(syn::Error::new_spanned(
+                &typed.pat,
+                "#[trace_fn_io] records arguments by name; bind this one to a name",
+            ));
+        };
+        if !args.skip.contains(&pat.ident) {
+            names.push(pat.ident.clone());
+        }
+    }
+    let keys = names.iter().map(|name| name.to_string());
+
+    let mut skip: Vec<TokenStream2> = args.skip.iter().map(|name| quote!(#name)).collect();
+    if sig.receiver().is_some() {
+        skip.insert(0, quote!(self));
+    }
+    let skip = (!skip.is_empty()).then(|| quote!(skip(#(#skip),*),));
+    let name = args.name.as_ref().map(|name| quote!(name = #name,));
+    let fields = args.fields.as_ref().map(|fields| quote!(, #fields));
+
+    let attrs = &input.attrs;
+    let vis = &input.vis;
+    let block = &input.block;
+    Ok(quote! {
+        #(#attrs)*
+        #[tracing::instrument(#name #skip fields(
+            input = tracing::field::Empty,
+            output = tracing::field::Empty
+            #fields
+        ))]
+        #vis #sig {
+            // Record input, skipping serialization when the span is not exported
+            let recorded = crate::trace_utils::is_recorded(&tracing::Span::current());
+            if recorded {
+                let input_json = serde_json::json!({ #(#keys: &#names),* });
+                tracing::Span::current().record("input", tracing::field::display(&input_json.to_string()));
+            }
+
+            let result = async move { #block }.await;
+
+            if let (true, Ok(output)) = (recorded, &result) {
+                let output_json = serde_json::json!(output);
+                tracing::Span::current().record("output", tracing::field::display(&output_json.to_string()));
+            }
+
+            result
+        }
+    })
+}
+
+/// Derives `trace_utils::Sensitive` for an arguments struct, listing the
+/// fields marked `#[sensitive]`. `#[trace_io]` then records their values as
+/// `"***"` in the `input` attribute, leaving the other fields readable.
+///
+/// A field's `#[serde(rename = "...")]` is honoured; `rename_all` is not.
+///
+/// Usage:
+/// ```rust,ignore
+/// #[derive(Deserialize, Serialize, JsonSchema, Sensitive)]
+/// struct LoginArgs {
+///     user: String,
+///     #[sensitive]
+///     api_key: String,
+/// }
+/// ```
+#[proc_macro_derive(Sensitive, attributes(sensitive))]
+pub fn derive_sensitive(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    expand_sensitive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_sensitive(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Sensitive can only be derived for structs with named fields",
+            ))
+        }
     };
 
-    TokenStream::from(result)
+    let keys = fields
+        .iter()
+        .filter(|field| {
+            field
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("sensitive"))
+        })
+        .map(serde_key)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crate::trace_utils::Sensitive for #name #type_generics #where_clause {
+            const SENSITIVE_FIELDS: &'static [&'static str] = &[#(#keys),*];
+        }
+    })
+}
+
+/// JSON key of `field`: its `#[serde(rename = "...")]`, else its name.
+fn serde_key(field: &syn::Field) -> syn::Result<String> {
+    let mut key = field
+        .ident
+        .as_ref()
+        .expect("named fields have identifiers")
+        .to_string();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("serde"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") && meta.input.peek(syn::Token![=]) {
+                key = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<syn::Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.input.parse::<proc_macro2::Group>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pretty(tokens: TokenStream2) -> String {
+        prettyplease::unparse(&syn::parse2(tokens).expect("expansion is a valid file"))
+    }
+
+    fn tool() -> ItemFn {
+        syn::parse_quote! {
+            async fn get_weather(
+                &self,
+                _request_context: RequestContext<RoleServer>,
+                params: Parameters<GetWeatherArgs>,
+            ) -> Result<CallToolResult, McpError> {
+                weather(&args.location)
+            }
+        }
+    }
+
+    #[test]
+    fn trace_io_expansion() {
+        insta::assert_snapshot!(pretty(expand_trace_io(quote!(), tool()).unwrap()));
+    }
+
+    #[test]
+    fn trace_io_expansion_with_metrics_and_output_extractor() {
+        let attr = quote!(metrics = true, output_with = "Self::summarize");
+        insta::assert_snapshot!(pretty(expand_trace_io(attr, tool()).unwrap()));
+    }
+
+    #[test]
+    fn trace_fn_io_expansion() {
+        let attr = quote!(
+            name = "provider.forecast",
+            skip(client),
+            fields(provider = "mock")
+        );
+        let input = syn::parse_quote! {
+            async fn forecast(
+                &self,
+                client: &Client,
+                location: &str,
+                days: u32,
+            ) -> Result<Vec<Forecast>, ProviderError> {
+                client.forecast(location, days).await
+            }
+        };
+        insta::assert_snapshot!(pretty(expand_trace_fn_io(attr, input).unwrap()));
+    }
+
+    #[test]
+    fn sensitive_expansion() {
+        let input = syn::parse_quote! {
+            struct LoginArgs<T> {
+                user: String,
+                #[sensitive]
+                #[serde(default, rename = "apiKey")]
+                api_key: String,
+                #[sensitive]
+                token: T,
+            }
+        };
+        insta::assert_snapshot!(pretty(expand_sensitive(input).unwrap()));
+    }
 }
@@ -0,0 +1,7 @@
+//! Misuses of the macros that must fail to compile with a clear message.
+//! Refresh the expected output with `TRYBUILD=overwrite cargo test`.
+
+#[test]
+fn compile_fail() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,309 @@
+//! Runs `#[trace_io]` tools under the OpenTelemetry layer with in-memory
+//! exporters, and checks the attributes and metrics they record.
+//!
+//! The expanded code calls into `crate::trace_utils`; the module below
+//! stands in for the server's.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, Value};
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use rmcp::handler::server::wrapper::Parameters;
+use rmcp::model::{CallToolResult, Content};
+use rmcp::ErrorData as McpError;
+use rmcp_trace_macro::{trace_fn_io, trace_io, Sensitive};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::layer::SubscriberExt;
+
+mod trace_utils {
+    use opentelemetry::trace::TraceContextExt;
+    use serde::Serialize;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    pub fn call_trace_context<C>(_: &C) -> Option<opentelemetry::Context> {
+        None
+    }
+
+    pub fn is_recorded(span: &tracing::Span) -> bool {
+        !span.is_disabled() && span.context().span().span_context().is_sampled()
+    }
+
+    pub trait Sensitive {
+        const SENSITIVE_FIELDS: &'static [&'static str];
+    }
+
+    pub struct InputArgs<'a, T>(pub &'a T);
+
+    pub trait MaskSensitive {
+        fn to_input_json(&self) -> serde_json::Value;
+    }
+
+    impl<T: Serialize + Sensitive> MaskSensitive for InputArgs<'_, T> {
+        fn to_input_json(&self) -> serde_json::Value {
+            let mut input = serde_json::json!(self.0);
+            for name in T::SENSITIVE_FIELDS {
+                input[*name] = "***".into();
+            }
+            input
+        }
+    }
+
+    pub trait MaskNothing {
+        fn to_input_json(&self) -> serde_json::Value;
+    }
+
+    impl<T: Serialize> MaskNothing for &InputArgs<'_, T> {
+        fn to_input_json(&self) -> serde_json::Value {
+            serde_json::json!(self.0)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LocationArgs {
+    location: String,
+}
+
+#[derive(Serialize, Deserialize, Sensitive)]
+struct LoginArgs {
+    user: String,
+    #[sensitive]
+    #[serde(rename = "apiKey")]
+    api_key: String,
+}
+
+struct Tools;
+
+impl Tools {
+    #[trace_io]
+    async fn get_weather(
+        &self,
+        _request_context: (),
+        params: Parameters<LocationArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        if args.location.is_empty() {
+            Err(McpError::invalid_params("location is required", None))?;
+        }
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Sunny in {}",
+            args.location
+        ))]))
+    }
+
+    #[trace_io]
+    async fn login(
+        &self,
+        _request_context: (),
+        params: Parameters<LoginArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        Ok(CallToolResult::success(vec![Content::text(args.user)]))
+    }
+
+    #[trace_io(metrics = true, output_with = "Self::content_kinds")]
+    async fn get_map(
+        &self,
+        _request_context: (),
+        params: Parameters<LocationArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        if args.location == "nowhere" {
+            return Ok(CallToolResult::error(vec![Content::text("no map")]));
+        }
+        Ok(CallToolResult::success(vec![
+            Content::image("iVBORw0KGgo=", "image/png"),
+            Content::text(format!("Map of {}", args.location)),
+        ]))
+    }
+
+    fn content_kinds(result: &CallToolResult) -> Vec<&'static str> {
+        result
+            .content
+            .iter()
+            .map(|content| {
+                if content.as_image().is_some() {
+                    "image"
+                } else {
+                    "text"
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct Client;
+
+#[derive(Debug, Serialize)]
+struct Forecast {
+    day: u32,
+    location: String,
+}
+
+#[trace_fn_io(name = "provider.forecast", skip(client), fields(provider = "mock"))]
+async fn forecast(client: &Client, location: &str, days: u32) -> Result<Vec<Forecast>, String> {
+    let _ = client;
+    if days == 0 {
+        return Err("no days".to_string());
+    }
+    Ok((0..days)
+        .map(|day| Forecast {
+            day,
+            location: location.to_string(),
+        })
+        .collect())
+}
+
+/// Run `call` with the OpenTelemetry layer installed and return the
+/// exported spans.
+async fn traced<F: std::future::Future>(call: F) -> Vec<SpanData> {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let _guard = tracing::subscriber::set_default(subscriber);
+    call.await;
+    exporter.get_finished_spans().unwrap()
+}
+
+fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+    span.attributes
+        .iter()
+        .find(|attribute| attribute.key.as_str() == key)
+        .map(|attribute| attribute.value.clone())
+}
+
+fn location(location: &str) -> Parameters<LocationArgs> {
+    Parameters(LocationArgs {
+        location: location.to_string(),
+    })
+}
+
+#[tokio::test]
+async fn input_and_first_text_output_are_recorded() {
+    let spans = traced(Tools.get_weather((), location("London"))).await;
+
+    let span = spans
+        .iter()
+        .find(|span| span.name == "get_weather")
+        .unwrap();
+    assert_eq!(
+        attribute(span, "input"),
+        Some(r#"{"location":"London"}"#.into())
+    );
+    assert_eq!(attribute(span, "output"), Some("Sunny in London".into()));
+}
+
+#[tokio::test]
+async fn errors_leave_the_output_unrecorded() {
+    let spans = traced(async {
+        assert!(Tools.get_weather((), location("")).await.is_err());
+    })
+    .await;
+
+    let span = spans
+        .iter()
+        .find(|span| span.name == "get_weather")
+        .unwrap();
+    assert_eq!(attribute(span, "input"), Some(r#"{"location":""}"#.into()));
+    assert_eq!(attribute(span, "output"), None);
+}
+
+#[tokio::test]
+async fn sensitive_fields_are_masked() {
+    let params = Parameters(LoginArgs {
+        user: "ada".to_string(),
+        api_key: "secret".to_string(),
+    });
+    let spans = traced(Tools.login((), params)).await;
+
+    let span = spans.iter().find(|span| span.name == "login").unwrap();
+    assert_eq!(
+        attribute(span, "input"),
+        Some(r#"{"apiKey":"***","user":"ada"}"#.into())
+    );
+}
+
+#[tokio::test]
+async fn extractor_output_and_metrics_are_recorded() {
+    let exporter = InMemoryMetricExporter::default();
+    let meter_provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter.clone()).build())
+        .build();
+    global::set_meter_provider(meter_provider.clone());
+
+    let spans = traced(async {
+        Tools.get_map((), location("London")).await.unwrap();
+        Tools.get_map((), location("nowhere")).await.unwrap();
+    })
+    .await;
+    meter_provider.force_flush().unwrap();
+
+    let span = spans.iter().find(|span| span.name == "get_map").unwrap();
+    assert_eq!(
+        attribute(span, "output"),
+        Some(r#"["image","text"]"#.into())
+    );
+
+    let metrics = exporter.get_finished_metrics().unwrap();
+    let metric = |name: &str| {
+        metrics
+            .iter()
+            .flat_map(|resource| resource.scope_metrics())
+            .flat_map(|scope| scope.metrics())
+            .find(|metric| metric.name() == name)
+            .unwrap()
+    };
+    let AggregatedMetrics::U64(MetricData::Sum(calls)) = metric("tool.calls").data() else {
+        panic!("tool.calls is not a u64 counter");
+    };
+    let mut outcomes: Vec<(String, u64)> = calls
+        .data_points()
+        .map(|point| {
+            let outcome = point
+                .attributes()
+                .find(|attribute| attribute.key.as_str() == "outcome")
+                .unwrap();
+            (outcome.value.to_string(), point.value())
+        })
+        .collect();
+    outcomes.sort();
+    assert_eq!(outcomes, [("error".to_string(), 1), ("ok".to_string(), 1)]);
+    let AggregatedMetrics::F64(MetricData::Histogram(duration)) = metric("tool.duration").data()
+    else {
+        panic!("tool.duration is not an f64 histogram");
+    };
+    assert_eq!(
+        duration
+            .data_points()
+            .map(|point| point.count())
+            .sum::<u64>(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn function_arguments_are_recorded_by_name() {
+    let spans = traced(async {
+        forecast(&Client, "London", 2).await.unwrap();
+        forecast(&Client, "London", 0).await.unwrap_err();
+    })
+    .await;
+
+    let [ok, err] = spans.as_slice() else {
+        panic!("expected two spans, got {}", spans.len());
+    };
+    assert_eq!(ok.name, "provider.forecast");
+    assert_eq!(attribute(ok, "provider"), Some("mock".into()));
+    assert_eq!(
+        attribute(ok, "input"),
+        Some(r#"{"days":2,"location":"London"}"#.into())
+    );
+    assert_eq!(
+        attribute(ok, "output"),
+        Some(r#"[{"day":0,"location":"London"},{"day":1,"location":"London"}]"#.into())
+    );
+    assert_eq!(attribute(ok, "client"), None);
+    assert_eq!(attribute(err, "output"), None);
+}
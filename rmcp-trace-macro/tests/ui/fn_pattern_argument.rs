@@ -0,0 +1,8 @@
+use rmcp_trace_macro::trace_fn_io;
+
+#[trace_fn_io]
+async fn distance((from, to): (u32, u32)) -> Result<u32, ()> {
+    Ok(to - from)
+}
+
+fn main() {}
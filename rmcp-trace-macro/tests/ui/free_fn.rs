@@ -0,0 +1,8 @@
+use rmcp_trace_macro::trace_io;
+
+#[trace_io]
+async fn get_weather(_request_context: (), params: ()) -> Result<(), ()> {
+    Ok(params)
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use rmcp_trace_macro::trace_io;
+
+struct Tools;
+
+impl Tools {
+    #[trace_io]
+    async fn get_weather(&self, _request_context: (), args: ()) -> Result<(), ()> {
+        Ok(args)
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use rmcp_trace_macro::Sensitive;
+
+#[derive(Sensitive)]
+enum Credentials {
+    ApiKey(String),
+    Token(String),
+}
+
+fn main() {}
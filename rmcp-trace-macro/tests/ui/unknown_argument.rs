@@ -0,0 +1,12 @@
+use rmcp_trace_macro::trace_io;
+
+struct Tools;
+
+impl Tools {
+    #[trace_io(metric = true)]
+    async fn get_weather(&self, _request_context: (), params: ()) -> Result<(), ()> {
+        Ok(params)
+    }
+}
+
+fn main() {}
@@ -0,0 +1,104 @@
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::config::AccessConfig;
+
+/// Client network allow/deny lists, enforced before any route runs.
+#[derive(Debug)]
+pub struct AccessControl {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl AccessControl {
+    /// `None` when neither list is configured, so every client is served.
+    pub fn from_config(config: &AccessConfig) -> Option<Arc<Self>> {
+        if config.allow.is_empty() && config.deny.is_empty() {
+            return None;
+        }
+        Some(Arc::new(Self {
+            allow: config.allow.clone(),
+            deny: config.deny.clone(),
+        }))
+    }
+
+    /// Denied networks win; with an allowlist, anything outside it is denied.
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        // IPv4 clients on a dual-stack socket show up as ::ffff:a.b.c.d
+        let ip = ip.to_canonical();
+        if self.deny.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// Answer 403 to clients outside the configured networks, without touching
+/// the MCP service, and record the attempt on the `audit` target.
+pub async fn enforce(
+    State(access): State<Arc<AccessControl>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if access.permits(peer.ip()) {
+        return next.run(request).await;
+    }
+
+    tracing::warn!(
+        target: "audit",
+        client = %peer.ip(),
+        method = %request.method(),
+        path = request.uri().path(),
+        "Rejected request from a disallowed network"
+    );
+    (StatusCode::FORBIDDEN, "client address not allowed\n").into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(allow: &[&str], deny: &[&str]) -> Arc<AccessControl> {
+        let nets = |cidrs: &[&str]| cidrs.iter().map(|cidr| cidr.parse().unwrap()).collect();
+        AccessControl::from_config(&AccessConfig {
+            allow: nets(allow),
+            deny: nets(deny),
+        })
+        .unwrap()
+    }
+
+    fn permits(access: &AccessControl, ip: &str) -> bool {
+        access.permits(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn allowlists_admit_only_their_networks() {
+        let access = control(&["10.0.0.0/8", "2001:db8::/32"], &[]);
+        assert!(permits(&access, "10.1.2.3"));
+        assert!(permits(&access, "2001:db8::1"));
+        assert!(!permits(&access, "11.0.0.1"));
+        assert!(!permits(&access, "2001:db9::1"));
+        // IPv4 clients seen on a dual-stack socket
+        assert!(permits(&access, "::ffff:10.1.2.3"));
+        assert!(!permits(&access, "::ffff:11.0.0.1"));
+    }
+
+    #[test]
+    fn denied_networks_win_over_allowed_ones() {
+        let access = control(&["10.0.0.0/8"], &["10.0.5.0/24"]);
+        assert!(permits(&access, "10.0.4.255"));
+        assert!(!permits(&access, "10.0.5.1"));
+        assert!(!permits(&access, "::ffff:10.0.5.1"));
+
+        let deny_only = control(&[], &["192.168.0.0/16"]);
+        assert!(!permits(&deny_only, "192.168.1.1"));
+        assert!(permits(&deny_only, "8.8.8.8"));
+        assert!(AccessControl::from_config(&AccessConfig::default()).is_none());
+    }
+}
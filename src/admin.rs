@@ -0,0 +1,210 @@
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::export_stats::{ExportStats, ExportTotals};
+use crate::span_repair::{self, PendingSession};
+use crate::tool_aliases::DeprecationStats;
+use crate::tracing_setup::{ExporterHandle, LogLevelHandle, Telemetry};
+
+/// Operational endpoints, mounted only when `ADMIN_TOKEN` is configured.
+#[derive(Clone)]
+pub struct AdminState {
+    token: Arc<str>,
+    log_level: LogLevelHandle,
+    tracer_provider: SdkTracerProvider,
+    exporter: ExporterHandle,
+    export_stats: Arc<ExportStats>,
+    deprecations: Arc<DeprecationStats>,
+}
+
+impl AdminState {
+    pub fn new(token: &str, telemetry: &Telemetry, deprecations: Arc<DeprecationStats>) -> Self {
+        Self {
+            token: token.into(),
+            log_level: telemetry.log_level.clone(),
+            tracer_provider: telemetry.tracer_provider.clone(),
+            exporter: telemetry.exporter.clone(),
+            export_stats: telemetry.export_stats.clone(),
+            deprecations,
+        }
+    }
+}
+
+pub fn router(state: AdminState) -> Router {
+    Router::new()
+        .route("/admin/log-level", get(get_log_level).put(put_log_level))
+        .route("/admin/flush-traces", post(flush_traces))
+        .route("/admin/exporter", get(get_exporter).put(put_exporter))
+        .route("/admin/deprecations", get(get_deprecations))
+        .route(
+            "/admin/trace-store",
+            get(get_trace_store).delete(delete_trace_store),
+        )
+        .route(
+            "/admin/trace-store/:session_id",
+            delete(delete_trace_store_session),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+/// Reject requests without `Authorization: Bearer <ADMIN_TOKEN>`.
+async fn require_token(State(state): State<AdminState>, request: Request, next: Next) -> Response {
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match presented {
+        Some(token) if constant_time_eq(token.as_bytes(), state.token.as_bytes()) => {
+            next.run(request).await
+        }
+        _ => (StatusCode::UNAUTHORIZED, "missing or invalid admin token\n").into_response(),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Current log filter in `RUST_LOG` syntax.
+async fn get_log_level(State(state): State<AdminState>) -> String {
+    format!("{}\n", state.log_level.current())
+}
+
+/// Replace the log filter with the request body, e.g. `debug,rmcp=info`.
+async fn put_log_level(State(state): State<AdminState>, body: String) -> Response {
+    match state.log_level.set(body.trim()) {
+        Ok(()) => format!("{}\n", state.log_level.current()).into_response(),
+        Err(error) => (StatusCode::BAD_REQUEST, format!("{error:#}\n")).into_response(),
+    }
+}
+
+#[derive(Serialize)]
+struct FlushReport {
+    flushed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Totals since startup; after a successful flush nothing is left queued.
+    spans: ExportTotals,
+}
+
+/// Export every queued span now and report the pipeline totals.
+async fn flush_traces(State(state): State<AdminState>) -> Response {
+    // Flushing blocks on the batch processor, which runs on this runtime.
+    let provider = state.tracer_provider.clone();
+    let result = tokio::task::spawn_blocking(move || provider.force_flush()).await;
+
+    let error = match result {
+        Ok(Ok(())) => None,
+        Ok(Err(error)) => Some(error.to_string()),
+        Err(join_error) => Some(join_error.to_string()),
+    };
+    let report = FlushReport {
+        flushed: error.is_none(),
+        error,
+        spans: state.export_stats.totals(),
+    };
+    let status = if report.flushed {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_GATEWAY
+    };
+    (status, Json(report)).into_response()
+}
+
+#[derive(Serialize)]
+struct ExporterReport {
+    host: String,
+    /// `None` when spans are not exported to a default project.
+    public_key: Option<String>,
+    /// Tenants exporting to their own projects, unaffected by updates.
+    tenants: Vec<String>,
+}
+
+impl ExporterReport {
+    fn of(exporter: &ExporterHandle) -> Self {
+        let target = exporter.target();
+        Self {
+            host: target.host,
+            public_key: target.public_key,
+            tenants: exporter.tenants(),
+        }
+    }
+}
+
+/// Fields to change; the others keep their current value.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExporterUpdate {
+    host: Option<String>,
+    public_key: Option<String>,
+    secret_key: Option<String>,
+}
+
+/// Langfuse host and public key spans are exported with.
+async fn get_exporter(State(state): State<AdminState>) -> Json<ExporterReport> {
+    Json(ExporterReport::of(&state.exporter))
+}
+
+/// Rotate the Langfuse keys or switch host without a restart, e.g.
+/// `{"public_key": "pk-lf-...", "secret_key": "sk-lf-..."}`.
+async fn put_exporter(
+    State(state): State<AdminState>,
+    Json(update): Json<ExporterUpdate>,
+) -> Response {
+    let mut target = state.exporter.target();
+    if let Some(host) = update.host {
+        target.host = host;
+    }
+    if let Some(public_key) = update.public_key {
+        target.public_key = Some(public_key);
+    }
+    if let Some(secret_key) = update.secret_key {
+        target.secret_key = Some(secret_key);
+    }
+    match state.exporter.reconfigure(target).await {
+        Ok(()) => Json(ExporterReport::of(&state.exporter)).into_response(),
+        Err(error) => (StatusCode::BAD_REQUEST, format!("{error:#}\n")).into_response(),
+    }
+}
+
+/// Calls made through each deprecated tool name since startup.
+async fn get_deprecations(State(state): State<AdminState>) -> Json<BTreeMap<&'static str, u64>> {
+    Json(state.deprecations.totals())
+}
+
+/// Sessions whose rmcp transport spans are still waiting to be moved into
+/// the session's trace, with the trace id and how long they have waited.
+async fn get_trace_store() -> Json<Vec<PendingSession>> {
+    Json(span_repair::pending())
+}
+
+#[derive(Serialize)]
+struct ForgetReport {
+    forgotten: usize,
+}
+
+/// Forget every waiting session.
+async fn delete_trace_store() -> Json<ForgetReport> {
+    Json(ForgetReport {
+        forgotten: span_repair::forget(None),
+    })
+}
+
+/// Forget one waiting session; 404 when it is not waiting.
+async fn delete_trace_store_session(Path(session_id): Path<String>) -> Response {
+    match span_repair::forget(Some(&session_id)) {
+        0 => (StatusCode::NOT_FOUND, "session not in the trace store\n").into_response(),
+        forgotten => Json(ForgetReport { forgotten }).into_response(),
+    }
+}
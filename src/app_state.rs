@@ -0,0 +1,265 @@
+use anyhow::Result;
+use rmcp::transport::streamable_http_server::session::local::LocalSessionManager;
+use std::sync::Arc;
+
+use crate::audit_log::AuditLog;
+use crate::concurrency::ConcurrencyLimits;
+use crate::config::Config;
+use crate::cpu_pool::CpuPool;
+use crate::http_client::HttpClient;
+use crate::keepalive::KeepaliveStats;
+use crate::langfuse_client::LangfuseClient;
+use crate::preferences::PreferenceStore;
+use crate::priority::PriorityLanes;
+use crate::providers::{build_provider, check_location, ProviderError, WeatherProvider};
+use crate::rbac::Rbac;
+use crate::recording::Recorder;
+use crate::shutdown::ShutdownHooks;
+use crate::slo::SloTracker;
+use crate::templates::Templates;
+use crate::tool_aliases::DeprecationStats;
+use crate::usage_stats::UsageStats;
+use crate::watches::WatchRegistry;
+use crate::weather_tools::{
+    Forecast, MarineConditions, PollenDay, SnowReport, UvIndexDay, Weather, WeatherService,
+};
+use crate::webhooks::WebhookRegistry;
+
+/// Longest forecast the tools will return.
+pub const MAX_FORECAST_DAYS: u32 = 7;
+
+/// Longest forecast with `long_range`, from providers that support it.
+pub const MAX_LONG_RANGE_DAYS: u32 = 16;
+
+/// Most past days the history covers.
+pub const MAX_HISTORY_DAYS: u32 = 30;
+
+/// Process-wide state shared by every MCP session.
+///
+/// `StreamableHttpService` builds a fresh `WeatherService` per session, so
+/// anything that must outlive a session (connection pools, caches) lives here.
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    #[allow(dead_code)]
+    pub http_client: HttpClient,
+    pub provider: Arc<dyn WeatherProvider>,
+    /// Langfuse REST client for scores, absent without credentials.
+    pub langfuse: Option<LangfuseClient>,
+    /// Tool call audit trail, absent without `AUDIT_LOG_PATH`.
+    pub audit: Option<AuditLog>,
+    /// Tool calls with their results, absent without `TOOL_RECORDING_PATH`.
+    pub recorder: Option<Recorder>,
+    /// Tool permissions by role, absent without `RBAC_ROLES`.
+    pub rbac: Option<Arc<Rbac>>,
+    /// Caps on simultaneous calls per tool, absent without `TOOL_CONCURRENCY`.
+    pub concurrency: Option<Arc<ConcurrencyLimits>>,
+    /// Slots shared by high and low priority calls, absent without
+    /// `TOOL_PRIORITY_SLOTS`.
+    pub priority: Option<Arc<PriorityLanes>>,
+    /// Latency objectives per tool, absent without `TOOL_SLO`.
+    pub slo: Option<Arc<SloTracker>>,
+    /// Threads for CPU-bound tool work, off the async runtime.
+    pub cpu_pool: Arc<CpuPool>,
+    /// Preferences by user, seeding each new session's.
+    pub preferences: PreferenceStore,
+    /// Built-in text templates, with overrides from `TEMPLATE_DIR`.
+    pub templates: Arc<Templates>,
+    /// Calls made through deprecated tool names.
+    pub deprecations: Arc<DeprecationStats>,
+    /// Calls, errors and latency per tool since startup.
+    pub usage: Arc<UsageStats>,
+    /// Open MCP sessions of the streamable HTTP transport.
+    pub sessions: Arc<LocalSessionManager>,
+    /// Keepalive ping outcomes across all sessions.
+    pub keepalive: Arc<KeepaliveStats>,
+    /// URLs notified of weather alerts, registered with `register_webhook`.
+    pub webhooks: WebhookRegistry,
+    /// Threshold rules of every session, created with `create_watch`.
+    pub watches: WatchRegistry,
+    /// Run once the HTTP server has stopped.
+    pub shutdown: ShutdownHooks,
+}
+
+impl AppState {
+    pub fn new(config: Config) -> Result<Self> {
+        let http_client = HttpClient::new(&config.http_client)?;
+        let provider = build_provider(&config, &http_client);
+        let langfuse = LangfuseClient::from_env(http_client.clone());
+        let audit = AuditLog::start(&config.audit)?;
+        let recorder = Recorder::open(config.recording_path.as_deref())?;
+        let rbac = Rbac::from_config(&config.rbac);
+        let concurrency = ConcurrencyLimits::from_config(
+            &config.concurrency,
+            &WeatherService::tool_definitions(),
+        )?;
+        let priority = PriorityLanes::from_config(&config.priority);
+        let slo = SloTracker::from_config(&config.slo);
+        let cpu_pool = Arc::new(CpuPool::new(config.cpu_pool_threads)?);
+        let preferences = PreferenceStore::open(config.preferences_path.as_deref())?;
+        let templates = Arc::new(Templates::load(config.template_dir.as_deref())?);
+
+        let shutdown = ShutdownHooks::default();
+        if let Some(audit) = audit.clone() {
+            shutdown.register(
+                "audit_log.flush",
+                config.server.shutdown_hook_timeout,
+                move || async move { audit.flush().await },
+            );
+        }
+
+        tracing::info!(provider = provider.name(), "Weather provider configured");
+        tracing::info!(threads = cpu_pool.threads(), "CPU pool started");
+        if let Some(path) = &config.audit.path {
+            tracing::info!(path = %path.display(), "Auditing tool calls");
+        }
+        if let Some(path) = &config.recording_path {
+            tracing::info!(path = %path.display(), "Recording tool calls");
+        }
+        if config.webhooks.signing_secret.is_some() {
+            tracing::info!("Webhook alerts enabled");
+        }
+        if concurrency.is_some() {
+            tracing::info!(
+                limits = ?config.concurrency.limits,
+                "Tool concurrency limited"
+            );
+        }
+        if let Some(slots) = config.priority.slots {
+            tracing::info!(
+                slots,
+                tools = ?config.priority.tools,
+                users = config.priority.users.len(),
+                "Tool calls scheduled by priority"
+            );
+        }
+        if slo.is_some() {
+            let objectives: Vec<String> = config
+                .slo
+                .objectives
+                .iter()
+                .map(|(tool, objective)| format!("{tool}={objective}"))
+                .collect();
+            tracing::info!(
+                ?objectives,
+                window = ?config.slo.window,
+                "Tracking tool latency SLOs"
+            );
+        }
+        if rbac.is_some() {
+            tracing::info!(
+                roles = ?config.rbac.roles.keys().collect::<Vec<_>>(),
+                "Tool access restricted by role"
+            );
+        }
+
+        Ok(Self {
+            config: Arc::new(config),
+            http_client,
+            provider,
+            langfuse,
+            audit,
+            recorder,
+            rbac,
+            concurrency,
+            priority,
+            slo,
+            cpu_pool,
+            preferences,
+            templates,
+            deprecations: Arc::default(),
+            usage: Arc::default(),
+            sessions: Arc::default(),
+            keepalive: Arc::default(),
+            webhooks: WebhookRegistry::default(),
+            watches: WatchRegistry::default(),
+            shutdown,
+        })
+    }
+}
+
+impl AppState {
+    /// Current conditions, shared by the MCP tools and the other facades.
+    /// Every lookup refuses overlong locations first.
+    pub async fn current_weather(&self, location: &str) -> Result<Weather, ProviderError> {
+        check_location(location)?;
+        self.provider.current(location).await
+    }
+
+    /// Daily forecast capped at [`MAX_FORECAST_DAYS`].
+    pub async fn forecast(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<Forecast>, ProviderError> {
+        check_location(location)?;
+        self.provider
+            .forecast(location, days.min(MAX_FORECAST_DAYS))
+            .await
+    }
+
+    /// Daily forecast of up to [`MAX_LONG_RANGE_DAYS`], failing for
+    /// providers that do not reach `days`.
+    pub async fn long_range_forecast(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<Forecast>, ProviderError> {
+        check_location(location)?;
+        let max_days = self.provider.max_forecast_days();
+        if days > max_days {
+            return Err(ProviderError::ForecastTooLong {
+                provider: self.provider.name(),
+                max_days,
+            });
+        }
+        self.provider.forecast(location, days).await
+    }
+
+    /// Daily conditions of the past days, capped at [`MAX_HISTORY_DAYS`].
+    pub async fn history(&self, location: &str, days: u32) -> Result<Vec<Forecast>, ProviderError> {
+        check_location(location)?;
+        self.provider
+            .history(location, days.min(MAX_HISTORY_DAYS))
+            .await
+    }
+
+    /// Daily UV index capped at [`MAX_FORECAST_DAYS`].
+    pub async fn uv_index(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<UvIndexDay>, ProviderError> {
+        check_location(location)?;
+        self.provider
+            .uv_index(location, days.min(MAX_FORECAST_DAYS))
+            .await
+    }
+
+    /// Daily pollen levels capped at [`MAX_FORECAST_DAYS`].
+    pub async fn pollen_forecast(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<PollenDay>, ProviderError> {
+        check_location(location)?;
+        self.provider
+            .pollen(location, days.min(MAX_FORECAST_DAYS))
+            .await
+    }
+
+    /// Current wave and sea conditions.
+    pub async fn marine_conditions(
+        &self,
+        location: &str,
+    ) -> Result<MarineConditions, ProviderError> {
+        check_location(location)?;
+        self.provider.marine(location).await
+    }
+
+    /// Current snow depth and recent snowfall.
+    pub async fn snow_report(&self, location: &str) -> Result<SnowReport, ProviderError> {
+        check_location(location)?;
+        self.provider.snow(location).await
+    }
+}
@@ -0,0 +1,166 @@
+//! Sun and moon data computed locally, without an upstream service.
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use std::f64::consts::PI;
+
+use crate::forecast_time::{self, Place};
+use crate::weather_tools::Astronomy;
+
+/// Mean length of a lunar cycle, new moon to new moon.
+const SYNODIC_MONTH_DAYS: f64 = 29.530_588_853;
+
+/// Julian day of a known new moon (2000-01-06 18:14 UTC).
+const REFERENCE_NEW_MOON_JD: f64 = 2_451_550.1;
+
+/// Phase names, each covering an eighth of the cycle centred on its point.
+const PHASE_NAMES: [&str; 8] = [
+    "New Moon",
+    "Waxing Crescent",
+    "First Quarter",
+    "Waxing Gibbous",
+    "Full Moon",
+    "Waning Gibbous",
+    "Last Quarter",
+    "Waning Crescent",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoonPhase {
+    pub name: &'static str,
+    /// Days since the last new moon.
+    pub age_days: f64,
+    /// Illuminated fraction of the disc, 0 to 1.
+    pub illumination: f64,
+}
+
+/// Moon phase at `at`, from the mean lunar cycle (accurate to about half a day).
+pub fn moon_phase(at: DateTime<Utc>) -> MoonPhase {
+    let julian_day = at.timestamp() as f64 / 86_400.0 + 2_440_587.5;
+    let age_days = (julian_day - REFERENCE_NEW_MOON_JD).rem_euclid(SYNODIC_MONTH_DAYS);
+    let cycle = age_days / SYNODIC_MONTH_DAYS;
+    let index = (cycle * 8.0).round() as usize % PHASE_NAMES.len();
+    MoonPhase {
+        name: PHASE_NAMES[index],
+        age_days,
+        illumination: (1.0 - (2.0 * PI * cycle).cos()) / 2.0,
+    }
+}
+
+/// Sunrise, sunset and moon phase on `date` at `place`; the moon is taken
+/// at local noon.
+pub fn astronomy(location: &str, place: &Place, date: NaiveDate) -> Astronomy {
+    let (sunrise, sunset) = forecast_time::sun_times(place, date).unzip();
+    let noon = forecast_time::localize(place.timezone, date.and_time(NaiveTime::MIN))
+        .map(|midnight| midnight + chrono::Duration::hours(12))
+        .map(|noon| noon.with_timezone(&Utc))
+        .unwrap_or_else(|| date.and_hms_opt(12, 0, 0).unwrap_or_default().and_utc());
+    let moon = moon_phase(noon);
+
+    Astronomy {
+        location: location.to_string(),
+        date: date.to_string(),
+        timezone: place.timezone.name().to_string(),
+        sunrise: sunrise.as_ref().map(forecast_time::format_local),
+        sunset: sunset.as_ref().map(forecast_time::format_local),
+        day_length_minutes: sunrise
+            .zip(sunset)
+            .map(|(sunrise, sunset)| (sunset - sunrise).num_minutes()),
+        moon_phase: moon.name.to_string(),
+        moon_illumination: (moon.illumination * 100.0).round() as i32,
+        moon_age_days: (moon.age_days * 10.0).round() / 10.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe;
+
+    fn utc(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn paris() -> Place {
+        forecast_time::bundled_place("paris").unwrap()
+    }
+
+    #[test]
+    fn moon_phases_of_april_2024() {
+        // Published times: new 04-08 18:21, first quarter 04-15 19:13,
+        // full 04-23 23:49, last quarter 05-01 11:27 (UTC)
+        let new = moon_phase(utc("2024-04-08T18:21:00Z"));
+        assert_eq!(new.name, "New Moon");
+        assert!(new.illumination < 0.01);
+
+        let first_quarter = moon_phase(utc("2024-04-15T19:13:00Z"));
+        assert_eq!(first_quarter.name, "First Quarter");
+        assert!((0.4..0.6).contains(&first_quarter.illumination));
+
+        let full = moon_phase(utc("2024-04-23T23:49:00Z"));
+        assert_eq!(full.name, "Full Moon");
+        assert!(full.illumination > 0.99);
+
+        let last_quarter = moon_phase(utc("2024-05-01T11:27:00Z"));
+        assert_eq!(last_quarter.name, "Last Quarter");
+    }
+
+    #[test]
+    fn phases_in_between_wax_then_wane() {
+        let waxing = moon_phase(utc("2024-04-11T12:00:00Z"));
+        assert_eq!(waxing.name, "Waxing Crescent");
+        let waning = moon_phase(utc("2024-04-28T12:00:00Z"));
+        assert_eq!(waning.name, "Waning Gibbous");
+        assert!(waning.age_days > waxing.age_days);
+    }
+
+    #[test]
+    fn age_wraps_at_each_new_moon() {
+        let before = moon_phase(utc("2024-04-08T12:00:00Z"));
+        let after = moon_phase(utc("2024-04-09T12:00:00Z"));
+        assert!(before.age_days > SYNODIC_MONTH_DAYS - 1.0);
+        assert!(after.age_days < 1.0);
+    }
+
+    #[test]
+    fn astronomy_for_paris_at_midsummer() {
+        let date = "2026-06-21".parse().unwrap();
+        let day = astronomy("Paris", &paris(), date);
+
+        assert_eq!(day.date, "2026-06-21");
+        assert_eq!(day.timezone, "Europe/Paris");
+        assert_eq!(day.sunrise.as_deref(), Some("2026-06-21T05:46:00+02:00"));
+        assert_eq!(day.sunset.as_deref(), Some("2026-06-21T21:57:00+02:00"));
+        assert_eq!(day.day_length_minutes, Some(16 * 60 + 11));
+        // Same inputs, same answer
+        assert_eq!(
+            serde_json::to_value(&day).unwrap(),
+            serde_json::to_value(astronomy("Paris", &paris(), date)).unwrap()
+        );
+    }
+
+    #[test]
+    fn astronomy_across_spring_forward() {
+        let before = astronomy("Paris", &paris(), "2026-03-28".parse().unwrap());
+        let after = astronomy("Paris", &paris(), "2026-03-29".parse().unwrap());
+        assert!(before.sunrise.unwrap().ends_with("+01:00"));
+        assert!(after.sunrise.unwrap().ends_with("+02:00"));
+        // Two to three more minutes of daylight, not an hour
+        let gained = after.day_length_minutes.unwrap() - before.day_length_minutes.unwrap();
+        assert!((2..=5).contains(&gained), "gained {gained} minutes");
+    }
+
+    #[test]
+    fn no_sunrise_at_midwinter_above_the_arctic_circle() {
+        let tromso = Place {
+            latitude: 69.6492,
+            longitude: 18.9553,
+            timezone: Europe::Oslo,
+        };
+        let day = astronomy("Tromsø", &tromso, "2026-12-21".parse().unwrap());
+        assert_eq!(day.sunrise, None);
+        assert_eq!(day.day_length_minutes, None);
+        assert!(!day.moon_phase.is_empty());
+    }
+}
@@ -0,0 +1,339 @@
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rmcp::model::JsonObject;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::OpenOptions;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Instant;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::config::AuditConfig;
+use crate::tool_middleware::{Next, ToolCall, ToolMiddleware, ToolResult};
+use crate::trace_utils::RequestAttribution;
+
+/// Records waiting for the writer; further calls are dropped (and logged)
+/// rather than slowing tools down.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Replacement for redacted argument values.
+const REDACTED: &str = "[redacted]";
+
+/// One line of the audit file.
+#[derive(Debug, Serialize)]
+pub struct AuditRecord {
+    /// When the call started, RFC 3339 in UTC.
+    pub timestamp: String,
+    pub session_id: Option<String>,
+    pub user_id: Option<String>,
+    /// `x-request-id` of the HTTP request that carried the call.
+    pub request_id: Option<String>,
+    pub tool: String,
+    /// Call arguments with the configured fields redacted.
+    pub arguments: Value,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: f64,
+    /// Trace the call's span was parented on, for lookup in Langfuse.
+    pub trace_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    /// The tool returned an error or a result flagged `isError`.
+    Error,
+    Panic,
+}
+
+/// What the writer task is sent.
+enum Message {
+    Record(Box<AuditRecord>),
+    /// Flush everything queued before, then reply.
+    Flush(oneshot::Sender<()>),
+}
+
+/// Append-only JSONL log of tool calls, written by a dedicated task so it
+/// works whether or not traces are exported.
+#[derive(Clone)]
+pub struct AuditLog {
+    sender: mpsc::Sender<Message>,
+    redact_fields: Arc<[String]>,
+}
+
+impl AuditLog {
+    /// Open the audit file and start its writer; `None` when `AUDIT_LOG_PATH`
+    /// is unset. Must be called from within the Tokio runtime.
+    pub fn start(config: &AuditConfig) -> Result<Option<Self>> {
+        let Some(path) = &config.path else {
+            return Ok(None);
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open audit log {}", path.display()))?;
+
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(write_records(tokio::fs::File::from_std(file), receiver));
+
+        Ok(Some(Self {
+            sender,
+            redact_fields: config.redact_fields.iter().cloned().collect(),
+        }))
+    }
+
+    /// Start timing a tool call.
+    fn begin(&self, call: &ToolCall) -> PendingCall {
+        let attribution = RequestAttribution::from_request_context(&call.context);
+        PendingCall {
+            log: self.clone(),
+            timestamp: OffsetDateTime::now_utc(),
+            started: Instant::now(),
+            session_id: attribution.session_id,
+            user_id: attribution.user_id,
+            request_id: attribution.request_id,
+            tool: call.name().to_string(),
+            arguments: self.redact(call.request.arguments.as_ref()),
+        }
+    }
+
+    fn redact(&self, arguments: Option<&JsonObject>) -> Value {
+        let mut arguments = arguments.cloned().map(Value::Object).unwrap_or(Value::Null);
+        redact_value(&mut arguments, &self.redact_fields);
+        arguments
+    }
+
+    fn record(&self, record: AuditRecord) {
+        if let Err(error) = self.sender.try_send(Message::Record(Box::new(record))) {
+            tracing::warn!(%error, "Dropped audit record");
+        }
+    }
+
+    /// Wait until the records queued so far are written to the file.
+    pub async fn flush(&self) -> Result<()> {
+        let (done, flushed) = oneshot::channel();
+        self.sender
+            .send(Message::Flush(done))
+            .await
+            .map_err(|_| anyhow!("audit log writer stopped"))?;
+        flushed.await.context("audit log writer stopped")
+    }
+}
+
+impl ToolMiddleware for AuditLog {
+    fn around<'a>(&'a self, call: &'a ToolCall, next: Next<'a>) -> BoxFuture<'a, ToolResult> {
+        Box::pin(async move {
+            let pending = self.begin(call);
+            match AssertUnwindSafe(next.run(call)).catch_unwind().await {
+                Ok(result) => {
+                    pending.finish(&result);
+                    result
+                }
+                Err(payload) => {
+                    // Record, then let the service turn the panic into an error
+                    pending.finish_panicked(crate::trace_utils::panic_message(payload.as_ref()));
+                    std::panic::resume_unwind(payload)
+                }
+            }
+        })
+    }
+}
+
+/// A tool call in flight.
+struct PendingCall {
+    log: AuditLog,
+    timestamp: OffsetDateTime,
+    started: Instant,
+    session_id: Option<String>,
+    user_id: Option<String>,
+    request_id: Option<String>,
+    tool: String,
+    arguments: Value,
+}
+
+impl PendingCall {
+    fn finish(self, result: &ToolResult) {
+        let (outcome, error) = match result {
+            Err(error) => (Outcome::Error, Some(error.message.to_string())),
+            Ok(result) if result.is_error == Some(true) => (Outcome::Error, None),
+            Ok(_) => (Outcome::Success, None),
+        };
+        self.record(outcome, error);
+    }
+
+    fn finish_panicked(self, message: &str) {
+        self.record(Outcome::Panic, Some(message.to_string()));
+    }
+
+    fn record(self, outcome: Outcome, error: Option<String>) {
+        self.log.record(AuditRecord {
+            timestamp: self.timestamp.format(&Rfc3339).unwrap_or_default(),
+            session_id: self.session_id,
+            user_id: self.user_id,
+            request_id: self.request_id,
+            tool: self.tool,
+            arguments: self.arguments,
+            outcome,
+            error,
+            duration_ms: self.started.elapsed().as_secs_f64() * 1_000.0,
+            trace_id: crate::trace_utils::tool_trace_id(),
+        });
+    }
+}
+
+/// Replace values under any of `fields` (case-insensitive), at any depth.
+fn redact_value(value: &mut Value, fields: &[String]) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if fields.iter().any(|field| field.eq_ignore_ascii_case(key)) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(value, fields);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact_value(item, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Append each record as one JSON line, flushing whenever the queue drains
+/// or a flush is asked for.
+async fn write_records(file: tokio::fs::File, mut receiver: mpsc::Receiver<Message>) {
+    let mut writer = BufWriter::new(file);
+    while let Some(message) = receiver.recv().await {
+        let record = match message {
+            Message::Record(record) => record,
+            Message::Flush(done) => {
+                if let Err(error) = writer.flush().await {
+                    tracing::warn!(%error, "Failed to flush audit log");
+                }
+                let _ = done.send(());
+                continue;
+            }
+        };
+        let mut line = match serde_json::to_vec(&record) {
+            Ok(line) => line,
+            Err(error) => {
+                tracing::warn!(%error, "Failed to serialize audit record");
+                continue;
+            }
+        };
+        line.push(b'\n');
+        if let Err(error) = writer.write_all(&line).await {
+            tracing::warn!(%error, "Failed to write audit record");
+        }
+        if receiver.is_empty() {
+            if let Err(error) = writer.flush().await {
+                tracing::warn!(%error, "Failed to flush audit log");
+            }
+        }
+    }
+    let _ = writer.flush().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::{CallToolResult, Content};
+    use rmcp::ErrorData as McpError;
+    use serde_json::json;
+
+    fn redacted(mut value: Value) -> Value {
+        redact_value(&mut value, &["api_key".to_string(), "Token".to_string()]);
+        value
+    }
+
+    #[test]
+    fn redaction_matches_keys_case_insensitively_at_any_depth() {
+        assert_eq!(
+            redacted(json!({ "API_KEY": "k", "token": "t", "location": "Paris" })),
+            json!({ "API_KEY": REDACTED, "token": REDACTED, "location": "Paris" })
+        );
+        assert_eq!(
+            redacted(json!({ "auth": { "Api_Key": { "nested": "k" } }, "days": 3 })),
+            json!({ "auth": { "Api_Key": REDACTED }, "days": 3 })
+        );
+        assert_eq!(
+            redacted(json!({ "items": [{ "token": "t", "id": 1 }, "token", [{ "TOKEN": 2 }]] })),
+            json!({ "items": [{ "token": REDACTED, "id": 1 }, "token", [{ "TOKEN": REDACTED }]] })
+        );
+        // Only whole keys match
+        let untouched = json!({ "api_key_id": 1, "tokens": ["a"], "note": "api_key" });
+        assert_eq!(redacted(untouched.clone()), untouched);
+    }
+
+    fn pending(log: &AuditLog, tool: &str) -> PendingCall {
+        PendingCall {
+            log: log.clone(),
+            timestamp: OffsetDateTime::now_utc(),
+            started: Instant::now(),
+            session_id: Some("session".to_string()),
+            user_id: None,
+            request_id: None,
+            tool: tool.to_string(),
+            arguments: json!({ "location": "Paris" }),
+        }
+    }
+
+    #[tokio::test]
+    async fn each_call_is_written_as_one_json_line() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = AuditConfig {
+            path: Some(path.clone()),
+            redact_fields: Vec::new(),
+        };
+        let log = AuditLog::start(&config).unwrap().unwrap();
+
+        pending(&log, "get_weather").finish(&Ok(CallToolResult::success(vec![])));
+        pending(&log, "get_forecast").finish(&Ok(CallToolResult::error(vec![Content::text(
+            "no forecast",
+        )])));
+        pending(&log, "get_alerts").finish(&Err(McpError::invalid_params("bad location", None)));
+        pending(&log, "compare_weather").finish_panicked("boom");
+        log.flush().await.unwrap();
+
+        let contents = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+        let records: Vec<Value> = contents
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let summary: Vec<_> = records
+            .iter()
+            .map(|record| {
+                (
+                    record["tool"].as_str().unwrap(),
+                    record["outcome"].as_str().unwrap(),
+                    record.get("error").and_then(Value::as_str),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("get_weather", "success", None),
+                ("get_forecast", "error", None),
+                ("get_alerts", "error", Some("bad location")),
+                ("compare_weather", "panic", Some("boom")),
+            ]
+        );
+        assert_eq!(records[0]["session_id"], "session");
+        assert_eq!(records[0]["arguments"], json!({ "location": "Paris" }));
+    }
+}
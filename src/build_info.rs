@@ -0,0 +1,70 @@
+//! What this binary was built from, embedded by `build.rs`: served at
+//! `/version` and as the `server-info://build` resource, and recorded on
+//! every span's resource so traces can be matched to the build that
+//! produced them.
+
+use axum::routing::get;
+use axum::{Json, Router};
+use once_cell::sync::Lazy;
+use opentelemetry::KeyValue;
+use rmcp::schemars;
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// URI of the build info resource.
+pub const BUILD_URI: &str = "server-info://build";
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct BuildInfo {
+    /// Crate version
+    pub version: &'static str,
+    /// Commit the binary was built from, "unknown" outside a git checkout
+    pub git_sha: &'static str,
+    /// When the binary was built, RFC 3339 in UTC (SOURCE_DATE_EPOCH when set)
+    pub build_timestamp: String,
+    /// `rustc --version` of the compiler
+    pub rustc_version: &'static str,
+    /// Cargo features enabled in the build
+    pub features: Vec<&'static str>,
+    /// Cargo profile, "debug" or "release"
+    pub profile: &'static str,
+}
+
+static BUILD_INFO: Lazy<BuildInfo> = Lazy::new(|| BuildInfo {
+    version: env!("CARGO_PKG_VERSION"),
+    git_sha: env!("BUILD_GIT_SHA"),
+    build_timestamp: env!("BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|seconds| OffsetDateTime::from_unix_timestamp(seconds).ok())
+        .and_then(|timestamp| timestamp.format(&Rfc3339).ok())
+        .unwrap_or_else(|| "unknown".to_string()),
+    rustc_version: env!("BUILD_RUSTC_VERSION"),
+    features: env!("BUILD_FEATURES")
+        .split(',')
+        .filter(|feature| !feature.is_empty())
+        .collect(),
+    profile: env!("BUILD_PROFILE"),
+});
+
+pub fn build_info() -> &'static BuildInfo {
+    &BUILD_INFO
+}
+
+/// Resource attributes describing the build, next to `service.version`.
+pub fn resource_attributes() -> Vec<KeyValue> {
+    let info = build_info();
+    vec![
+        KeyValue::new("vcs.ref.head.revision", info.git_sha),
+        KeyValue::new("build.timestamp", info.build_timestamp.clone()),
+        KeyValue::new("build.rustc_version", info.rustc_version),
+        KeyValue::new("build.features", info.features.join(",")),
+        KeyValue::new("build.profile", info.profile),
+    ]
+}
+
+/// `GET /version`, public like the OpenAPI document.
+pub fn router() -> Router {
+    Router::new().route("/version", get(|| async { Json(build_info()) }))
+}
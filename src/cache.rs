@@ -0,0 +1,157 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Freshness of a cache lookup, recorded on tool spans as `cache.state`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheState {
+    /// Entry is younger than the TTL.
+    Fresh,
+    /// Entry is past the TTL but inside the stale window; it is served while
+    /// a background revalidation refreshes it.
+    Stale,
+    /// No usable entry.
+    Miss,
+}
+
+impl CacheState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fresh => "fresh",
+            Self::Stale => "stale",
+            Self::Miss => "miss",
+        }
+    }
+}
+
+struct Entry<V> {
+    value: V,
+    stored_at: Instant,
+}
+
+/// An in-memory TTL cache with stale-while-revalidate semantics.
+pub struct SwrCache<K, V> {
+    entries: Mutex<HashMap<K, Entry<V>>>,
+    revalidating: Mutex<HashSet<K>>,
+    ttl: Duration,
+    stale_ttl: Duration,
+    max_entries: usize,
+}
+
+impl<K, V> SwrCache<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn new(ttl: Duration, stale_ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            revalidating: Mutex::new(HashSet::new()),
+            ttl,
+            stale_ttl,
+            max_entries,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> (CacheState, Option<V>) {
+        let mut entries = self.entries.lock().expect("cache lock poisoned");
+        let Some(entry) = entries.get(key) else {
+            return (CacheState::Miss, None);
+        };
+
+        let age = entry.stored_at.elapsed();
+        if age < self.ttl {
+            (CacheState::Fresh, Some(entry.value.clone()))
+        } else if age < self.ttl + self.stale_ttl {
+            (CacheState::Stale, Some(entry.value.clone()))
+        } else {
+            entries.remove(key);
+            (CacheState::Miss, None)
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().expect("cache lock poisoned");
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                value,
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Claim the revalidation of `key`. Returns `false` if one is already running.
+    pub fn begin_revalidation(&self, key: &K) -> bool {
+        self.revalidating
+            .lock()
+            .expect("cache lock poisoned")
+            .insert(key.clone())
+    }
+
+    pub fn end_revalidation(&self, key: &K) {
+        self.revalidating
+            .lock()
+            .expect("cache lock poisoned")
+            .remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn entries_age_from_fresh_to_stale_to_missing() {
+        let fresh = SwrCache::new(HOUR, HOUR, 8);
+        fresh.insert("paris", 1);
+        assert_eq!(fresh.get(&"paris"), (CacheState::Fresh, Some(1)));
+        assert_eq!(fresh.get(&"rome"), (CacheState::Miss, None));
+
+        let stale = SwrCache::new(Duration::ZERO, HOUR, 8);
+        stale.insert("paris", 1);
+        assert_eq!(stale.get(&"paris"), (CacheState::Stale, Some(1)));
+
+        let expired = SwrCache::new(Duration::ZERO, Duration::ZERO, 8);
+        expired.insert("paris", 1);
+        assert_eq!(expired.get(&"paris"), (CacheState::Miss, None));
+        assert!(expired.entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn the_oldest_entry_is_evicted_when_full() {
+        let cache = SwrCache::new(HOUR, HOUR, 2);
+        cache.insert("paris", 1);
+        std::thread::sleep(Duration::from_millis(2));
+        cache.insert("rome", 2);
+        // Replacing an entry does not evict another
+        cache.insert("rome", 3);
+        cache.insert("oslo", 4);
+
+        assert_eq!(cache.get(&"paris"), (CacheState::Miss, None));
+        assert_eq!(cache.get(&"rome"), (CacheState::Fresh, Some(3)));
+        assert_eq!(cache.get(&"oslo"), (CacheState::Fresh, Some(4)));
+    }
+
+    #[test]
+    fn one_revalidation_runs_per_key() {
+        let cache: SwrCache<&str, u32> = SwrCache::new(HOUR, HOUR, 8);
+        assert!(cache.begin_revalidation(&"paris"));
+        assert!(!cache.begin_revalidation(&"paris"));
+        assert!(cache.begin_revalidation(&"rome"));
+        cache.end_revalidation(&"paris");
+        assert!(cache.begin_revalidation(&"paris"));
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{bail, Result};
+use opentelemetry::trace::{Span as _, Tracer as _, TracerProvider as _};
+use opentelemetry::KeyValue;
+
+use crate::app_state::AppState;
+use crate::config::{Config, ProviderKind, TelemetryConfig};
+use crate::tracing_setup::{build_tracer_provider, langfuse_host, missing_langfuse_credentials};
+
+/// Outcome of a single check, printed as one line.
+struct Check {
+    name: &'static str,
+    result: Result<String, String>,
+}
+
+/// Validate configuration, exporter credentials, and provider connectivity.
+///
+/// Prints one line per check and fails if any check failed, so it can gate a
+/// deployment script.
+pub async fn run() -> Result<()> {
+    let mut checks = Vec::new();
+
+    let config = match Config::from_env() {
+        Ok(config) => {
+            checks.push(Check {
+                name: "configuration",
+                result: Ok(format!("loaded (provider: {:?})", config.provider)),
+            });
+            Some(config)
+        }
+        Err(error) => {
+            checks.push(Check {
+                name: "configuration",
+                result: Err(format!(
+                    "{error:#}. Fix the variable in your environment or .env"
+                )),
+            });
+            None
+        }
+    };
+
+    checks.push(Check {
+        name: "langfuse credentials",
+        result: check_langfuse_credentials(),
+    });
+    if checks.last().is_some_and(|check| check.result.is_ok()) {
+        let telemetry = config
+            .as_ref()
+            .map(|config| config.telemetry.clone())
+            .unwrap_or_default();
+        checks.push(Check {
+            name: "langfuse export",
+            result: send_test_span(&telemetry).await,
+        });
+    }
+
+    if let Some(config) = config {
+        checks.push(Check {
+            name: "weather provider",
+            result: check_provider(config).await,
+        });
+    }
+
+    let mut failures = 0;
+    for check in &checks {
+        match &check.result {
+            Ok(detail) => println!("[ok]   {}: {detail}", check.name),
+            Err(detail) => {
+                failures += 1;
+                println!("[fail] {}: {detail}", check.name);
+            }
+        }
+    }
+
+    if failures > 0 {
+        bail!("{failures} configuration check(s) failed");
+    }
+    Ok(())
+}
+
+fn check_langfuse_credentials() -> Result<String, String> {
+    let missing = missing_langfuse_credentials();
+    if !missing.is_empty() {
+        return Err(format!(
+            "{} not set. Copy the keys from your Langfuse project settings into .env",
+            missing.join(", ")
+        ));
+    }
+
+    Ok(format!("keys present, endpoint {}", langfuse_host()))
+}
+
+/// Export one span through the same pipeline the server uses and wait for
+/// the backend to accept it.
+async fn send_test_span(telemetry: &TelemetryConfig) -> Result<String, String> {
+    let provider = build_tracer_provider(telemetry).map_err(|error| format!("{error:#}"))?;
+
+    let tracer = provider.tracer("check-config");
+    let mut span = tracer.start("check-config");
+    span.set_attribute(KeyValue::new("check_config.test", true));
+    let trace_id = span.span_context().trace_id();
+    span.end();
+
+    // Flushing blocks on the batch processor, which runs on this runtime.
+    let flush_provider = provider.clone();
+    let flushed = tokio::task::spawn_blocking(move || flush_provider.force_flush())
+        .await
+        .map_err(|error| format!("flush task failed: {error}"))?;
+    let _ = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+
+    match flushed {
+        Ok(()) => Ok(format!("test span accepted (trace id {trace_id})")),
+        Err(error) => {
+            let message = error.to_string();
+            let hint = if message.contains("401") || message.contains("403") {
+                "the keys were rejected; check they belong to the project at LANGFUSE_HOST"
+            } else {
+                "check that LANGFUSE_HOST / LANGFUSE_BASE_URL is reachable from this machine"
+            };
+            Err(format!("{message}. {hint}"))
+        }
+    }
+}
+
+async fn check_provider(config: Config) -> Result<String, String> {
+    let kind = config.provider;
+    let state = AppState::new(config).map_err(|error| format!("{error:#}"))?;
+
+    match kind {
+        ProviderKind::Mock => Ok("mock provider needs no credentials".to_string()),
+        ProviderKind::OpenMeteo => state
+            .current_weather("Brussels")
+            .await
+            .map(|_| "open-meteo reachable (no API key required)".to_string())
+            .map_err(|error| format!("{error}. Check outbound network access and HTTPS_PROXY")),
+    }
+}
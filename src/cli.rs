@@ -0,0 +1,80 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+/// Rust MCP weather server with OpenTelemetry tracing.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the MCP HTTP server (default when no subcommand is given).
+    Serve {
+        /// Write a Chrome trace of the session to this file on shutdown, for
+        /// ui.perfetto.dev or chrome://tracing.
+        #[arg(long, value_name = "FILE")]
+        profile_out: Option<PathBuf>,
+    },
+    /// Validate configuration, send a test span, and check the weather provider.
+    ///
+    /// Exits non-zero if any check fails.
+    CheckConfig,
+    /// Summarize a span file written with `TRACE_FILE_PATH`: calls, error
+    /// rate and latency per tool.
+    TraceReport {
+        /// JSONL span file.
+        file: PathBuf,
+        /// List every span name, not only tools.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Re-issue the tool calls recorded with `TOOL_RECORDING_PATH` against
+    /// this build and report results that differ.
+    ///
+    /// Exits non-zero if any result differs.
+    Replay {
+        /// JSONL recording.
+        file: PathBuf,
+        /// Result key to leave out of the comparison, at any depth (repeatable).
+        #[arg(long, value_name = "KEY")]
+        ignore: Vec<String>,
+    },
+    /// Scripted demo sessions.
+    Scenario {
+        #[command(subcommand)]
+        command: ScenarioCommand,
+    },
+    /// Print every tool definition (name, description, input/output schemas).
+    PrintSchemas {
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = SchemaFormat::Json)]
+        format: SchemaFormat,
+    },
+}
+
+impl Command {
+    /// Whether the command reads the configuration, and so needs secrets
+    /// resolved first.
+    pub fn loads_config(&self) -> bool {
+        !matches!(self, Self::TraceReport { .. } | Self::PrintSchemas { .. })
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ScenarioCommand {
+    /// Run the tool calls, pauses and faults of a YAML scenario against an
+    /// in-process server, exporting one trace.
+    Run {
+        /// YAML scenario file.
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SchemaFormat {
+    Json,
+    Markdown,
+}
@@ -0,0 +1,45 @@
+//! What the connected client declared in `initialize`, so tools can avoid
+//! requests it cannot answer (sampling, elicitation).
+
+use rmcp::model::InitializeRequestParam;
+use rmcp::service::Peer;
+use rmcp::RoleServer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Optional client features the server makes use of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientSupport {
+    /// `sampling/createMessage`, used by `summarize_weather`
+    pub sampling: bool,
+    /// `elicitation/create`, used to ask for a missing location
+    pub elicitation: bool,
+    /// `roots/list`
+    pub roots: bool,
+}
+
+impl ClientSupport {
+    pub fn from_params(params: &InitializeRequestParam) -> Self {
+        let capabilities = &params.capabilities;
+        Self {
+            sampling: capabilities.sampling.is_some(),
+            elicitation: capabilities.elicitation.is_some(),
+            roots: capabilities.roots.is_some(),
+        }
+    }
+
+    /// As declared by the session's client; nothing before `initialize`.
+    pub fn of(peer: &Peer<RoleServer>) -> Self {
+        peer.peer_info().map(Self::from_params).unwrap_or_default()
+    }
+}
+
+/// Record the client and what it declared on `span`.
+pub fn record_negotiated(span: &tracing::Span, params: &InitializeRequestParam) {
+    let support = ClientSupport::from_params(params);
+    span.set_attribute("mcp.client.name", params.client_info.name.clone());
+    span.set_attribute("mcp.client.version", params.client_info.version.clone());
+    span.set_attribute("mcp.protocol.version", params.protocol_version.to_string());
+    span.set_attribute("mcp.client.capabilities.sampling", support.sampling);
+    span.set_attribute("mcp.client.capabilities.elicitation", support.elicitation);
+    span.set_attribute("mcp.client.capabilities.roots", support.roots);
+}
@@ -0,0 +1,276 @@
+//! Caps on simultaneous calls of a tool, configured with `TOOL_CONCURRENCY`.
+//!
+//! Calls over a tool's limit wait in a queue for a free slot. A call is
+//! refused with a `busy` tool error when the queue is full or the wait
+//! exceeds `TOOL_CONCURRENCY_QUEUE_TIMEOUT_MS`. Queued calls wait in a
+//! `tool.queue` span recording the queue depth, and the counters appear in
+//! `get_server_stats`.
+
+use anyhow::{bail, Result};
+use futures::future::BoxFuture;
+use rmcp::model::Tool;
+use rmcp::{schemars, ErrorData as McpError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{field::Empty, Instrument};
+
+use crate::config::ConcurrencyConfig;
+use crate::tool_errors;
+use crate::tool_middleware::{Next, ToolCall, ToolMiddleware, ToolResult};
+
+/// Code of calls refused for lack of a slot.
+pub const BUSY: &str = "busy";
+
+/// Per-tool limits, shared by every session.
+pub struct ConcurrencyLimits {
+    budgets: BTreeMap<String, Budget>,
+    max_queued: usize,
+    queue_timeout: Duration,
+}
+
+struct Budget {
+    limit: usize,
+    slots: Arc<Semaphore>,
+    queued: AtomicUsize,
+    peak_queued: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+/// Takes a call off its tool's queue when dropped, however the wait ends.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ConcurrencyLimits {
+    /// `None` when no tool has a limit. Fails when a limit names none of
+    /// `tools`, which would never apply.
+    pub fn from_config(config: &ConcurrencyConfig, tools: &[Tool]) -> Result<Option<Arc<Self>>> {
+        if config.limits.is_empty() {
+            return Ok(None);
+        }
+        for tool in config.limits.keys() {
+            if !tools.iter().any(|known| known.name == *tool) {
+                bail!("TOOL_CONCURRENCY names unknown tool '{tool}'");
+            }
+        }
+        let budgets = config
+            .limits
+            .iter()
+            .map(|(tool, limit)| {
+                let budget = Budget {
+                    limit: *limit,
+                    slots: Arc::new(Semaphore::new(*limit)),
+                    queued: AtomicUsize::new(0),
+                    peak_queued: AtomicUsize::new(0),
+                    rejected: AtomicU64::new(0),
+                };
+                (tool.clone(), budget)
+            })
+            .collect();
+        Ok(Some(Arc::new(Self {
+            budgets,
+            max_queued: config.max_queued,
+            queue_timeout: config.queue_timeout,
+        })))
+    }
+
+    /// Current occupancy of every limited tool, by name.
+    pub fn snapshot(&self) -> Vec<ToolConcurrency> {
+        self.budgets
+            .iter()
+            .map(|(tool, budget)| ToolConcurrency {
+                tool: tool.clone(),
+                limit: budget.limit,
+                running: budget.limit - budget.slots.available_permits(),
+                queued: budget.queued.load(Ordering::Relaxed),
+                peak_queued: budget.peak_queued.load(Ordering::Relaxed),
+                rejected: budget.rejected.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    fn busy(&self, tool: &str, budget: &Budget, reason: &str) -> McpError {
+        budget.rejected.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(
+            tool,
+            limit = budget.limit,
+            reason,
+            "Tool call refused as busy"
+        );
+        tool_errors::with_code(
+            McpError::internal_error(
+                format!(
+                    "{tool} is busy: all {} slots are in use {reason}",
+                    budget.limit
+                ),
+                None,
+            ),
+            BUSY,
+            true,
+        )
+    }
+}
+
+impl ConcurrencyLimits {
+    /// A slot for a call of `tool`, after waiting in its queue when all are
+    /// taken; `None` when the tool is unlimited.
+    async fn acquire(&self, tool: &str) -> Result<Option<OwnedSemaphorePermit>, McpError> {
+        let Some(budget) = self.budgets.get(tool) else {
+            return Ok(None);
+        };
+        if let Ok(permit) = budget.slots.clone().try_acquire_owned() {
+            return Ok(Some(permit));
+        }
+
+        let depth = budget.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        let _queued = Queued(&budget.queued);
+        if depth > self.max_queued {
+            return Err(self.busy(tool, budget, "and the queue is full"));
+        }
+        budget.peak_queued.fetch_max(depth, Ordering::Relaxed);
+
+        let span = tracing::info_span!(
+            "tool.queue",
+            tool,
+            tool.queue.depth = depth,
+            tool.queue.wait_ms = Empty,
+            otel.status_code = Empty,
+        );
+        let started = Instant::now();
+        let acquired =
+            tokio::time::timeout(self.queue_timeout, budget.slots.clone().acquire_owned())
+                .instrument(span.clone())
+                .await;
+        span.record("tool.queue.wait_ms", started.elapsed().as_millis() as u64);
+        match acquired {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            _ => {
+                span.record("otel.status_code", "ERROR");
+                Err(self.busy(tool, budget, "and none freed up in time"))
+            }
+        }
+    }
+}
+
+impl ToolMiddleware for ConcurrencyLimits {
+    fn around<'a>(&'a self, call: &'a ToolCall, next: Next<'a>) -> BoxFuture<'a, ToolResult> {
+        Box::pin(async move {
+            let _permit = self.acquire(call.name()).await?;
+            next.run(call).await
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ToolConcurrency {
+    /// Tool name
+    pub tool: String,
+    /// Calls allowed to run at once
+    pub limit: usize,
+    /// Calls running now
+    pub running: usize,
+    /// Calls waiting for a slot now
+    pub queued: usize,
+    /// Most calls ever waiting at once
+    pub peak_queued: usize,
+    /// Calls refused as busy since startup
+    pub rejected: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::JsonObject;
+    use std::collections::HashMap;
+
+    const TOOL: &str = "get_forecast";
+
+    fn limits(max_queued: usize) -> Arc<ConcurrencyLimits> {
+        let config = ConcurrencyConfig {
+            limits: HashMap::from([(TOOL.to_string(), 1)]),
+            max_queued,
+            queue_timeout: Duration::from_millis(50),
+        };
+        let tools = [Tool::new(TOOL, "", Arc::new(JsonObject::new()))];
+        ConcurrencyLimits::from_config(&config, &tools)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn occupancy(limits: &ConcurrencyLimits) -> ToolConcurrency {
+        limits.snapshot().remove(0)
+    }
+
+    /// Wait in `tool`'s queue from a task of its own.
+    fn queue(
+        limits: &Arc<ConcurrencyLimits>,
+    ) -> tokio::task::JoinHandle<Result<Option<OwnedSemaphorePermit>, McpError>> {
+        let limits = limits.clone();
+        tokio::spawn(async move { limits.acquire(TOOL).await })
+    }
+
+    #[test]
+    fn limits_must_name_registered_tools() {
+        let config = ConcurrencyConfig {
+            limits: HashMap::from([("get_wether".to_string(), 1)]),
+            ..Default::default()
+        };
+        let tools = [Tool::new("get_weather", "", Arc::new(JsonObject::new()))];
+        let error = ConcurrencyLimits::from_config(&config, &tools)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("get_wether"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn calls_over_the_limit_wait_for_a_slot() {
+        let limits = limits(4);
+        assert!(limits.acquire("get_weather").await.unwrap().is_none());
+        let running = limits.acquire(TOOL).await.unwrap();
+        assert!(running.is_some());
+
+        let waiting = queue(&limits);
+        tokio::task::yield_now().await;
+        let state = occupancy(&limits);
+        assert_eq!((state.running, state.queued, state.peak_queued), (1, 1, 1));
+
+        drop(running);
+        assert!(waiting.await.unwrap().unwrap().is_some());
+        let state = occupancy(&limits);
+        assert_eq!((state.queued, state.peak_queued, state.rejected), (0, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn calls_beyond_the_queue_are_refused() {
+        let limits = limits(1);
+        let _running = limits.acquire(TOOL).await.unwrap();
+        let waiting = queue(&limits);
+        tokio::task::yield_now().await;
+
+        let error = limits.acquire(TOOL).await.unwrap_err();
+        let report = tool_errors::report(&error, None).unwrap();
+        assert_eq!((report.code.as_str(), report.retryable), (BUSY, true));
+        let state = occupancy(&limits);
+        assert_eq!((state.queued, state.peak_queued, state.rejected), (1, 1, 1));
+        waiting.abort();
+    }
+
+    #[tokio::test]
+    async fn queued_calls_time_out() {
+        let limits = limits(4);
+        let _running = limits.acquire(TOOL).await.unwrap();
+
+        let error = limits.acquire(TOOL).await.unwrap_err();
+        assert!(error.message.contains("none freed up in time"), "{error:?}");
+        let state = occupancy(&limits);
+        assert_eq!((state.queued, state.peak_queued, state.rejected), (0, 1, 1));
+    }
+}
@@ -0,0 +1,1251 @@
+use anyhow::{anyhow, Context, Result};
+use axum::http::HeaderName;
+use ipnet::IpNet;
+use sentry::types::Dsn;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Display};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Runtime configuration assembled from environment variables (and `.env`).
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub http_client: HttpClientConfig,
+    pub provider: ProviderKind,
+    /// Seed making the mock provider's data reproducible.
+    pub mock_seed: Option<u64>,
+    /// Share one upstream fetch between identical concurrent lookups.
+    pub coalesce_requests: bool,
+    pub cache: CacheConfig,
+    pub chaos: ChaosConfig,
+    /// Artificial delay added to each call of a tool, by tool name.
+    pub tool_latency: HashMap<String, LatencySpec>,
+    pub concurrency: ConcurrencyConfig,
+    pub priority: PriorityConfig,
+    pub slo: SloConfig,
+    /// Threads of the pool running CPU-bound tool work; one per CPU when unset.
+    pub cpu_pool_threads: Option<usize>,
+    pub logging: LoggingConfig,
+    /// Bearer token for the `/admin` endpoints; they are not mounted when unset.
+    pub admin_token: Option<String>,
+    pub trace_labels: TraceLabels,
+    pub telemetry: TelemetryConfig,
+    pub access: AccessConfig,
+    pub signing: SigningConfig,
+    pub server: ServerConfig,
+    pub audit: AuditConfig,
+    pub rbac: RbacConfig,
+    pub tenants: TenantsConfig,
+    /// JSON file user preferences are saved to; they are kept in memory only when unset.
+    pub preferences_path: Option<PathBuf>,
+    /// JSONL file every tool call and its result are recorded to, for `replay`.
+    pub recording_path: Option<PathBuf>,
+    /// Directory whose templates replace the built-in ones of the same name.
+    pub template_dir: Option<PathBuf>,
+    pub resources: ResourcesConfig,
+    /// Most tools returned by one `tools/list` page.
+    pub tools_page_size: usize,
+    /// Days per partial `get_forecast` result streamed as a progress notification.
+    pub forecast_chunk_days: usize,
+    pub keepalive: KeepaliveConfig,
+    /// How often the locations of webhooks and watches are looked up.
+    pub refresh_interval: Duration,
+    pub webhooks: WebhookConfig,
+    pub metrics: MetricsConfig,
+    pub feature_flags: FeatureFlagsConfig,
+    /// Whether every tool is exercised against the mock provider before the
+    /// server accepts traffic.
+    pub self_test: SelfTestMode,
+}
+
+/// What a startup self-test failure does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SelfTestMode {
+    /// No self-test.
+    #[default]
+    Off,
+    /// Log the failures and start anyway.
+    Warn,
+    /// Refuse to start.
+    Fail,
+}
+
+impl FromStr for SelfTestMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" | "false" | "0" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "fail" => Ok(Self::Fail),
+            other => Err(anyhow!(
+                "unknown self-test mode '{other}' (expected 'off', 'warn' or 'fail')"
+            )),
+        }
+    }
+}
+
+/// Where the feature flags switching experimental behaviors come from, on
+/// top of their defaults.
+#[derive(Debug, Clone)]
+pub struct FeatureFlagsConfig {
+    /// JSON file of flag values, re-read every poll.
+    pub path: Option<PathBuf>,
+    /// URL serving the same JSON, fetched every poll; it wins over the file.
+    pub url: Option<String>,
+    pub poll_interval: Duration,
+}
+
+/// Periodic push of the server statistics as Prometheus metrics, for
+/// environments without a Prometheus to scrape the server.
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    /// Pushgateway base URL or remote-write endpoint; nothing is pushed when
+    /// unset.
+    pub push_url: Option<String>,
+    pub push_format: PushFormat,
+    pub push_interval: Duration,
+    /// `job` label of the pushed series.
+    pub job: String,
+}
+
+/// Protocol of [`MetricsConfig::push_url`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushFormat {
+    /// Text exposition format, `PUT` to `<url>/metrics/job/<job>`.
+    Pushgateway,
+    /// Snappy-compressed protobuf `WriteRequest`, `POST` to the URL.
+    RemoteWrite,
+}
+
+impl FromStr for PushFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "pushgateway" => Ok(Self::Pushgateway),
+            "remote_write" | "remote-write" => Ok(Self::RemoteWrite),
+            other => Err(anyhow!(
+                "unknown metrics push format '{other}' (expected 'pushgateway' or 'remote_write')"
+            )),
+        }
+    }
+}
+
+impl MetricsConfig {
+    fn from_env() -> Result<Self> {
+        let config = Self {
+            push_url: env_string("METRICS_PUSH_URL"),
+            push_format: env_parse("METRICS_PUSH_FORMAT", PushFormat::Pushgateway)?,
+            push_interval: env_duration_secs("METRICS_PUSH_INTERVAL_SECS", 15)?,
+            job: env_string("METRICS_JOB").unwrap_or_else(|| "rmcp-demo".to_string()),
+        };
+        if config.push_interval.is_zero() {
+            return Err(anyhow!("METRICS_PUSH_INTERVAL_SECS must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+impl FeatureFlagsConfig {
+    /// Whether flags can be switched away from their defaults.
+    pub fn is_configured(&self) -> bool {
+        self.path.is_some() || self.url.is_some()
+    }
+
+    fn from_env() -> Result<Self> {
+        let config = Self {
+            path: env_string("FEATURE_FLAGS_PATH").map(PathBuf::from),
+            url: env_string("FEATURE_FLAGS_URL"),
+            poll_interval: env_duration_secs("FEATURE_FLAGS_POLL_SECS", 30)?,
+        };
+        if config.poll_interval.is_zero() {
+            return Err(anyhow!("FEATURE_FLAGS_POLL_SECS must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+/// Settings for the trace export pipeline.
+#[derive(Debug, Clone, Default)]
+pub struct TelemetryConfig {
+    /// Refuse to start without Langfuse credentials instead of running with
+    /// export disabled.
+    pub strict: bool,
+    /// Also print every exported span to stdout (`OTEL_DEBUG=1`).
+    pub debug_exporter: bool,
+    /// Also append every span to this file as JSON lines.
+    pub span_file: Option<PathBuf>,
+    pub batch: BatchExportConfig,
+    pub limits: SpanLimitsConfig,
+    /// Recorded as the `deployment.environment` resource attribute.
+    pub deployment_environment: Option<String>,
+    /// Report tool errors and panics to Sentry when set.
+    pub sentry_dsn: Option<Dsn>,
+}
+
+/// Batch span processor tuning, read from the standard `OTEL_BSP_*` variables.
+///
+/// Defaults are larger and flush sooner than the SDK's (2048 / 512 / 5s), which
+/// drop spans once the queue fills under the load-test scenario.
+#[derive(Debug, Clone)]
+pub struct BatchExportConfig {
+    /// Spans buffered before new ones are dropped.
+    pub max_queue_size: usize,
+    /// Spans sent per export request; at most `max_queue_size`.
+    pub max_export_batch_size: usize,
+    /// Delay between two consecutive exports.
+    pub scheduled_delay: Duration,
+    /// How long one export may take before it is abandoned.
+    pub export_timeout: Duration,
+}
+
+impl Default for BatchExportConfig {
+    fn default() -> Self {
+        Self {
+            max_queue_size: 8_192,
+            max_export_batch_size: 1_024,
+            scheduled_delay: Duration::from_millis(1_000),
+            export_timeout: Duration::from_millis(30_000),
+        }
+    }
+}
+
+/// Caps on exported span attributes, from the standard `OTEL_SPAN_*`
+/// variables plus a list of attributes hashed into buckets.
+#[derive(Debug, Clone)]
+pub struct SpanLimitsConfig {
+    /// Attributes kept per span; later ones are dropped and counted.
+    pub max_attributes: u32,
+    /// Longer string values are truncated; no limit when unset.
+    pub max_attribute_length: Option<usize>,
+    /// Attributes with unbounded values (locations, free text), exported as
+    /// `<key>.bucket` instead of the raw value.
+    pub hashed_attributes: Vec<String>,
+    /// Number of buckets hashed attributes are spread over.
+    pub hash_buckets: u64,
+}
+
+impl Default for SpanLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_attributes: 128,
+            max_attribute_length: None,
+            hashed_attributes: Vec::new(),
+            hash_buckets: 64,
+        }
+    }
+}
+
+/// Langfuse trace name, tags and environment applied to every tool span, so
+/// traces can be filtered in the Langfuse UI.
+#[derive(Debug, Clone, Default)]
+pub struct TraceLabels {
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    pub environment: Option<String>,
+}
+
+/// Client networks allowed to reach the server, checked before any routing.
+#[derive(Debug, Clone, Default)]
+pub struct AccessConfig {
+    /// When non-empty, only clients in these networks are served.
+    pub allow: Vec<IpNet>,
+    /// Clients in these networks are rejected, even if also allowed.
+    pub deny: Vec<IpNet>,
+}
+
+/// Limits and encodings applied to every incoming HTTP exchange.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Largest accepted request body, after decompression.
+    pub max_request_body_bytes: usize,
+    /// Compress responses (gzip/deflate) for clients that accept it.
+    pub compression: bool,
+    /// Serve the `weather.v1.Weather` gRPC service next to MCP and REST.
+    pub grpc: bool,
+    /// Serve the GraphQL endpoint at `/graphql`.
+    pub graphql: bool,
+    /// Longest each shutdown hook may run before it is given up on.
+    pub shutdown_hook_timeout: Duration,
+    /// Request headers recorded on the `http.request` span and echoed on the
+    /// response, e.g. `x-request-id`.
+    pub propagation_headers: Vec<HeaderName>,
+}
+
+/// MCP resources, and the capability flags advertised for them.
+#[derive(Debug, Clone)]
+pub struct ResourcesConfig {
+    /// Serve `stats://` and embed tool output as resources; the `resources`
+    /// capability is not advertised when off.
+    pub enabled: bool,
+    /// Notify the client when a tool call adds to this session's resources.
+    pub list_changed: bool,
+}
+
+/// Liveness checks and idle limits for the streamable HTTP transport, the
+/// only one served.
+#[derive(Debug, Clone)]
+pub struct KeepaliveConfig {
+    /// MCP `ping` sent to a session idle this long; no pings when unset.
+    pub ping_interval: Option<Duration>,
+    /// How long a ping may go unanswered before it counts as missed.
+    pub ping_timeout: Duration,
+    /// Missed pings in a row after which the session is closed.
+    pub max_missed: u32,
+    /// SSE comment sent on open streams so proxies keep them; off when unset.
+    pub sse_keep_alive: Option<Duration>,
+    /// Sessions with no client request for this long are closed; never when
+    /// unset.
+    pub idle_timeout: Option<Duration>,
+    /// How long before the idle timeout the client is warned.
+    pub idle_warning: Duration,
+}
+
+/// Weather alerts pushed to URLs registered with `register_webhook`.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// HMAC key deliveries are signed with; webhooks are off when unset.
+    pub signing_secret: Option<String>,
+    /// Temperature in °C at or above which a `heat` alert fires.
+    pub heat_celsius: i32,
+    /// Temperature in °C at or below which a `frost` alert fires.
+    pub frost_celsius: i32,
+    /// Wind speed in km/h at or above which a `wind` alert fires.
+    pub wind_kmh: i32,
+    /// Hosts webhooks may use even when they resolve to an internal address.
+    pub allowed_hosts: Vec<String>,
+}
+
+/// Append-only record of tool calls, kept apart from the trace pipeline.
+#[derive(Debug, Clone, Default)]
+pub struct AuditConfig {
+    /// JSONL file records are appended to; auditing is off when unset.
+    pub path: Option<PathBuf>,
+    /// Argument keys whose values are replaced before a record is written.
+    pub redact_fields: Vec<String>,
+}
+
+/// Caps on simultaneous calls of a tool, across all sessions.
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyConfig {
+    /// Tool -> calls allowed to run at once. Unlisted tools are unlimited.
+    pub limits: HashMap<String, usize>,
+    /// Calls of a tool waiting for a slot; more are refused as busy.
+    pub max_queued: usize,
+    /// Longest wait for a slot before the call is refused as busy.
+    pub queue_timeout: Duration,
+}
+
+/// Lanes sharing a fixed number of tool call slots, so low priority calls
+/// cannot crowd out high priority ones.
+#[derive(Debug, Clone, Default)]
+pub struct PriorityConfig {
+    /// Tool calls run at once across all tools; no scheduling when unset.
+    pub slots: Option<usize>,
+    /// Tool -> lane. Unlisted tools are high priority.
+    pub tools: HashMap<String, Priority>,
+    /// `x-user-id` -> lane, overriding the tool's.
+    pub users: HashMap<String, Priority>,
+    /// High priority calls started for each low priority one while both wait.
+    pub weight: u32,
+}
+
+/// Scheduling lane of a tool call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    Low,
+}
+
+impl FromStr for Priority {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "high" => Ok(Self::High),
+            "low" => Ok(Self::Low),
+            other => Err(anyhow!(
+                "unknown priority '{other}' (expected 'high' or 'low')"
+            )),
+        }
+    }
+}
+
+/// Latency objectives of tools, tracked over a sliding window.
+#[derive(Debug, Clone, Default)]
+pub struct SloConfig {
+    /// Tool -> objective. No tracking when empty.
+    pub objectives: HashMap<String, SloObjective>,
+    /// Period compliance and burn rate are computed over.
+    pub window: Duration,
+    /// Burn rate above which a tool's objective is reported as burning.
+    pub burn_rate_alert: f64,
+}
+
+/// `quantile` of calls must finish within `threshold`, e.g. `p95<200ms`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SloObjective {
+    /// Fraction of calls, e.g. `0.95` for `p95`.
+    pub quantile: f64,
+    pub threshold: Duration,
+}
+
+impl FromStr for SloObjective {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid objective '{value}' (expected e.g. p95<200ms)");
+        let (percentile, threshold) = value.trim().split_once('<').ok_or_else(invalid)?;
+        let percentile: f64 = percentile
+            .trim()
+            .strip_prefix('p')
+            .and_then(|percentile| percentile.parse().ok())
+            .ok_or_else(invalid)?;
+        if !(percentile > 0.0 && percentile < 100.0) {
+            return Err(anyhow!(
+                "invalid objective '{value}' (the percentile must be between 0 and 100)"
+            ));
+        }
+        Ok(Self {
+            quantile: percentile / 100.0,
+            threshold: parse_delay(threshold)?,
+        })
+    }
+}
+
+impl fmt::Display for SloObjective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p{}<{}ms",
+            self.quantile * 100.0,
+            self.threshold.as_secs_f64() * 1_000.0
+        )
+    }
+}
+
+impl SloConfig {
+    fn from_env() -> Result<Self> {
+        let config = Self {
+            objectives: env_slos("TOOL_SLO")?,
+            window: env_duration_secs("SLO_WINDOW_SECS", 3_600)?,
+            burn_rate_alert: env_parse("SLO_BURN_RATE_ALERT", 2.0)?,
+        };
+        if config.window.is_zero() {
+            return Err(anyhow!("SLO_WINDOW_SECS must be at least 1"));
+        }
+        if config.burn_rate_alert.is_nan() || config.burn_rate_alert <= 0.0 {
+            return Err(anyhow!("SLO_BURN_RATE_ALERT must be positive"));
+        }
+        Ok(config)
+    }
+}
+
+/// Which tools each role may call, and how a caller's roles are found.
+#[derive(Debug, Clone, Default)]
+pub struct RbacConfig {
+    /// Role -> allowed tool names (`*` for all). Tools are unrestricted when empty.
+    pub roles: HashMap<String, Vec<String>>,
+    /// `x-user-id` -> roles, used only when `trust_user_header` is set.
+    pub users: HashMap<String, Vec<String>>,
+    /// Roles of callers matched by neither a token nor `users`.
+    pub default_roles: Vec<String>,
+    /// HS256 secret verifying `Authorization: Bearer` JWTs carrying a `roles` claim.
+    pub jwt_secret: Option<String>,
+    /// Whether `x-user-id` is set by a trusted proxy, so `users` applies.
+    pub trust_user_header: bool,
+}
+
+/// Per-tenant Langfuse projects, and how a caller's tenant is found.
+#[derive(Debug, Clone, Default)]
+pub struct TenantsConfig {
+    /// Tenant -> its Langfuse public and secret key. Routing is off when empty.
+    pub credentials: HashMap<String, (String, String)>,
+    /// `x-api-key` value -> tenant.
+    pub api_keys: HashMap<String, String>,
+    /// Claim naming the tenant in bearer JWTs verified with `RBAC_JWT_SECRET`.
+    pub jwt_claim: String,
+}
+
+/// HMAC verification of REST facade calls, for webhook-style integrations.
+#[derive(Debug, Clone)]
+pub struct SigningConfig {
+    /// Shared secret; REST calls are not checked when unset.
+    pub secret: Option<String>,
+    /// How far a signed timestamp may be from the server clock.
+    pub tolerance: Duration,
+}
+
+/// Settings for the shared outbound HTTP client.
+#[derive(Debug, Clone)]
+pub struct HttpClientConfig {
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+    pub pool_idle_timeout: Duration,
+    pub pool_max_idle_per_host: usize,
+    pub https_proxy: Option<String>,
+}
+
+/// Settings for the provider result cache.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// How long an entry is served as fresh.
+    pub ttl: Duration,
+    /// How long after `ttl` an entry may still be served while revalidating.
+    pub stale_ttl: Duration,
+    pub max_entries: usize,
+}
+
+/// Faults injected into provider calls, so demo traces show realistic
+/// error and latency distributions.
+#[derive(Debug, Clone)]
+pub struct ChaosConfig {
+    pub enabled: bool,
+    /// Probability (0-1) of delaying a call by `latency`.
+    pub latency_rate: f64,
+    pub latency: Duration,
+    /// Probability of failing a call with a provider error.
+    pub error_rate: f64,
+    /// Probability of hanging for `timeout`, then failing as timed out.
+    pub timeout_rate: f64,
+    pub timeout: Duration,
+}
+
+/// A delay of `base`, plus or minus up to `jitter` (uniformly distributed),
+/// written `300ms`, `300ms±100`, `300+-100` or `1.5s`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySpec {
+    pub base: Duration,
+    pub jitter: Duration,
+}
+
+impl FromStr for LatencySpec {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let (base, jitter) = match value.split_once('±').or_else(|| value.split_once("+-")) {
+            Some((base, jitter)) => (base, Some(jitter)),
+            None => (value, None),
+        };
+        let base = parse_delay(base)?;
+        let jitter = jitter.map(parse_delay).transpose()?.unwrap_or_default();
+        Ok(Self { base, jitter })
+    }
+}
+
+/// Milliseconds, with an optional `ms` or `s` unit.
+fn parse_delay(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (number, scale) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 1.0)
+    } else if let Some(secs) = value.strip_suffix('s') {
+        (secs, 1_000.0)
+    } else {
+        (value, 1.0)
+    };
+    let millis: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("invalid delay '{value}' (expected e.g. 300ms or 1.5s)"))?;
+    if !millis.is_finite() || millis < 0.0 {
+        return Err(anyhow!("invalid delay '{value}' (must not be negative)"));
+    }
+    Ok(Duration::from_secs_f64(millis * scale / 1_000.0))
+}
+
+/// Settings for the optional rolling log file written alongside stdout.
+#[derive(Debug, Clone)]
+pub struct LoggingConfig {
+    /// Directory for log files; file logging is disabled when unset.
+    pub dir: Option<PathBuf>,
+    pub rotation: LogRotation,
+}
+
+/// How often the log file rolls over to a new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl FromStr for LogRotation {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "minutely" => Ok(Self::Minutely),
+            "hourly" => Ok(Self::Hourly),
+            "daily" => Ok(Self::Daily),
+            "never" => Ok(Self::Never),
+            other => Err(anyhow!(
+                "unknown log rotation '{other}' (expected 'minutely', 'hourly', 'daily' or 'never')"
+            )),
+        }
+    }
+}
+
+/// Which weather data source backs the tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    /// Random data generated locally, no network access required.
+    Mock,
+    /// The free Open-Meteo forecast and geocoding APIs.
+    OpenMeteo,
+}
+
+impl FromStr for ProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "mock" => Ok(Self::Mock),
+            "open-meteo" | "open_meteo" | "openmeteo" => Ok(Self::OpenMeteo),
+            other => Err(anyhow!(
+                "unknown weather provider '{other}' (expected 'mock' or 'open-meteo')"
+            )),
+        }
+    }
+}
+
+impl Config {
+    /// Configuration from `vars` alone, as if they were the whole
+    /// environment, e.g. for tests the environment must not change.
+    pub fn from_vars<K: Into<String>, V: Into<String>>(
+        vars: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self> {
+        with_vars(vars, Self::from_env)
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let config = Self {
+            http_client: HttpClientConfig::from_env()?,
+            provider: env_parse("WEATHER_PROVIDER", ProviderKind::Mock)?,
+            mock_seed: env_parse_opt("MOCK_SEED")?,
+            coalesce_requests: env_flag("COALESCE_REQUESTS", true)?,
+            cache: CacheConfig::from_env()?,
+            chaos: ChaosConfig::from_env()?,
+            tool_latency: env_tool_latency("TOOL_LATENCY")?,
+            concurrency: ConcurrencyConfig::from_env()?,
+            priority: PriorityConfig::from_env()?,
+            slo: SloConfig::from_env()?,
+            cpu_pool_threads: env_parse_opt("CPU_POOL_THREADS")?,
+            logging: LoggingConfig::from_env()?,
+            admin_token: env_string("ADMIN_TOKEN"),
+            trace_labels: TraceLabels::from_env(),
+            telemetry: TelemetryConfig::from_env()?,
+            access: AccessConfig::from_env()?,
+            signing: SigningConfig::from_env()?,
+            server: ServerConfig::from_env()?,
+            audit: AuditConfig::from_env(),
+            rbac: RbacConfig::from_env()?,
+            tenants: TenantsConfig::from_env()?,
+            preferences_path: env_string("PREFERENCES_PATH").map(PathBuf::from),
+            recording_path: env_string("TOOL_RECORDING_PATH").map(PathBuf::from),
+            template_dir: env_string("TEMPLATE_DIR").map(PathBuf::from),
+            resources: ResourcesConfig::from_env()?,
+            tools_page_size: env_parse("MCP_TOOLS_PAGE_SIZE", 50)?,
+            forecast_chunk_days: env_parse("FORECAST_CHUNK_DAYS", 3)?,
+            keepalive: KeepaliveConfig::from_env()?,
+            refresh_interval: env_duration_secs("REFRESH_INTERVAL_SECS", 60)?,
+            webhooks: WebhookConfig::from_env()?,
+            metrics: MetricsConfig::from_env()?,
+            feature_flags: FeatureFlagsConfig::from_env()?,
+            self_test: env_parse("SELF_TEST", SelfTestMode::Off)?,
+        };
+        if config.refresh_interval.is_zero() {
+            return Err(anyhow!("REFRESH_INTERVAL_SECS must be at least 1"));
+        }
+        if config.cpu_pool_threads == Some(0) {
+            return Err(anyhow!("CPU_POOL_THREADS must be at least 1"));
+        }
+        if config.tools_page_size == 0 {
+            return Err(anyhow!("MCP_TOOLS_PAGE_SIZE must be at least 1"));
+        }
+        if config.forecast_chunk_days == 0 {
+            return Err(anyhow!("FORECAST_CHUNK_DAYS must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+impl TelemetryConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            strict: env_flag("TRACING_STRICT", false)?,
+            debug_exporter: env_flag("OTEL_DEBUG", false)?,
+            span_file: env_string("TRACE_FILE_PATH").map(PathBuf::from),
+            batch: BatchExportConfig::from_env()?,
+            limits: SpanLimitsConfig::from_env()?,
+            deployment_environment: env_string("DEPLOYMENT_ENVIRONMENT"),
+            sentry_dsn: env_parse_opt("SENTRY_DSN")?,
+        })
+    }
+}
+
+impl SpanLimitsConfig {
+    fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        let config = Self {
+            max_attributes: env_parse("OTEL_SPAN_ATTRIBUTE_COUNT_LIMIT", defaults.max_attributes)?,
+            max_attribute_length: env_parse_opt("OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT")?,
+            hashed_attributes: env_list("SPAN_HASHED_ATTRIBUTES"),
+            hash_buckets: env_parse("SPAN_HASH_BUCKETS", defaults.hash_buckets)?,
+        };
+        if config.max_attribute_length == Some(0) {
+            return Err(anyhow!(
+                "OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT must be at least 1"
+            ));
+        }
+        if config.hash_buckets == 0 {
+            return Err(anyhow!("SPAN_HASH_BUCKETS must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+impl BatchExportConfig {
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        let config = Self {
+            max_queue_size: env_parse("OTEL_BSP_MAX_QUEUE_SIZE", defaults.max_queue_size)?,
+            max_export_batch_size: env_parse(
+                "OTEL_BSP_MAX_EXPORT_BATCH_SIZE",
+                defaults.max_export_batch_size,
+            )?,
+            scheduled_delay: env_duration_ms(
+                "OTEL_BSP_SCHEDULE_DELAY",
+                defaults.scheduled_delay.as_millis() as u64,
+            )?,
+            export_timeout: env_duration_ms(
+                "OTEL_BSP_EXPORT_TIMEOUT",
+                defaults.export_timeout.as_millis() as u64,
+            )?,
+        };
+
+        if config.max_export_batch_size > config.max_queue_size {
+            return Err(anyhow!(
+                "OTEL_BSP_MAX_EXPORT_BATCH_SIZE ({}) must not exceed OTEL_BSP_MAX_QUEUE_SIZE ({})",
+                config.max_export_batch_size,
+                config.max_queue_size
+            ));
+        }
+        Ok(config)
+    }
+}
+
+impl TraceLabels {
+    fn from_env() -> Self {
+        Self {
+            name: env_string("LANGFUSE_TRACE_NAME"),
+            tags: env_list("LANGFUSE_TRACE_TAGS"),
+            environment: env_string("LANGFUSE_TRACING_ENVIRONMENT"),
+        }
+    }
+}
+
+impl AccessConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            allow: env_networks("ALLOWED_CIDRS")?,
+            deny: env_networks("DENIED_CIDRS")?,
+        })
+    }
+}
+
+impl ServerConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            max_request_body_bytes: env_parse("MAX_REQUEST_BODY_BYTES", 1024 * 1024)?,
+            compression: env_flag("HTTP_COMPRESSION", true)?,
+            grpc: env_flag("GRPC_ENABLED", false)?,
+            graphql: env_flag("GRAPHQL_ENABLED", false)?,
+            shutdown_hook_timeout: env_duration_secs("SHUTDOWN_HOOK_TIMEOUT_SECS", 5)?,
+            propagation_headers: env_header_names("PROPAGATION_HEADERS")?,
+        })
+    }
+}
+
+impl ResourcesConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            enabled: env_flag("MCP_RESOURCES", true)?,
+            list_changed: env_flag("MCP_RESOURCES_LIST_CHANGED", true)?,
+        })
+    }
+}
+
+impl KeepaliveConfig {
+    fn from_env() -> Result<Self> {
+        let optional_secs = |key, default| {
+            env_duration_secs(key, default).map(|interval| Some(interval).filter(|i| !i.is_zero()))
+        };
+        Ok(Self {
+            ping_interval: optional_secs("MCP_PING_INTERVAL_SECS", 0)?,
+            ping_timeout: env_duration_secs("MCP_PING_TIMEOUT_SECS", 10)?,
+            max_missed: env_parse("MCP_PING_MAX_MISSED", 3)?,
+            sse_keep_alive: optional_secs("SSE_KEEPALIVE_SECS", 15)?,
+            idle_timeout: optional_secs("SESSION_IDLE_TIMEOUT_SECS", 0)?,
+            idle_warning: env_duration_secs("SESSION_IDLE_WARNING_SECS", 60)?,
+        })
+    }
+}
+
+impl WebhookConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            signing_secret: env_string("WEBHOOK_SIGNING_SECRET"),
+            heat_celsius: env_parse("WEBHOOK_HEAT_CELSIUS", 30)?,
+            frost_celsius: env_parse("WEBHOOK_FROST_CELSIUS", 0)?,
+            wind_kmh: env_parse("WEBHOOK_WIND_KMH", 50)?,
+            allowed_hosts: env_list("WEBHOOK_ALLOWED_HOSTS"),
+        })
+    }
+}
+
+impl AuditConfig {
+    fn from_env() -> Self {
+        let redact_fields = match env_string("AUDIT_REDACT_FIELDS") {
+            Some(_) => env_list("AUDIT_REDACT_FIELDS"),
+            // Feedback comments are free text typed by the user
+            None => vec!["comment".to_string()],
+        };
+        Self {
+            path: env_string("AUDIT_LOG_PATH").map(PathBuf::from),
+            redact_fields,
+        }
+    }
+}
+
+impl ConcurrencyConfig {
+    fn from_env() -> Result<Self> {
+        let limits = env_list("TOOL_CONCURRENCY")
+            .iter()
+            .map(|item| {
+                let (tool, limit) = item
+                    .split_once('=')
+                    .and_then(|(tool, limit)| Some((tool, limit.trim().parse::<usize>().ok()?)))
+                    .filter(|(_, limit)| *limit > 0)
+                    .ok_or_else(|| {
+                        anyhow!("invalid value for TOOL_CONCURRENCY: '{item}' (expected tool=4)")
+                    })?;
+                Ok((tool.trim().to_string(), limit))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            limits,
+            max_queued: env_parse("TOOL_CONCURRENCY_MAX_QUEUED", 16)?,
+            queue_timeout: env_duration_ms("TOOL_CONCURRENCY_QUEUE_TIMEOUT_MS", 10_000)?,
+        })
+    }
+}
+
+impl PriorityConfig {
+    fn from_env() -> Result<Self> {
+        let config = Self {
+            slots: env_parse_opt("TOOL_PRIORITY_SLOTS")?,
+            tools: env_priorities("TOOL_PRIORITY")?,
+            users: env_priorities("TOOL_PRIORITY_USERS")?,
+            weight: env_parse("TOOL_PRIORITY_WEIGHT", 4)?,
+        };
+        if config.slots == Some(0) {
+            return Err(anyhow!("TOOL_PRIORITY_SLOTS must be at least 1"));
+        }
+        if config.weight == 0 {
+            return Err(anyhow!("TOOL_PRIORITY_WEIGHT must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+impl RbacConfig {
+    fn from_env() -> Result<Self> {
+        let config = Self {
+            roles: env_map("RBAC_ROLES")?,
+            users: env_map("RBAC_USERS")?,
+            default_roles: env_list("RBAC_DEFAULT_ROLES"),
+            jwt_secret: env_string("RBAC_JWT_SECRET"),
+            trust_user_header: env_flag("RBAC_TRUST_USER_HEADER", false)?,
+        };
+
+        if !config.users.is_empty() && !config.trust_user_header {
+            return Err(anyhow!(
+                "RBAC_USERS requires RBAC_TRUST_USER_HEADER=1, as x-user-id can be set by any caller"
+            ));
+        }
+        if config.trust_user_header && config.jwt_secret.is_some() {
+            return Err(anyhow!(
+                "RBAC_TRUST_USER_HEADER cannot be combined with RBAC_JWT_SECRET"
+            ));
+        }
+
+        let assigned = config.users.values().flatten();
+        for role in assigned.chain(&config.default_roles) {
+            if !config.roles.contains_key(role) {
+                return Err(anyhow!(
+                    "role '{role}' is assigned but not defined in RBAC_ROLES"
+                ));
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl TenantsConfig {
+    fn from_env() -> Result<Self> {
+        let mut credentials = HashMap::new();
+        for (tenant, keys) in env_map("LANGFUSE_TENANTS")? {
+            let [public_key, secret_key] = <[String; 2]>::try_from(keys).map_err(|_| {
+                anyhow!("LANGFUSE_TENANTS entry '{tenant}' must be tenant=public_key|secret_key")
+            })?;
+            credentials.insert(tenant, (public_key, secret_key));
+        }
+
+        let mut api_keys = HashMap::new();
+        for (key, tenants) in env_map("TENANT_API_KEYS")? {
+            let [tenant] = <[String; 1]>::try_from(tenants)
+                .map_err(|_| anyhow!("TENANT_API_KEYS maps each key to one tenant"))?;
+            if !credentials.contains_key(&tenant) {
+                return Err(anyhow!(
+                    "tenant '{tenant}' has an API key but no LANGFUSE_TENANTS entry"
+                ));
+            }
+            api_keys.insert(key, tenant);
+        }
+
+        Ok(Self {
+            credentials,
+            api_keys,
+            jwt_claim: env_string("TENANT_JWT_CLAIM").unwrap_or_else(|| "tenant".to_string()),
+        })
+    }
+}
+
+impl SigningConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            secret: env_string("REST_SIGNING_SECRET"),
+            tolerance: env_duration_secs("REST_SIGNATURE_TOLERANCE_SECS", 300)?,
+        })
+    }
+}
+
+impl LoggingConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            dir: env_string("LOG_DIR").map(PathBuf::from),
+            rotation: env_parse("LOG_ROTATION", LogRotation::Daily)?,
+        })
+    }
+}
+
+impl CacheConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            enabled: env_flag("CACHE_ENABLED", true)?,
+            ttl: env_duration_secs("CACHE_TTL_SECS", 60)?,
+            stale_ttl: env_duration_secs("CACHE_STALE_TTL_SECS", 600)?,
+            max_entries: env_parse("CACHE_MAX_ENTRIES", 1_000)?,
+        })
+    }
+}
+
+impl ChaosConfig {
+    fn from_env() -> Result<Self> {
+        let config = Self {
+            enabled: env_flag("CHAOS_ENABLED", false)?,
+            latency_rate: env_parse("CHAOS_LATENCY_RATE", 0.1)?,
+            latency: env_duration_ms("CHAOS_LATENCY_MS", 1_500)?,
+            error_rate: env_parse("CHAOS_ERROR_RATE", 0.05)?,
+            timeout_rate: env_parse("CHAOS_TIMEOUT_RATE", 0.02)?,
+            timeout: env_duration_ms("CHAOS_TIMEOUT_MS", 5_000)?,
+        };
+
+        for (key, rate) in [
+            ("CHAOS_LATENCY_RATE", config.latency_rate),
+            ("CHAOS_ERROR_RATE", config.error_rate),
+            ("CHAOS_TIMEOUT_RATE", config.timeout_rate),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(anyhow!("{key} must be between 0 and 1, got {rate}"));
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl HttpClientConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            connect_timeout: env_duration_ms("HTTP_CONNECT_TIMEOUT_MS", 5_000)?,
+            request_timeout: env_duration_ms("HTTP_REQUEST_TIMEOUT_MS", 15_000)?,
+            pool_idle_timeout: env_duration_ms("HTTP_POOL_IDLE_TIMEOUT_MS", 90_000)?,
+            pool_max_idle_per_host: env_parse("HTTP_POOL_MAX_IDLE_PER_HOST", 8)?,
+            https_proxy: env_string("HTTPS_PROXY").or_else(|| env_string("https_proxy")),
+        })
+    }
+}
+
+thread_local! {
+    /// Variables read instead of the process environment while
+    /// [`with_vars`] runs.
+    static VARS: RefCell<Option<HashMap<String, String>>> = const { RefCell::new(None) };
+}
+
+/// Run `read` with `vars` as the whole environment seen by [`env_string`]
+/// and the helpers built on it, on this thread.
+pub(crate) fn with_vars<K: Into<String>, V: Into<String>, T>(
+    vars: impl IntoIterator<Item = (K, V)>,
+    read: impl FnOnce() -> T,
+) -> T {
+    let vars = vars
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect();
+    let previous = VARS.replace(Some(vars));
+    let result = read();
+    VARS.set(previous);
+    result
+}
+
+/// Read a non-empty environment variable.
+pub fn env_string(key: &str) -> Option<String> {
+    let value = VARS.with_borrow(|vars| match vars {
+        Some(vars) => vars.get(key).cloned(),
+        None => env::var(key).ok(),
+    });
+    value.filter(|value| !value.trim().is_empty())
+}
+
+/// Read a comma-separated list, skipping empty items.
+pub fn env_list(key: &str) -> Vec<String> {
+    env_string(key)
+        .map(|raw| {
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse comma-separated `key=value|value` entries, e.g. `reader=get_weather|get_forecast`.
+pub fn env_map(key: &str) -> Result<HashMap<String, Vec<String>>> {
+    env_list(key)
+        .iter()
+        .map(|item| {
+            let (name, values) = item
+                .split_once('=')
+                .ok_or_else(|| anyhow!("invalid value for {key}: '{item}' (expected name=a|b)"))?;
+            let values = values
+                .split('|')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+                .collect();
+            Ok((name.trim().to_string(), values))
+        })
+        .collect()
+}
+
+/// Parse comma-separated `tool=delay` entries, e.g. `get_forecast=300ms±100`.
+fn env_tool_latency(key: &str) -> Result<HashMap<String, LatencySpec>> {
+    env_list(key)
+        .iter()
+        .map(|item| {
+            let (tool, spec) = item.split_once('=').ok_or_else(|| {
+                anyhow!("invalid value for {key}: '{item}' (expected tool=300ms±100)")
+            })?;
+            let spec = spec
+                .parse()
+                .with_context(|| format!("invalid value for {key}: '{item}'"))?;
+            Ok((tool.trim().to_string(), spec))
+        })
+        .collect()
+}
+
+/// Parse `name=high,other=low` pairs.
+fn env_priorities(key: &str) -> Result<HashMap<String, Priority>> {
+    env_list(key)
+        .iter()
+        .map(|item| {
+            let (name, priority) = item.split_once('=').ok_or_else(|| {
+                anyhow!("invalid value for {key}: '{item}' (expected name=high or name=low)")
+            })?;
+            let priority = priority
+                .parse()
+                .with_context(|| format!("invalid value for {key}: '{item}'"))?;
+            Ok((name.trim().to_string(), priority))
+        })
+        .collect()
+}
+
+/// Parse `tool=p95<200ms` pairs.
+fn env_slos(key: &str) -> Result<HashMap<String, SloObjective>> {
+    env_list(key)
+        .iter()
+        .map(|item| {
+            let (tool, objective) = item.split_once('=').ok_or_else(|| {
+                anyhow!("invalid value for {key}: '{item}' (expected tool=p95<200ms)")
+            })?;
+            let objective = objective
+                .parse()
+                .with_context(|| format!("invalid value for {key}: '{item}'"))?;
+            Ok((tool.trim().to_string(), objective))
+        })
+        .collect()
+}
+
+/// Parse a comma-separated list of CIDR networks; a bare address is a single host.
+pub fn env_networks(key: &str) -> Result<Vec<IpNet>> {
+    env_list(key)
+        .iter()
+        .map(|item| {
+            item.parse::<IpNet>()
+                .or_else(|_| item.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| anyhow!("invalid value for {key}: '{item}' is not an IP network"))
+        })
+        .collect()
+}
+
+/// Parse a comma-separated list of HTTP header names.
+fn env_header_names(key: &str) -> Result<Vec<HeaderName>> {
+    env_list(key)
+        .iter()
+        .map(|item| {
+            HeaderName::from_str(item)
+                .map_err(|_| anyhow!("invalid value for {key}: '{item}' is not a header name"))
+        })
+        .collect()
+}
+
+/// Parse an environment variable, falling back to `default` when it is unset.
+pub fn env_parse<T>(key: &str, default: T) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    Ok(env_parse_opt(key)?.unwrap_or(default))
+}
+
+/// Parse an optional value from an environment variable, `None` when unset.
+pub fn env_parse_opt<T>(key: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    env_string(key)
+        .map(|raw| {
+            raw.trim()
+                .parse()
+                .map_err(|error| anyhow!("{error}"))
+                .with_context(|| format!("invalid value for {key}: '{raw}'"))
+        })
+        .transpose()
+}
+
+/// Parse a boolean switch, accepting `1`/`0`, `true`/`false`, `yes`/`no` and `on`/`off`.
+pub fn env_flag(key: &str, default: bool) -> Result<bool> {
+    match env_string(key) {
+        Some(raw) => match raw.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(true),
+            "0" | "false" | "no" | "off" => Ok(false),
+            _ => Err(anyhow!(
+                "invalid value for {key}: '{raw}' (expected 1/0, true/false, yes/no or on/off)"
+            )),
+        },
+        None => Ok(default),
+    }
+}
+
+/// Parse a millisecond duration from an environment variable.
+pub fn env_duration_ms(key: &str, default_ms: u64) -> Result<Duration> {
+    env_parse(key, default_ms).map(Duration::from_millis)
+}
+
+/// Parse a duration in whole seconds from an environment variable.
+pub fn env_duration_secs(key: &str, default_secs: u64) -> Result<Duration> {
+    env_parse(key, default_secs).map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latency(value: &str) -> Result<LatencySpec> {
+        value.parse()
+    }
+
+    #[test]
+    fn flags_accept_common_spellings() {
+        let flag = |value: &str| with_vars([("FLAG", value)], || env_flag("FLAG", false));
+        for value in ["1", "true", "Yes", " ON "] {
+            assert!(flag(value).unwrap(), "{value}");
+        }
+        for value in ["0", "false", "NO", "off"] {
+            assert!(!flag(value).unwrap(), "{value}");
+        }
+        assert!(with_vars([("OTHER", "1")], || env_flag("FLAG", true)).unwrap());
+        assert_eq!(
+            flag("enabled").unwrap_err().to_string(),
+            "invalid value for FLAG: 'enabled' (expected 1/0, true/false, yes/no or on/off)"
+        );
+    }
+
+    #[test]
+    fn strict_tracing_is_a_flag() {
+        let strict = |value: &str| {
+            Config::from_vars([("TRACING_STRICT", value)]).map(|config| config.telemetry.strict)
+        };
+        assert!(strict("1").unwrap());
+        assert!(strict("yes").unwrap());
+        assert!(!strict("off").unwrap());
+        assert!(
+            !Config::from_vars([("OTHER", "1")])
+                .unwrap()
+                .telemetry
+                .strict
+        );
+    }
+
+    #[test]
+    fn latency_specs_accept_units_and_jitter() {
+        let ms = Duration::from_millis;
+        let cases = [
+            ("300", ms(300), ms(0)),
+            ("300ms", ms(300), ms(0)),
+            (" 1.5s ", ms(1500), ms(0)),
+            ("0", ms(0), ms(0)),
+            ("300ms±100", ms(300), ms(100)),
+            ("300 +- 100ms", ms(300), ms(100)),
+            ("1s±0.5s", ms(1000), ms(500)),
+        ];
+        for (value, base, jitter) in cases {
+            assert_eq!(
+                latency(value).unwrap(),
+                LatencySpec { base, jitter },
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_latency_specs_are_rejected() {
+        for value in ["", "fast", "300m", "-5ms", "300±", "±100", "NaNms", "infs"] {
+            assert!(latency(value).is_err(), "{value}");
+        }
+        assert_eq!(
+            parse_delay("-1").unwrap_err().to_string(),
+            "invalid delay '-1' (must not be negative)"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Dedicated threads for CPU-bound tool work, sized with `CPU_POOL_THREADS`.
+//!
+//! Rendering a chart or aggregating a forecast on a tokio worker stalls every
+//! other task scheduled there. Such work runs on this rayon pool instead, and
+//! the calling task awaits its result. Each task gets a `cpu.task` span
+//! recording how long it waited for a thread (`cpu.queue_wait_ms`) apart from
+//! how long it ran (`cpu.execution_ms`); spans opened by the work nest under
+//! it.
+
+use anyhow::Result;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::oneshot;
+use tracing::field::Empty;
+
+/// Thread pool shared by every session.
+pub struct CpuPool {
+    pool: rayon::ThreadPool,
+    /// Tasks submitted but not yet picked up by a thread.
+    queued: Arc<AtomicUsize>,
+}
+
+impl CpuPool {
+    /// A pool of `threads` threads, or one per CPU when `None`.
+    pub fn new(threads: Option<usize>) -> Result<Self> {
+        let threads = threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, |threads| threads.get())
+        });
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("cpu-pool-{index}"))
+            .build()?;
+        Ok(Self {
+            pool,
+            queued: Arc::default(),
+        })
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Run `work` on the pool and wait for its result without blocking the
+    /// async runtime. A panic in `work` is resumed in the caller, where the
+    /// tool router reports it like any other.
+    pub async fn run<T, F>(&self, task: &'static str, work: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let depth = self.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        let span = tracing::info_span!(
+            "cpu.task",
+            cpu.task = task,
+            cpu.queue_depth = depth,
+            cpu.queue_wait_ms = Empty,
+            cpu.execution_ms = Empty,
+            otel.status_code = Empty,
+        );
+        let queued = Arc::clone(&self.queued);
+        let submitted = Instant::now();
+        let (sender, receiver) = oneshot::channel();
+        let worker_span = span.clone();
+        self.pool.spawn(move || {
+            queued.fetch_sub(1, Ordering::Relaxed);
+            let _entered = worker_span.enter();
+            worker_span.record("cpu.queue_wait_ms", submitted.elapsed().as_millis() as u64);
+
+            let started = Instant::now();
+            let outcome = panic::catch_unwind(AssertUnwindSafe(work));
+            worker_span.record("cpu.execution_ms", started.elapsed().as_millis() as u64);
+            if outcome.is_err() {
+                worker_span.record("otel.status_code", "ERROR");
+            }
+            // The caller may have gone away, e.g. a cancelled request
+            let _ = sender.send(outcome);
+        });
+
+        match receiver.await {
+            Ok(Ok(value)) => value,
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(_) => panic!("CPU pool dropped the {task} task"),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use opentelemetry::trace::{SpanId, TraceContextExt, TraceId};
+use sentry::integrations::tracing::{self as sentry_tracing, EventMapping};
+use sentry::protocol::{Event, TraceContext};
+use sentry::ClientInitGuard;
+use std::borrow::Cow;
+use std::sync::Arc;
+use tracing::{Level, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::config::TelemetryConfig;
+use crate::tracing_setup::otel_ids;
+
+/// Start the Sentry client when `SENTRY_DSN` is set.
+///
+/// Its panic hook reports panics; `ERROR` events (including tool calls that
+/// return an `McpError`) arrive through [`layer`]. Keep the guard until
+/// shutdown so queued reports are sent.
+pub fn init(telemetry: &TelemetryConfig) -> Option<ClientInitGuard> {
+    let dsn = telemetry.sentry_dsn.clone()?;
+    Some(sentry::init(sentry::ClientOptions {
+        dsn: Some(dsn),
+        release: sentry::release_name!(),
+        environment: telemetry.deployment_environment.clone().map(Cow::Owned),
+        attach_stacktrace: true,
+        before_send: Some(Arc::new(|mut event| {
+            // Panic reports are captured on the panicking thread, still inside
+            // the tool's span.
+            let context = tracing::Span::current().context();
+            let span = context.span();
+            let span_context = span.span_context();
+            if span_context.is_valid() {
+                link_trace(&mut event, span_context.trace_id(), span_context.span_id());
+            }
+            Some(event)
+        })),
+        ..Default::default()
+    }))
+}
+
+/// Tracing layer sending `ERROR` events to Sentry and keeping `WARN`/`INFO`
+/// events as breadcrumbs. A no-op until [`init`] has bound a client.
+///
+/// Spans are left to the OTel pipeline; each report instead carries the OTel
+/// trace id of the event's span, so it can be looked up in Langfuse.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    sentry_tracing::layer()
+        .span_filter(|_| false)
+        .event_mapper(
+            |event, ctx: Context<'_, S>| match *event.metadata().level() {
+                Level::ERROR => {
+                    let mut report = sentry_tracing::event_from_event(event, &ctx);
+                    if let Some((trace_id, span_id)) = ctx.event_scope(event).and_then(otel_ids) {
+                        link_trace(&mut report, trace_id, span_id);
+                    }
+                    EventMapping::Event(report)
+                }
+                Level::WARN | Level::INFO => {
+                    EventMapping::Breadcrumb(sentry_tracing::breadcrumb_from_event(event, &ctx))
+                }
+                _ => EventMapping::Ignore,
+            },
+        )
+}
+
+/// Use the OTel ids as the Sentry trace context and add them as tags.
+fn link_trace(event: &mut Event<'static>, trace_id: TraceId, span_id: SpanId) {
+    let trace = TraceContext {
+        trace_id: trace_id.to_bytes().into(),
+        span_id: span_id.to_bytes().into(),
+        ..Default::default()
+    };
+    event.contexts.insert("trace".into(), trace.into());
+    event.tags.insert("trace_id".into(), trace_id.to_string());
+    event.tags.insert("span_id".into(), span_id.to_string());
+}
@@ -0,0 +1,118 @@
+use opentelemetry::Context;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::resource::Resource;
+use opentelemetry_sdk::trace::{Span, SpanData, SpanExporter, SpanProcessor};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Running totals for the Langfuse export pipeline.
+#[derive(Debug, Default)]
+pub struct ExportStats {
+    ended: AtomicU64,
+    exported: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Point-in-time copy of [`ExportStats`].
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ExportTotals {
+    /// Spans handed to the batch processor.
+    pub ended: u64,
+    /// Spans the backend accepted.
+    pub exported: u64,
+    /// Spans in batches the backend rejected or that timed out.
+    pub failed: u64,
+    /// Spans never sent: dropped on a full queue, or still queued unless the
+    /// pipeline was just flushed.
+    pub dropped: u64,
+}
+
+impl ExportStats {
+    pub fn totals(&self) -> ExportTotals {
+        let ended = self.ended.load(Ordering::Relaxed);
+        let exported = self.exported.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        ExportTotals {
+            ended,
+            exported,
+            failed,
+            dropped: ended.saturating_sub(exported + failed),
+        }
+    }
+}
+
+/// Exporter wrapper counting accepted and failed spans.
+#[derive(Debug)]
+pub struct CountingExporter<E> {
+    inner: E,
+    stats: Arc<ExportStats>,
+}
+
+impl<E> CountingExporter<E> {
+    pub fn new(inner: E, stats: Arc<ExportStats>) -> Self {
+        Self { inner, stats }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for CountingExporter<E> {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let count = batch.len() as u64;
+        let result = self.inner.export(batch).await;
+        let counter = match result {
+            Ok(()) => &self.stats.exported,
+            Err(_) => &self.stats.failed,
+        };
+        counter.fetch_add(count, Ordering::Relaxed);
+        result
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Processor that only counts ended spans, registered next to the batch
+/// processor so spans dropped on a full queue can be derived.
+#[derive(Debug)]
+pub struct EndedSpanCounter {
+    stats: Arc<ExportStats>,
+}
+
+impl EndedSpanCounter {
+    pub fn new(stats: Arc<ExportStats>) -> Self {
+        Self { stats }
+    }
+
+    /// Count `span` as ended, for processors that forward spans elsewhere.
+    pub fn count(&self, span: &SpanData) {
+        if span.span_context.is_sampled() {
+            self.stats.ended.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl SpanProcessor for EndedSpanCounter {
+    fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+    fn on_end(&self, span: SpanData) {
+        self.count(&span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+//! Feature flags switching experimental behaviors without a redeploy.
+//!
+//! Flags start from their defaults, overridden by the JSON object in
+//! `FEATURE_FLAGS_PATH`, then by the one served at `FEATURE_FLAGS_URL`
+//! (e.g. `{"streaming_results": false}`); both are re-read every
+//! `FEATURE_FLAGS_POLL_SECS`. A source that cannot be read or parsed keeps
+//! its previous values.
+//!
+//! Each tool call sees the flags as they were when it started, and records
+//! them as `feature_flag.<name>` attributes on its span, so traces can be
+//! filtered and compared by flag in Langfuse.
+
+use anyhow::{Context as _, Result};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::RwLock;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::app_state::AppState;
+use crate::config::FeatureFlagsConfig;
+
+/// The evaluated flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flags {
+    /// Send provider calls to Open-Meteo whatever `WEATHER_PROVIDER` says.
+    pub open_meteo_provider: bool,
+    /// Send `get_forecast` chunks as progress notifications.
+    pub streaming_results: bool,
+    /// Ask the user for a missing location, when the client supports it.
+    pub elicitation: bool,
+}
+
+impl Default for Flags {
+    fn default() -> Self {
+        Self {
+            open_meteo_provider: false,
+            streaming_results: true,
+            elicitation: true,
+        }
+    }
+}
+
+impl Flags {
+    /// Each flag with its value, in name order.
+    pub fn entries(&self) -> [(&'static str, bool); 3] {
+        [
+            ("elicitation", self.elicitation),
+            ("open_meteo_provider", self.open_meteo_provider),
+            ("streaming_results", self.streaming_results),
+        ]
+    }
+
+    /// These flags with the values in `overrides`; other names are ignored.
+    fn with(mut self, overrides: &BTreeMap<String, bool>) -> Self {
+        for (name, value) in overrides {
+            match name.as_str() {
+                "elicitation" => self.elicitation = *value,
+                "open_meteo_provider" => self.open_meteo_provider = *value,
+                "streaming_results" => self.streaming_results = *value,
+                _ => {}
+            }
+        }
+        self
+    }
+}
+
+/// Flag values in a JSON object.
+fn parse(json: &str) -> Result<BTreeMap<String, bool>> {
+    serde_json::from_str(json).context("expected a JSON object of flag names to booleans")
+}
+
+/// Flags outside tool calls, and at the start of new ones.
+static LIVE: Lazy<RwLock<Flags>> = Lazy::new(RwLock::default);
+
+tokio::task_local! {
+    /// Flags of the tool call running on this task.
+    static CALL_FLAGS: Flags;
+}
+
+fn live() -> Flags {
+    *LIVE.read().expect("feature flags lock poisoned")
+}
+
+/// Flags of the tool call in progress, else the live ones.
+pub fn current() -> Flags {
+    CALL_FLAGS
+        .try_with(|flags| *flags)
+        .unwrap_or_else(|_| live())
+}
+
+/// Run a tool call with the live flags, unchanged until it ends.
+pub async fn scope<F: Future>(call: F) -> F::Output {
+    CALL_FLAGS.scope(live(), call).await
+}
+
+/// Record the current flags on `span` as `feature_flag.<name>` attributes.
+pub fn record(span: &tracing::Span) {
+    for (name, value) in current().entries() {
+        span.set_attribute(format!("feature_flag.{name}"), value);
+    }
+}
+
+/// Last values read from each source.
+#[derive(Default)]
+struct Sources {
+    file: BTreeMap<String, bool>,
+    remote: BTreeMap<String, bool>,
+}
+
+/// Replace a source's values with `read`. Names that are not flags are
+/// logged, once per change of the source, and ignored, so a flag can be
+/// retired before the sources drop it.
+fn update(source: &mut BTreeMap<String, bool>, read: BTreeMap<String, bool>) {
+    if *source == read {
+        return;
+    }
+    let known = Flags::default().entries().map(|(name, _)| name);
+    for name in read.keys() {
+        if !known.contains(&name.as_str()) {
+            tracing::warn!(flag = %name, "Unknown feature flag ignored");
+        }
+    }
+    *source = read;
+}
+
+impl Sources {
+    fn flags(&self) -> Flags {
+        Flags::default().with(&self.file).with(&self.remote)
+    }
+}
+
+fn read_file(path: &Path) -> Result<BTreeMap<String, bool>> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read feature flags from {}", path.display()))?;
+    parse(&json).with_context(|| format!("invalid feature flags in {}", path.display()))
+}
+
+/// Apply the flags file, if any. An unreadable or invalid file is an error
+/// here, where it stops startup; later polls only log it.
+pub fn load(config: &FeatureFlagsConfig) -> Result<()> {
+    if let Some(path) = &config.path {
+        let mut sources = Sources::default();
+        update(&mut sources.file, read_file(path)?);
+        *LIVE.write().expect("feature flags lock poisoned") = sources.flags();
+    }
+    let flags = live();
+    tracing::info!(flags = ?flags.entries(), "Feature flags loaded");
+    Ok(())
+}
+
+/// Re-read the flag sources every poll interval, if any is configured.
+pub fn spawn(state: AppState) {
+    let config = state.config.feature_flags.clone();
+    if !config.is_configured() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut sources = Sources::default();
+        let mut ticker = tokio::time::interval(config.poll_interval);
+        loop {
+            ticker.tick().await;
+            if let Some(path) = &config.path {
+                match read_file(path) {
+                    Ok(file) => update(&mut sources.file, file),
+                    Err(error) => {
+                        tracing::warn!(error = %format!("{error:#}"), "Keeping previous feature flags")
+                    }
+                }
+            }
+            if let Some(url) = &config.url {
+                match fetch(&state, url).await {
+                    Ok(remote) => update(&mut sources.remote, remote),
+                    Err(error) => {
+                        tracing::warn!(error = %format!("{error:#}"), url = %url, "Keeping previous remote feature flags")
+                    }
+                }
+            }
+            let flags = sources.flags();
+            let previous = std::mem::replace(
+                &mut *LIVE.write().expect("feature flags lock poisoned"),
+                flags,
+            );
+            if flags != previous {
+                tracing::info!(flags = ?flags.entries(), "Feature flags changed");
+            }
+        }
+    });
+}
+
+async fn fetch(state: &AppState, url: &str) -> Result<BTreeMap<String, bool>> {
+    // Not through `HttpClient::send`: a poll is not worth a trace
+    let body = state
+        .http_client
+        .inner()
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    parse(&body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_flags_win_over_the_file_and_unknown_names_are_ignored() {
+        let sources = Sources {
+            file: parse(r#"{"streaming_results": false, "elicitation": false}"#).unwrap(),
+            remote: parse(r#"{"elicitation": true, "retired_flag": true}"#).unwrap(),
+        };
+
+        assert_eq!(
+            sources.flags(),
+            Flags {
+                open_meteo_provider: false,
+                streaming_results: false,
+                elicitation: true,
+            }
+        );
+        assert!(parse(r#"{"elicitation": "yes"}"#).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! Weekly statistics computed on the server from a daily forecast.
+
+use tracing::instrument;
+
+use crate::weather_tools::{Forecast, ForecastSummary, SummaryDay, Units};
+
+/// Temperature (°C) at which a day scores best for being outdoors.
+const IDEAL_HIGH: f64 = 22.0;
+
+/// Aggregate `forecast` (metric, as providers return it), reporting
+/// temperatures in `units`. `None` for an empty forecast.
+#[instrument(name = "forecast_summary.summarize", skip(forecast), fields(days = forecast.len()))]
+pub fn summarize(location: &str, forecast: &[Forecast], units: Units) -> Option<ForecastSummary> {
+    let (mean_high, mean_low) = mean_temperatures(forecast)?;
+    // `max_by_key` keeps the last maximum; reversed, that is the earliest day
+    let rainiest = forecast
+        .iter()
+        .rev()
+        .max_by_key(|day| day.precipitation_chance)?;
+    let best = best_outdoor_day(forecast)?;
+
+    Some(ForecastSummary {
+        location: location.to_string(),
+        days: forecast.len() as u32,
+        mean_high: one_decimal(units.precise_temperature(mean_high)),
+        mean_low: one_decimal(units.precise_temperature(mean_low)),
+        units,
+        rainiest_day: summary_day(rainiest),
+        best_outdoor_day: summary_day(best),
+    })
+}
+
+#[instrument(name = "forecast_summary.mean_temperatures", skip_all)]
+fn mean_temperatures(forecast: &[Forecast]) -> Option<(f64, f64)> {
+    if forecast.is_empty() {
+        return None;
+    }
+    let days = forecast.len() as f64;
+    let high = forecast.iter().map(|day| day.high as f64).sum::<f64>() / days;
+    let low = forecast.iter().map(|day| day.low as f64).sum::<f64>() / days;
+    Some((high, low))
+}
+
+/// Highest outdoor score; the earlier day on a tie.
+#[instrument(name = "forecast_summary.best_outdoor_day", skip_all, fields(score = tracing::field::Empty))]
+fn best_outdoor_day(forecast: &[Forecast]) -> Option<&Forecast> {
+    let best = forecast.iter().rev().max_by_key(|day| outdoor_score(day))?;
+    tracing::Span::current().record("score", outdoor_score(best));
+    Some(best)
+}
+
+/// 0 to 100: rain, bad skies and temperatures away from [`IDEAL_HIGH`]
+/// each take points off.
+fn outdoor_score(day: &Forecast) -> i32 {
+    let sky = match day.condition.as_str() {
+        "Sunny" => 0.0,
+        "Partly Cloudy" => 5.0,
+        "Cloudy" => 10.0,
+        "Foggy" => 15.0,
+        "Rainy" | "Snowy" => 30.0,
+        "Stormy" => 50.0,
+        _ => 10.0,
+    };
+    let rain = day.precipitation_chance as f64 * 0.4;
+    let temperature = (day.high as f64 - IDEAL_HIGH).abs() * 2.0;
+    (100.0 - sky - rain - temperature).clamp(0.0, 100.0).round() as i32
+}
+
+fn summary_day(day: &Forecast) -> SummaryDay {
+    SummaryDay {
+        date: day.date.clone(),
+        condition: day.condition.clone(),
+        precipitation_chance: day.precipitation_chance,
+        outdoor_score: outdoor_score(day),
+    }
+}
+
+fn one_decimal(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(
+        date: &str,
+        condition: &str,
+        high: i32,
+        low: i32,
+        precipitation_chance: i32,
+    ) -> Forecast {
+        Forecast {
+            day: 1,
+            date: date.to_string(),
+            sunrise: None,
+            sunset: None,
+            high,
+            low,
+            condition: condition.to_string(),
+            precipitation_chance,
+        }
+    }
+
+    #[test]
+    fn outdoor_scores_take_points_off_and_stay_in_range() {
+        let cases = [
+            ("Sunny", 22, 0, 100),
+            ("Sunny", 22, 100, 60),
+            ("Partly Cloudy", 20, 10, 87),
+            ("Foggy", 25, 0, 79),
+            ("Rainy", 12, 80, 18),
+            ("Stormy", -10, 100, 0),
+            ("Hail", 22, 0, 90),
+        ];
+        for (condition, high, rain, expected) in cases {
+            let score = outdoor_score(&day("2026-01-01", condition, high, 0, rain));
+            assert_eq!(score, expected, "{condition} at {high}°C, {rain}% rain");
+        }
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_day() {
+        let forecast = [
+            day("2026-01-01", "Rainy", 15, 8, 70),
+            day("2026-01-02", "Sunny", 22, 12, 0),
+            day("2026-01-03", "Sunny", 22, 14, 0),
+            day("2026-01-04", "Stormy", 18, 10, 70),
+        ];
+        assert_eq!(best_outdoor_day(&forecast).unwrap().date, "2026-01-02");
+        assert!(best_outdoor_day(&[]).is_none());
+
+        let summary = summarize("Paris", &forecast, Units::Metric).unwrap();
+        assert_eq!(summary.best_outdoor_day.date, "2026-01-02");
+        assert_eq!(summary.rainiest_day.date, "2026-01-01");
+    }
+
+    #[test]
+    fn summaries_average_in_the_requested_units() {
+        let forecast = [
+            day("2026-01-01", "Sunny", 20, 10, 0),
+            day("2026-01-02", "Cloudy", 21, 11, 40),
+            day("2026-01-03", "Cloudy", 21, 10, 20),
+        ];
+        let metric = summarize("Paris", &forecast, Units::Metric).unwrap();
+        assert_eq!(metric.days, 3);
+        assert_eq!((metric.mean_high, metric.mean_low), (20.7, 10.3));
+
+        let imperial = summarize("Paris", &forecast, Units::Imperial).unwrap();
+        assert_eq!((imperial.mean_high, imperial.mean_low), (69.2, 50.6));
+        assert!(summarize("Paris", &[], Units::Metric).is_none());
+    }
+}
@@ -0,0 +1,264 @@
+//! Calendar dates and sunrise/sunset for forecast days, in the location's
+//! own timezone.
+
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use std::f64::consts::PI;
+
+/// Where a forecast is for: enough to place it on the local calendar.
+#[derive(Debug, Clone, Copy)]
+pub struct Place {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub timezone: Tz,
+}
+
+/// Demo cities known without a geocoding service, for the mock provider.
+const BUNDLED_PLACES: &[(&str, f64, f64, Tz)] = &[
+    ("amsterdam", 52.3676, 4.9041, Tz::Europe__Amsterdam),
+    ("berlin", 52.52, 13.405, Tz::Europe__Berlin),
+    ("brussels", 50.8503, 4.3517, Tz::Europe__Brussels),
+    ("london", 51.5074, -0.1278, Tz::Europe__London),
+    ("madrid", 40.4168, -3.7038, Tz::Europe__Madrid),
+    ("new york", 40.7128, -74.006, Tz::America__New_York),
+    ("paris", 48.8566, 2.3522, Tz::Europe__Paris),
+    (
+        "san francisco",
+        37.7749,
+        -122.4194,
+        Tz::America__Los_Angeles,
+    ),
+    ("sydney", -33.8688, 151.2093, Tz::Australia__Sydney),
+    ("tokyo", 35.6762, 139.6503, Tz::Asia__Tokyo),
+];
+
+/// Look up a bundled demo city by (normalized) name.
+pub fn bundled_place(location: &str) -> Option<Place> {
+    BUNDLED_PLACES
+        .iter()
+        .find(|(name, ..)| *name == location)
+        .map(|&(_, latitude, longitude, timezone)| Place {
+            latitude,
+            longitude,
+            timezone,
+        })
+}
+
+/// Names of the bundled demo cities, for error messages.
+pub fn bundled_place_names() -> impl Iterator<Item = &'static str> {
+    BUNDLED_PLACES.iter().map(|(name, ..)| *name)
+}
+
+/// Local dates of a `days`-long forecast starting today in `timezone`.
+pub fn forecast_dates(timezone: Tz, now: DateTime<Utc>, days: u32) -> Vec<NaiveDate> {
+    let today = now.with_timezone(&timezone).date_naive();
+    today.iter_days().take(days as usize).collect()
+}
+
+/// Sunrise and sunset on `date` at `place`, in local time. `None` during
+/// polar day or night, when the sun does not cross the horizon.
+///
+/// Uses the NOAA solar position approximation (within a couple of minutes
+/// outside polar regions).
+pub fn sun_times(place: &Place, date: NaiveDate) -> Option<(DateTime<Tz>, DateTime<Tz>)> {
+    let day_of_year = date.ordinal0() as f64;
+    let gamma = 2.0 * PI / 365.0 * day_of_year;
+
+    // Equation of time (minutes) and solar declination (radians)
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * gamma.cos()
+            - 0.032077 * gamma.sin()
+            - 0.014615 * (2.0 * gamma).cos()
+            - 0.040849 * (2.0 * gamma).sin());
+    let declination = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin()
+        - 0.006758 * (2.0 * gamma).cos()
+        + 0.000907 * (2.0 * gamma).sin()
+        - 0.002697 * (3.0 * gamma).cos()
+        + 0.00148 * (3.0 * gamma).sin();
+
+    // Hour angle at which the sun's upper limb touches the horizon, allowing
+    // for atmospheric refraction (90.833°)
+    let latitude = place.latitude.to_radians();
+    let cos_hour_angle = 90.833_f64.to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    // Minutes after midnight UTC of `date`; may fall on the neighbouring
+    // UTC day far from Greenwich, which the local conversion takes care of.
+    let sunrise = 720.0 - 4.0 * (place.longitude + hour_angle) - equation_of_time;
+    let sunset = 720.0 - 4.0 * (place.longitude - hour_angle) - equation_of_time;
+
+    let midnight = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?);
+    let at = |minutes: f64| {
+        // Whole minutes: the approximation is not finer than that
+        (midnight + Duration::minutes(minutes.round() as i64)).with_timezone(&place.timezone)
+    };
+    Some((at(sunrise), at(sunset)))
+}
+
+/// Attach `timezone` to a local wall-clock time. A time skipped by a DST
+/// change is moved forward by the gap; a repeated one takes the earlier
+/// (pre-change) offset.
+pub fn localize(timezone: Tz, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(time) => Some(time),
+        LocalResult::Ambiguous(earliest, _) => Some(earliest),
+        LocalResult::None => {
+            // Read the wall clock with the offset in force before the change
+            let before = timezone
+                .offset_from_utc_datetime(&(local - Duration::days(1)))
+                .fix();
+            let utc = local - Duration::seconds(before.local_minus_utc().into());
+            Some(Utc.from_utc_datetime(&utc).with_timezone(&timezone))
+        }
+    }
+}
+
+/// Sunrise and sunset on `date` formatted with [`format_local`].
+pub fn formatted_sun_times(place: &Place, date: NaiveDate) -> (Option<String>, Option<String>) {
+    sun_times(place, date)
+        .map(|(sunrise, sunset)| (format_local(&sunrise), format_local(&sunset)))
+        .unzip()
+}
+
+/// ISO-8601 with the local UTC offset, e.g. `2026-03-29T07:18:00+02:00`.
+pub fn format_local(time: &DateTime<Tz>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%:z").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+    use chrono_tz::{America, Australia, Europe};
+
+    fn utc(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        value.parse().unwrap()
+    }
+
+    fn paris() -> Place {
+        Place {
+            latitude: 48.8566,
+            longitude: 2.3522,
+            timezone: Europe::Paris,
+        }
+    }
+
+    /// Minutes past local midnight.
+    fn clock_minutes(time: &DateTime<Tz>) -> i64 {
+        (time.hour() * 60 + time.minute()) as i64
+    }
+
+    #[test]
+    fn dates_start_on_the_local_day() {
+        // 23:30 UTC is already the next day in Paris...
+        let dates = forecast_dates(Europe::Paris, utc("2026-03-28T23:30:00Z"), 2);
+        assert_eq!(dates, [date("2026-03-29"), date("2026-03-30")]);
+        // ...and still the previous day in New York
+        let dates = forecast_dates(America::New_York, utc("2026-11-01T03:30:00Z"), 1);
+        assert_eq!(dates, [date("2026-10-31")]);
+    }
+
+    #[test]
+    fn dates_are_consecutive_across_dst_changes() {
+        // Spring forward (2026-03-29) and fall back (2026-10-25) in Paris
+        for start in ["2026-03-27T12:00:00Z", "2026-10-23T12:00:00Z"] {
+            let dates = forecast_dates(Europe::Paris, utc(start), 7);
+            assert_eq!(dates.len(), 7);
+            for pair in dates.windows(2) {
+                assert_eq!(pair[0].succ_opt(), Some(pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn sunrise_offset_follows_spring_forward() {
+        let (before, _) = sun_times(&paris(), date("2026-03-28")).unwrap();
+        let (after, _) = sun_times(&paris(), date("2026-03-29")).unwrap();
+
+        assert_eq!(before.offset().to_string(), "CET");
+        assert_eq!(after.offset().to_string(), "CEST");
+        assert!(format_local(&before).ends_with("+01:00"));
+        assert!(format_local(&after).ends_with("+02:00"));
+        assert_eq!(before.date_naive(), date("2026-03-28"));
+        assert_eq!(after.date_naive(), date("2026-03-29"));
+
+        // Same instant give or take two minutes of daylight, one hour later on the clock
+        let jump = clock_minutes(&after) - clock_minutes(&before);
+        assert!((56..=60).contains(&jump), "clock moved {jump} minutes");
+    }
+
+    #[test]
+    fn sunset_offset_follows_fall_back() {
+        let (_, before) = sun_times(&paris(), date("2026-10-24")).unwrap();
+        let (_, after) = sun_times(&paris(), date("2026-10-25")).unwrap();
+
+        assert!(format_local(&before).ends_with("+02:00"));
+        assert!(format_local(&after).ends_with("+01:00"));
+        let jump = clock_minutes(&before) - clock_minutes(&after);
+        assert!((58..=63).contains(&jump), "clock moved {jump} minutes");
+    }
+
+    #[test]
+    fn southern_hemisphere_dst_ends_in_april() {
+        let sydney = Place {
+            latitude: -33.8688,
+            longitude: 151.2093,
+            timezone: Australia::Sydney,
+        };
+        let (before, _) = sun_times(&sydney, date("2026-04-04")).unwrap();
+        let (after, _) = sun_times(&sydney, date("2026-04-05")).unwrap();
+        assert!(format_local(&before).ends_with("+11:00"));
+        assert!(format_local(&after).ends_with("+10:00"));
+        // East of UTC, sunrise falls on the previous UTC day
+        assert_eq!(after.date_naive(), date("2026-04-05"));
+        assert_eq!(after.with_timezone(&Utc).date_naive(), date("2026-04-04"));
+    }
+
+    #[test]
+    fn summer_solstice_in_paris() {
+        let (sunrise, sunset) = sun_times(&paris(), date("2026-06-21")).unwrap();
+        // Published times: 05:46 and 21:58 CEST
+        assert!((clock_minutes(&sunrise) - (5 * 60 + 46)).abs() <= 3);
+        assert!((clock_minutes(&sunset) - (21 * 60 + 58)).abs() <= 3);
+    }
+
+    #[test]
+    fn no_sunrise_in_polar_night() {
+        let tromso = Place {
+            latitude: 69.6492,
+            longitude: 18.9553,
+            timezone: Europe::Oslo,
+        };
+        assert!(sun_times(&tromso, date("2026-12-21")).is_none());
+        assert!(sun_times(&tromso, date("2026-06-21")).is_none());
+        assert!(sun_times(&tromso, date("2026-03-21")).is_some());
+    }
+
+    #[test]
+    fn localize_resolves_gaps_and_overlaps() {
+        let local = |value: &str| value.parse::<NaiveDateTime>().unwrap();
+
+        // 02:30 does not exist on 2026-03-29 in Paris
+        let skipped = localize(Europe::Paris, local("2026-03-29T02:30:00")).unwrap();
+        assert_eq!(format_local(&skipped), "2026-03-29T03:30:00+02:00");
+
+        // 02:30 happens twice on 2026-10-25; take the first (summer time)
+        let repeated = localize(Europe::Paris, local("2026-10-25T02:30:00")).unwrap();
+        assert_eq!(format_local(&repeated), "2026-10-25T02:30:00+02:00");
+
+        let plain = localize(Europe::Paris, local("2026-07-01T06:00:00")).unwrap();
+        assert_eq!(format_local(&plain), "2026-07-01T06:00:00+02:00");
+    }
+}
@@ -0,0 +1,75 @@
+use rmcp::model::{CreateMessageRequestParam, CreateMessageResult};
+use rmcp::service::{Peer, ServiceError};
+use rmcp::RoleServer;
+use serde_json::json;
+use tracing::{field::Empty, Instrument, Span};
+
+/// Open a span following the OpenTelemetry `gen_ai` semantic conventions.
+///
+/// Langfuse renders spans carrying a model and `langfuse.observation.type =
+/// "generation"` as generations, with prompt, completion and token usage.
+/// Wrap every LLM call (MCP sampling or a direct API call) in one of these and
+/// record `gen_ai.usage.*` when the model reports token counts.
+pub fn generation_span(operation: &str, system: &str, request_model: Option<&str>) -> Span {
+    tracing::info_span!(
+        "gen_ai.generation",
+        otel.name = %format!("{operation} {}", request_model.unwrap_or(system)),
+        otel.kind = "client",
+        langfuse.observation.type = "generation",
+        gen_ai.operation.name = operation,
+        gen_ai.system = system,
+        gen_ai.request.model = request_model,
+        gen_ai.request.max_tokens = Empty,
+        gen_ai.request.temperature = Empty,
+        gen_ai.prompt = Empty,
+        gen_ai.completion = Empty,
+        gen_ai.response.model = Empty,
+        gen_ai.response.finish_reasons = Empty,
+        gen_ai.usage.input_tokens = Empty,
+        gen_ai.usage.output_tokens = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// Ask the client's LLM for a completion via MCP sampling, inside a generation span.
+///
+/// Sampling responses do not report token counts, so usage stays unset.
+pub async fn sample(
+    peer: &Peer<RoleServer>,
+    request: CreateMessageRequestParam,
+) -> Result<CreateMessageResult, ServiceError> {
+    let span = generation_span("chat", "mcp.sampling", None);
+    span.record("gen_ai.request.max_tokens", request.max_tokens);
+    if let Some(temperature) = request.temperature {
+        span.record("gen_ai.request.temperature", f64::from(temperature));
+    }
+    let prompt = json!({
+        "system": request.system_prompt,
+        "messages": request.messages,
+    });
+    span.record("gen_ai.prompt", tracing::field::display(&prompt));
+
+    let result = peer.create_message(request).instrument(span.clone()).await;
+
+    match &result {
+        Ok(result) => {
+            let completion = result
+                .message
+                .content
+                .as_text()
+                .map(|text| text.text.as_str())
+                .unwrap_or_default();
+            span.record("gen_ai.completion", completion);
+            span.record("gen_ai.response.model", result.model.as_str());
+            if let Some(stop_reason) = &result.stop_reason {
+                span.record("gen_ai.response.finish_reasons", stop_reason.as_str());
+            }
+        }
+        Err(error) => {
+            span.record("otel.status_code", "ERROR");
+            tracing::warn!(parent: &span, error = %error, "Sampling request failed");
+        }
+    }
+
+    result
+}
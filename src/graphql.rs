@@ -0,0 +1,192 @@
+//! GraphQL endpoint at `/graphql` for web clients, served next to MCP and
+//! REST when `GRAPHQL_ENABLED` is set.
+//!
+//! The queries share the provider and cache layers with the other facades.
+//! Each request gets a `graphql.request` span, parented on the caller's
+//! trace like the REST handlers, with one span per resolved field under it.
+//! `GET /graphql` serves GraphiQL to explore the schema.
+
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object, Schema};
+use axum::extract::State;
+use axum::response::{Html, IntoResponse};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use tracing::{instrument, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::app_state::{AppState, MAX_FORECAST_DAYS, MAX_HISTORY_DAYS, MAX_LONG_RANGE_DAYS};
+use crate::i18n::Lang;
+use crate::providers::ProviderError;
+use crate::trace_utils::{enrich_span, RequestAttribution};
+use crate::usage_stats::ServerStats;
+use crate::weather_tools::{
+    default_days, default_history_days, Forecast, ForecastResponse, Units, Weather,
+};
+use rmcp_otel::TraceParentContext;
+
+pub type WeatherSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Routes of the endpoint, to merge into the HTTP router.
+pub fn router(state: AppState) -> Router {
+    let schema = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(state)
+        .finish();
+    Router::new()
+        .route("/graphql", get(graphiql).post(execute))
+        .with_state(schema)
+}
+
+async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+async fn execute(
+    State(schema): State<WeatherSchema>,
+    parent: Option<Extension<TraceParentContext>>,
+    attribution: Option<Extension<RequestAttribution>>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let span = tracing::info_span!(
+        "graphql.request",
+        graphql.operation.name = request.operation_name.as_deref(),
+        graphql.errors = tracing::field::Empty,
+        otel.status_code = tracing::field::Empty,
+    );
+    if let Some(Extension(TraceParentContext(context))) = parent {
+        let _ = span.set_parent(context);
+    }
+    if let Some(Extension(attribution)) = attribution {
+        enrich_span(&span, &attribution);
+    }
+
+    let response = schema.execute(request).instrument(span.clone()).await;
+    if response.is_err() {
+        span.record("graphql.errors", response.errors.len());
+        span.record("otel.status_code", "ERROR");
+    }
+    Json(response)
+}
+
+/// Provider failures as GraphQL errors, with the tool error code as the
+/// `code` extension.
+fn graphql_error(error: ProviderError) -> async_graphql::Error {
+    tracing::Span::current().record("otel.status_code", "ERROR");
+    let code = error.code();
+    async_graphql::Error::new(error.to_string()).extend_with(|_, extensions| {
+        extensions.set("code", code);
+    })
+}
+
+fn invalid_days(max_days: u32, qualifier: &str) -> async_graphql::Error {
+    tracing::Span::current().record("otel.status_code", "ERROR");
+    async_graphql::Error::new(format!("days must be between 1 and {max_days}{qualifier}"))
+        .extend_with(|_, extensions| extensions.set("code", "invalid_params"))
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Current conditions for a location
+    #[instrument(name = "graphql.weather", skip(self, ctx), fields(
+        otel.status_code = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn weather(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "City name")] location: String,
+        #[graphql(desc = "Unit system, metric by default")] units: Option<Units>,
+        #[graphql(desc = "Language of condition names, en by default")] lang: Option<String>,
+    ) -> async_graphql::Result<Weather> {
+        let state = ctx.data::<AppState>()?;
+        let weather = state
+            .current_weather(&location)
+            .await
+            .map_err(graphql_error)?;
+        Ok(weather
+            .in_units(units.unwrap_or_default())
+            .localized(Lang::resolve(lang.as_deref())))
+    }
+
+    /// Daily forecast for a location, starting today
+    #[instrument(name = "graphql.forecast", skip(self, ctx), fields(
+        otel.status_code = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn forecast(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "City name")] location: String,
+        #[graphql(desc = "Number of days (1-7, or 1-16 with longRange), 3 by default")]
+        days: Option<u32>,
+        #[graphql(desc = "Unit system, metric by default")] units: Option<Units>,
+        #[graphql(desc = "Language of condition names, en by default")] lang: Option<String>,
+        #[graphql(
+            desc = "Allow up to 16 days from providers that forecast that far",
+            default
+        )]
+        long_range: bool,
+    ) -> async_graphql::Result<ForecastResponse> {
+        let state = ctx.data::<AppState>()?;
+        let days = days.unwrap_or_else(default_days);
+        let items = if long_range {
+            if !(1..=MAX_LONG_RANGE_DAYS).contains(&days) {
+                return Err(invalid_days(MAX_LONG_RANGE_DAYS, " with longRange"));
+            }
+            state.long_range_forecast(&location, days).await
+        } else {
+            if !(1..=MAX_FORECAST_DAYS).contains(&days) {
+                return Err(invalid_days(MAX_FORECAST_DAYS, ""));
+            }
+            state.forecast(&location, days).await
+        }
+        .map_err(graphql_error)?;
+        Ok(ForecastResponse::in_units(items, units.unwrap_or_default())
+            .localized(Lang::resolve(lang.as_deref())))
+    }
+
+    /// Daily conditions of the past days, oldest first; `day` counts back
+    /// from 0 for yesterday
+    #[instrument(name = "graphql.history", skip(self, ctx), fields(
+        otel.status_code = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "City name")] location: String,
+        #[graphql(desc = "Number of past days (1-30), 7 by default")] days: Option<u32>,
+        #[graphql(desc = "Unit system, metric by default")] units: Option<Units>,
+        #[graphql(desc = "Language of condition names, en by default")] lang: Option<String>,
+    ) -> async_graphql::Result<Vec<Forecast>> {
+        let state = ctx.data::<AppState>()?;
+        let days = days.unwrap_or_else(default_history_days);
+        if !(1..=MAX_HISTORY_DAYS).contains(&days) {
+            return Err(invalid_days(MAX_HISTORY_DAYS, ""));
+        }
+        let items = state
+            .history(&location, days)
+            .await
+            .map_err(graphql_error)?;
+        Ok(ForecastResponse::in_units(items, units.unwrap_or_default())
+            .localized(Lang::resolve(lang.as_deref()))
+            .items)
+    }
+
+    /// Tool usage, latency and session counters, as get_server_stats
+    /// returns them
+    #[instrument(name = "graphql.stats", skip(self, ctx))]
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<async_graphql::Json<ServerStats>> {
+        let state = ctx.data::<AppState>()?;
+        Ok(async_graphql::Json(state.usage.snapshot(
+            &state.deprecations,
+            &state.keepalive,
+            state.concurrency.as_deref(),
+        )))
+    }
+}
@@ -0,0 +1,447 @@
+//! gRPC facade (`weather.v1.Weather`, see `proto/weather.proto`) served on
+//! the HTTP port next to MCP and REST, when `GRPC_ENABLED` is set.
+//!
+//! The calls share the provider and cache layers with the other facades.
+//! gRPC metadata are HTTP/2 headers, so `TracePropagationLayer` picks up a
+//! `traceparent` sent as metadata like any other: each call's span is a child
+//! of its `http.request` span and continues the caller's trace.
+
+use axum::body::Body;
+use axum::http::{Request, Response};
+use axum::Router;
+use futures::future::BoxFuture;
+use std::convert::Infallible;
+use std::future::Future;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::server::{Grpc, UnaryService};
+use tonic::Status;
+use tower::Service;
+use tracing::instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::app_state::{AppState, MAX_LONG_RANGE_DAYS};
+use crate::i18n::Lang;
+use crate::providers::ProviderError;
+use crate::trace_utils::{enrich_span, is_recorded, RequestAttribution};
+use crate::weather_tools::{default_days, Forecast, ForecastResponse, Units, Weather};
+use rmcp_otel::TraceParentContext;
+
+/// Fully qualified name of the service, the first segment of its paths.
+pub const SERVICE: &str = "weather.v1.Weather";
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetWeatherRequest {
+    #[prost(string, tag = "1")]
+    pub location: String,
+    #[prost(string, tag = "2")]
+    pub lang: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WeatherReply {
+    #[prost(string, tag = "1")]
+    pub location: String,
+    #[prost(int32, tag = "2")]
+    pub temperature: i32,
+    #[prost(string, tag = "3")]
+    pub condition: String,
+    #[prost(int32, tag = "4")]
+    pub humidity: i32,
+    #[prost(int32, tag = "5")]
+    pub wind_speed: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetForecastRequest {
+    #[prost(string, tag = "1")]
+    pub location: String,
+    #[prost(uint32, tag = "2")]
+    pub days: u32,
+    #[prost(string, tag = "3")]
+    pub lang: String,
+    #[prost(bool, tag = "4")]
+    pub long_range: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ForecastDay {
+    #[prost(int32, tag = "1")]
+    pub day: i32,
+    #[prost(string, tag = "2")]
+    pub date: String,
+    #[prost(string, tag = "3")]
+    pub sunrise: String,
+    #[prost(string, tag = "4")]
+    pub sunset: String,
+    #[prost(int32, tag = "5")]
+    pub high: i32,
+    #[prost(int32, tag = "6")]
+    pub low: i32,
+    #[prost(string, tag = "7")]
+    pub condition: String,
+    #[prost(int32, tag = "8")]
+    pub precipitation_chance: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ForecastReply {
+    #[prost(message, repeated, tag = "1")]
+    pub items: Vec<ForecastDay>,
+}
+
+impl From<Weather> for WeatherReply {
+    fn from(weather: Weather) -> Self {
+        Self {
+            location: weather.location,
+            temperature: weather.temperature,
+            condition: weather.condition,
+            humidity: weather.humidity,
+            wind_speed: weather.wind_speed,
+        }
+    }
+}
+
+impl From<Forecast> for ForecastDay {
+    fn from(day: Forecast) -> Self {
+        Self {
+            day: day.day,
+            date: day.date,
+            sunrise: day.sunrise.unwrap_or_default(),
+            sunset: day.sunset.unwrap_or_default(),
+            high: day.high,
+            low: day.low,
+            condition: day.condition,
+            precipitation_chance: day.precipitation_chance,
+        }
+    }
+}
+
+fn status(error: ProviderError) -> Status {
+    let message = error.to_string();
+    match error {
+        ProviderError::LocationNotFound(_) | ProviderError::NoData(_) => Status::not_found(message),
+        ProviderError::ForecastTooLong { .. } => Status::failed_precondition(message),
+        ProviderError::Timeout(_) => Status::deadline_exceeded(message),
+        ProviderError::LocationTooLong => Status::invalid_argument(message),
+        _ => Status::unavailable(message),
+    }
+}
+
+/// Routes of the service, to merge into the HTTP router.
+pub fn router(state: AppState) -> Router {
+    Router::new().route_service(&format!("/{SERVICE}/*method"), WeatherGrpc { state })
+}
+
+#[derive(Clone)]
+struct WeatherGrpc {
+    state: AppState,
+}
+
+impl Service<Request<Body>> for WeatherGrpc {
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let state = self.state.clone();
+        Box::pin(async move {
+            let method = request.uri().path().rsplit('/').next().unwrap_or_default();
+            let response = match method {
+                "GetWeather" => {
+                    let handler = Unary(|request: tonic::Request<GetWeatherRequest>| {
+                        get_weather(state.clone(), request)
+                    });
+                    Grpc::new(ProstCodec::default())
+                        .unary(handler, request)
+                        .await
+                }
+                "GetForecast" => {
+                    let handler = Unary(|request: tonic::Request<GetForecastRequest>| {
+                        get_forecast(state.clone(), request)
+                    });
+                    Grpc::new(ProstCodec::default())
+                        .unary(handler, request)
+                        .await
+                }
+                other => {
+                    Status::unimplemented(format!("unknown method {SERVICE}/{other}")).into_http()
+                }
+            };
+            Ok(response)
+        })
+    }
+}
+
+/// A handler function as tonic's unary service.
+struct Unary<F>(F);
+
+impl<Req, Reply, F, Fut> UnaryService<Req> for Unary<F>
+where
+    F: FnMut(tonic::Request<Req>) -> Fut,
+    Fut: Future<Output = Result<tonic::Response<Reply>, Status>>,
+{
+    type Response = Reply;
+    type Future = Fut;
+
+    fn call(&mut self, request: tonic::Request<Req>) -> Fut {
+        (self.0)(request)
+    }
+}
+
+/// Parent the handler span on the request's `http.request` span, record the
+/// caller's session and user, and the outcome once known.
+fn attach_parent<T>(request: &tonic::Request<T>) {
+    let span = tracing::Span::current();
+    if let Some(TraceParentContext(context)) = request.extensions().get() {
+        let _ = span.set_parent(context.clone());
+    }
+    if let Some(attribution) = request.extensions().get::<RequestAttribution>() {
+        enrich_span(&span, attribution);
+    }
+}
+
+fn record_outcome<T: std::fmt::Debug>(result: &Result<T, Status>) {
+    let span = tracing::Span::current();
+    match result {
+        Ok(reply) => {
+            span.record("rpc.grpc.status_code", 0);
+            if is_recorded(&span) {
+                span.record("output", tracing::field::debug(reply));
+            }
+        }
+        Err(status) => {
+            span.record("rpc.grpc.status_code", status.code() as i32);
+            span.record("otel.status_code", "ERROR");
+        }
+    }
+}
+
+fn lang(code: &str) -> Lang {
+    Lang::resolve((!code.is_empty()).then_some(code))
+}
+
+#[instrument(name = "grpc.GetWeather", skip_all, fields(
+    rpc.system = "grpc",
+    rpc.service = SERVICE,
+    rpc.method = "GetWeather",
+    rpc.grpc.status_code = tracing::field::Empty,
+    otel.status_code = tracing::field::Empty,
+    output = tracing::field::Empty,
+    cache.state = tracing::field::Empty
+))]
+async fn get_weather(
+    state: AppState,
+    request: tonic::Request<GetWeatherRequest>,
+) -> Result<tonic::Response<WeatherReply>, Status> {
+    attach_parent(&request);
+    let request = request.into_inner();
+
+    let result = state
+        .current_weather(&request.location)
+        .await
+        .map(|weather| WeatherReply::from(weather.localized(lang(&request.lang))))
+        .map_err(status);
+    record_outcome(&result);
+    result.map(tonic::Response::new)
+}
+
+#[instrument(name = "grpc.GetForecast", skip_all, fields(
+    rpc.system = "grpc",
+    rpc.service = SERVICE,
+    rpc.method = "GetForecast",
+    rpc.grpc.status_code = tracing::field::Empty,
+    otel.status_code = tracing::field::Empty,
+    output = tracing::field::Empty,
+    cache.state = tracing::field::Empty
+))]
+async fn get_forecast(
+    state: AppState,
+    request: tonic::Request<GetForecastRequest>,
+) -> Result<tonic::Response<ForecastReply>, Status> {
+    attach_parent(&request);
+    let request = request.into_inner();
+
+    let days = match request.days {
+        0 => default_days(),
+        days => days,
+    };
+    let result = if !request.long_range {
+        state
+            .forecast(&request.location, days)
+            .await
+            .map_err(status)
+    } else if days > MAX_LONG_RANGE_DAYS {
+        Err(Status::invalid_argument(format!(
+            "days must be between 1 and {MAX_LONG_RANGE_DAYS} with long_range"
+        )))
+    } else {
+        state
+            .long_range_forecast(&request.location, days)
+            .await
+            .map_err(status)
+    };
+    let lang = lang(&request.lang);
+    let result = result.map(|days| ForecastReply {
+        items: ForecastResponse::in_units(days, Units::Metric)
+            .localized(lang)
+            .items
+            .into_iter()
+            .map(ForecastDay::from)
+            .collect(),
+    });
+    record_outcome(&result);
+    result.map(tonic::Response::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::encoding::{self, WireType};
+    use prost::Message;
+    use std::collections::HashMap;
+
+    /// A field of a message in `proto/weather.proto`.
+    #[derive(Clone)]
+    struct ProtoField {
+        repeated: bool,
+        kind: String,
+        name: String,
+        tag: u32,
+    }
+
+    /// `proto/weather.proto` without comments.
+    fn proto() -> String {
+        include_str!("../proto/weather.proto")
+            .lines()
+            .map(|line| line.split("//").next().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn proto_messages() -> HashMap<String, Vec<ProtoField>> {
+        let proto = proto();
+        let mut messages = HashMap::new();
+        for block in proto.split("message ").skip(1) {
+            let (name, body) = block.split_once('{').unwrap();
+            let body = body.split('}').next().unwrap();
+            let fields = body
+                .split(';')
+                .map(str::trim)
+                .filter(|declaration| !declaration.is_empty())
+                .map(|declaration| {
+                    let (field, tag) = declaration.split_once('=').unwrap();
+                    let words: Vec<&str> = field.split_whitespace().collect();
+                    let (repeated, kind, name) = match words[..] {
+                        ["repeated", kind, name] => (true, kind, name),
+                        [kind, name] => (false, kind, name),
+                        _ => panic!("unexpected field '{declaration}'"),
+                    };
+                    ProtoField {
+                        repeated,
+                        kind: kind.to_string(),
+                        name: name.to_string(),
+                        tag: tag.trim().parse().unwrap(),
+                    }
+                })
+                .collect();
+            messages.insert(name.trim().to_string(), fields);
+        }
+        messages
+    }
+
+    /// Message `name` with every field set to a value that only survives a
+    /// decode and encode when the struct has that field with that type.
+    fn probe(messages: &HashMap<String, Vec<ProtoField>>, name: &str) -> Vec<u8> {
+        let mut fields = messages[name].clone();
+        fields.sort_by_key(|field| field.tag);
+        let mut buf = Vec::new();
+        for field in fields {
+            match field.kind.as_str() {
+                "string" => encoding::string::encode(field.tag, &"x".to_string(), &mut buf),
+                "int32" => encoding::int32::encode(field.tag, &-1, &mut buf),
+                "uint32" => encoding::uint32::encode(field.tag, &u32::MAX, &mut buf),
+                "bool" => encoding::bool::encode(field.tag, &true, &mut buf),
+                message => {
+                    let nested = probe(messages, message);
+                    encoding::encode_key(field.tag, WireType::LengthDelimited, &mut buf);
+                    encoding::encode_varint(nested.len() as u64, &mut buf);
+                    buf.extend(nested);
+                }
+            }
+        }
+        buf
+    }
+
+    fn check<M: Message + Default>(messages: &HashMap<String, Vec<ProtoField>>, name: &str) {
+        let probe = probe(messages, name);
+        let decoded = M::decode(probe.as_slice())
+            .unwrap_or_else(|error| panic!("{name} does not match the proto: {error}"));
+        assert_eq!(
+            decoded.encode_to_vec(),
+            probe,
+            "{name} does not match the proto"
+        );
+
+        // Debug lists the struct's fields in order, `name: value`
+        let debug = format!("{:?}", M::default());
+        let (_, body) = debug.split_once('{').unwrap();
+        let fields: Vec<&str> = body
+            .trim_end_matches('}')
+            .split(", ")
+            .filter_map(|field| field.split_once(':'))
+            .map(|(field, _)| field.trim())
+            .collect();
+        let expected: Vec<&str> = messages[name]
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(fields, expected, "{name} fields differ from the proto");
+        for field in messages[name].iter().filter(|field| field.repeated) {
+            assert!(
+                format!("{decoded:?}").contains(&format!("{}: [", field.name)),
+                "{name}.{} is not repeated",
+                field.name
+            );
+        }
+    }
+
+    #[test]
+    fn messages_match_the_proto() {
+        let messages = proto_messages();
+        assert_eq!(messages.len(), 5, "a message is missing from this test");
+        check::<GetWeatherRequest>(&messages, "GetWeatherRequest");
+        check::<WeatherReply>(&messages, "WeatherReply");
+        check::<GetForecastRequest>(&messages, "GetForecastRequest");
+        check::<ForecastDay>(&messages, "ForecastDay");
+        check::<ForecastReply>(&messages, "ForecastReply");
+
+        let proto = proto();
+        let (package, _) = proto
+            .split_once("package ")
+            .and_then(|(_, rest)| rest.split_once(';'))
+            .unwrap();
+        let (service, _) = proto
+            .split_once("service ")
+            .and_then(|(_, rest)| rest.split_once('{'))
+            .unwrap();
+        assert_eq!(format!("{package}.{}", service.trim()), SERVICE);
+        let rpcs: Vec<String> = proto
+            .split("rpc ")
+            .skip(1)
+            .map(|rpc| rpc.split(';').next().unwrap().split_whitespace().collect())
+            .collect();
+        assert_eq!(
+            rpcs,
+            [
+                "GetWeather(GetWeatherRequest)returns(WeatherReply)",
+                "GetForecast(GetForecastRequest)returns(ForecastReply)",
+            ]
+        );
+    }
+}
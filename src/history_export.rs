@@ -0,0 +1,189 @@
+//! Files produced by `export_history` and `/api/history/{city}/export`.
+//!
+//! CSV has the columns of the forecast resources. Parquet has one row per
+//! day in a single row group, for loading into notebooks and warehouses.
+
+use parquet::data_type::{ByteArray, ByteArrayType, Int32Type};
+use parquet::errors::{ParquetError, Result};
+use parquet::file::writer::{
+    SerializedColumnWriter, SerializedFileWriter, SerializedRowGroupWriter,
+};
+use parquet::schema::parser::parse_message_type;
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::resources;
+use crate::weather_tools::{Forecast, ForecastResponse, Units};
+
+const PARQUET_SCHEMA: &str = "
+    message weather_history {
+        REQUIRED INT32 day;
+        REQUIRED BYTE_ARRAY date (UTF8);
+        REQUIRED INT32 high_c;
+        REQUIRED INT32 low_c;
+        REQUIRED BYTE_ARRAY condition (UTF8);
+        REQUIRED INT32 precipitation_chance;
+        OPTIONAL BYTE_ARRAY sunrise (UTF8);
+        OPTIONAL BYTE_ARRAY sunset (UTF8);
+    }
+";
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    schemars::JsonSchema,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// Metric days, oldest first, as CSV.
+pub fn csv(days: Vec<Forecast>) -> String {
+    resources::forecast_csv(&ForecastResponse::in_units(days, Units::Metric))
+}
+
+/// Metric days, oldest first, as a Parquet file.
+pub fn parquet(days: &[Forecast]) -> Result<Vec<u8>> {
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let mut writer = SerializedFileWriter::new(Vec::new(), schema, Default::default())?;
+    let mut row_group = writer.next_row_group()?;
+
+    // In schema order
+    write_ints(&mut row_group, days.iter().map(|day| day.day))?;
+    write_text(
+        &mut row_group,
+        days.iter().map(|day| Some(day.date.as_str())),
+    )?;
+    write_ints(&mut row_group, days.iter().map(|day| day.high))?;
+    write_ints(&mut row_group, days.iter().map(|day| day.low))?;
+    write_text(
+        &mut row_group,
+        days.iter().map(|day| Some(day.condition.as_str())),
+    )?;
+    write_ints(
+        &mut row_group,
+        days.iter().map(|day| day.precipitation_chance),
+    )?;
+    write_text(
+        &mut row_group,
+        days.iter().map(|day| day.sunrise.as_deref()),
+    )?;
+    write_text(&mut row_group, days.iter().map(|day| day.sunset.as_deref()))?;
+
+    row_group.close()?;
+    writer.into_inner()
+}
+
+fn next_column<'a, W: Write + Send>(
+    row_group: &'a mut SerializedRowGroupWriter<'_, W>,
+) -> Result<SerializedColumnWriter<'a>> {
+    row_group
+        .next_column()?
+        .ok_or_else(|| ParquetError::General("more values than schema columns".to_string()))
+}
+
+fn write_ints<W: Write + Send>(
+    row_group: &mut SerializedRowGroupWriter<'_, W>,
+    values: impl Iterator<Item = i32>,
+) -> Result<()> {
+    let mut column = next_column(row_group)?;
+    let values: Vec<i32> = values.collect();
+    column
+        .typed::<Int32Type>()
+        .write_batch(&values, None, None)?;
+    column.close()
+}
+
+/// A text column; `None` values are only allowed in optional columns.
+fn write_text<'v, W: Write + Send>(
+    row_group: &mut SerializedRowGroupWriter<'_, W>,
+    values: impl Iterator<Item = Option<&'v str>>,
+) -> Result<()> {
+    let mut column = next_column(row_group)?;
+    let writer = column.typed::<ByteArrayType>();
+    let values: Vec<Option<&str>> = values.collect();
+    let present: Vec<ByteArray> = values
+        .iter()
+        .flatten()
+        .map(|value| (*value).into())
+        .collect();
+    if writer.get_descriptor().max_def_level() > 0 {
+        let levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+        writer.write_batch(&present, Some(&levels), None)?;
+    } else {
+        writer.write_batch(&present, None, None)?;
+    }
+    column.close()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn day(day: i32, date: &str, sunrise: Option<&str>) -> Forecast {
+        Forecast {
+            day,
+            date: date.to_string(),
+            sunrise: sunrise.map(str::to_string),
+            sunset: sunrise.map(|_| "2026-03-28T18:40:00+01:00".to_string()),
+            high: 12,
+            low: 4,
+            condition: "Rainy".to_string(),
+            precipitation_chance: 80,
+        }
+    }
+
+    #[test]
+    fn parquet_has_one_row_per_day_with_absent_sun_times_as_nulls() {
+        let days = [
+            day(-1, "2026-03-27", None),
+            day(0, "2026-03-28", Some("2026-03-28T06:10:00+01:00")),
+        ];
+        let path = std::env::temp_dir().join(format!("history-{}.parquet", std::process::id()));
+        std::fs::write(&path, parquet(&days).unwrap()).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let rows: Vec<String> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().to_string())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            rows,
+            [
+                r#"{day: -1, date: "2026-03-27", high_c: 12, low_c: 4, condition: "Rainy", precipitation_chance: 80, sunrise: null, sunset: null}"#,
+                r#"{day: 0, date: "2026-03-28", high_c: 12, low_c: 4, condition: "Rainy", precipitation_chance: 80, sunrise: "2026-03-28T06:10:00+01:00", sunset: "2026-03-28T18:40:00+01:00"}"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use reqwest::{NoProxy, Proxy, RequestBuilder, Response};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tower::{layer::Layer, Service};
+use tracing::Instrument;
+
+use crate::config::HttpClientConfig;
+use crate::trace_utils;
+
+/// Counters shared by every clone of the pooled client.
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    requests: AtomicU64,
+    connections_opened: AtomicU64,
+}
+
+impl ConnectionStats {
+    pub fn connections_opened(&self) -> u64 {
+        self.connections_opened.load(Ordering::Relaxed)
+    }
+}
+
+/// A single pooled `reqwest::Client` shared by all providers.
+///
+/// Cloning is cheap: the connection pool and the stats live behind `Arc`s.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    stats: Arc<ConnectionStats>,
+}
+
+impl HttpClient {
+    pub fn new(config: &HttpClientConfig) -> Result<Self> {
+        let stats = Arc::new(ConnectionStats::default());
+
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.request_timeout)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .user_agent(concat!("rmcp-demo/", env!("CARGO_PKG_VERSION")))
+            .connector_layer(CountConnectionsLayer {
+                stats: stats.clone(),
+            });
+
+        if let Some(proxy_url) = &config.https_proxy {
+            let proxy = Proxy::https(proxy_url)
+                .with_context(|| format!("invalid HTTPS_PROXY url '{proxy_url}'"))?
+                .no_proxy(NoProxy::from_env());
+            builder = builder.proxy(proxy);
+            tracing::info!(proxy = %proxy_url, "Routing outbound HTTPS traffic through proxy");
+        }
+
+        let client = builder.build().context("failed to build HTTP client")?;
+
+        Ok(Self { client, stats })
+    }
+
+    /// Access the underlying client to build requests.
+    pub fn inner(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Send a request inside an `http.client.request` span that records the
+    /// status code and whether a pooled connection was reused. The span's
+    /// context is injected into the request headers, so every provider and
+    /// Langfuse call carries a `traceparent`.
+    ///
+    /// Reuse is inferred from the connection counter not moving while the
+    /// request was in flight, so it is approximate under heavy concurrency.
+    pub async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let mut request = request.build()?;
+        let span = tracing::info_span!(
+            "http.client.request",
+            otel.kind = "client",
+            http.request.method = %request.method(),
+            server.address = request.url().host_str().unwrap_or_default(),
+            url.path = request.url().path(),
+            http.response.status_code = tracing::field::Empty,
+            http.connection.reused = tracing::field::Empty,
+            http.client.requests_total = tracing::field::Empty,
+            http.client.connections_opened = tracing::field::Empty,
+        );
+
+        async move {
+            trace_utils::inject_headers(request.headers_mut());
+            let opened_before = self.stats.connections_opened();
+            let result = self.client.execute(request).await;
+            let opened_after = self.stats.connections_opened();
+            let requests_total = self.stats.requests.fetch_add(1, Ordering::Relaxed) + 1;
+
+            let span = tracing::Span::current();
+            span.record("http.connection.reused", opened_before == opened_after);
+            span.record("http.client.requests_total", requests_total);
+            span.record("http.client.connections_opened", opened_after);
+            if let Ok(response) = &result {
+                span.record("http.response.status_code", response.status().as_u16());
+            }
+
+            result
+        }
+        .instrument(span)
+        .await
+    }
+}
+
+/// Connector layer that counts newly established connections.
+#[derive(Clone)]
+struct CountConnectionsLayer {
+    stats: Arc<ConnectionStats>,
+}
+
+impl<S> Layer<S> for CountConnectionsLayer {
+    type Service = CountConnections<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CountConnections {
+            inner,
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct CountConnections<S> {
+    inner: S,
+    stats: Arc<ConnectionStats>,
+}
+
+impl<S, R> Service<R> for CountConnections<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let stats = self.stats.clone();
+        let connect = self.inner.call(req);
+
+        Box::pin(async move {
+            let connection = connect.await?;
+            stats.connections_opened.fetch_add(1, Ordering::Relaxed);
+            Ok(connection)
+        })
+    }
+}
@@ -0,0 +1,150 @@
+//! Translations of condition names, and language negotiation for tool
+//! `lang` arguments and the REST facade's `Accept-Language` header.
+
+/// Supported languages; anything else falls back to English.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+/// Condition names as providers report them, with their translations in
+/// German, French and Spanish.
+const CONDITIONS: &[(&str, &str, &str, &str)] = &[
+    ("Sunny", "Sonnig", "Ensoleillé", "Soleado"),
+    (
+        "Partly Cloudy",
+        "Teilweise bewölkt",
+        "Partiellement nuageux",
+        "Parcialmente nublado",
+    ),
+    ("Cloudy", "Bewölkt", "Nuageux", "Nublado"),
+    ("Foggy", "Neblig", "Brumeux", "Neblinoso"),
+    ("Rainy", "Regnerisch", "Pluvieux", "Lluvioso"),
+    ("Snowy", "Verschneit", "Neigeux", "Nevado"),
+    ("Stormy", "Stürmisch", "Orageux", "Tormentoso"),
+    ("Unknown", "Unbekannt", "Inconnu", "Desconocido"),
+];
+
+impl Lang {
+    pub const ALL: [Lang; 4] = [Lang::En, Lang::De, Lang::Fr, Lang::Es];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::De => "de",
+            Lang::Fr => "fr",
+            Lang::Es => "es",
+        }
+    }
+
+    /// A BCP 47 tag such as `de`, `de-CH` or `fr_FR`, by its primary
+    /// language. `None` for unsupported languages.
+    pub fn from_tag(tag: &str) -> Option<Lang> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        Lang::ALL.into_iter().find(|lang| lang.code() == primary)
+    }
+
+    /// The requested language, or English when absent or unsupported.
+    pub fn resolve(tag: Option<&str>) -> Lang {
+        tag.and_then(Lang::from_tag).unwrap_or_default()
+    }
+
+    /// Best supported language in an `Accept-Language` header value, by
+    /// quality and then order; English when none is supported.
+    pub fn negotiate(accept_language: &str) -> Lang {
+        let mut best: Option<(f32, Lang)> = None;
+        for entry in accept_language.split(',') {
+            let mut parts = entry.split(';');
+            let tag = parts.next().unwrap_or_default();
+            let quality = parts
+                .find_map(|parameter| parameter.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |value| value.trim().parse::<f32>().ok());
+            let (Some(lang), Some(quality)) = (Lang::from_tag(tag), quality) else {
+                continue;
+            };
+            if quality > 0.0 && best.is_none_or(|(best, _)| quality > best) {
+                best = Some((quality, lang));
+            }
+        }
+        best.map(|(_, lang)| lang).unwrap_or_default()
+    }
+}
+
+/// `condition` in `lang`; conditions without a translation are returned
+/// unchanged.
+pub fn condition(lang: Lang, condition: &str) -> String {
+    CONDITIONS
+        .iter()
+        .find(|(english, ..)| *english == condition)
+        .map(|&(english, german, french, spanish)| match lang {
+            Lang::En => english,
+            Lang::De => german,
+            Lang::Fr => french,
+            Lang::Es => spanish,
+        })
+        .unwrap_or(condition)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_match_by_primary_language() {
+        assert_eq!(Lang::from_tag("de"), Some(Lang::De));
+        assert_eq!(Lang::from_tag("de-CH"), Some(Lang::De));
+        assert_eq!(Lang::from_tag("FR_fr"), Some(Lang::Fr));
+        assert_eq!(Lang::from_tag(" es-419 "), Some(Lang::Es));
+        assert_eq!(Lang::from_tag("pt-BR"), None);
+        assert_eq!(Lang::from_tag(""), None);
+    }
+
+    #[test]
+    fn unsupported_or_missing_languages_fall_back_to_english() {
+        assert_eq!(Lang::resolve(None), Lang::En);
+        assert_eq!(Lang::resolve(Some("pt")), Lang::En);
+        assert_eq!(Lang::resolve(Some("es")), Lang::Es);
+    }
+
+    #[test]
+    fn accept_language_prefers_highest_quality() {
+        assert_eq!(Lang::negotiate("fr-CH, fr;q=0.9, en;q=0.8"), Lang::Fr);
+        assert_eq!(Lang::negotiate("en;q=0.5, de;q=0.9"), Lang::De);
+        // Unsupported first choice: the next supported one wins
+        assert_eq!(Lang::negotiate("pt-BR, es;q=0.7, en;q=0.3"), Lang::Es);
+        // Equal quality: the earlier one
+        assert_eq!(Lang::negotiate("es, de"), Lang::Es);
+    }
+
+    #[test]
+    fn accept_language_ignores_refused_and_malformed_entries() {
+        assert_eq!(Lang::negotiate("de;q=0, fr;q=0.1"), Lang::Fr);
+        assert_eq!(Lang::negotiate("de;q=abc"), Lang::En);
+        assert_eq!(Lang::negotiate("*"), Lang::En);
+        assert_eq!(Lang::negotiate(""), Lang::En);
+    }
+
+    #[test]
+    fn every_condition_is_translated() {
+        for &(english, ..) in CONDITIONS {
+            assert_eq!(condition(Lang::En, english), english);
+            for lang in [Lang::De, Lang::Fr, Lang::Es] {
+                let translated = condition(lang, english);
+                assert!(!translated.is_empty());
+                assert_ne!(translated, english, "{english} in {lang:?}");
+            }
+        }
+        assert_eq!(condition(Lang::De, "Partly Cloudy"), "Teilweise bewölkt");
+        assert_eq!(condition(Lang::Fr, "Rainy"), "Pluvieux");
+    }
+
+    #[test]
+    fn unknown_conditions_pass_through() {
+        assert_eq!(condition(Lang::Fr, "Hail"), "Hail");
+    }
+}
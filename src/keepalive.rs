@@ -0,0 +1,293 @@
+//! MCP `ping` requests on idle sessions, closing sessions whose client has
+//! stopped answering or that stay idle past the configured timeout.
+
+use opentelemetry::Context;
+use rmcp::model::{LoggingLevel, LoggingMessageNotificationParam, PingRequest, ServerRequest};
+use rmcp::schemars;
+use rmcp::service::{Peer, PeerRequestOptions};
+use rmcp::transport::streamable_http_server::session::local::LocalSessionManager;
+use rmcp::transport::streamable_http_server::SessionManager;
+use rmcp::RoleServer;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::config::KeepaliveConfig;
+use crate::trace_utils;
+
+/// The session a keepalive task watches over.
+#[derive(Clone)]
+pub struct Session {
+    pub peer: Peer<RoleServer>,
+    pub id: String,
+    /// Context of the session's `mcp.session` span, parent of the task's spans.
+    pub context: Option<Context>,
+}
+
+/// When the session last handled a client request.
+#[derive(Debug)]
+pub struct Activity(Mutex<Instant>);
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self(Mutex::new(Instant::now()))
+    }
+}
+
+impl Activity {
+    pub fn touch(&self) {
+        *self.0.lock().expect("activity lock poisoned") = Instant::now();
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.0.lock().expect("activity lock poisoned").elapsed()
+    }
+}
+
+/// Ping outcomes across all sessions since startup.
+#[derive(Debug, Default)]
+pub struct KeepaliveStats {
+    sent: AtomicU64,
+    answered: AtomicU64,
+    missed: AtomicU64,
+    sessions_closed: AtomicU64,
+    idle_timeouts: AtomicU64,
+    rtt_total_micros: AtomicU64,
+    rtt_max_micros: AtomicU64,
+}
+
+/// Point-in-time copy of [`KeepaliveStats`].
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct KeepaliveTotals {
+    /// Pings sent to idle sessions
+    pub pings: u64,
+    /// Pings the client answered in time
+    pub answered: u64,
+    /// Pings that failed or timed out
+    pub missed: u64,
+    /// Sessions closed after too many missed pings in a row
+    pub sessions_closed: u64,
+    /// Sessions closed after staying idle past the idle timeout
+    pub idle_timeouts: u64,
+    /// Mean round-trip time of answered pings, in milliseconds
+    pub mean_rtt_ms: f64,
+    /// Longest round-trip time of an answered ping, in milliseconds
+    pub max_rtt_ms: f64,
+}
+
+impl KeepaliveStats {
+    pub fn totals(&self) -> KeepaliveTotals {
+        let answered = self.answered.load(Ordering::Relaxed);
+        let total = self.rtt_total_micros.load(Ordering::Relaxed) as f64;
+        KeepaliveTotals {
+            pings: self.sent.load(Ordering::Relaxed),
+            answered,
+            missed: self.missed.load(Ordering::Relaxed),
+            sessions_closed: self.sessions_closed.load(Ordering::Relaxed),
+            idle_timeouts: self.idle_timeouts.load(Ordering::Relaxed),
+            mean_rtt_ms: if answered == 0 {
+                0.0
+            } else {
+                (total / answered as f64 / 100.0).round() / 10.0
+            },
+            max_rtt_ms: (self.rtt_max_micros.load(Ordering::Relaxed) as f64 / 100.0).round() / 10.0,
+        }
+    }
+
+    fn answered(&self, rtt: Duration) {
+        let micros = u64::try_from(rtt.as_micros()).unwrap_or(u64::MAX);
+        self.answered.fetch_add(1, Ordering::Relaxed);
+        self.rtt_total_micros.fetch_add(micros, Ordering::Relaxed);
+        self.rtt_max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+}
+
+/// Ping the session's client whenever it has been idle for a full interval.
+/// Pings are traced under the session span. After
+/// `max_missed` unanswered pings in a row the session is closed. Ends with
+/// the session.
+pub fn spawn(
+    session: Session,
+    activity: Arc<Activity>,
+    config: KeepaliveConfig,
+    stats: Arc<KeepaliveStats>,
+    sessions: Arc<LocalSessionManager>,
+) {
+    let Some(interval) = config.ping_interval else {
+        return;
+    };
+    let Session {
+        peer,
+        id: session_id,
+        context: session,
+    } = session;
+    trace_utils::spawn_traced(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        let mut missed_in_row = 0;
+        loop {
+            ticker.tick().await;
+            if peer.is_transport_closed() {
+                break;
+            }
+            if activity.idle_for() < interval {
+                continue;
+            }
+
+            let span = tracing::info_span!(
+                "mcp.ping",
+                session.id = %session_id,
+                rtt_ms = tracing::field::Empty,
+                missed_in_row = tracing::field::Empty,
+            );
+            if let Some(parent) = &session {
+                let _ = span.set_parent(parent.clone());
+            }
+            let started = Instant::now();
+            stats.sent.fetch_add(1, Ordering::Relaxed);
+            let answer = ping(&peer, config.ping_timeout)
+                .instrument(span.clone())
+                .await;
+            match answer {
+                Ok(()) => {
+                    let rtt = started.elapsed();
+                    stats.answered(rtt);
+                    span.record("rtt_ms", rtt.as_secs_f64() * 1_000.0);
+                    missed_in_row = 0;
+                }
+                Err(error) => {
+                    stats.missed.fetch_add(1, Ordering::Relaxed);
+                    missed_in_row += 1;
+                    span.record("missed_in_row", missed_in_row);
+                    tracing::debug!(%session_id, %error, missed_in_row, "Ping not answered");
+                }
+            }
+
+            if missed_in_row >= config.max_missed {
+                tracing::warn!(
+                    %session_id,
+                    missed = missed_in_row,
+                    "Closing session after unanswered pings"
+                );
+                stats.sessions_closed.fetch_add(1, Ordering::Relaxed);
+                close(&sessions, &session_id).await;
+                break;
+            }
+        }
+    });
+}
+
+/// Close the session once it has handled no client request for the idle
+/// timeout, after warning the client with a `notifications/message` when
+/// `idle_warning` remains (unless its log level filters warnings out). The
+/// timeout is recorded on an `mcp.session.idle_timeout` span under the
+/// session span. Ends with the session.
+pub fn spawn_idle_timeout(
+    session: Session,
+    activity: Arc<Activity>,
+    config: KeepaliveConfig,
+    log_level: Arc<Mutex<LoggingLevel>>,
+    stats: Arc<KeepaliveStats>,
+    sessions: Arc<LocalSessionManager>,
+) {
+    let Some(timeout) = config.idle_timeout else {
+        return;
+    };
+    let warn_after = timeout.saturating_sub(config.idle_warning);
+    let Session {
+        peer,
+        id: session_id,
+        context: session,
+    } = session;
+    trace_utils::spawn_traced(async move {
+        let mut warned = false;
+        loop {
+            if peer.is_transport_closed() {
+                break;
+            }
+            let idle = activity.idle_for();
+            if idle >= timeout {
+                let span = tracing::info_span!(
+                    "mcp.session.idle_timeout",
+                    session.id = %session_id,
+                    session.idle_timeout_secs = timeout.as_secs(),
+                    session.idle_secs = idle.as_secs(),
+                    session.warned = warned,
+                );
+                if let Some(parent) = &session {
+                    let _ = span.set_parent(parent.clone());
+                }
+                async {
+                    tracing::warn!(%session_id, idle_secs = idle.as_secs(), "Closing idle session");
+                    stats.idle_timeouts.fetch_add(1, Ordering::Relaxed);
+                    close(&sessions, &session_id).await;
+                }
+                .instrument(span)
+                .await;
+                break;
+            }
+            if idle < warn_after {
+                warned = false;
+                tokio::time::sleep(warn_after - idle).await;
+                continue;
+            }
+            if !warned {
+                warned = true;
+                let level = *log_level.lock().expect("log level lock poisoned");
+                if enabled(level, LoggingLevel::Warning) {
+                    warn_idle(&peer, timeout, timeout - idle).await;
+                }
+            }
+            tokio::time::sleep(timeout - idle).await;
+        }
+    });
+}
+
+/// Whether a message at `level` passes the client's minimum `min`.
+fn enabled(min: LoggingLevel, level: LoggingLevel) -> bool {
+    level as u8 >= min as u8
+}
+
+async fn warn_idle(peer: &Peer<RoleServer>, timeout: Duration, remaining: Duration) {
+    let remaining = remaining.as_secs_f64().round() as u64;
+    let message =
+        format!("Session idle; it will be closed in {remaining}s unless a request arrives");
+    let notification = LoggingMessageNotificationParam {
+        level: LoggingLevel::Warning,
+        logger: Some("session".to_string()),
+        data: serde_json::json!({
+            "message": message,
+            "idle_timeout_secs": timeout.as_secs(),
+            "closes_in_secs": remaining,
+        }),
+    };
+    if let Err(error) = peer.notify_logging_message(notification).await {
+        tracing::debug!(%error, "Idle warning not delivered");
+    }
+}
+
+/// Close the session, ending its streams.
+async fn close(sessions: &LocalSessionManager, session_id: &str) {
+    if let Err(error) = sessions.close_session(&session_id.into()).await {
+        tracing::warn!(%session_id, %error, "Failed to close session");
+    }
+}
+
+async fn ping(peer: &Peer<RoleServer>, timeout: Duration) -> Result<(), String> {
+    let options = PeerRequestOptions {
+        timeout: Some(timeout),
+        meta: None,
+    };
+    let handle = peer
+        .send_request_with_option(ServerRequest::PingRequest(PingRequest::default()), options)
+        .await
+        .map_err(|error| error.to_string())?;
+    handle
+        .await_response()
+        .await
+        .map(|_| ())
+        .map_err(|error| error.to_string())
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::config::env_string;
+use crate::http_client::HttpClient;
+use crate::tracing_setup::langfuse_host;
+
+/// Langfuse public REST API failures.
+#[derive(Debug, thiserror::Error)]
+pub enum LangfuseError {
+    #[error("Langfuse request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Langfuse rejected the request ({status}): {body}")]
+    Api {
+        status: reqwest::StatusCode,
+        body: String,
+    },
+}
+
+/// A score attached to a trace, e.g. end-user feedback on an answer.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateScore {
+    pub trace_id: String,
+    pub name: String,
+    pub value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatedScore {
+    pub id: String,
+}
+
+/// Minimal async client for the Langfuse public REST API, sharing the pooled
+/// HTTP client so its calls show up as `http.client.request` spans.
+#[derive(Clone)]
+pub struct LangfuseClient {
+    http_client: HttpClient,
+    base_url: String,
+    public_key: String,
+    secret_key: String,
+}
+
+impl LangfuseClient {
+    /// Build a client from the `LANGFUSE_*` variables, or `None` without keys.
+    pub fn from_env(http_client: HttpClient) -> Option<Self> {
+        Some(Self {
+            http_client,
+            base_url: langfuse_host().trim_end_matches('/').to_string(),
+            public_key: env_string("LANGFUSE_PUBLIC_KEY")?,
+            secret_key: env_string("LANGFUSE_SECRET_KEY")?,
+        })
+    }
+
+    #[instrument(name = "langfuse.create_score", skip(self, score), fields(
+        langfuse.score.trace_id = %score.trace_id,
+        langfuse.score.name = %score.name,
+        langfuse.score.value = score.value
+    ))]
+    pub async fn create_score(&self, score: &CreateScore) -> Result<CreatedScore, LangfuseError> {
+        let request = self
+            .http_client
+            .inner()
+            .post(format!("{}/api/public/scores", self.base_url))
+            .basic_auth(&self.public_key, Some(&self.secret_key))
+            .json(score);
+
+        let response = self.http_client.send(request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(LangfuseError::Api { status, body });
+        }
+
+        Ok(response.json().await?)
+    }
+}
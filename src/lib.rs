@@ -0,0 +1,69 @@
+//! Rust MCP weather server with OpenTelemetry tracing exported to Langfuse.
+//!
+//! The binary in `main.rs` wires these modules into the HTTP server; they are
+//! exposed as a library so benchmarks can drive them directly.
+
+pub mod access_control;
+pub mod admin;
+pub mod app_state;
+pub mod astronomy;
+pub mod audit_log;
+pub mod build_info;
+pub mod cache;
+pub mod check_config;
+pub mod cli;
+pub mod client_capabilities;
+pub mod concurrency;
+pub mod config;
+pub mod cpu_pool;
+pub mod error_reporting;
+pub mod export_stats;
+pub mod feature_flags;
+pub mod forecast_summary;
+pub mod forecast_time;
+pub mod generation;
+pub mod graphql;
+pub mod grpc;
+pub mod history_export;
+pub mod http_client;
+pub mod i18n;
+pub mod keepalive;
+pub mod langfuse_client;
+pub mod metrics;
+pub mod post_processing;
+pub mod preferences;
+pub mod priority;
+pub mod providers;
+pub mod rbac;
+pub mod recording;
+pub mod refresh;
+pub mod request_id;
+pub mod request_signing;
+pub mod resource_detection;
+pub mod resources;
+pub mod rest_api;
+pub mod scenario;
+pub mod schema_export;
+pub mod secrets;
+pub mod self_test;
+pub mod shutdown;
+pub mod simulated_latency;
+pub mod singleflight;
+pub mod slo;
+pub mod span_file;
+pub mod span_limits;
+pub mod span_repair;
+pub mod systemd;
+pub mod templates;
+pub mod tenants;
+pub mod tool_aliases;
+pub mod tool_errors;
+pub mod tool_middleware;
+pub mod tool_pagination;
+pub mod trace_utils;
+pub mod tracing_setup;
+pub mod usage_stats;
+pub mod watches;
+pub mod weather_map;
+pub mod weather_tools;
+pub mod webhooks;
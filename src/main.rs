@@ -1,71 +1,278 @@
-use anyhow::Result;
-use axum::Router;
+use anyhow::{bail, Result};
+use axum::{middleware, Router};
+use clap::Parser;
 use dotenv::dotenv;
-use rmcp::transport::streamable_http_server::{
-    session::local::LocalSessionManager, StreamableHttpService,
-};
+use rmcp::transport::streamable_http_server::{StreamableHttpServerConfig, StreamableHttpService};
+use rmcp_otel::TracePropagationLayer;
+use std::net::SocketAddr;
+use std::path::Path;
 use std::time::Duration;
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::CorsLayer;
+use tower_http::decompression::RequestDecompressionLayer;
+use tower_http::limit::RequestBodyLimitLayer;
 use tracing::info;
 
-mod trace_store;
-mod trace_utils;
-mod tracing_middleware;
-mod tracing_setup;
-mod weather_tools;
-
-use crate::tracing_setup::init_tracing;
-use crate::weather_tools::WeatherService;
-use tracing_middleware::TracePropagationLayer;
+use rmcp_demo::access_control::{self, AccessControl};
+use rmcp_demo::admin::{self, AdminState};
+use rmcp_demo::app_state::AppState;
+use rmcp_demo::cli::{Cli, Command, ScenarioCommand};
+use rmcp_demo::config::{Config, SelfTestMode};
+use rmcp_demo::request_signing::RequestSigning;
+use rmcp_demo::scenario::{self, Scenario};
+use rmcp_demo::tracing_setup::{init_tracing, Telemetry};
+use rmcp_demo::weather_tools::WeatherService;
+use rmcp_demo::{
+    build_info, check_config, feature_flags, graphql, grpc, metrics, post_processing, recording,
+    refresh, request_id, rest_api, schema_export, secrets, self_test, shutdown, span_file, systemd,
+    tenants, trace_utils,
+};
 
 const BIND_ADDRESS: &str = "0.0.0.0:8001";
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     dotenv().ok();
 
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve { profile_out: None });
+    if command.loads_config() {
+        // Resolve secrets on a runtime that is shut down before the main one
+        // starts, so the environment changes while no other thread reads it.
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let secrets = runtime.block_on(secrets::load())?;
+        drop(runtime);
+        for (var, value) in secrets {
+            std::env::set_var(var, value);
+        }
+    }
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(command))
+}
+
+async fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Serve { profile_out } => serve(profile_out.as_deref()).await,
+        Command::CheckConfig => check_config::run().await,
+        Command::TraceReport { file, all } => {
+            let tools: Vec<String> = WeatherService::tool_definitions()
+                .iter()
+                .map(|tool| tool.name.to_string())
+                .collect();
+            print!(
+                "{}",
+                span_file::report(&file, (!all).then_some(&tools[..]))?
+            );
+            Ok(())
+        }
+        Command::Replay { file, ignore } => {
+            let (report, changed) = recording::replay(&file, &ignore).await?;
+            print!("{report}");
+            if changed {
+                bail!("replayed results differ from the recording");
+            }
+            Ok(())
+        }
+        Command::Scenario {
+            command: ScenarioCommand::Run { file },
+        } => run_scenario(&file).await,
+        Command::PrintSchemas { format } => {
+            let mut tools = WeatherService::tool_definitions();
+            tools.iter_mut().for_each(post_processing::advertise);
+            println!("{}", schema_export::render(&tools, format)?);
+            Ok(())
+        }
+    }
+}
+
+async fn serve(profile_out: Option<&Path>) -> Result<()> {
+    let config = Config::from_env()?;
+
     // Initialize tracing with OpenTelemetry
-    let tracer_provider = init_tracing()?;
+    let telemetry = init_tracing(&config, profile_out)?;
 
-    info!(
-        "Starting Rust Weather Assistant MCP Server on http://{}",
-        BIND_ADDRESS
-    );
+    let inherited = systemd::listener()?;
+    match &inherited {
+        Some(listener) => info!(
+            "Starting Rust Weather Assistant MCP Server on http://{} (socket from systemd)",
+            listener.local_addr()?
+        ),
+        None => info!(
+            "Starting Rust Weather Assistant MCP Server on http://{}",
+            BIND_ADDRESS
+        ),
+    }
     info!("MCP endpoint available at http://localhost:8001/weather");
+    info!("REST API available at http://localhost:8001/api (OpenAPI: /api/openapi.json)");
+
+    trace_utils::set_trace_labels(config.trace_labels.clone());
+    tenants::install(&config.tenants, config.rbac.jwt_secret.as_deref());
+    trace_utils::record_panics_on_spans();
+
+    // Re-read RUST_LOG on SIGHUP
+    #[cfg(unix)]
+    telemetry.log_level.clone().reload_on_sighup()?;
+
+    let access = AccessControl::from_config(&config.access);
+    if access.is_some() {
+        info!(
+            allow = ?config.access.allow,
+            deny = ?config.access.deny,
+            "Client network access control enabled"
+        );
+    }
+
+    let signing = RequestSigning::from_config(&config.signing);
+    if signing.is_some() {
+        info!("REST API requests must be signed (x-signature)");
+    }
+
+    let server_config = config.server.clone();
+
+    if config.self_test != SelfTestMode::Off {
+        let report = self_test::run(&config).await?;
+        report.log();
+        if !report.failed.is_empty() && config.self_test == SelfTestMode::Fail {
+            bail!(
+                "self-test failed for {} tool(s); set SELF_TEST=warn to start anyway",
+                report.failed.len()
+            );
+        }
+    }
+
+    feature_flags::load(&config.feature_flags)?;
+
+    // Shared state (pooled HTTP client, provider) reused by every session
+    let state = AppState::new(config)?;
+    let shutdown_hooks = state.shutdown.clone();
+    refresh::spawn(state.clone());
+    metrics::spawn(state.clone(), telemetry.export_stats.clone());
+    feature_flags::spawn(state.clone());
+
+    let admin = state
+        .config
+        .admin_token
+        .as_deref()
+        .map(|token| AdminState::new(token, &telemetry, state.deprecations.clone()));
+    if admin.is_some() {
+        info!("Admin endpoints available at http://localhost:8001/admin");
+    }
+
+    // Generated from the tool router; built now rather than on the first initialize
+    tracing::debug!(
+        instructions = WeatherService::instructions(),
+        "Server instructions"
+    );
 
     // Create the MCP service with HTTP transport
+    let mcp_state = state.clone();
     let service = StreamableHttpService::new(
-        || Ok(WeatherService::new()),
-        LocalSessionManager::default().into(),
-        Default::default(),
+        move || Ok(WeatherService::new(mcp_state.clone())),
+        state.sessions.clone(),
+        StreamableHttpServerConfig {
+            sse_keep_alive: state.config.keepalive.sse_keep_alive,
+            ..Default::default()
+        },
     );
 
     // Create the router with the MCP service at /weather endpoint
-    let router = Router::new()
-        .nest_service("/weather", service)
-        .layer(TracePropagationLayer)
-        .layer(CorsLayer::permissive());
+    let mut router = Router::new().nest_service("/weather", service);
+    if server_config.grpc {
+        info!(
+            "gRPC service {} available at http://localhost:8001",
+            grpc::SERVICE
+        );
+        router = router.merge(grpc::router(state.clone()));
+    }
+    if server_config.graphql {
+        info!("GraphQL endpoint available at http://localhost:8001/graphql");
+        router = router.merge(graphql::router(state.clone()));
+    }
+    let mut router = router
+        .merge(rest_api::router(state, signing))
+        .merge(build_info::router());
+    if let Some(admin) = admin {
+        router = router.merge(admin::router(admin));
+    }
+    // Decompression and the body limit wrap the trace middleware, so it sees
+    // decompressed bodies; the limit applies to the decompressed size.
+    let mut router = router
+        .layer(
+            TracePropagationLayer::new()
+                .on_request(trace_utils::attribute_request)
+                .with_propagated_headers(server_config.propagation_headers.clone()),
+        )
+        .layer(RequestBodyLimitLayer::new(
+            server_config.max_request_body_bytes,
+        ))
+        .layer(RequestDecompressionLayer::new());
+    if server_config.compression {
+        router = router.layer(CompressionLayer::new());
+    }
+    router = router
+        .layer(CorsLayer::permissive())
+        .layer(middleware::from_fn(request_id::assign));
+    // Outermost, so rejected clients never reach CORS, tracing or MCP
+    if let Some(access) = access {
+        router = router.layer(middleware::from_fn_with_state(
+            access,
+            access_control::enforce,
+        ));
+    }
 
-    // Start the server
-    let listener = tokio::net::TcpListener::bind(BIND_ADDRESS).await?;
+    // Start the server, on the socket systemd bound when socket-activated
+    let listener = match inherited {
+        Some(listener) => tokio::net::TcpListener::from_std(listener)?,
+        None => tokio::net::TcpListener::bind(BIND_ADDRESS).await?,
+    };
+    systemd::notify("READY=1");
 
     let shutdown_signal = async {
-        if tokio::signal::ctrl_c().await.is_ok() {
-            info!("Shutting down server...");
-        } else {
-            tracing::warn!("Failed to listen for Ctrl+C; forcing shutdown");
-        }
+        let signal = shutdown::signal().await;
+        info!(signal, "Shutting down server...");
+        systemd::notify("STOPPING=1");
     };
 
-    axum::serve(listener, router)
-        .with_graceful_shutdown(shutdown_signal)
-        .await?;
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal)
+    .await?;
+
+    shutdown_hooks.run().await;
 
     // Ensure all spans are flushed before exiting
+    shutdown_tracing(telemetry).await;
+
+    Ok(())
+}
+
+async fn run_scenario(file: &Path) -> Result<()> {
+    let scenario = Scenario::load(file)?;
+    let config = Config::from_env()?;
+    let telemetry = init_tracing(&config, None)?;
+
+    let result = scenario::run(&scenario, config).await;
+    shutdown_tracing(telemetry).await;
+    print!("{}", result?);
+    Ok(())
+}
+
+/// Flush and shut down the trace pipeline, waiting at most 10 seconds.
+async fn shutdown_tracing(telemetry: Telemetry) {
     let shutdown_timeout = Duration::from_secs(10);
-    let tracer_provider_for_shutdown = tracer_provider.clone();
-    let mut shutdown_handle =
-        tokio::task::spawn_blocking(move || tracer_provider_for_shutdown.shutdown());
+    let tracer_provider_for_shutdown = telemetry.tracer_provider.clone();
+    let mut shutdown_handle = tokio::task::spawn_blocking(move || {
+        // Flush first so the export result is reported before processors stop
+        if let Err(error) = tracer_provider_for_shutdown.force_flush() {
+            tracing::warn!(error = %error, "Failed to flush spans before shutdown");
+        }
+        tracer_provider_for_shutdown.shutdown()
+    });
 
     tokio::select! {
         shutdown_result = &mut shutdown_handle => {
@@ -91,5 +298,15 @@ async fn main() -> Result<()> {
         }
     }
 
-    Ok(())
+    let totals = telemetry.export_stats.totals();
+    info!(
+        ended = totals.ended,
+        exported = totals.exported,
+        failed = totals.failed,
+        dropped = totals.dropped,
+        "Trace export totals"
+    );
+
+    // Flush buffered file logs
+    drop(telemetry);
 }
@@ -0,0 +1,431 @@
+//! The server statistics as Prometheus metrics, pushed every
+//! `METRICS_PUSH_INTERVAL_SECS` to a Pushgateway or a remote-write endpoint
+//! when `METRICS_PUSH_URL` is set.
+//!
+//! The metrics are the `get_server_stats` counters, the tool latency SLOs,
+//! the span export totals and the tool span parent counts. Pushes are not
+//! traced: a root trace per push would bury the traces of actual requests. A
+//! last push at shutdown sends the final counts.
+
+use prost::Message;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::app_state::AppState;
+use crate::config::{MetricsConfig, PushFormat};
+use crate::export_stats::ExportStats;
+
+/// Prefix of every metric name.
+const PREFIX: &str = "rmcp_demo";
+
+/// Metrics sharing a name, help text and type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Family {
+    pub name: String,
+    pub help: &'static str,
+    pub kind: Kind,
+    pub samples: Vec<Sample>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Counter,
+    Gauge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+impl Family {
+    fn new(name: &str, help: &'static str, kind: Kind) -> Self {
+        Self {
+            name: format!("{PREFIX}_{name}"),
+            help,
+            kind,
+            samples: Vec::new(),
+        }
+    }
+
+    fn sample(mut self, labels: &[(&'static str, &str)], value: f64) -> Self {
+        self.samples.push(Sample {
+            labels: labels
+                .iter()
+                .map(|(name, value)| (*name, value.to_string()))
+                .collect(),
+            value,
+        });
+        self
+    }
+}
+
+/// Start pushing until shutdown, if a push URL is configured.
+pub fn spawn(state: AppState, export_stats: Arc<ExportStats>) {
+    let config = state.config.metrics.clone();
+    let Some(url) = config.push_url.clone() else {
+        return;
+    };
+    tracing::info!(
+        url = %url,
+        format = ?config.push_format,
+        interval = ?config.push_interval,
+        "Pushing metrics"
+    );
+    let shutdown = state.shutdown.clone();
+    let timeout = state.config.server.shutdown_hook_timeout;
+    let pusher = Arc::new(Pusher {
+        state,
+        config,
+        url,
+        export_stats,
+    });
+    let job = tokio::spawn({
+        let pusher = pusher.clone();
+        async move {
+            let mut ticker = tokio::time::interval(pusher.config.push_interval);
+            loop {
+                ticker.tick().await;
+                match pusher.push_now().await {
+                    Ok(()) => tracing::debug!("Metrics pushed"),
+                    Err(error) => tracing::warn!(%error, url = %pusher.url, "Metrics push failed"),
+                }
+            }
+        }
+    });
+    shutdown.register("metrics.push", timeout, move || async move {
+        job.abort();
+        pusher.push_now().await
+    });
+}
+
+/// What a push needs, shared by the timer and the shutdown hook.
+struct Pusher {
+    state: AppState,
+    config: MetricsConfig,
+    url: String,
+    export_stats: Arc<ExportStats>,
+}
+
+impl Pusher {
+    async fn push_now(&self) -> anyhow::Result<()> {
+        let families = snapshot(&self.state, &self.export_stats);
+        push(&self.state, &self.config, &self.url, &families).await
+    }
+}
+
+/// Current values of every metric.
+pub fn snapshot(state: &AppState, export_stats: &ExportStats) -> Vec<Family> {
+    use Kind::{Counter, Gauge};
+
+    let stats = state.usage.snapshot(
+        &state.deprecations,
+        &state.keepalive,
+        state.concurrency.as_deref(),
+    );
+    let spans = export_stats.totals();
+    let parents = crate::trace_utils::trace_parent_totals();
+    let keepalive = &stats.keepalive;
+
+    let mut calls = Family::new("tool_calls_total", "Tool calls since startup", Counter);
+    let mut errors = Family::new(
+        "tool_errors_total",
+        "Tool calls that failed or returned an error result",
+        Counter,
+    );
+    let mut latency = Family::new(
+        "tool_latency_milliseconds",
+        "Tool call duration: mean and max since startup, p50 and p95 of the latest 256 calls",
+        Gauge,
+    );
+    for tool in &stats.tools {
+        let name = tool.tool.as_str();
+        calls = calls.sample(&[("tool", name)], tool.calls as f64);
+        errors = errors.sample(&[("tool", name)], tool.errors as f64);
+        for (stat, value) in [
+            ("mean", tool.mean_latency_ms),
+            ("p50", tool.p50_latency_ms),
+            ("p95", tool.p95_latency_ms),
+            ("max", tool.max_latency_ms),
+        ] {
+            latency = latency.sample(&[("tool", name), ("stat", stat)], value);
+        }
+    }
+
+    let mut deprecated = Family::new(
+        "deprecated_tool_calls_total",
+        "Tool calls made through a deprecated name",
+        Counter,
+    );
+    for (alias, count) in &stats.deprecated_calls {
+        deprecated = deprecated.sample(&[("alias", alias)], *count as f64);
+    }
+
+    let mut running = Family::new(
+        "tool_concurrency_running",
+        "Calls of a concurrency-limited tool running now",
+        Gauge,
+    );
+    let mut queued = Family::new(
+        "tool_concurrency_queued",
+        "Calls of a concurrency-limited tool waiting for a slot now",
+        Gauge,
+    );
+    let mut rejected = Family::new(
+        "tool_concurrency_rejected_total",
+        "Calls of a concurrency-limited tool refused as busy",
+        Counter,
+    );
+    for tool in &stats.concurrency {
+        let name = tool.tool.as_str();
+        running = running.sample(&[("tool", name)], tool.running as f64);
+        queued = queued.sample(&[("tool", name)], tool.queued as f64);
+        rejected = rejected.sample(&[("tool", name)], tool.rejected as f64);
+    }
+
+    let mut compliance = Family::new(
+        "slo_compliance_ratio",
+        "Share of a tool's calls within its latency objective over the SLO window",
+        Gauge,
+    );
+    let mut burn_rate = Family::new(
+        "slo_burn_rate",
+        "Share of a tool's slow calls over the share its latency objective allows",
+        Gauge,
+    );
+    for slo in state.slo.iter().flat_map(|slo| slo.snapshot()) {
+        let labels = [
+            ("tool", slo.tool.as_str()),
+            ("objective", slo.objective.as_str()),
+        ];
+        compliance = compliance.sample(&labels, slo.compliance);
+        burn_rate = burn_rate.sample(&labels, slo.burn_rate);
+    }
+
+    vec![
+        Family::new("uptime_seconds", "Seconds since the server started", Gauge)
+            .sample(&[], stats.uptime_seconds as f64),
+        calls,
+        errors,
+        latency,
+        deprecated,
+        running,
+        queued,
+        rejected,
+        compliance,
+        burn_rate,
+        Family::new(
+            "keepalive_pings_total",
+            "Keepalive pings sent to idle sessions, by outcome",
+            Counter,
+        )
+        .sample(&[("outcome", "answered")], keepalive.answered as f64)
+        .sample(&[("outcome", "missed")], keepalive.missed as f64),
+        Family::new(
+            "sessions_closed_total",
+            "Sessions closed by the server, by reason",
+            Counter,
+        )
+        .sample(
+            &[("reason", "missed_pings")],
+            keepalive.sessions_closed as f64,
+        )
+        .sample(&[("reason", "idle")], keepalive.idle_timeouts as f64),
+        Family::new(
+            "spans_total",
+            "Spans handed to the Langfuse export pipeline, by outcome",
+            Counter,
+        )
+        .sample(&[("outcome", "exported")], spans.exported as f64)
+        .sample(&[("outcome", "failed")], spans.failed as f64)
+        .sample(&[("outcome", "dropped")], spans.dropped as f64),
+        Family::new(
+            "tool_span_parents_total",
+            "Tool spans by parent: the session span, the HTTP request span (no session) or none",
+            Counter,
+        )
+        .sample(&[("parent", "session")], parents.session as f64)
+        .sample(&[("parent", "request")], parents.request as f64)
+        .sample(&[("parent", "none")], parents.none as f64),
+    ]
+}
+
+async fn push(
+    state: &AppState,
+    config: &MetricsConfig,
+    url: &str,
+    families: &[Family],
+) -> anyhow::Result<()> {
+    let client = state.http_client.inner();
+    let request = match config.push_format {
+        PushFormat::Pushgateway => client
+            .put(format!(
+                "{}/metrics/job/{}",
+                url.trim_end_matches('/'),
+                config.job
+            ))
+            .header("content-type", "text/plain; version=0.0.4")
+            .body(text_format(families)),
+        PushFormat::RemoteWrite => {
+            let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+            let body = write_request(families, &config.job, timestamp_ms).encode_to_vec();
+            client
+                .post(url)
+                .header("content-type", "application/x-protobuf")
+                .header("content-encoding", "snappy")
+                .header("x-prometheus-remote-write-version", "0.1.0")
+                .body(snap::raw::Encoder::new().compress_vec(&body)?)
+        }
+    };
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+/// The Prometheus text exposition format.
+pub fn text_format(families: &[Family]) -> String {
+    let mut text = String::new();
+    for family in families {
+        let kind = match family.kind {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+        };
+        let _ = writeln!(text, "# HELP {} {}", family.name, family.help);
+        let _ = writeln!(text, "# TYPE {} {kind}", family.name);
+        for sample in &family.samples {
+            text.push_str(&family.name);
+            if !sample.labels.is_empty() {
+                let labels: Vec<String> = sample
+                    .labels
+                    .iter()
+                    .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+                    .collect();
+                let _ = write!(text, "{{{}}}", labels.join(","));
+            }
+            let _ = writeln!(text, " {}", sample.value);
+        }
+    }
+    text
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Remote-write request with one series per sample, labelled with `job`.
+fn write_request(families: &[Family], job: &str, timestamp_ms: i64) -> WriteRequest {
+    let timeseries = families
+        .iter()
+        .flat_map(|family| {
+            family.samples.iter().map(|sample| {
+                let mut labels = vec![
+                    Label {
+                        name: "__name__".to_string(),
+                        value: family.name.clone(),
+                    },
+                    Label {
+                        name: "job".to_string(),
+                        value: job.to_string(),
+                    },
+                ];
+                labels.extend(sample.labels.iter().map(|(name, value)| Label {
+                    name: name.to_string(),
+                    value: value.clone(),
+                }));
+                // Receivers expect labels sorted by name
+                labels.sort_by(|a, b| a.name.cmp(&b.name));
+                TimeSeries {
+                    labels,
+                    samples: vec![RemoteSample {
+                        value: sample.value,
+                        timestamp: timestamp_ms,
+                    }],
+                }
+            })
+        })
+        .collect();
+    WriteRequest { timeseries }
+}
+
+// Messages of the Prometheus remote-write 1.0 protocol (prompb), written out
+// by hand like the gRPC facade's.
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    samples: Vec<RemoteSample>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Label {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct RemoteSample {
+    #[prost(double, tag = "1")]
+    value: f64,
+    #[prost(int64, tag = "2")]
+    timestamp: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calls() -> Family {
+        Family::new(
+            "tool_calls_total",
+            "Tool calls since startup",
+            Kind::Counter,
+        )
+        .sample(&[("tool", "get_weather")], 3.0)
+        .sample(&[("tool", "say \"hi\"")], 1.0)
+    }
+
+    #[test]
+    fn text_format_has_help_type_and_escaped_labels() {
+        assert_eq!(
+            text_format(&[calls()]),
+            "# HELP rmcp_demo_tool_calls_total Tool calls since startup\n\
+             # TYPE rmcp_demo_tool_calls_total counter\n\
+             rmcp_demo_tool_calls_total{tool=\"get_weather\"} 3\n\
+             rmcp_demo_tool_calls_total{tool=\"say \\\"hi\\\"\"} 1\n"
+        );
+    }
+
+    #[test]
+    fn remote_write_series_have_sorted_labels_with_name_and_job() {
+        let request = write_request(&[calls()], "rmcp-demo", 1_000);
+        assert_eq!(request.timeseries.len(), 2);
+        let labels: Vec<(&str, &str)> = request.timeseries[0]
+            .labels
+            .iter()
+            .map(|label| (label.name.as_str(), label.value.as_str()))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                ("__name__", "rmcp_demo_tool_calls_total"),
+                ("job", "rmcp-demo"),
+                ("tool", "get_weather"),
+            ]
+        );
+        assert_eq!(request.timeseries[0].samples[0].timestamp, 1_000);
+    }
+}
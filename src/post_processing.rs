@@ -0,0 +1,495 @@
+//! Post-processing of tool results, applied the same way after every tool.
+//!
+//! A call opts in with reserved arguments, which are taken out before the
+//! tool sees its arguments:
+//!
+//! - `units`: convert temperatures and speeds of results that state their
+//!   `units`
+//! - `lang`: translate `condition` values
+//! - `precision`: round fractional numbers to this many decimals
+//! - `fields`: keep only these (dot-separated) paths in the result's text
+//!
+//! Stages run in that order. `fields` only trims the JSON text, so the
+//! structured content keeps every field its output schema requires. `units` and `lang` are left to tools that take
+//! them themselves. Every tool advertises the arguments in its input schema.
+
+use rmcp::model::{CallToolResult, JsonObject, RawContent, Tool};
+use rmcp::ErrorData as McpError;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+use crate::i18n::{self, Lang};
+use crate::weather_tools::Units;
+
+pub const UNITS_ARG: &str = "units";
+pub const LANG_ARG: &str = "lang";
+pub const PRECISION_ARG: &str = "precision";
+pub const FIELDS_ARG: &str = "fields";
+
+/// Largest `precision`.
+const MAX_PRECISION: u64 = 6;
+
+/// Fields holding a temperature in °C when their object is metric.
+const TEMPERATURE_FIELDS: &[&str] = &["temperature", "high", "low", "mean_high", "mean_low"];
+
+/// Fields holding a speed in km/h when their object is metric.
+const SPEED_FIELDS: &[&str] = &["wind_speed"];
+
+/// One step of the pipeline, rewriting a structured result in place.
+pub trait Stage: Send + Sync {
+    /// Recorded on the `tool.post_process` span.
+    fn name(&self) -> &'static str;
+
+    fn apply(&self, value: &mut Value);
+
+    /// Whether the stage only rewrites the text content, leaving the
+    /// structured content as the tool returned it.
+    fn text_only(&self) -> bool {
+        false
+    }
+}
+
+/// Converts metric results to imperial. Only objects with `"units":
+/// "metric"` (and what they contain) are converted.
+pub struct UnitConversion(pub Units);
+
+impl Stage for UnitConversion {
+    fn name(&self) -> &'static str {
+        "units"
+    }
+
+    fn apply(&self, value: &mut Value) {
+        if self.0 == Units::Imperial {
+            convert_units(value, false);
+        }
+    }
+}
+
+/// Translates `condition` values.
+pub struct Localization(pub Lang);
+
+impl Stage for Localization {
+    fn name(&self) -> &'static str {
+        "lang"
+    }
+
+    fn apply(&self, value: &mut Value) {
+        if self.0 != Lang::En {
+            localize(value, self.0);
+        }
+    }
+}
+
+/// Rounds fractional numbers to a number of decimals.
+pub struct Rounding(pub u32);
+
+impl Stage for Rounding {
+    fn name(&self) -> &'static str {
+        "precision"
+    }
+
+    fn apply(&self, value: &mut Value) {
+        round(value, 10f64.powi(self.0 as i32));
+    }
+}
+
+/// Keeps only the listed paths. `a.b` selects `b` inside `a`, and inside
+/// each element when `a` is an array.
+pub struct FieldFilter(pub Vec<String>);
+
+impl Stage for FieldFilter {
+    fn name(&self) -> &'static str {
+        "fields"
+    }
+
+    fn apply(&self, value: &mut Value) {
+        let paths: Vec<Vec<&str>> = self
+            .0
+            .iter()
+            .map(|path| path.split('.').collect())
+            .collect();
+        *value = select(value, &paths);
+    }
+
+    fn text_only(&self) -> bool {
+        true
+    }
+}
+
+/// The stages requested by one call.
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl Pipeline {
+    /// Take the post-processing arguments out of `arguments`. `units` and
+    /// `lang` stay in place when the tool's `input_schema` declares them.
+    pub fn from_arguments(
+        arguments: &mut Option<JsonObject>,
+        input_schema: Option<&JsonObject>,
+    ) -> Result<Self, McpError> {
+        let Some(arguments) = arguments.as_mut() else {
+            return Ok(Self::default());
+        };
+        let declared = |name: &str| {
+            input_schema
+                .and_then(|schema| schema.get("properties"))
+                .and_then(|properties| properties.get(name))
+                .is_some()
+        };
+        let mut take = |name: &str| {
+            if declared(name) {
+                None
+            } else {
+                arguments.remove(name).filter(|value| !value.is_null())
+            }
+        };
+
+        let mut stages: Vec<Box<dyn Stage>> = Vec::new();
+        if let Some(units) = take(UNITS_ARG) {
+            let units = serde_json::from_value(units).map_err(|_| {
+                McpError::invalid_params("units must be \"metric\" or \"imperial\"", None)
+            })?;
+            stages.push(Box::new(UnitConversion(units)));
+        }
+        if let Some(lang) = take(LANG_ARG) {
+            let lang = lang
+                .as_str()
+                .ok_or_else(|| McpError::invalid_params("lang must be a string", None))?;
+            stages.push(Box::new(Localization(Lang::resolve(Some(lang)))));
+        }
+        if let Some(precision) = take(PRECISION_ARG) {
+            let precision = precision
+                .as_u64()
+                .filter(|precision| *precision <= MAX_PRECISION)
+                .ok_or_else(|| {
+                    McpError::invalid_params(
+                        format!("precision must be an integer from 0 to {MAX_PRECISION}"),
+                        None,
+                    )
+                })?;
+            stages.push(Box::new(Rounding(precision as u32)));
+        }
+        if let Some(fields) = take(FIELDS_ARG) {
+            let fields: Vec<String> = serde_json::from_value(fields).map_err(|_| {
+                McpError::invalid_params("fields must be a list of field names", None)
+            })?;
+            if fields
+                .iter()
+                .any(|field| field.split('.').any(str::is_empty))
+            {
+                return Err(McpError::invalid_params(
+                    "fields must not contain empty names",
+                    None,
+                ));
+            }
+            stages.push(Box::new(FieldFilter(fields)));
+        }
+        Ok(Self { stages })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Names of the stages, in order.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|stage| stage.name()).collect()
+    }
+
+    /// Run the stages over the structured content of a successful result,
+    /// and over its text when that is the same JSON; text-only stages run
+    /// over the text alone. Other results are returned unchanged.
+    pub fn apply(&self, result: &mut CallToolResult) {
+        if result.is_error == Some(true) {
+            return;
+        }
+        let Some(structured) = result.structured_content.as_mut() else {
+            return;
+        };
+        let original = structured.to_string();
+        let (text_only, structured_stages): (Vec<_>, Vec<_>) =
+            self.stages.iter().partition(|stage| stage.text_only());
+        for stage in structured_stages {
+            stage.apply(structured);
+        }
+        let mut text = structured.clone();
+        for stage in text_only {
+            stage.apply(&mut text);
+        }
+        let processed = text.to_string();
+        for content in &mut result.content {
+            if let RawContent::Text(text) = &mut content.raw {
+                if text.text == original {
+                    text.text = processed.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Add the post-processing arguments the tool does not declare itself to
+/// its input schema.
+pub fn advertise(tool: &mut Tool) {
+    let mut schema = (*tool.input_schema).clone();
+    let properties = schema
+        .entry("properties")
+        .or_insert_with(|| Value::Object(Map::new()));
+    let Value::Object(properties) = properties else {
+        return;
+    };
+    let arguments = [
+        (
+            UNITS_ARG,
+            json!({
+                "type": "string",
+                "enum": ["metric", "imperial"],
+                "description": "Convert temperatures and speeds of the result"
+            }),
+        ),
+        (
+            LANG_ARG,
+            json!({
+                "type": "string",
+                "description": "Translate weather conditions in the result: en, de, fr or es"
+            }),
+        ),
+        (
+            PRECISION_ARG,
+            json!({
+                "type": "integer",
+                "minimum": 0,
+                "maximum": MAX_PRECISION,
+                "description": "Round fractional numbers of the result to this many decimals"
+            }),
+        ),
+        (
+            FIELDS_ARG,
+            json!({
+                "type": "array",
+                "items": {"type": "string"},
+                "description": "Return only these fields in the result's text; use dots for nested fields, e.g. items.high"
+            }),
+        ),
+    ];
+    for (name, property) in arguments {
+        properties.entry(name).or_insert(property);
+    }
+    tool.input_schema = Arc::new(schema);
+}
+
+fn convert_units(value: &mut Value, metric: bool) {
+    match value {
+        Value::Object(object) => {
+            let metric = metric || object.get("units").and_then(Value::as_str) == Some("metric");
+            if metric && object.contains_key("units") {
+                object.insert("units".to_string(), json!("imperial"));
+            }
+            for (key, field) in object.iter_mut() {
+                match field {
+                    Value::Number(number) if metric => {
+                        let Some(metric_value) = number.as_f64() else {
+                            continue;
+                        };
+                        let converted = if TEMPERATURE_FIELDS.contains(&key.as_str()) {
+                            Units::Imperial.precise_temperature(metric_value)
+                        } else if SPEED_FIELDS.contains(&key.as_str()) {
+                            metric_value / 1.609_344
+                        } else {
+                            continue;
+                        };
+                        // Keep whole numbers whole, as the tools report them
+                        *field = if number.is_f64() {
+                            json!(converted)
+                        } else {
+                            json!(converted.round() as i64)
+                        };
+                    }
+                    _ => convert_units(field, metric),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                convert_units(item, metric);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn localize(value: &mut Value, lang: Lang) {
+    match value {
+        Value::Object(object) => {
+            for (key, field) in object.iter_mut() {
+                match field {
+                    Value::String(condition) if key == "condition" => {
+                        *condition = i18n::condition(lang, condition);
+                    }
+                    _ => localize(field, lang),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                localize(item, lang);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn round(value: &mut Value, scale: f64) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            if let Some(float) = number.as_f64() {
+                *value = json!((float * scale).round() / scale);
+            }
+        }
+        Value::Object(object) => object.values_mut().for_each(|field| round(field, scale)),
+        Value::Array(items) => items.iter_mut().for_each(|item| round(item, scale)),
+        _ => {}
+    }
+}
+
+/// The parts of `value` named by `paths`, each a list of keys.
+fn select(value: &Value, paths: &[Vec<&str>]) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.iter().map(|item| select(item, paths)).collect()),
+        Value::Object(object) => {
+            let mut selected = Map::new();
+            for (key, field) in object {
+                let rest: Vec<Vec<&str>> = paths
+                    .iter()
+                    .filter(|path| path.first() == Some(&key.as_str()))
+                    .map(|path| path[1..].to_vec())
+                    .collect();
+                if rest.is_empty() {
+                    continue;
+                }
+                // A path ending here keeps the whole field
+                let field = if rest.iter().any(Vec::is_empty) {
+                    field.clone()
+                } else {
+                    select(field, &rest)
+                };
+                selected.insert(key.clone(), field);
+            }
+            Value::Object(selected)
+        }
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::Content;
+
+    fn arguments(value: Value) -> Option<JsonObject> {
+        value.as_object().cloned()
+    }
+
+    #[test]
+    fn metric_objects_are_converted_to_imperial() {
+        let mut value = json!({
+            "units": "metric",
+            "temperature": 20,
+            "wind_speed": 16.09344,
+            "humidity": 50,
+            "items": [{"high": 0, "low": -40}],
+            "other": {"temperature": 20}
+        });
+        convert_units(&mut value, false);
+        assert_eq!(value["units"], "imperial");
+        assert_eq!(value["temperature"], 68);
+        assert!((value["wind_speed"].as_f64().unwrap() - 10.0).abs() < 1e-9);
+        assert_eq!(value["humidity"], 50);
+        assert_eq!(value["items"][0], json!({"high": 32, "low": -40}));
+        assert_eq!(value["other"]["temperature"], 68);
+
+        let mut unstated = json!({"temperature": 20});
+        convert_units(&mut unstated, false);
+        assert_eq!(unstated["temperature"], 20);
+    }
+
+    #[test]
+    fn only_fractional_numbers_are_rounded() {
+        let mut value = json!({"a": 1.23456, "b": [2.5, 7], "c": {"d": -0.049}});
+        round(&mut value, 100.0);
+        assert_eq!(value, json!({"a": 1.23, "b": [2.5, 7], "c": {"d": -0.05}}));
+        round(&mut value, 1.0);
+        assert_eq!(value, json!({"a": 1.0, "b": [3.0, 7], "c": {"d": -0.0}}));
+    }
+
+    #[test]
+    fn select_keeps_paths_through_objects_and_arrays() {
+        let value = json!({
+            "location": "Paris",
+            "units": "metric",
+            "items": [{"date": "d1", "high": 20, "low": 10}, {"date": "d2", "high": 21}]
+        });
+        let paths = vec![vec!["items", "high"], vec!["location"], vec!["missing"]];
+        assert_eq!(
+            select(&value, &paths),
+            json!({"location": "Paris", "items": [{"high": 20}, {"high": 21}]})
+        );
+        assert_eq!(select(&value, &[vec!["items"]])["items"], value["items"]);
+        assert_eq!(select(&value, &[]), json!({}));
+    }
+
+    #[test]
+    fn from_arguments_takes_out_undeclared_stages() {
+        let mut args = arguments(json!({
+            "location": "Paris",
+            "units": "imperial",
+            "lang": "de",
+            "precision": 1,
+            "fields": ["temperature"]
+        }));
+        let pipeline = Pipeline::from_arguments(&mut args, None).unwrap();
+        assert_eq!(pipeline.names(), ["units", "lang", "precision", "fields"]);
+        assert_eq!(args, arguments(json!({"location": "Paris"})));
+
+        let schema = arguments(json!({"properties": {"units": {}}})).unwrap();
+        let mut args = arguments(json!({"units": "imperial", "precision": null}));
+        let pipeline = Pipeline::from_arguments(&mut args, Some(&schema)).unwrap();
+        assert!(pipeline.is_empty());
+        assert_eq!(args, arguments(json!({"units": "imperial"})));
+
+        for invalid in [
+            json!({"units": "kelvin"}),
+            json!({"lang": 1}),
+            json!({"precision": MAX_PRECISION + 1}),
+            json!({"precision": -1}),
+            json!({"fields": "temperature"}),
+            json!({"fields": ["items..high"]}),
+        ] {
+            let mut args = arguments(invalid.clone());
+            assert!(
+                Pipeline::from_arguments(&mut args, None).is_err(),
+                "{invalid} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn fields_trim_the_text_but_not_the_structured_content() {
+        let structured = json!({"location": "Paris", "temperature": 20.456});
+        let mut result = CallToolResult::structured(structured.clone());
+        let mut args = arguments(json!({"precision": 1, "fields": ["temperature"]}));
+        Pipeline::from_arguments(&mut args, None)
+            .unwrap()
+            .apply(&mut result);
+
+        assert_eq!(
+            result.structured_content,
+            Some(json!({"location": "Paris", "temperature": 20.5}))
+        );
+        assert_eq!(
+            result.content,
+            vec![Content::text(json!({"temperature": 20.5}).to_string())]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::weather_tools::Units;
+
+/// Settings a caller can change with the `set_preference` and favorites tools.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Preferences {
+    /// Location used when a tool is called without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_location: Option<String>,
+    /// Unit system of temperatures and wind speeds
+    #[serde(default)]
+    pub units: Units,
+    /// Locations saved with `add_favorite`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub favorites: Vec<String>,
+}
+
+/// Preferences by trusted `x-user-id`, so a user's next session starts with the
+/// ones they last set. Kept in memory, and in a JSON file when
+/// `PREFERENCES_PATH` is set.
+#[derive(Clone, Default)]
+pub struct PreferenceStore {
+    users: Arc<Mutex<HashMap<String, Preferences>>>,
+    path: Option<PathBuf>,
+    /// Orders file writes, so an older snapshot never replaces a newer one.
+    write_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl PreferenceStore {
+    /// Load the file at `path`, if any; a missing file starts empty.
+    pub fn open(path: Option<&Path>) -> Result<Self> {
+        let users = match path {
+            Some(path) if path.exists() => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("reading preferences from {}", path.display()))?;
+                serde_json::from_str(&contents)
+                    .with_context(|| format!("parsing preferences in {}", path.display()))?
+            }
+            _ => HashMap::new(),
+        };
+        Ok(Self {
+            users: Arc::new(Mutex::new(users)),
+            path: path.map(Path::to_path_buf),
+            write_lock: Arc::default(),
+        })
+    }
+
+    pub fn get(&self, user_id: &str) -> Option<Preferences> {
+        self.users
+            .lock()
+            .expect("preferences lock poisoned")
+            .get(user_id)
+            .cloned()
+    }
+
+    /// Apply `change` to the preferences stored for `user_id` and return the
+    /// result, writing the file if configured. The change is made under the
+    /// lock, so concurrent sessions of one user never undo each other's
+    /// changes; nothing is stored if it fails. A failed write is logged; the
+    /// preferences still apply in memory.
+    pub async fn update<E>(
+        &self,
+        user_id: &str,
+        change: impl FnOnce(&mut Preferences) -> Result<(), E>,
+    ) -> Result<Preferences, E> {
+        let updated = {
+            let mut users = self.users.lock().expect("preferences lock poisoned");
+            let mut preferences = users.get(user_id).cloned().unwrap_or_default();
+            change(&mut preferences)?;
+            users.insert(user_id.to_string(), preferences.clone());
+            preferences
+        };
+        self.save().await;
+        Ok(updated)
+    }
+
+    async fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let _guard = self.write_lock.lock().await;
+        let snapshot = {
+            let users = self.users.lock().expect("preferences lock poisoned");
+            serde_json::to_vec_pretty(&*users).expect("preferences serialize to JSON")
+        };
+        // Write beside the file and rename, so a crash never leaves half a file
+        let temp = path.with_extension("tmp");
+        let written = async {
+            tokio::fs::write(&temp, snapshot).await?;
+            tokio::fs::rename(&temp, path).await
+        };
+        if let Err(error) = written.await {
+            tracing::warn!(path = %path.display(), %error, "Failed to save preferences");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_favorite(location: &str) -> impl FnOnce(&mut Preferences) -> Result<(), ()> + '_ {
+        move |preferences| {
+            preferences.favorites.push(location.to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn updates_from_concurrent_sessions_are_merged() {
+        let store = PreferenceStore::default();
+        store.update("alice", add_favorite("Paris")).await.unwrap();
+
+        // Two sessions seeded with the same preferences each add a favorite
+        let (first, second) = tokio::join!(
+            store.update("alice", add_favorite("Rome")),
+            store.update("alice", add_favorite("Oslo")),
+        );
+        assert_eq!(first.unwrap().favorites.len(), 2);
+        assert_eq!(second.unwrap().favorites.len(), 3);
+        assert_eq!(
+            store.get("alice").unwrap().favorites,
+            ["Paris", "Rome", "Oslo"]
+        );
+        assert!(store.get("bob").is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_updates_change_nothing() {
+        let store = PreferenceStore::default();
+        store.update("alice", add_favorite("Paris")).await.unwrap();
+        let failed = store
+            .update("alice", |preferences| {
+                preferences.favorites.clear();
+                Err("refused")
+            })
+            .await;
+        assert_eq!(failed, Err("refused"));
+        assert_eq!(store.get("alice").unwrap().favorites, ["Paris"]);
+    }
+
+    #[tokio::test]
+    async fn preferences_survive_reopening_the_file() {
+        let path = std::env::temp_dir().join(format!("preferences-{}.json", std::process::id()));
+        let store = PreferenceStore::open(Some(&path)).unwrap();
+        store
+            .update("alice", |preferences| {
+                preferences.default_location = Some("Paris".into());
+                Ok::<_, ()>(())
+            })
+            .await
+            .unwrap();
+
+        let reopened = PreferenceStore::open(Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reopened.get("alice"), store.get("alice"));
+    }
+}
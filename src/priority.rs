@@ -0,0 +1,232 @@
+//! High and low priority lanes for tool calls, enabled with
+//! `TOOL_PRIORITY_SLOTS`.
+//!
+//! All tools share that many slots. When every slot is taken, calls wait in
+//! their lane, and a freed slot goes to a high priority call unless
+//! `TOOL_PRIORITY_WEIGHT` of them have started in a row while low priority
+//! calls were waiting. Bulk callers thus slow down under load instead of
+//! starving interactive ones, and never starve themselves. A call's lane is
+//! its caller's (`TOOL_PRIORITY_USERS`, by `x-user-id`), else its tool's
+//! (`TOOL_PRIORITY`), else high.
+
+use futures::future::BoxFuture;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::oneshot;
+use tracing::{field::Empty, Instrument};
+
+use crate::config::{Priority, PriorityConfig};
+use crate::tool_middleware::{Next, ToolCall, ToolMiddleware, ToolResult};
+use crate::trace_utils::RequestAttribution;
+
+/// Shared by every session.
+pub struct PriorityLanes {
+    slots: usize,
+    tools: HashMap<String, Priority>,
+    users: HashMap<String, Priority>,
+    state: Mutex<State>,
+}
+
+struct State {
+    running: usize,
+    waiting: Lanes<oneshot::Sender<()>>,
+}
+
+/// Waiting entries of both lanes, popped in weighted order.
+struct Lanes<T> {
+    high: VecDeque<T>,
+    low: VecDeque<T>,
+    weight: u32,
+    /// High priority entries popped since the last low priority one.
+    streak: u32,
+}
+
+impl<T> Lanes<T> {
+    fn new(weight: u32) -> Self {
+        Self {
+            high: VecDeque::new(),
+            low: VecDeque::new(),
+            weight,
+            streak: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.high.is_empty() && self.low.is_empty()
+    }
+
+    fn push(&mut self, priority: Priority, entry: T) {
+        match priority {
+            Priority::High => self.high.push_back(entry),
+            Priority::Low => self.low.push_back(entry),
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.low.is_empty() {
+            return self.high.pop_front();
+        }
+        if !self.high.is_empty() && self.streak < self.weight {
+            self.streak += 1;
+            return self.high.pop_front();
+        }
+        self.streak = 0;
+        self.low.pop_front()
+    }
+}
+
+/// A slot held by a running call, passed to the next waiting call on drop.
+struct Slot<'a>(&'a PriorityLanes);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// A call waiting for a slot. Dropped before it is granted one, e.g. when
+/// the request is cancelled, it gives back any slot handed to it meanwhile.
+struct Waiter<'a> {
+    lanes: &'a PriorityLanes,
+    receiver: oneshot::Receiver<()>,
+    granted: bool,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if !self.granted {
+            self.receiver.close();
+            if self.receiver.try_recv().is_ok() {
+                self.lanes.release();
+            }
+        }
+    }
+}
+
+impl PriorityLanes {
+    /// `None` unless `TOOL_PRIORITY_SLOTS` is set.
+    pub fn from_config(config: &PriorityConfig) -> Option<Arc<Self>> {
+        Some(Arc::new(Self {
+            slots: config.slots?,
+            tools: config.tools.clone(),
+            users: config.users.clone(),
+            state: Mutex::new(State {
+                running: 0,
+                waiting: Lanes::new(config.weight),
+            }),
+        }))
+    }
+
+    fn priority(&self, call: &ToolCall) -> Priority {
+        let user_id = RequestAttribution::from_request_context(&call.context).user_id;
+        user_id
+            .and_then(|user_id| self.users.get(&user_id))
+            .or_else(|| self.tools.get(call.name()))
+            .copied()
+            .unwrap_or(Priority::High)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("priority lanes lock poisoned")
+    }
+
+    /// A free slot, or a place in `priority`'s lane.
+    fn try_acquire(&self, priority: Priority) -> Result<Slot<'_>, (Waiter<'_>, usize)> {
+        let mut state = self.lock();
+        if state.running < self.slots && state.waiting.is_empty() {
+            state.running += 1;
+            return Ok(Slot(self));
+        }
+        let (sender, receiver) = oneshot::channel();
+        state.waiting.push(priority, sender);
+        let depth = state.waiting.high.len() + state.waiting.low.len();
+        Err((
+            Waiter {
+                lanes: self,
+                receiver,
+                granted: false,
+            },
+            depth,
+        ))
+    }
+
+    /// Hand the slot of a finished call to the next waiting one, or free it.
+    fn release(&self) {
+        let mut state = self.lock();
+        while let Some(waiter) = state.waiting.pop() {
+            // Waiters that went away before their turn are skipped
+            if waiter.send(()).is_ok() {
+                return;
+            }
+        }
+        state.running -= 1;
+    }
+}
+
+impl ToolMiddleware for PriorityLanes {
+    fn around<'a>(&'a self, call: &'a ToolCall, next: Next<'a>) -> BoxFuture<'a, ToolResult> {
+        Box::pin(async move {
+            let priority = self.priority(call);
+            let _slot = match self.try_acquire(priority) {
+                Ok(slot) => slot,
+                Err((mut waiter, depth)) => {
+                    let lane = match priority {
+                        Priority::High => "high",
+                        Priority::Low => "low",
+                    };
+                    let span = tracing::info_span!(
+                        "tool.schedule",
+                        tool = call.name(),
+                        tool.priority = lane,
+                        tool.schedule.depth = depth,
+                        tool.schedule.wait_ms = Empty,
+                    );
+                    let started = Instant::now();
+                    // The sender is only dropped by `release`, after sending
+                    let _ = (&mut waiter.receiver).instrument(span.clone()).await;
+                    waiter.granted = true;
+                    span.record(
+                        "tool.schedule.wait_ms",
+                        started.elapsed().as_millis() as u64,
+                    );
+                    Slot(self)
+                }
+            };
+            next.run(call).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(lanes: &mut Lanes<&'static str>) -> Vec<&'static str> {
+        std::iter::from_fn(|| lanes.pop()).collect()
+    }
+
+    #[test]
+    fn high_priority_goes_first_until_its_weight_is_used() {
+        let mut lanes = Lanes::new(2);
+        for entry in ["low 1", "low 2", "low 3"] {
+            lanes.push(Priority::Low, entry);
+        }
+        for entry in ["high 1", "high 2", "high 3"] {
+            lanes.push(Priority::High, entry);
+        }
+        assert_eq!(
+            order(&mut lanes),
+            ["high 1", "high 2", "low 1", "high 3", "low 2", "low 3"]
+        );
+    }
+
+    #[test]
+    fn a_single_lane_is_served_in_arrival_order() {
+        let mut lanes = Lanes::new(1);
+        for entry in ["high 1", "high 2", "high 3"] {
+            lanes.push(Priority::High, entry);
+        }
+        assert_eq!(order(&mut lanes), ["high 1", "high 2", "high 3"]);
+    }
+}
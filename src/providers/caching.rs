@@ -0,0 +1,293 @@
+use std::hash::Hash;
+use std::sync::Arc;
+use tracing::Instrument;
+
+use super::{normalize_location, ProviderError, ProviderFuture, WeatherProvider};
+use crate::cache::{CacheState, SwrCache};
+use crate::config::CacheConfig;
+use crate::weather_tools::{
+    Forecast, MarineConditions, PollenDay, SnowReport, UvIndexDay, Weather,
+};
+
+/// Serves provider results from an in-memory stale-while-revalidate cache.
+///
+/// The lookup outcome is recorded as `cache.state` on the calling tool span.
+pub struct CachingProvider {
+    inner: Arc<dyn WeatherProvider>,
+    current: Arc<SwrCache<String, Weather>>,
+    forecast: Arc<SwrCache<(String, u32), Vec<Forecast>>>,
+    history: Arc<SwrCache<(String, u32), Vec<Forecast>>>,
+    uv_index: Arc<SwrCache<(String, u32), Vec<UvIndexDay>>>,
+    pollen: Arc<SwrCache<(String, u32), Vec<PollenDay>>>,
+    marine: Arc<SwrCache<String, MarineConditions>>,
+    snow: Arc<SwrCache<String, SnowReport>>,
+}
+
+impl CachingProvider {
+    pub fn new(inner: Arc<dyn WeatherProvider>, config: &CacheConfig) -> Self {
+        Self {
+            inner,
+            current: Arc::new(SwrCache::new(
+                config.ttl,
+                config.stale_ttl,
+                config.max_entries,
+            )),
+            forecast: Arc::new(SwrCache::new(
+                config.ttl,
+                config.stale_ttl,
+                config.max_entries,
+            )),
+            history: Arc::new(SwrCache::new(
+                config.ttl,
+                config.stale_ttl,
+                config.max_entries,
+            )),
+            uv_index: Arc::new(SwrCache::new(
+                config.ttl,
+                config.stale_ttl,
+                config.max_entries,
+            )),
+            pollen: Arc::new(SwrCache::new(
+                config.ttl,
+                config.stale_ttl,
+                config.max_entries,
+            )),
+            marine: Arc::new(SwrCache::new(
+                config.ttl,
+                config.stale_ttl,
+                config.max_entries,
+            )),
+            snow: Arc::new(SwrCache::new(
+                config.ttl,
+                config.stale_ttl,
+                config.max_entries,
+            )),
+        }
+    }
+}
+
+impl WeatherProvider for CachingProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn max_forecast_days(&self) -> u32 {
+        self.inner.max_forecast_days()
+    }
+
+    fn current<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, Weather> {
+        let inner = self.inner.clone();
+        let location = location.to_string();
+        Box::pin(lookup(
+            self.current.clone(),
+            normalize_location(&location),
+            move || {
+                let inner = inner.clone();
+                let location = location.clone();
+                Box::pin(async move { inner.current(&location).await })
+            },
+        ))
+    }
+
+    fn forecast<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        let inner = self.inner.clone();
+        let location = location.to_string();
+        Box::pin(lookup(
+            self.forecast.clone(),
+            (normalize_location(&location), days),
+            move || {
+                let inner = inner.clone();
+                let location = location.clone();
+                Box::pin(async move { inner.forecast(&location, days).await })
+            },
+        ))
+    }
+
+    fn history<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        let inner = self.inner.clone();
+        let location = location.to_string();
+        Box::pin(lookup(
+            self.history.clone(),
+            (normalize_location(&location), days),
+            move || {
+                let inner = inner.clone();
+                let location = location.clone();
+                Box::pin(async move { inner.history(&location, days).await })
+            },
+        ))
+    }
+
+    fn uv_index<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<UvIndexDay>> {
+        let inner = self.inner.clone();
+        let location = location.to_string();
+        Box::pin(lookup(
+            self.uv_index.clone(),
+            (normalize_location(&location), days),
+            move || {
+                let inner = inner.clone();
+                let location = location.clone();
+                Box::pin(async move { inner.uv_index(&location, days).await })
+            },
+        ))
+    }
+
+    fn pollen<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<PollenDay>> {
+        let inner = self.inner.clone();
+        let location = location.to_string();
+        Box::pin(lookup(
+            self.pollen.clone(),
+            (normalize_location(&location), days),
+            move || {
+                let inner = inner.clone();
+                let location = location.clone();
+                Box::pin(async move { inner.pollen(&location, days).await })
+            },
+        ))
+    }
+
+    fn marine<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, MarineConditions> {
+        let inner = self.inner.clone();
+        let location = location.to_string();
+        Box::pin(lookup(
+            self.marine.clone(),
+            normalize_location(&location),
+            move || {
+                let inner = inner.clone();
+                let location = location.clone();
+                Box::pin(async move { inner.marine(&location).await })
+            },
+        ))
+    }
+
+    fn snow<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, SnowReport> {
+        let inner = self.inner.clone();
+        let location = location.to_string();
+        Box::pin(lookup(
+            self.snow.clone(),
+            normalize_location(&location),
+            move || {
+                let inner = inner.clone();
+                let location = location.clone();
+                Box::pin(async move { inner.snow(&location).await })
+            },
+        ))
+    }
+}
+
+async fn lookup<K, V, F>(cache: Arc<SwrCache<K, V>>, key: K, fetch: F) -> Result<V, ProviderError>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    F: Fn() -> ProviderFuture<'static, V> + Send + 'static,
+{
+    let (state, cached) = cache.get(&key);
+    tracing::Span::current().record("cache.state", state.as_str());
+
+    match (state, cached) {
+        (CacheState::Fresh, Some(value)) => {
+            crate::trace_event!("cache.hit", cache.state = state.as_str());
+            Ok(value)
+        }
+        (CacheState::Stale, Some(value)) => {
+            let revalidating = cache.begin_revalidation(&key);
+            crate::trace_event!(
+                "cache.hit",
+                cache.state = state.as_str(),
+                cache.revalidating = revalidating
+            );
+            if revalidating {
+                let span = tracing::info_span!("cache.revalidate");
+                let revalidation = Revalidation {
+                    cache: cache.clone(),
+                    key: key.clone(),
+                };
+                crate::trace_utils::spawn_traced(
+                    async move {
+                        let _revalidation = revalidation;
+                        match fetch().await {
+                            Ok(fresh) => cache.insert(key, fresh),
+                            Err(error) => {
+                                tracing::warn!(error = %error, "Cache revalidation failed")
+                            }
+                        }
+                    }
+                    .instrument(span),
+                );
+            }
+            Ok(value)
+        }
+        _ => {
+            crate::trace_event!("cache.miss", cache.state = state.as_str());
+            let value = fetch().await?;
+            cache.insert(key, value.clone());
+            Ok(value)
+        }
+    }
+}
+
+/// Ends the revalidation of `key` when dropped, so a fetch that panics
+/// cannot leave the key claimed and keep it stale until it expires.
+struct Revalidation<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    cache: Arc<SwrCache<K, V>>,
+    key: K,
+}
+
+impl<K, V> Drop for Revalidation<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    fn drop(&mut self) {
+        self.cache.end_revalidation(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn panicking_revalidations_release_the_key() {
+        // Every entry is stale as soon as it is stored
+        let cache = Arc::new(SwrCache::new(Duration::ZERO, Duration::from_secs(3600), 8));
+        cache.insert("paris", 1);
+
+        let value = lookup(cache.clone(), "paris", || {
+            Box::pin(async { panic!("provider bug") })
+        })
+        .await;
+        assert_eq!(value.unwrap(), 1);
+
+        let mut released = false;
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            if cache.begin_revalidation(&"paris") {
+                released = true;
+                break;
+            }
+        }
+        assert!(released, "the key stayed claimed after the fetch panicked");
+    }
+
+    #[tokio::test]
+    async fn stale_entries_are_refreshed_in_the_background() {
+        let cache = Arc::new(SwrCache::new(Duration::ZERO, Duration::from_secs(3600), 8));
+        cache.insert("paris", 1);
+
+        let value = lookup(cache.clone(), "paris", || Box::pin(async { Ok(2) })).await;
+        assert_eq!(value.unwrap(), 1);
+
+        for _ in 0..100 {
+            if cache.get(&"paris").1 == Some(2) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the stale entry was not refreshed");
+    }
+}
@@ -0,0 +1,183 @@
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use super::{ProviderError, ProviderFuture, WeatherProvider};
+use crate::config::ChaosConfig;
+use crate::weather_tools::{
+    Forecast, MarineConditions, PollenDay, SnowReport, UvIndexDay, Weather,
+};
+
+/// Wraps a provider and randomly delays or fails its calls (`CHAOS_ENABLED`).
+///
+/// Each injected fault is recorded as `chaos.fault` (`latency`, `error` or
+/// `timeout`) on the calling span, and as a `chaos.fault` event at the moment
+/// it happens, so it can be told apart from real upstream trouble in Langfuse.
+pub struct ChaosProvider {
+    inner: Arc<dyn WeatherProvider>,
+    config: ChaosConfig,
+}
+
+/// A fault a scenario step forces on every provider call it makes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    Latency,
+    Error,
+    Timeout,
+}
+
+impl Fault {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Latency => "latency",
+            Self::Error => "error",
+            Self::Timeout => "timeout",
+        }
+    }
+}
+
+/// Fault replacing the random draw while set.
+static FORCED: Lazy<Mutex<Option<Fault>>> = Lazy::new(Mutex::default);
+
+/// Make every call take `fault` instead of drawing faults at random, until
+/// called again with `None`. Only affects providers wrapped in
+/// [`ChaosProvider`].
+pub fn force(fault: Option<Fault>) {
+    *FORCED.lock().expect("forced fault lock poisoned") = fault;
+}
+
+/// Faults drawn for one call.
+struct Faults {
+    latency: bool,
+    error: bool,
+    timeout: bool,
+}
+
+impl ChaosProvider {
+    pub fn new(inner: Arc<dyn WeatherProvider>, config: &ChaosConfig) -> Self {
+        tracing::warn!(
+            latency_rate = config.latency_rate,
+            error_rate = config.error_rate,
+            timeout_rate = config.timeout_rate,
+            "Fault injection enabled for provider calls"
+        );
+        Self {
+            inner,
+            config: config.clone(),
+        }
+    }
+
+    fn draw(&self) -> Faults {
+        if let Some(fault) = *FORCED.lock().expect("forced fault lock poisoned") {
+            return Faults {
+                latency: fault == Fault::Latency,
+                error: fault == Fault::Error,
+                timeout: fault == Fault::Timeout,
+            };
+        }
+        let mut rng = rand::thread_rng();
+        Faults {
+            latency: rng.gen_bool(self.config.latency_rate),
+            error: rng.gen_bool(self.config.error_rate),
+            timeout: rng.gen_bool(self.config.timeout_rate),
+        }
+    }
+
+    /// Apply this call's faults before it reaches the wrapped provider.
+    async fn inject(&self) -> Result<(), ProviderError> {
+        let faults = self.draw();
+        let span = tracing::Span::current();
+
+        if faults.timeout {
+            span.set_attribute("chaos.fault", "timeout");
+            tokio::time::sleep(self.config.timeout).await;
+            crate::trace_event!("chaos.fault", chaos.fault = "timeout");
+            return Err(ProviderError::Timeout(self.config.timeout));
+        }
+        if faults.latency {
+            span.set_attribute("chaos.latency_ms", duration_ms(self.config.latency));
+            tokio::time::sleep(self.config.latency).await;
+            crate::trace_event!(
+                "chaos.fault",
+                chaos.fault = "latency",
+                chaos.latency_ms = duration_ms(self.config.latency)
+            );
+        }
+        if faults.error {
+            span.set_attribute("chaos.fault", "error");
+            crate::trace_event!("chaos.fault", chaos.fault = "error");
+            return Err(ProviderError::Injected);
+        }
+        if faults.latency {
+            span.set_attribute("chaos.fault", "latency");
+        }
+        Ok(())
+    }
+}
+
+fn duration_ms(duration: Duration) -> i64 {
+    duration.as_millis().try_into().unwrap_or(i64::MAX)
+}
+
+impl WeatherProvider for ChaosProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn max_forecast_days(&self) -> u32 {
+        self.inner.max_forecast_days()
+    }
+
+    fn current<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, Weather> {
+        Box::pin(async move {
+            self.inject().await?;
+            self.inner.current(location).await
+        })
+    }
+
+    fn forecast<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        Box::pin(async move {
+            self.inject().await?;
+            self.inner.forecast(location, days).await
+        })
+    }
+
+    fn history<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        Box::pin(async move {
+            self.inject().await?;
+            self.inner.history(location, days).await
+        })
+    }
+
+    fn uv_index<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<UvIndexDay>> {
+        Box::pin(async move {
+            self.inject().await?;
+            self.inner.uv_index(location, days).await
+        })
+    }
+
+    fn pollen<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<PollenDay>> {
+        Box::pin(async move {
+            self.inject().await?;
+            self.inner.pollen(location, days).await
+        })
+    }
+
+    fn marine<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, MarineConditions> {
+        Box::pin(async move {
+            self.inject().await?;
+            self.inner.marine(location).await
+        })
+    }
+
+    fn snow<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, SnowReport> {
+        Box::pin(async move {
+            self.inject().await?;
+            self.inner.snow(location).await
+        })
+    }
+}
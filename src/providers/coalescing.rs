@@ -0,0 +1,190 @@
+use rmcp_trace_macro::trace_fn_io;
+use std::sync::Arc;
+
+use super::{normalize_location, ProviderError, ProviderFuture, WeatherProvider};
+use crate::singleflight::Singleflight;
+use crate::weather_tools::{
+    Forecast, MarineConditions, PollenDay, SnowReport, UvIndexDay, Weather,
+};
+
+/// Wraps a provider so identical concurrent lookups share one upstream fetch.
+pub struct CoalescingProvider {
+    inner: Arc<dyn WeatherProvider>,
+    current: Singleflight<String, Result<Weather, ProviderError>>,
+    forecast: Singleflight<(String, u32), Result<Vec<Forecast>, ProviderError>>,
+    history: Singleflight<(String, u32), Result<Vec<Forecast>, ProviderError>>,
+    uv_index: Singleflight<(String, u32), Result<Vec<UvIndexDay>, ProviderError>>,
+    pollen: Singleflight<(String, u32), Result<Vec<PollenDay>, ProviderError>>,
+    marine: Singleflight<String, Result<MarineConditions, ProviderError>>,
+    snow: Singleflight<String, Result<SnowReport, ProviderError>>,
+}
+
+impl CoalescingProvider {
+    pub fn new(inner: Arc<dyn WeatherProvider>) -> Self {
+        Self {
+            inner,
+            current: Singleflight::default(),
+            forecast: Singleflight::default(),
+            history: Singleflight::default(),
+            uv_index: Singleflight::default(),
+            pollen: Singleflight::default(),
+            marine: Singleflight::default(),
+            snow: Singleflight::default(),
+        }
+    }
+
+    #[trace_fn_io(name = "provider.current", fields(
+        provider = self.inner.name(),
+        coalesced = tracing::field::Empty
+    ))]
+    async fn coalesced_current(&self, location: &str) -> Result<Weather, ProviderError> {
+        let (result, shared) = self
+            .current
+            .run(normalize_location(location), || {
+                self.inner.current(location)
+            })
+            .await;
+        tracing::Span::current().record("coalesced", shared);
+        result
+    }
+
+    #[trace_fn_io(name = "provider.forecast", fields(
+        provider = self.inner.name(),
+        coalesced = tracing::field::Empty
+    ))]
+    async fn coalesced_forecast(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<Forecast>, ProviderError> {
+        let (result, shared) = self
+            .forecast
+            .run((normalize_location(location), days), || {
+                self.inner.forecast(location, days)
+            })
+            .await;
+        tracing::Span::current().record("coalesced", shared);
+        result
+    }
+
+    #[trace_fn_io(name = "provider.history", fields(
+        provider = self.inner.name(),
+        coalesced = tracing::field::Empty
+    ))]
+    async fn coalesced_history(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<Forecast>, ProviderError> {
+        let (result, shared) = self
+            .history
+            .run((normalize_location(location), days), || {
+                self.inner.history(location, days)
+            })
+            .await;
+        tracing::Span::current().record("coalesced", shared);
+        result
+    }
+
+    #[trace_fn_io(name = "provider.uv_index", fields(
+        provider = self.inner.name(),
+        coalesced = tracing::field::Empty
+    ))]
+    async fn coalesced_uv_index(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<UvIndexDay>, ProviderError> {
+        let (result, shared) = self
+            .uv_index
+            .run((normalize_location(location), days), || {
+                self.inner.uv_index(location, days)
+            })
+            .await;
+        tracing::Span::current().record("coalesced", shared);
+        result
+    }
+
+    #[trace_fn_io(name = "provider.pollen", fields(
+        provider = self.inner.name(),
+        coalesced = tracing::field::Empty
+    ))]
+    async fn coalesced_pollen(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<PollenDay>, ProviderError> {
+        let (result, shared) = self
+            .pollen
+            .run((normalize_location(location), days), || {
+                self.inner.pollen(location, days)
+            })
+            .await;
+        tracing::Span::current().record("coalesced", shared);
+        result
+    }
+
+    #[trace_fn_io(name = "provider.marine", fields(
+        provider = self.inner.name(),
+        coalesced = tracing::field::Empty
+    ))]
+    async fn coalesced_marine(&self, location: &str) -> Result<MarineConditions, ProviderError> {
+        let (result, shared) = self
+            .marine
+            .run(normalize_location(location), || self.inner.marine(location))
+            .await;
+        tracing::Span::current().record("coalesced", shared);
+        result
+    }
+
+    #[trace_fn_io(name = "provider.snow", fields(
+        provider = self.inner.name(),
+        coalesced = tracing::field::Empty
+    ))]
+    async fn coalesced_snow(&self, location: &str) -> Result<SnowReport, ProviderError> {
+        let (result, shared) = self
+            .snow
+            .run(normalize_location(location), || self.inner.snow(location))
+            .await;
+        tracing::Span::current().record("coalesced", shared);
+        result
+    }
+}
+
+impl WeatherProvider for CoalescingProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn max_forecast_days(&self) -> u32 {
+        self.inner.max_forecast_days()
+    }
+
+    fn current<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, Weather> {
+        Box::pin(self.coalesced_current(location))
+    }
+
+    fn forecast<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        Box::pin(self.coalesced_forecast(location, days))
+    }
+
+    fn history<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        Box::pin(self.coalesced_history(location, days))
+    }
+
+    fn uv_index<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<UvIndexDay>> {
+        Box::pin(self.coalesced_uv_index(location, days))
+    }
+
+    fn pollen<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<PollenDay>> {
+        Box::pin(self.coalesced_pollen(location, days))
+    }
+
+    fn marine<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, MarineConditions> {
+        Box::pin(self.coalesced_marine(location))
+    }
+
+    fn snow<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, SnowReport> {
+        Box::pin(self.coalesced_snow(location))
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use super::{ProviderFuture, WeatherProvider};
+use crate::feature_flags;
+use crate::weather_tools::{
+    Forecast, MarineConditions, PollenDay, SnowReport, UvIndexDay, Weather,
+};
+
+/// Sends each call to Open-Meteo while the `open_meteo_provider` feature
+/// flag is on, else to the configured provider. The flag is read per call,
+/// so flipping it takes effect without a restart.
+pub struct FlaggedProvider {
+    configured: Arc<dyn WeatherProvider>,
+    candidate: Arc<dyn WeatherProvider>,
+}
+
+impl FlaggedProvider {
+    pub fn new(configured: Arc<dyn WeatherProvider>, candidate: Arc<dyn WeatherProvider>) -> Self {
+        Self {
+            configured,
+            candidate,
+        }
+    }
+
+    fn pick(&self) -> &dyn WeatherProvider {
+        if feature_flags::current().open_meteo_provider {
+            self.candidate.as_ref()
+        } else {
+            self.configured.as_ref()
+        }
+    }
+}
+
+impl WeatherProvider for FlaggedProvider {
+    fn name(&self) -> &'static str {
+        self.pick().name()
+    }
+
+    fn max_forecast_days(&self) -> u32 {
+        self.pick().max_forecast_days()
+    }
+
+    fn current<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, Weather> {
+        self.pick().current(location)
+    }
+
+    fn forecast<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        self.pick().forecast(location, days)
+    }
+
+    fn history<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        self.pick().history(location, days)
+    }
+
+    fn uv_index<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<UvIndexDay>> {
+        self.pick().uv_index(location, days)
+    }
+
+    fn pollen<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<PollenDay>> {
+        self.pick().pollen(location, days)
+    }
+
+    fn marine<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, MarineConditions> {
+        self.pick().marine(location)
+    }
+
+    fn snow<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, SnowReport> {
+        self.pick().snow(location)
+    }
+}
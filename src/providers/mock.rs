@@ -0,0 +1,211 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{normalize_location, uv_risk, PollenType, ProviderFuture, WeatherProvider};
+use crate::forecast_time::{self, Place};
+use crate::weather_tools::{
+    CompassDirection, Forecast, MarineConditions, PollenDay, SeaState, SnowConditions, SnowReport,
+    Units, UvIndexDay, Weather,
+};
+
+/// Generates plausible random weather without touching the network.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockProvider {
+    seed: Option<u64>,
+}
+
+impl MockProvider {
+    /// With a `seed`, each kind of data for a location is generated from it,
+    /// the same on every call and run; without, from the OS.
+    pub const fn new(seed: Option<u64>) -> Self {
+        Self { seed }
+    }
+
+    /// Random numbers for generating `kind` data for `location`: seeded from
+    /// both (FNV-1a) when the provider has a seed.
+    fn rng(&self, kind: &str, location: &str) -> StdRng {
+        let Some(seed) = self.seed else {
+            return StdRng::from_entropy();
+        };
+        let key = format!("{kind}\0{}", normalize_location(location));
+        let hash = key
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64 ^ seed, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+            });
+        StdRng::seed_from_u64(hash)
+    }
+
+    /// Unknown places are treated as being on the equator at Greenwich.
+    fn place(location: &str) -> Place {
+        forecast_time::bundled_place(&normalize_location(location)).unwrap_or(Place {
+            latitude: 0.0,
+            longitude: 0.0,
+            timezone: Tz::UTC,
+        })
+    }
+
+    fn generate_current(&self, location: &str) -> Weather {
+        let mut rng = self.rng("current", location);
+        let weather_conditions = ["Sunny", "Cloudy", "Rainy", "Partly Cloudy"];
+
+        Weather {
+            location: location.to_string(),
+            temperature: rng.gen_range(15..=30),
+            condition: weather_conditions[rng.gen_range(0..weather_conditions.len())].to_string(),
+            humidity: rng.gen_range(40..=80),
+            wind_speed: rng.gen_range(5..=25),
+            units: Units::Metric,
+        }
+    }
+
+    fn generate_forecast(&self, location: &str, days: u32) -> Vec<Forecast> {
+        self.generate_days("forecast", location, Utc::now(), 1, days)
+    }
+
+    fn generate_history(&self, location: &str, days: u32) -> Vec<Forecast> {
+        let start = Utc::now() - chrono::Duration::days(days.into());
+        self.generate_days("history", location, start, 1 - days as i32, days)
+    }
+
+    /// `days` days from the local date of `start`, numbered from `first_day`.
+    fn generate_days(
+        &self,
+        kind: &str,
+        location: &str,
+        start: DateTime<Utc>,
+        first_day: i32,
+        days: u32,
+    ) -> Vec<Forecast> {
+        let mut rng = self.rng(kind, location);
+        let conditions = ["Sunny", "Cloudy", "Rainy", "Stormy"];
+        let place = Self::place(location);
+
+        forecast_time::forecast_dates(place.timezone, start, days)
+            .into_iter()
+            .enumerate()
+            .map(|(index, date)| {
+                let (sunrise, sunset) = forecast_time::formatted_sun_times(&place, date);
+                Forecast {
+                    day: first_day + index as i32,
+                    date: date.to_string(),
+                    sunrise,
+                    sunset,
+                    high: rng.gen_range(20..=35),
+                    low: rng.gen_range(10..=20),
+                    condition: conditions[rng.gen_range(0..conditions.len())].to_string(),
+                    precipitation_chance: rng.gen_range(0..=100),
+                }
+            })
+            .collect()
+    }
+
+    fn generate_uv_index(&self, location: &str, days: u32) -> Vec<UvIndexDay> {
+        let mut rng = self.rng("uv_index", location);
+        let place = Self::place(location);
+
+        forecast_time::forecast_dates(place.timezone, Utc::now(), days)
+            .into_iter()
+            .enumerate()
+            .map(|(index, date)| {
+                let uv_index = (rng.gen_range(0.0..=11.0_f64) * 10.0).round() / 10.0;
+                UvIndexDay {
+                    day: index as i32 + 1,
+                    date: date.to_string(),
+                    uv_index,
+                    risk: uv_risk(uv_index).to_string(),
+                }
+            })
+            .collect()
+    }
+
+    fn generate_pollen(&self, location: &str, days: u32) -> Vec<PollenDay> {
+        let mut rng = self.rng("pollen", location);
+        let place = Self::place(location);
+
+        forecast_time::forecast_dates(place.timezone, Utc::now(), days)
+            .into_iter()
+            .enumerate()
+            .map(|(index, date)| PollenDay {
+                day: index as i32 + 1,
+                date: date.to_string(),
+                tree: PollenType::Tree.count(rng.gen_range(0.0..=200.0)),
+                grass: PollenType::Grass.count(rng.gen_range(0.0..=60.0)),
+                weed: PollenType::Weed.count(rng.gen_range(0.0..=80.0)),
+            })
+            .collect()
+    }
+
+    fn generate_marine(&self, location: &str) -> MarineConditions {
+        let mut rng = self.rng("marine", location);
+        let wave_height = (rng.gen_range(0.2..=4.0_f64) * 10.0).round() / 10.0;
+
+        MarineConditions {
+            location: location.to_string(),
+            wave_height,
+            wave_period: rng.gen_range(4..=14) as f64,
+            wave_direction: CompassDirection::from_degrees(rng.gen_range(0.0..360.0)),
+            sea_state: SeaState::from_wave_height(wave_height),
+            water_temperature: rng.gen_range(10..=26) as f64,
+        }
+    }
+
+    fn generate_snow(&self, location: &str) -> SnowReport {
+        let mut rng = self.rng("snow", location);
+        let base_depth = rng.gen_range(0..=250);
+        let fresh_snow = if base_depth == 0 {
+            0
+        } else {
+            rng.gen_range(0..=30)
+        };
+        let temperature = rng.gen_range(-15..=5);
+
+        SnowReport {
+            location: location.to_string(),
+            base_depth,
+            fresh_snow,
+            temperature,
+            conditions: SnowConditions::classify(
+                base_depth as f64,
+                fresh_snow as f64,
+                temperature as f64,
+            ),
+        }
+    }
+}
+
+impl WeatherProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn current<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, Weather> {
+        Box::pin(async move { Ok(self.generate_current(location)) })
+    }
+
+    fn forecast<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        Box::pin(async move { Ok(self.generate_forecast(location, days)) })
+    }
+
+    fn history<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        Box::pin(async move { Ok(self.generate_history(location, days)) })
+    }
+
+    fn uv_index<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<UvIndexDay>> {
+        Box::pin(async move { Ok(self.generate_uv_index(location, days)) })
+    }
+
+    fn pollen<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<PollenDay>> {
+        Box::pin(async move { Ok(self.generate_pollen(location, days)) })
+    }
+
+    fn marine<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, MarineConditions> {
+        Box::pin(async move { Ok(self.generate_marine(location)) })
+    }
+
+    fn snow<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, SnowReport> {
+        Box::pin(async move { Ok(self.generate_snow(location)) })
+    }
+}
@@ -0,0 +1,295 @@
+use rmcp::ErrorData as McpError;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::app_state::MAX_FORECAST_DAYS;
+use crate::config::{Config, ProviderKind};
+use crate::http_client::HttpClient;
+use crate::tool_errors;
+use crate::weather_tools::{
+    Forecast, MarineConditions, PollenCount, PollenDay, SnowReport, UvIndexDay, Weather,
+};
+
+pub mod caching;
+pub mod chaos;
+pub mod coalescing;
+pub mod flagged;
+pub mod mock;
+pub mod open_meteo;
+pub mod schema;
+
+pub type ProviderFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, ProviderError>> + Send + 'a>>;
+
+/// Provider failures. Cloneable so coalesced callers can share one outcome.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ProviderError {
+    #[error("location not found: {0}")]
+    LocationNotFound(String),
+    #[error("upstream request failed: {0}")]
+    Http(Arc<reqwest::Error>),
+    #[error("unexpected upstream response: {0}")]
+    InvalidResponse(String),
+    #[error("upstream request timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("no data for this location: {0}")]
+    NoData(String),
+    #[error("upstream unavailable (injected fault)")]
+    Injected,
+    #[error("{0}")]
+    SchemaMismatch(schema::ProviderSchemaMismatch),
+    #[error("{provider} forecasts cover at most {max_days} days")]
+    ForecastTooLong {
+        provider: &'static str,
+        max_days: u32,
+    },
+    #[error("location must be at most {MAX_LOCATION_CHARS} characters")]
+    LocationTooLong,
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(error: reqwest::Error) -> Self {
+        Self::Http(Arc::new(error))
+    }
+}
+
+impl ProviderError {
+    /// Identifier reported in failed tool results.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::LocationNotFound(_) => "location_not_found",
+            Self::Http(_) | Self::Injected => "upstream_unavailable",
+            Self::InvalidResponse(_) => "upstream_invalid_response",
+            Self::Timeout(_) => "upstream_timeout",
+            Self::NoData(_) => "no_data",
+            Self::SchemaMismatch(_) => "upstream_schema_mismatch",
+            Self::ForecastTooLong { .. } => "long_range_unsupported",
+            Self::LocationTooLong => "location_too_long",
+        }
+    }
+
+    /// Whether the same lookup may succeed later.
+    pub fn retryable(&self) -> bool {
+        matches!(self, Self::Http(_) | Self::Timeout(_) | Self::Injected)
+    }
+}
+
+impl From<ProviderError> for McpError {
+    fn from(error: ProviderError) -> Self {
+        let mcp = match error {
+            ProviderError::LocationNotFound(_)
+            | ProviderError::NoData(_)
+            | ProviderError::ForecastTooLong { .. }
+            | ProviderError::LocationTooLong => McpError::invalid_params(error.to_string(), None),
+            _ => McpError::internal_error(error.to_string(), None),
+        };
+        tool_errors::with_code(mcp, error.code(), error.retryable())
+    }
+}
+
+/// Generator of the trait's default fallbacks. Providers whose fallbacks
+/// should follow `MOCK_SEED` hold a seeded [`mock::MockProvider`] instead.
+static UNSEEDED_MOCK: mock::MockProvider = mock::MockProvider::new(None);
+
+/// A source of current conditions and daily forecasts.
+pub trait WeatherProvider: Send + Sync {
+    /// Short identifier recorded on spans.
+    fn name(&self) -> &'static str;
+
+    fn current<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, Weather>;
+
+    fn forecast<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>>;
+
+    /// Most days `forecast` can return.
+    fn max_forecast_days(&self) -> u32 {
+        MAX_FORECAST_DAYS
+    }
+
+    /// Daily conditions of the `days` days before today, oldest first, with
+    /// `day` counting back from 0 for yesterday. Providers without past
+    /// data fall back to generated values.
+    fn history<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        UNSEEDED_MOCK.history(location, days)
+    }
+
+    /// Daily maximum UV index. Providers without UV data fall back to
+    /// generated values.
+    fn uv_index<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<UvIndexDay>> {
+        UNSEEDED_MOCK.uv_index(location, days)
+    }
+
+    /// Daily pollen levels. Providers without pollen data fall back to
+    /// generated values.
+    fn pollen<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<PollenDay>> {
+        UNSEEDED_MOCK.pollen(location, days)
+    }
+
+    /// Current sea conditions. Providers without marine data fall back to
+    /// generated values.
+    fn marine<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, MarineConditions> {
+        UNSEEDED_MOCK.marine(location)
+    }
+
+    /// Current snow report. Providers without snow data fall back to
+    /// generated values.
+    fn snow<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, SnowReport> {
+        UNSEEDED_MOCK.snow(location)
+    }
+}
+
+/// Build the configured provider, handing it the shared HTTP client.
+pub fn build_provider(config: &Config, http_client: &HttpClient) -> Arc<dyn WeatherProvider> {
+    // Also generates the data kinds Open-Meteo lacks for a location
+    let mock = mock::MockProvider::new(config.mock_seed);
+    let provider: Arc<dyn WeatherProvider> = match config.provider {
+        ProviderKind::Mock => Arc::new(mock),
+        ProviderKind::OpenMeteo => Arc::new(open_meteo::OpenMeteoProvider::new(
+            http_client.clone(),
+            mock,
+        )),
+    };
+
+    // Below the chaos layer, so faults hit whichever provider the flag picks
+    let provider: Arc<dyn WeatherProvider> =
+        if config.provider != ProviderKind::OpenMeteo && config.feature_flags.is_configured() {
+            Arc::new(flagged::FlaggedProvider::new(
+                provider,
+                Arc::new(open_meteo::OpenMeteoProvider::new(
+                    http_client.clone(),
+                    mock,
+                )),
+            ))
+        } else {
+            provider
+        };
+
+    // Below caching and coalescing, so injected faults look like upstream ones to them
+    let provider: Arc<dyn WeatherProvider> = if config.chaos.enabled {
+        Arc::new(chaos::ChaosProvider::new(provider, &config.chaos))
+    } else {
+        provider
+    };
+
+    let provider: Arc<dyn WeatherProvider> = if config.coalesce_requests {
+        Arc::new(coalescing::CoalescingProvider::new(provider))
+    } else {
+        provider
+    };
+
+    if config.cache.enabled {
+        Arc::new(caching::CachingProvider::new(provider, &config.cache))
+    } else {
+        provider
+    }
+}
+
+/// Longest location a tool accepts, in characters. Results repeat the
+/// location, so it bounds their size.
+pub const MAX_LOCATION_CHARS: usize = 200;
+
+/// Refuse locations longer than [`MAX_LOCATION_CHARS`], whichever facade
+/// they came through.
+pub fn check_location(location: &str) -> Result<(), ProviderError> {
+    if location.chars().count() > MAX_LOCATION_CHARS {
+        return Err(ProviderError::LocationTooLong);
+    }
+    Ok(())
+}
+
+/// Canonical form of a location used for cache and coalescing keys.
+pub fn normalize_location(location: &str) -> String {
+    location.trim().to_lowercase()
+}
+
+/// WHO exposure category for a UV index.
+pub fn uv_risk(uv_index: f64) -> &'static str {
+    match uv_index {
+        value if value < 3.0 => "Low",
+        value if value < 6.0 => "Moderate",
+        value if value < 8.0 => "High",
+        value if value < 11.0 => "Very High",
+        _ => "Extreme",
+    }
+}
+
+/// Pollen types, each with its own thresholds for what counts as high.
+#[derive(Debug, Clone, Copy)]
+pub enum PollenType {
+    Tree,
+    Grass,
+    Weed,
+}
+
+impl PollenType {
+    /// Upper bounds (grains/m³) of the "Low", "Moderate" and "High" levels,
+    /// after the US National Allergy Bureau scale.
+    fn thresholds(self) -> [f64; 3] {
+        match self {
+            PollenType::Tree => [15.0, 90.0, 1500.0],
+            PollenType::Grass => [5.0, 20.0, 200.0],
+            PollenType::Weed => [10.0, 50.0, 500.0],
+        }
+    }
+
+    /// A peak concentration with its level.
+    pub fn count(self, grains_per_m3: f64) -> PollenCount {
+        let [low, moderate, high] = self.thresholds();
+        let level = match grains_per_m3 {
+            value if value < 1.0 => "None",
+            value if value < low => "Low",
+            value if value < moderate => "Moderate",
+            value if value < high => "High",
+            _ => "Very High",
+        };
+        PollenCount {
+            grains_per_m3: grains_per_m3.round() as i32,
+            level: level.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uv_risk_categories_start_at_their_lower_bound() {
+        let cases = [
+            (0.0, "Low"),
+            (2.9, "Low"),
+            (3.0, "Moderate"),
+            (5.9, "Moderate"),
+            (6.0, "High"),
+            (8.0, "Very High"),
+            (10.9, "Very High"),
+            (11.0, "Extreme"),
+        ];
+        for (uv_index, expected) in cases {
+            assert_eq!(uv_risk(uv_index), expected, "UV index {uv_index}");
+        }
+    }
+
+    #[test]
+    fn pollen_levels_follow_each_type_thresholds() {
+        let cases = [
+            (PollenType::Tree, 0.4, "None", 0),
+            (PollenType::Tree, 1.0, "Low", 1),
+            (PollenType::Tree, 15.0, "Moderate", 15),
+            (PollenType::Tree, 89.6, "Moderate", 90),
+            (PollenType::Tree, 1500.0, "Very High", 1500),
+            (PollenType::Grass, 5.0, "Moderate", 5),
+            (PollenType::Grass, 20.0, "High", 20),
+            (PollenType::Grass, 200.0, "Very High", 200),
+            (PollenType::Weed, 9.9, "Low", 10),
+            (PollenType::Weed, 50.0, "High", 50),
+            (PollenType::Weed, 499.0, "High", 499),
+        ];
+        for (pollen, grains, level, rounded) in cases {
+            let count = pollen.count(grains);
+            assert_eq!(count.level, level, "{pollen:?} at {grains}");
+            assert_eq!(count.grains_per_m3, rounded);
+        }
+    }
+}
@@ -0,0 +1,507 @@
+use chrono::NaiveDateTime;
+use chrono_tz::Tz;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::instrument;
+
+use super::{
+    mock::MockProvider, schema, uv_risk, PollenType, ProviderError, ProviderFuture, WeatherProvider,
+};
+use crate::app_state::MAX_LONG_RANGE_DAYS;
+use crate::forecast_time;
+use crate::http_client::HttpClient;
+use crate::weather_tools::{
+    CompassDirection, Forecast, MarineConditions, PollenDay, SeaState, SnowConditions, SnowReport,
+    Units, UvIndexDay, Weather,
+};
+
+const GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com/v1/search";
+const FORECAST_URL: &str = "https://api.open-meteo.com/v1/forecast";
+const MARINE_URL: &str = "https://marine-api.open-meteo.com/v1/marine";
+const AIR_QUALITY_URL: &str = "https://air-quality-api.open-meteo.com/v1/air-quality";
+
+/// Hourly pollen series requested from the air quality API.
+const POLLEN_SERIES: &str =
+    "alder_pollen,birch_pollen,olive_pollen,grass_pollen,mugwort_pollen,ragweed_pollen";
+
+/// Weather data from the free Open-Meteo APIs (no API key required).
+pub struct OpenMeteoProvider {
+    http_client: HttpClient,
+    /// Generates pollen data for locations Open-Meteo has none for.
+    fallback: MockProvider,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct GeocodingResponse {
+    #[serde(default)]
+    results: Vec<GeocodingResult>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct GeocodingResult {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct CurrentResponse {
+    current: CurrentBlock,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct CurrentBlock {
+    temperature_2m: f64,
+    relative_humidity_2m: f64,
+    wind_speed_10m: f64,
+    weather_code: u8,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct DailyResponse {
+    /// IANA name of the location's timezone, which `daily` is expressed in
+    timezone: String,
+    daily: DailyBlock,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct DailyBlock {
+    time: Vec<String>,
+    sunrise: Vec<Option<String>>,
+    sunset: Vec<Option<String>>,
+    temperature_2m_max: Vec<f64>,
+    temperature_2m_min: Vec<f64>,
+    weather_code: Vec<u8>,
+    precipitation_probability_max: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct UvResponse {
+    daily: UvBlock,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct UvBlock {
+    time: Vec<String>,
+    uv_index_max: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct MarineResponse {
+    current: MarineBlock,
+}
+
+/// All null away from the sea.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct MarineBlock {
+    wave_height: Option<f64>,
+    wave_period: Option<f64>,
+    wave_direction: Option<f64>,
+    sea_surface_temperature: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SnowResponse {
+    current: SnowCurrentBlock,
+    hourly: SnowHourlyBlock,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SnowCurrentBlock {
+    temperature_2m: f64,
+    /// Metres
+    snow_depth: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SnowHourlyBlock {
+    /// Centimetres per hour
+    snowfall: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct PollenResponse {
+    hourly: PollenBlock,
+}
+
+/// Hourly concentrations in grains/m³; null outside the covered region (Europe).
+#[derive(Debug, Deserialize, JsonSchema)]
+struct PollenBlock {
+    time: Vec<String>,
+    alder_pollen: Vec<Option<f64>>,
+    birch_pollen: Vec<Option<f64>>,
+    olive_pollen: Vec<Option<f64>>,
+    grass_pollen: Vec<Option<f64>>,
+    mugwort_pollen: Vec<Option<f64>>,
+    ragweed_pollen: Vec<Option<f64>>,
+}
+
+impl OpenMeteoProvider {
+    pub fn new(http_client: HttpClient, fallback: MockProvider) -> Self {
+        Self {
+            http_client,
+            fallback,
+        }
+    }
+
+    #[instrument(skip(self), fields(provider = "open-meteo"))]
+    async fn geocode(&self, location: &str) -> Result<GeocodingResult, ProviderError> {
+        let request = self.http_client.inner().get(GEOCODING_URL).query(&[
+            ("name", location),
+            ("count", "1"),
+            ("format", "json"),
+        ]);
+
+        let response = self.http_client.send(request).await?.error_for_status()?;
+        let response: GeocodingResponse = schema::decode("geocoding", response).await?;
+
+        response
+            .results
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::LocationNotFound(location.to_string()))
+    }
+
+    #[instrument(skip(self), fields(provider = "open-meteo"))]
+    async fn fetch_current(&self, location: &str) -> Result<Weather, ProviderError> {
+        let place = self.geocode(location).await?;
+        let request = self.http_client.inner().get(FORECAST_URL).query(&[
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            (
+                "current",
+                "temperature_2m,relative_humidity_2m,wind_speed_10m,weather_code".to_string(),
+            ),
+        ]);
+
+        let response = self.http_client.send(request).await?.error_for_status()?;
+        let response: CurrentResponse = schema::decode("forecast", response).await?;
+        let current = response.current;
+
+        Ok(Weather {
+            location: place.name,
+            temperature: current.temperature_2m.round() as i32,
+            condition: condition_for_code(current.weather_code).to_string(),
+            humidity: current.relative_humidity_2m.round() as i32,
+            wind_speed: current.wind_speed_10m.round() as i32,
+            units: Units::Metric,
+        })
+    }
+
+    #[instrument(skip(self), fields(provider = "open-meteo"))]
+    async fn fetch_forecast(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<Forecast>, ProviderError> {
+        self.fetch_daily(location, 0, days).await
+    }
+
+    #[instrument(skip(self), fields(provider = "open-meteo"))]
+    async fn fetch_history(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<Forecast>, ProviderError> {
+        self.fetch_daily(location, days, 0).await
+    }
+
+    /// The `past_days` days before today and the `forecast_days` from today,
+    /// with `day` 1 for today.
+    async fn fetch_daily(
+        &self,
+        location: &str,
+        past_days: u32,
+        forecast_days: u32,
+    ) -> Result<Vec<Forecast>, ProviderError> {
+        let place = self.geocode(location).await?;
+        let request = self.http_client.inner().get(FORECAST_URL).query(&[
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            (
+                "daily",
+                "temperature_2m_max,temperature_2m_min,weather_code,precipitation_probability_max,sunrise,sunset"
+                    .to_string(),
+            ),
+            ("past_days", past_days.to_string()),
+            ("forecast_days", forecast_days.to_string()),
+            ("timezone", "auto".to_string()),
+        ]);
+
+        let response = self.http_client.send(request).await?.error_for_status()?;
+        let response: DailyResponse = schema::decode("forecast", response).await?;
+        let timezone: Tz = response.timezone.parse().map_err(|_| {
+            ProviderError::InvalidResponse(format!("unknown timezone '{}'", response.timezone))
+        })?;
+        let daily = response.daily;
+
+        let len = daily.temperature_2m_max.len();
+        if daily.temperature_2m_min.len() != len
+            || daily.weather_code.len() != len
+            || daily.precipitation_probability_max.len() != len
+            || daily.time.len() != len
+            || daily.sunrise.len() != len
+            || daily.sunset.len() != len
+        {
+            return Err(ProviderError::InvalidResponse(
+                "daily series have mismatched lengths".to_string(),
+            ));
+        }
+
+        Ok((0..len)
+            .map(|index| Forecast {
+                day: index as i32 + 1 - past_days as i32,
+                date: daily.time[index].clone(),
+                sunrise: local_time(timezone, daily.sunrise[index].as_deref()),
+                sunset: local_time(timezone, daily.sunset[index].as_deref()),
+                high: daily.temperature_2m_max[index].round() as i32,
+                low: daily.temperature_2m_min[index].round() as i32,
+                condition: condition_for_code(daily.weather_code[index]).to_string(),
+                precipitation_chance: daily.precipitation_probability_max[index]
+                    .unwrap_or_default()
+                    .round() as i32,
+            })
+            .collect())
+    }
+
+    #[instrument(skip(self), fields(provider = "open-meteo"))]
+    async fn fetch_uv_index(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<UvIndexDay>, ProviderError> {
+        let place = self.geocode(location).await?;
+        let request = self.http_client.inner().get(FORECAST_URL).query(&[
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            ("daily", "uv_index_max".to_string()),
+            ("forecast_days", days.to_string()),
+            ("timezone", "auto".to_string()),
+        ]);
+
+        let response = self.http_client.send(request).await?.error_for_status()?;
+        let response: UvResponse = schema::decode("forecast", response).await?;
+        let daily = response.daily;
+        if daily.time.len() != daily.uv_index_max.len() {
+            return Err(ProviderError::InvalidResponse(
+                "daily series have mismatched lengths".to_string(),
+            ));
+        }
+
+        Ok(daily
+            .time
+            .into_iter()
+            .zip(daily.uv_index_max)
+            .enumerate()
+            .map(|(index, (date, uv_index))| {
+                let uv_index = uv_index.unwrap_or_default();
+                UvIndexDay {
+                    day: index as i32 + 1,
+                    date,
+                    uv_index,
+                    risk: uv_risk(uv_index).to_string(),
+                }
+            })
+            .collect())
+    }
+
+    #[instrument(skip(self), fields(provider = "open-meteo"))]
+    async fn fetch_marine(&self, location: &str) -> Result<MarineConditions, ProviderError> {
+        let place = self.geocode(location).await?;
+        let request = self.http_client.inner().get(MARINE_URL).query(&[
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            (
+                "current",
+                "wave_height,wave_period,wave_direction,sea_surface_temperature".to_string(),
+            ),
+        ]);
+
+        let response = self.http_client.send(request).await?.error_for_status()?;
+        let response: MarineResponse = schema::decode("marine", response).await?;
+        let current = response.current;
+        let (Some(wave_height), Some(water_temperature)) =
+            (current.wave_height, current.sea_surface_temperature)
+        else {
+            return Err(ProviderError::NoData(format!(
+                "{} is not on the coast",
+                place.name
+            )));
+        };
+
+        Ok(MarineConditions {
+            location: place.name,
+            wave_height,
+            wave_period: current.wave_period.unwrap_or_default(),
+            wave_direction: CompassDirection::from_degrees(
+                current.wave_direction.unwrap_or_default(),
+            ),
+            sea_state: SeaState::from_wave_height(wave_height),
+            water_temperature,
+        })
+    }
+
+    #[instrument(skip(self), fields(provider = "open-meteo"))]
+    async fn fetch_snow(&self, location: &str) -> Result<SnowReport, ProviderError> {
+        let place = self.geocode(location).await?;
+        let request = self.http_client.inner().get(FORECAST_URL).query(&[
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            ("current", "temperature_2m,snow_depth".to_string()),
+            ("hourly", "snowfall".to_string()),
+            ("past_hours", "24".to_string()),
+            ("forecast_hours", "0".to_string()),
+        ]);
+
+        let response = self.http_client.send(request).await?.error_for_status()?;
+        let response: SnowResponse = schema::decode("forecast", response).await?;
+        let base_depth = response.current.snow_depth.unwrap_or_default() * 100.0;
+        let fresh_snow: f64 = response.hourly.snowfall.iter().flatten().sum();
+        let temperature = response.current.temperature_2m;
+
+        Ok(SnowReport {
+            location: place.name,
+            base_depth: base_depth.round() as i32,
+            fresh_snow: fresh_snow.round() as i32,
+            temperature: temperature.round() as i32,
+            conditions: SnowConditions::classify(base_depth, fresh_snow, temperature),
+        })
+    }
+
+    /// Pollen from the air quality API, which only covers Europe; elsewhere
+    /// the series are all null and generated values are returned instead,
+    /// with `provider.fallback = "mock"` on the span.
+    #[instrument(skip(self), fields(provider = "open-meteo", provider.fallback = tracing::field::Empty))]
+    async fn fetch_pollen(
+        &self,
+        location: &str,
+        days: u32,
+    ) -> Result<Vec<PollenDay>, ProviderError> {
+        let place = self.geocode(location).await?;
+        let request = self.http_client.inner().get(AIR_QUALITY_URL).query(&[
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            ("hourly", POLLEN_SERIES.to_string()),
+            ("forecast_days", days.to_string()),
+            ("timezone", "auto".to_string()),
+        ]);
+
+        let response = self.http_client.send(request).await?.error_for_status()?;
+        let response: PollenResponse = schema::decode("air-quality", response).await?;
+        let hourly = response.hourly;
+        let len = hourly.time.len();
+        let series = [
+            &hourly.alder_pollen,
+            &hourly.birch_pollen,
+            &hourly.olive_pollen,
+            &hourly.grass_pollen,
+            &hourly.mugwort_pollen,
+            &hourly.ragweed_pollen,
+        ];
+        if series.iter().any(|values| values.len() != len) {
+            return Err(ProviderError::InvalidResponse(
+                "hourly series have mismatched lengths".to_string(),
+            ));
+        }
+        if series
+            .iter()
+            .all(|values| values.iter().all(Option::is_none))
+        {
+            tracing::Span::current().record("provider.fallback", "mock");
+            tracing::info!(location = %place.name, "No pollen data for location, using generated values");
+            return self.fallback.pollen(location, days).await;
+        }
+
+        // Daily peak per pollen type; `time` is local "YYYY-MM-DDTHH:MM"
+        let mut peaks: Vec<(String, [f64; 3])> = Vec::new();
+        for (index, time) in hourly.time.iter().enumerate() {
+            let date = time.get(..10).unwrap_or(time);
+            if peaks.last().map(|(last, _)| last.as_str()) != Some(date) {
+                peaks.push((date.to_string(), [0.0; 3]));
+            }
+            let value = |values: &Vec<Option<f64>>| values[index].unwrap_or_default();
+            let hour = [
+                value(&hourly.alder_pollen)
+                    .max(value(&hourly.birch_pollen))
+                    .max(value(&hourly.olive_pollen)),
+                value(&hourly.grass_pollen),
+                value(&hourly.mugwort_pollen).max(value(&hourly.ragweed_pollen)),
+            ];
+            if let Some((_, peak)) = peaks.last_mut() {
+                for (peak, hour) in peak.iter_mut().zip(hour) {
+                    *peak = peak.max(hour);
+                }
+            }
+        }
+
+        Ok(peaks
+            .into_iter()
+            .enumerate()
+            .map(|(index, (date, [tree, grass, weed]))| PollenDay {
+                day: index as i32 + 1,
+                date,
+                tree: PollenType::Tree.count(tree),
+                grass: PollenType::Grass.count(grass),
+                weed: PollenType::Weed.count(weed),
+            })
+            .collect())
+    }
+}
+
+impl WeatherProvider for OpenMeteoProvider {
+    fn name(&self) -> &'static str {
+        "open-meteo"
+    }
+
+    fn max_forecast_days(&self) -> u32 {
+        MAX_LONG_RANGE_DAYS
+    }
+
+    fn current<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, Weather> {
+        Box::pin(self.fetch_current(location))
+    }
+
+    fn forecast<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        Box::pin(self.fetch_forecast(location, days))
+    }
+
+    fn history<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<Forecast>> {
+        Box::pin(self.fetch_history(location, days))
+    }
+
+    fn uv_index<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<UvIndexDay>> {
+        Box::pin(self.fetch_uv_index(location, days))
+    }
+
+    fn pollen<'a>(&'a self, location: &'a str, days: u32) -> ProviderFuture<'a, Vec<PollenDay>> {
+        Box::pin(self.fetch_pollen(location, days))
+    }
+
+    fn marine<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, MarineConditions> {
+        Box::pin(self.fetch_marine(location))
+    }
+
+    fn snow<'a>(&'a self, location: &'a str) -> ProviderFuture<'a, SnowReport> {
+        Box::pin(self.fetch_snow(location))
+    }
+}
+
+/// Add the UTC offset to a local `YYYY-MM-DDTHH:MM` time from Open-Meteo.
+fn local_time(timezone: Tz, value: Option<&str>) -> Option<String> {
+    let local = NaiveDateTime::parse_from_str(value?, "%Y-%m-%dT%H:%M").ok()?;
+    forecast_time::localize(timezone, local).map(|time| forecast_time::format_local(&time))
+}
+
+/// Map WMO weather interpretation codes onto the demo's condition names.
+fn condition_for_code(code: u8) -> &'static str {
+    match code {
+        0 => "Sunny",
+        1 | 2 => "Partly Cloudy",
+        3 => "Cloudy",
+        45 | 48 => "Foggy",
+        51..=67 | 80..=82 => "Rainy",
+        71..=77 | 85 | 86 => "Snowy",
+        95..=99 => "Stormy",
+        _ => "Unknown",
+    }
+}
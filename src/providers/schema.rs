@@ -0,0 +1,329 @@
+//! Checks upstream JSON against the schema of the type it is read into,
+//! before mapping it into tool results.
+//!
+//! A provider changing its API shows up as a [`ProviderSchemaMismatch`]
+//! naming every offending field, rather than as a bare serde error or, for
+//! lenient fields, silently wrong data. Each check runs in a
+//! `provider.validate` span; mismatches are recorded there with the
+//! expected and found JSON types, never the values.
+
+use schemars::{gen::SchemaSettings, JsonSchema};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::Empty;
+
+use super::ProviderError;
+
+/// Mismatches listed on the span and in the error; the rest are counted.
+const MAX_REPORTED: usize = 10;
+
+/// Upstream JSON that does not have the expected shape.
+#[derive(Debug, Clone)]
+pub struct ProviderSchemaMismatch {
+    /// Upstream endpoint, e.g. `forecast`
+    pub endpoint: &'static str,
+    /// `<path>: expected <type>, found <type>`, at most [`MAX_REPORTED`]
+    pub fields: Vec<String>,
+    /// All mismatches, including those not listed
+    pub total: usize,
+}
+
+impl fmt::Display for ProviderSchemaMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} response does not match its schema: {}",
+            self.endpoint,
+            self.fields.join("; ")
+        )?;
+        if self.total > self.fields.len() {
+            write!(f, " (and {} more)", self.total - self.fields.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Read `response` as `T`, failing with [`ProviderError::SchemaMismatch`]
+/// when its JSON does not match `T`'s schema.
+pub async fn decode<T: DeserializeOwned + JsonSchema + 'static>(
+    endpoint: &'static str,
+    response: reqwest::Response,
+) -> Result<T, ProviderError> {
+    let body = response.bytes().await?;
+    let span = tracing::info_span!(
+        "provider.validate",
+        provider.endpoint = endpoint,
+        schema.valid = Empty,
+        schema.mismatches = Empty,
+        schema.mismatch_count = Empty,
+        otel.status_code = Empty,
+    );
+    let _entered = span.enter();
+
+    let value: Value = serde_json::from_slice(&body).map_err(|error| {
+        span.record("otel.status_code", "ERROR");
+        ProviderError::InvalidResponse(format!("{endpoint} response is not JSON: {error}"))
+    })?;
+    let mut mismatches = mismatches::<T>(&value);
+    span.record("schema.valid", mismatches.is_empty());
+    if !mismatches.is_empty() {
+        let total = mismatches.len();
+        mismatches.truncate(MAX_REPORTED);
+        span.record("schema.mismatches", mismatches.join("; ").as_str());
+        span.record("schema.mismatch_count", total);
+        span.record("otel.status_code", "ERROR");
+        tracing::warn!(
+            endpoint,
+            total,
+            "Provider response does not match its schema"
+        );
+        return Err(ProviderError::SchemaMismatch(ProviderSchemaMismatch {
+            endpoint,
+            fields: mismatches,
+            total,
+        }));
+    }
+
+    // Anything the schema cannot express, e.g. integer ranges
+    serde_json::from_value(value).map_err(|error| {
+        span.record("otel.status_code", "ERROR");
+        ProviderError::InvalidResponse(format!("{endpoint} response: {error}"))
+    })
+}
+
+/// Where `value` departs from `T`'s schema, as `<path>: <problem>` lines.
+fn mismatches<T: JsonSchema + 'static>(value: &Value) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    let schema = schema_of::<T>();
+    check(&schema, &schema, value, "$", &mut mismatches);
+    mismatches
+}
+
+/// `T`'s JSON schema with its definitions, generated once per type.
+fn schema_of<T: JsonSchema + 'static>() -> Arc<Value> {
+    static SCHEMAS: Mutex<Option<HashMap<TypeId, Arc<Value>>>> = Mutex::new(None);
+    let mut schemas = SCHEMAS.lock().expect("schema lock poisoned");
+    schemas
+        .get_or_insert_with(HashMap::new)
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| {
+            let schema = SchemaSettings::draft07()
+                .into_generator()
+                .into_root_schema_for::<T>();
+            Arc::new(serde_json::to_value(schema).expect("schema serializes"))
+        })
+        .clone()
+}
+
+/// Add a line to `mismatches` for each place `value` departs from `schema`.
+/// Handles the subset of JSON schema that schemars emits for plain structs:
+/// `type`, `properties`, `required`, `items`, `anyOf`, `$ref` and `minimum`.
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, mismatches: &mut Vec<String>) {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.trim_start_matches("#/definitions/");
+        if let Some(definition) = root.pointer(&format!("/definitions/{name}")) {
+            check(root, definition, value, path, mismatches);
+        }
+        return;
+    }
+    if let Some(variants) = schema.get("anyOf").and_then(Value::as_array) {
+        let fits = variants.iter().any(|variant| {
+            let mut nested = Vec::new();
+            check(root, variant, value, path, &mut nested);
+            nested.is_empty()
+        });
+        if !fits {
+            mismatches.push(format!(
+                "{path}: expected {}, found {}",
+                expected_any(root, variants),
+                json_type(value)
+            ));
+        }
+        return;
+    }
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(single) => vec![single.as_str()],
+            Value::Array(many) => many.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.iter().any(|expected| matches_type(expected, value)) {
+            mismatches.push(format!(
+                "{path}: expected {}, found {}",
+                types.join(" or "),
+                json_type(value)
+            ));
+            return;
+        }
+    }
+
+    match value {
+        Value::Object(object) => check_object(root, schema, object, path, mismatches),
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(
+                        root,
+                        item_schema,
+                        item,
+                        &format!("{path}[{index}]"),
+                        mismatches,
+                    );
+                }
+            }
+        }
+        Value::Number(number) => {
+            let minimum = schema.get("minimum").and_then(Value::as_f64);
+            if let (Some(minimum), Some(number)) = (minimum, number.as_f64()) {
+                if number < minimum {
+                    mismatches.push(format!("{path}: expected at least {minimum}, found less"));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_object(
+    root: &Value,
+    schema: &Value,
+    object: &Map<String, Value>,
+    path: &str,
+    mismatches: &mut Vec<String>,
+) {
+    let required = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for name in required.iter().filter_map(Value::as_str) {
+        if !object.contains_key(name) {
+            mismatches.push(format!("{path}.{name}: missing"));
+        }
+    }
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        for (name, property) in properties {
+            if let Some(field) = object.get(name) {
+                check(root, property, field, &format!("{path}.{name}"), mismatches);
+            }
+        }
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
+
+/// JSON type of `value`, reported instead of the value itself.
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn expected_any(root: &Value, variants: &[Value]) -> String {
+    variants
+        .iter()
+        .map(|variant| {
+            let variant = match variant.get("$ref").and_then(Value::as_str) {
+                Some(reference) => root
+                    .pointer(&format!(
+                        "/definitions/{}",
+                        reference.trim_start_matches("#/definitions/")
+                    ))
+                    .unwrap_or(variant),
+                None => variant,
+            };
+            match variant.get("type") {
+                Some(Value::String(single)) => single.clone(),
+                _ => "value".to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Response {
+        timezone: String,
+        daily: Daily,
+        #[serde(default)]
+        results: Vec<String>,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Daily {
+        temperature_2m_max: Vec<f64>,
+        precipitation_probability_max: Vec<Option<f64>>,
+        weather_code: Vec<u8>,
+    }
+
+    fn daily(temperatures: Value) -> Value {
+        json!({
+            "timezone": "Europe/Paris",
+            "daily": {
+                "temperature_2m_max": temperatures,
+                "precipitation_probability_max": [10, null],
+                "weather_code": [3, 61],
+            },
+        })
+    }
+
+    #[test]
+    fn matching_response_has_no_mismatches() {
+        assert!(mismatches::<Response>(&daily(json!([21.5, 19]))).is_empty());
+    }
+
+    #[test]
+    fn mismatches_name_the_field_and_types_but_not_the_value() {
+        let found = mismatches::<Response>(&daily(json!([21.5, "secret"])));
+        assert_eq!(
+            found,
+            ["$.daily.temperature_2m_max[1]: expected number, found string"]
+        );
+    }
+
+    #[test]
+    fn missing_required_fields_are_reported() {
+        let found = mismatches::<Response>(&json!({ "daily": {
+            "temperature_2m_max": [],
+            "precipitation_probability_max": [],
+            "weather_code": [-1],
+        }}));
+        assert_eq!(
+            found,
+            [
+                "$.timezone: missing",
+                "$.daily.weather_code[0]: expected at least 0, found less"
+            ]
+        );
+    }
+}
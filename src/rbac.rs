@@ -0,0 +1,236 @@
+use axum::http::{header, request::Parts, HeaderMap};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use rmcp::model::Tool;
+use rmcp::{service::RequestContext, ErrorData as McpError, RoleServer};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config::RbacConfig;
+use crate::tool_middleware::{ToolCall, ToolMiddleware};
+use crate::trace_utils::USER_ID_HEADER;
+
+/// Grants every tool when listed in a role.
+const ALL_TOOLS: &str = "*";
+
+/// Claims read from a caller's bearer token.
+#[derive(Debug, Deserialize)]
+struct Claims {
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RbacError {
+    #[error("invalid bearer token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+}
+
+impl From<RbacError> for McpError {
+    fn from(error: RbacError) -> Self {
+        McpError::invalid_request(error.to_string(), None)
+    }
+}
+
+/// Role -> tool permissions, enforced on `tools/call` and applied to
+/// `tools/list`.
+pub struct Rbac {
+    roles: HashMap<String, Vec<String>>,
+    users: HashMap<String, Vec<String>>,
+    default_roles: Vec<String>,
+    jwt: Option<(DecodingKey, Validation)>,
+    trust_user_header: bool,
+}
+
+impl Rbac {
+    /// `None` when `RBAC_ROLES` is unset, so every caller may use every tool.
+    pub fn from_config(config: &RbacConfig) -> Option<Arc<Self>> {
+        if config.roles.is_empty() {
+            return None;
+        }
+        let jwt = config.jwt_secret.as_ref().map(|secret| {
+            let mut validation = Validation::new(Algorithm::HS256);
+            validation.validate_aud = false;
+            (DecodingKey::from_secret(secret.as_bytes()), validation)
+        });
+        Some(Arc::new(Self {
+            roles: config.roles.clone(),
+            users: config.users.clone(),
+            default_roles: config.default_roles.clone(),
+            jwt,
+            trust_user_header: config.trust_user_header,
+        }))
+    }
+
+    /// Roles of the caller behind an MCP request.
+    pub fn grant(&self, context: &RequestContext<RoleServer>) -> Result<Grant<'_>, RbacError> {
+        let headers = context
+            .extensions
+            .get::<Parts>()
+            .map(|parts| &parts.headers);
+        self.grant_headers(headers)
+    }
+
+    /// Roles of a caller sending `headers`. With JWTs configured, the bearer
+    /// token's `roles` claim, or the default roles without a token. Otherwise
+    /// the roles mapped to its `x-user-id` when that header is trusted, else
+    /// the default roles.
+    fn grant_headers(&self, headers: Option<&HeaderMap>) -> Result<Grant<'_>, RbacError> {
+        let header_value = |name| {
+            headers
+                .and_then(|headers| headers.get(name))
+                .and_then(|value| value.to_str().ok())
+        };
+
+        if let Some((key, validation)) = &self.jwt {
+            let bearer = header_value(header::AUTHORIZATION.as_str())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            let roles = match bearer {
+                Some(token) => {
+                    jsonwebtoken::decode::<Claims>(token, key, validation)?
+                        .claims
+                        .roles
+                }
+                None => self.default_roles.clone(),
+            };
+            return Ok(Grant { rbac: self, roles });
+        }
+
+        let roles = header_value(USER_ID_HEADER)
+            .filter(|_| self.trust_user_header)
+            .and_then(|user| self.users.get(user))
+            .unwrap_or(&self.default_roles);
+        Ok(Grant {
+            rbac: self,
+            roles: roles.clone(),
+        })
+    }
+}
+
+impl ToolMiddleware for Rbac {
+    fn before(&self, call: &ToolCall) -> Result<(), McpError> {
+        let grant = self.grant(&call.context)?;
+        if grant.permits(call.name()) {
+            return Ok(());
+        }
+        tracing::warn!(
+            target: "audit",
+            tool = call.name(),
+            roles = ?grant.roles,
+            "Denied tool call"
+        );
+        Err(McpError::invalid_request(
+            format!("tool '{}' is not permitted for this caller", call.name()),
+            None,
+        ))
+    }
+
+    fn filter_tools(
+        &self,
+        tools: &mut Vec<Tool>,
+        context: &RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        let grant = self.grant(context)?;
+        tools.retain(|tool| grant.permits(&tool.name));
+        Ok(())
+    }
+}
+
+/// Roles resolved for one request.
+#[derive(Clone)]
+pub struct Grant<'a> {
+    rbac: &'a Rbac,
+    pub roles: Vec<String>,
+}
+
+impl Grant<'_> {
+    /// Whether any of the caller's roles allows `tool`; unknown roles allow nothing.
+    pub fn permits(&self, tool: &str) -> bool {
+        self.roles
+            .iter()
+            .filter_map(|role| self.rbac.roles.get(role))
+            .flatten()
+            .any(|allowed| allowed == ALL_TOOLS || allowed == tool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    const SECRET: &str = "test-secret";
+
+    fn rbac(jwt_secret: Option<&str>, trust_user_header: bool) -> Arc<Rbac> {
+        Rbac::from_config(&RbacConfig {
+            roles: HashMap::from([
+                ("reader".to_string(), vec!["get_weather".to_string()]),
+                ("admin".to_string(), vec![ALL_TOOLS.to_string()]),
+            ]),
+            users: HashMap::from([("alice".to_string(), vec!["admin".to_string()])]),
+            default_roles: vec!["reader".to_string()],
+            jwt_secret: jwt_secret.map(str::to_string),
+            trust_user_header,
+        })
+        .unwrap()
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect()
+    }
+
+    fn bearer(roles: &[&str]) -> String {
+        let claims = serde_json::json!({ "roles": roles, "exp": u32::MAX });
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap();
+        format!("Bearer {token}")
+    }
+
+    #[test]
+    fn roles_come_from_a_verified_token() {
+        let rbac = rbac(Some(SECRET), false);
+        let token = bearer(&["admin"]);
+        let grant = rbac
+            .grant_headers(Some(&headers(&[("authorization", &token)])))
+            .unwrap();
+        assert_eq!(grant.roles, ["admin"]);
+        assert!(grant.permits("export_history"));
+
+        let forged = headers(&[("authorization", "Bearer not-a-jwt")]);
+        assert!(rbac.grant_headers(Some(&forged)).is_err());
+    }
+
+    #[test]
+    fn user_header_is_only_trusted_when_configured() {
+        let alice = headers(&[(USER_ID_HEADER, "alice")]);
+        let trusted = rbac(None, true);
+        assert_eq!(
+            trusted.grant_headers(Some(&alice)).unwrap().roles,
+            ["admin"]
+        );
+
+        let untrusted = rbac(None, false);
+        assert_eq!(
+            untrusted.grant_headers(Some(&alice)).unwrap().roles,
+            ["reader"]
+        );
+    }
+
+    #[test]
+    fn callers_without_a_token_get_the_default_roles() {
+        let rbac = rbac(Some(SECRET), true);
+        let alice = headers(&[(USER_ID_HEADER, "alice")]);
+        let grant = rbac.grant_headers(Some(&alice)).unwrap();
+        assert_eq!(grant.roles, ["reader"]);
+        assert!(grant.permits("get_weather"));
+        assert!(!grant.permits("export_history"));
+        assert_eq!(rbac.grant_headers(None).unwrap().roles, ["reader"]);
+    }
+}
@@ -0,0 +1,243 @@
+//! Tool calls recorded to a file, and replayed against the current build to
+//! spot changes in their results (provider or schema changes).
+
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use rmcp::model::{CallToolRequestParam, CallToolResult, JsonObject};
+use rmcp::{ErrorData as McpError, ServiceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::app_state::AppState;
+use crate::config::Config;
+use crate::tool_middleware::{Next, ToolCall, ToolMiddleware, ToolResult};
+use crate::weather_tools::WeatherService;
+
+/// One line of the recording.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedCall {
+    /// When the call started, RFC 3339 in UTC.
+    pub timestamp: String,
+    pub tool: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<JsonObject>,
+    /// The result the client received, unless the call failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<CallToolResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<McpError>,
+    pub duration_ms: f64,
+}
+
+impl RecordedCall {
+    /// Result or error as JSON, the form results are compared in.
+    fn outcome(&self) -> Value {
+        match (&self.result, &self.error) {
+            (Some(result), _) => serde_json::to_value(result).unwrap_or_default(),
+            (None, Some(error)) => serde_json::json!({ "error": error }),
+            (None, None) => Value::Null,
+        }
+    }
+}
+
+/// Middleware appending every tool call, with its full result, to a JSONL
+/// file for [`replay`].
+#[derive(Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl Recorder {
+    /// Open the recording; `None` when `TOOL_RECORDING_PATH` is unset.
+    pub fn open(path: Option<&Path>) -> Result<Option<Self>> {
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open recording {}", path.display()))?;
+        Ok(Some(Self {
+            file: Arc::new(Mutex::new(BufWriter::new(file))),
+        }))
+    }
+
+    fn record(&self, call: &RecordedCall) {
+        let mut file = self.file.lock().expect("recording lock poisoned");
+        let written = serde_json::to_writer(&mut *file, call)
+            .map_err(std::io::Error::from)
+            .and_then(|()| file.write_all(b"\n"))
+            .and_then(|()| file.flush());
+        if let Err(error) = written {
+            tracing::warn!(%error, tool = call.tool, "Failed to record tool call");
+        }
+    }
+}
+
+impl ToolMiddleware for Recorder {
+    fn around<'a>(&'a self, call: &'a ToolCall, next: Next<'a>) -> BoxFuture<'a, ToolResult> {
+        Box::pin(async move {
+            let timestamp = OffsetDateTime::now_utc();
+            let started = Instant::now();
+            let result = next.run(call).await;
+            let (recorded, error) = match &result {
+                Ok(result) => (Some(result.clone()), None),
+                Err(error) => (None, Some(error.clone())),
+            };
+            self.record(&RecordedCall {
+                timestamp: timestamp.format(&Rfc3339).unwrap_or_default(),
+                tool: call.name().to_string(),
+                arguments: call.request.arguments.clone(),
+                result: recorded,
+                error,
+                duration_ms: started.elapsed().as_secs_f64() * 1_000.0,
+            });
+            result
+        })
+    }
+}
+
+/// Re-issue every call of a recording to an in-process server built from
+/// the current configuration and compare the results.
+///
+/// Returns the report and whether any result differed. Keys in `ignore`
+/// (at any depth) are left out of the comparison, for values that change
+/// on every call.
+pub async fn replay(path: &Path, ignore: &[String]) -> Result<(String, bool)> {
+    let file =
+        File::open(path).with_context(|| format!("failed to open recording {}", path.display()))?;
+    let mut calls = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let call: RecordedCall = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: not a recorded call", path.display(), number + 1))?;
+        calls.push(call);
+    }
+
+    let mut config = Config::from_env()?;
+    // Replaying must not append to the recording it reads
+    config.recording_path = None;
+    let service = WeatherService::new(AppState::new(config)?);
+    let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        if let Ok(server) = service.serve(server_io).await {
+            let _ = server.waiting().await;
+        }
+    });
+    let client = ().serve(client_io).await.context("failed to connect to the server")?;
+
+    let mut out = String::new();
+    let mut changed = 0;
+    for call in &calls {
+        let started = Instant::now();
+        let result = client
+            .call_tool(CallToolRequestParam {
+                name: call.tool.clone().into(),
+                arguments: call.arguments.clone(),
+            })
+            .await;
+        let duration_ms = started.elapsed().as_secs_f64() * 1_000.0;
+        let replayed = RecordedCall {
+            timestamp: String::new(),
+            tool: call.tool.clone(),
+            arguments: None,
+            result: result.as_ref().ok().cloned(),
+            error: match result {
+                Ok(_) => None,
+                Err(rmcp::ServiceError::McpError(error)) => Some(error),
+                Err(error) => Some(McpError::internal_error(error.to_string(), None)),
+            },
+            duration_ms,
+        };
+
+        let mut expected = call.outcome();
+        let mut actual = replayed.outcome();
+        strip(&mut expected, ignore);
+        strip(&mut actual, ignore);
+        let mut differences = Vec::new();
+        diff("", &expected, &actual, &mut differences);
+
+        let status = if differences.is_empty() {
+            "same"
+        } else {
+            changed += 1;
+            "diff"
+        };
+        let _ = writeln!(
+            out,
+            "[{status}] {} {:.1} ms (recorded {:.1} ms)",
+            call.tool, duration_ms, call.duration_ms
+        );
+        for difference in differences {
+            let _ = writeln!(out, "    {difference}");
+        }
+    }
+    let _ = client.cancel().await;
+
+    let _ = writeln!(
+        out,
+        "\n{} calls replayed, {changed} with different results",
+        calls.len()
+    );
+    Ok((out, changed > 0))
+}
+
+/// Remove `keys` from `value` at any depth.
+fn strip(value: &mut Value, keys: &[String]) {
+    match value {
+        Value::Object(object) => {
+            object.retain(|key, _| !keys.contains(key));
+            for value in object.values_mut() {
+                strip(value, keys);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                strip(item, keys);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Append one line per differing leaf, addressed by JSON pointer.
+fn diff(pointer: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, value) in expected {
+                let pointer = format!("{pointer}/{key}");
+                match actual.get(key) {
+                    Some(other) => diff(&pointer, value, other, out),
+                    None => out.push(format!("{pointer}: {value} -> (missing)")),
+                }
+            }
+            for (key, value) in actual {
+                if !expected.contains_key(key) {
+                    out.push(format!("{pointer}/{key}: (missing) -> {value}"));
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (index, (value, other)) in expected.iter().zip(actual).enumerate() {
+                diff(&format!("{pointer}/{index}"), value, other, out);
+            }
+        }
+        _ if expected != actual => {
+            let pointer = if pointer.is_empty() { "/" } else { pointer };
+            out.push(format!("{pointer}: {expected} -> {actual}"));
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,73 @@
+//! Background job looking up the current weather of every location with a
+//! webhook or a watch, once per `REFRESH_INTERVAL_SECS`.
+//!
+//! Each location's lookup is the root of a `weather.refresh` trace; webhook
+//! deliveries and triggered watches link to it. At shutdown the job stops
+//! after the pass in progress, so its webhook deliveries are not cut off.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tracing::{field::Empty, Instrument};
+
+use crate::app_state::AppState;
+use crate::{watches, webhooks};
+
+/// Run the refresh job until shutdown.
+pub fn spawn(state: AppState) {
+    let stop = Arc::new(Notify::new());
+    let job = tokio::spawn({
+        let stop = stop.clone();
+        let state = state.clone();
+        async move {
+            let mut ticker = tokio::time::interval(state.config.refresh_interval);
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = stop.notified() => return,
+                }
+                let mut locations: BTreeSet<String> = state.webhooks.locations();
+                locations.extend(state.watches.locations());
+                for location in locations {
+                    refresh(&state, &location).await;
+                }
+            }
+        }
+    });
+    state.shutdown.register(
+        "refresh.drain",
+        state.config.server.shutdown_hook_timeout,
+        move || async move {
+            // Stored as a permit when a pass is in progress
+            stop.notify_one();
+            job.await?;
+            Ok(())
+        },
+    );
+}
+
+async fn refresh(state: &AppState, location: &str) {
+    let span = tracing::info_span!(
+        parent: None,
+        "weather.refresh",
+        location = %location,
+        alerts = Empty,
+        watches_triggered = Empty,
+        otel.status_code = Empty,
+    );
+    async {
+        let weather = match state.current_weather(location).await {
+            Ok(weather) => weather,
+            Err(error) => {
+                tracing::Span::current().record("otel.status_code", "ERROR");
+                tracing::warn!(%location, %error, "Weather refresh failed");
+                return;
+            }
+        };
+        webhooks::notify(state, location, &weather).await;
+        watches::evaluate(state, location, &weather).await;
+    }
+    .instrument(span)
+    .await;
+}
@@ -0,0 +1,77 @@
+//! One correlation id per HTTP request, in `x-request-id`.
+//!
+//! A caller's (or gateway's) id is kept, else a UUID v7 is generated, so ids
+//! sort by time. The id is set on the request before any other middleware
+//! sees it and echoed on the response. From there it is recorded as
+//! `request.id` on the request's and tools' spans, and added to audit
+//! records, tool error reports and REST error bodies.
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::future::Future;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest caller id kept; longer ones are replaced.
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    /// Id of the request handled on this task.
+    static REQUEST_ID: String;
+}
+
+/// Id of the request handled on this task, for handlers answering it
+/// directly (REST). MCP tools run on the session's task and read theirs from
+/// `RequestAttribution` instead.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(String::clone).ok()
+}
+
+/// Run `task` with `id` as the [`current`] request id.
+pub async fn scope<F: Future>(id: String, task: F) -> F::Output {
+    REQUEST_ID.scope(id, task).await
+}
+
+/// The caller's id when it is short printable ASCII, else a new one.
+fn resolve(caller: Option<&HeaderValue>) -> HeaderValue {
+    caller
+        .filter(|value| {
+            let bytes = value.as_bytes();
+            !bytes.is_empty() && bytes.len() <= MAX_LEN && bytes.iter().all(u8::is_ascii_graphic)
+        })
+        .cloned()
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&Uuid::now_v7().to_string()).expect("a UUID is a header value")
+        })
+}
+
+/// Middleware giving every request an id, outermost but for access control.
+pub async fn assign(mut request: Request, next: Next) -> Response {
+    let id = resolve(request.headers().get(&REQUEST_ID_HEADER));
+    request.headers_mut().insert(REQUEST_ID_HEADER, id.clone());
+    let scoped = id.to_str().unwrap_or_default().to_string();
+    let mut response = scope(scoped, next.run(request)).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, id);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caller_ids_are_kept_unless_malformed() {
+        let caller = HeaderValue::from_static("gw-42");
+        assert_eq!(resolve(Some(&caller)), "gw-42");
+
+        for malformed in ["", "has space", &"x".repeat(MAX_LEN + 1)] {
+            let generated = resolve(Some(&HeaderValue::from_str(malformed).unwrap()));
+            let uuid = Uuid::parse_str(generated.to_str().unwrap()).unwrap();
+            assert_eq!(uuid.get_version_num(), 7);
+        }
+        assert_ne!(resolve(None), resolve(None));
+    }
+}
@@ -0,0 +1,259 @@
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::SigningConfig;
+
+/// `sha256=<hex HMAC>` over the canonical request, see [`signature`].
+pub const SIGNATURE_HEADER: &str = "x-signature";
+/// Unix time in seconds at which the request was signed.
+pub const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+/// Single-use value; a second request with the same nonce is a replay.
+pub const NONCE_HEADER: &str = "x-signature-nonce";
+
+/// Largest body buffered for verification.
+const MAX_SIGNED_BODY_BYTES: usize = 1024 * 1024;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HMAC-SHA256 of `timestamp \n nonce \n METHOD \n path?query \n body`, hex encoded.
+pub fn signature(
+    secret: &[u8],
+    timestamp: u64,
+    nonce: &str,
+    method: &Method,
+    path_and_query: &str,
+    body: &[u8],
+) -> String {
+    hex::encode(
+        mac(secret, timestamp, nonce, method, path_and_query, body)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+fn mac(
+    secret: &[u8],
+    timestamp: u64,
+    nonce: &str,
+    method: &Method,
+    path_and_query: &str,
+    body: &[u8],
+) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(format!("{timestamp}\n{nonce}\n{method}\n{path_and_query}\n").as_bytes());
+    mac.update(body);
+    mac
+}
+
+/// Why a signed request was refused; the message is returned to the caller.
+#[derive(Debug, thiserror::Error)]
+enum Rejection {
+    #[error("missing or malformed {0} header")]
+    MissingHeader(&'static str),
+    #[error("signature timestamp outside the allowed window")]
+    Expired,
+    #[error("signature nonce already used")]
+    Replayed,
+    #[error("signature does not match")]
+    BadSignature,
+    #[error("request body too large to verify")]
+    BodyTooLarge,
+}
+
+/// Verifies [`SIGNATURE_HEADER`] on REST calls and remembers nonces seen
+/// within the timestamp window.
+pub struct RequestSigning {
+    secret: Vec<u8>,
+    tolerance: Duration,
+    /// Nonce -> signed timestamp, pruned once outside the window.
+    seen_nonces: Mutex<HashMap<String, u64>>,
+}
+
+impl RequestSigning {
+    /// `None` when `REST_SIGNING_SECRET` is unset.
+    pub fn from_config(config: &SigningConfig) -> Option<Arc<Self>> {
+        let secret = config.secret.as_ref()?;
+        Some(Arc::new(Self {
+            secret: secret.as_bytes().to_vec(),
+            tolerance: config.tolerance,
+            seen_nonces: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    fn verify(
+        &self,
+        headers: &HeaderMap,
+        method: &Method,
+        path_and_query: &str,
+        body: &[u8],
+    ) -> Result<(), Rejection> {
+        let header = |name: &'static str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .filter(|value| !value.is_empty())
+                .ok_or(Rejection::MissingHeader(name))
+        };
+        let timestamp: u64 = header(TIMESTAMP_HEADER)?
+            .parse()
+            .map_err(|_| Rejection::MissingHeader(TIMESTAMP_HEADER))?;
+        let nonce = header(NONCE_HEADER)?;
+        let presented = header(SIGNATURE_HEADER)?
+            .strip_prefix("sha256=")
+            .and_then(|hex| hex::decode(hex).ok())
+            .ok_or(Rejection::MissingHeader(SIGNATURE_HEADER))?;
+
+        let now = unix_now();
+        if now.abs_diff(timestamp) > self.tolerance.as_secs() {
+            return Err(Rejection::Expired);
+        }
+
+        // Constant-time comparison
+        mac(&self.secret, timestamp, nonce, method, path_and_query, body)
+            .verify_slice(&presented)
+            .map_err(|_| Rejection::BadSignature)?;
+
+        // Only remember nonces of authentic requests, so the map cannot be
+        // filled by unauthenticated callers.
+        let mut seen = self.seen_nonces.lock().expect("nonce lock poisoned");
+        let window = self.tolerance.as_secs();
+        seen.retain(|_, signed_at| now.abs_diff(*signed_at) <= window);
+        if seen.insert(nonce.to_string(), timestamp).is_some() {
+            return Err(Rejection::Replayed);
+        }
+        Ok(())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Reject REST calls without a valid, fresh, unused signature with 401, and
+/// record the attempt on the `audit` target.
+pub async fn verify_signature(
+    State(signing): State<Arc<RequestSigning>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+
+    let verified = match to_bytes(body, MAX_SIGNED_BODY_BYTES).await {
+        Ok(body) => signing
+            .verify(&parts.headers, &parts.method, path_and_query, &body)
+            .map(|()| body),
+        Err(_) => Err(Rejection::BodyTooLarge),
+    };
+
+    match verified {
+        Ok(body) => next.run(Request::from_parts(parts, Body::from(body))).await,
+        Err(rejection) => {
+            tracing::warn!(
+                target: "audit",
+                method = %parts.method,
+                path = path_and_query,
+                reason = %rejection,
+                "Rejected unsigned or invalid REST request"
+            );
+            (StatusCode::UNAUTHORIZED, format!("{rejection}\n")).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "secret";
+    const PATH: &str = "/weather?location=Paris";
+
+    fn signing() -> Arc<RequestSigning> {
+        RequestSigning::from_config(&SigningConfig {
+            secret: Some(SECRET.into()),
+            tolerance: Duration::from_secs(300),
+        })
+        .unwrap()
+    }
+
+    fn signed(timestamp: u64, nonce: &str, body: &[u8]) -> HeaderMap {
+        let digest = signature(
+            SECRET.as_bytes(),
+            timestamp,
+            nonce,
+            &Method::POST,
+            PATH,
+            body,
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            SIGNATURE_HEADER,
+            format!("sha256={digest}").parse().unwrap(),
+        );
+        headers.insert(TIMESTAMP_HEADER, timestamp.into());
+        headers.insert(NONCE_HEADER, nonce.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn valid_signatures_are_accepted() {
+        let headers = signed(unix_now(), "n1", b"{}");
+        assert!(signing()
+            .verify(&headers, &Method::POST, PATH, b"{}")
+            .is_ok());
+    }
+
+    #[test]
+    fn tampered_requests_are_rejected() {
+        let signing = signing();
+        let headers = signed(unix_now(), "n1", b"{}");
+        let result = signing.verify(&headers, &Method::POST, PATH, b"{\"a\":1}");
+        assert!(matches!(result, Err(Rejection::BadSignature)));
+        let result = signing.verify(&headers, &Method::PUT, PATH, b"{}");
+        assert!(matches!(result, Err(Rejection::BadSignature)));
+
+        let mut unsigned = headers.clone();
+        unsigned.remove(SIGNATURE_HEADER);
+        let result = signing.verify(&unsigned, &Method::POST, PATH, b"{}");
+        assert!(matches!(
+            result,
+            Err(Rejection::MissingHeader(SIGNATURE_HEADER))
+        ));
+    }
+
+    #[test]
+    fn stale_timestamps_are_rejected() {
+        let headers = signed(unix_now() - 600, "n1", b"{}");
+        let result = signing().verify(&headers, &Method::POST, PATH, b"{}");
+        assert!(matches!(result, Err(Rejection::Expired)));
+    }
+
+    #[test]
+    fn nonces_are_single_use() {
+        let signing = signing();
+        let headers = signed(unix_now(), "n1", b"{}");
+        assert!(signing.verify(&headers, &Method::POST, PATH, b"{}").is_ok());
+        let result = signing.verify(&headers, &Method::POST, PATH, b"{}");
+        assert!(matches!(result, Err(Rejection::Replayed)));
+
+        // A rejected signature does not burn the nonce
+        let other = signed(unix_now(), "n2", b"{}");
+        assert!(signing.verify(&other, &Method::POST, PATH, b"x").is_err());
+        assert!(signing.verify(&other, &Method::POST, PATH, b"{}").is_ok());
+    }
+}
@@ -0,0 +1,54 @@
+use opentelemetry::KeyValue;
+use opentelemetry_resource_detectors::{
+    HostResourceDetector, OsResourceDetector, ProcessResourceDetector,
+};
+use opentelemetry_sdk::resource::{Resource, ResourceDetector};
+
+/// Detectors for host, OS, process and container attributes, so traces from
+/// different demo deployments can be told apart in Langfuse.
+pub fn detectors() -> Vec<Box<dyn ResourceDetector>> {
+    vec![
+        Box::new(HostResourceDetector::default()),
+        Box::new(OsResourceDetector),
+        Box::new(ProcessResourceDetector),
+        Box::new(ContainerResourceDetector),
+    ]
+}
+
+/// Sets `container.id` when running inside a container.
+///
+/// The id is the 64-hex-digit segment Docker, containerd and CRI-O put in the
+/// cgroup path (cgroup v1) or in the mount sources of `/etc/hostname` (cgroup v2).
+#[derive(Debug)]
+pub struct ContainerResourceDetector;
+
+impl ResourceDetector for ContainerResourceDetector {
+    fn detect(&self) -> Resource {
+        let from_cgroup = || {
+            let cgroup = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+            cgroup.lines().find_map(container_id)
+        };
+        let from_mounts = || {
+            let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+            mountinfo
+                .lines()
+                .filter(|line| line.split_whitespace().nth(4) == Some("/etc/hostname"))
+                .find_map(container_id)
+        };
+        let id = from_cgroup().or_else(from_mounts);
+
+        let builder = Resource::builder_empty();
+        match id {
+            Some(id) => builder
+                .with_attribute(KeyValue::new("container.id", id))
+                .build(),
+            None => builder.build(),
+        }
+    }
+}
+
+fn container_id(line: &str) -> Option<String> {
+    line.split(|c: char| c == '/' || c == '-' || c == '.' || c.is_whitespace())
+        .find(|segment| segment.len() == 64 && segment.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_string)
+}
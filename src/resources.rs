@@ -0,0 +1,201 @@
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use rmcp::model::{AnnotateAble, RawResource, Resource, ResourceContents};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::history_export::ExportFormat;
+use crate::weather_tools::{Forecast, ForecastResponse, Units};
+
+/// Resources kept per session before the oldest is dropped.
+const CAPACITY: usize = 32;
+
+/// Resources embedded in this session's tool results, so
+/// `resources/read` can serve them again afterwards.
+#[derive(Default)]
+pub struct SessionResources {
+    entries: Mutex<VecDeque<StoredResource>>,
+}
+
+struct StoredResource {
+    uri: String,
+    name: String,
+    mime_type: String,
+    contents: Contents,
+}
+
+enum Contents {
+    Text(String),
+    /// Base64, as sent.
+    Blob {
+        base64: String,
+        size: usize,
+    },
+}
+
+impl StoredResource {
+    fn contents(&self) -> ResourceContents {
+        let uri = self.uri.clone();
+        let mime_type = Some(self.mime_type.clone());
+        match &self.contents {
+            Contents::Text(text) => ResourceContents::TextResourceContents {
+                uri,
+                mime_type,
+                text: text.clone(),
+                meta: None,
+            },
+            Contents::Blob { base64, .. } => ResourceContents::BlobResourceContents {
+                uri,
+                mime_type,
+                blob: base64.clone(),
+                meta: None,
+            },
+        }
+    }
+
+    fn size(&self) -> usize {
+        match &self.contents {
+            Contents::Text(text) => text.len(),
+            Contents::Blob { size, .. } => *size,
+        }
+    }
+}
+
+impl SessionResources {
+    /// Keep `text` under `uri` (replacing an earlier one) and return it as
+    /// resource contents, ready to embed in a tool result.
+    pub fn insert(&self, uri: &str, name: &str, mime_type: &str, text: String) -> ResourceContents {
+        self.store(uri, name, mime_type, Contents::Text(text))
+    }
+
+    /// Like [`Self::insert`], for binary contents.
+    pub fn insert_blob(
+        &self,
+        uri: &str,
+        name: &str,
+        mime_type: &str,
+        bytes: &[u8],
+    ) -> ResourceContents {
+        let contents = Contents::Blob {
+            base64: BASE64_STANDARD.encode(bytes),
+            size: bytes.len(),
+        };
+        self.store(uri, name, mime_type, contents)
+    }
+
+    fn store(
+        &self,
+        uri: &str,
+        name: &str,
+        mime_type: &str,
+        contents: Contents,
+    ) -> ResourceContents {
+        let mut entries = self.entries.lock().expect("resources lock poisoned");
+        entries.retain(|entry| entry.uri != uri);
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+        let entry = StoredResource {
+            uri: uri.to_string(),
+            name: name.to_string(),
+            mime_type: mime_type.to_string(),
+            contents,
+        };
+        let contents = entry.contents();
+        entries.push_back(entry);
+        contents
+    }
+
+    pub fn get(&self, uri: &str) -> Option<ResourceContents> {
+        let entries = self.entries.lock().expect("resources lock poisoned");
+        entries
+            .iter()
+            .find(|entry| entry.uri == uri)
+            .map(StoredResource::contents)
+    }
+
+    /// Newest first.
+    pub fn list(&self) -> Vec<Resource> {
+        let entries = self.entries.lock().expect("resources lock poisoned");
+        entries
+            .iter()
+            .rev()
+            .map(|entry| {
+                let mut resource = RawResource::new(&entry.uri, &entry.name);
+                resource.mime_type = Some(entry.mime_type.clone());
+                resource.size = u32::try_from(entry.size()).ok();
+                resource.no_annotation()
+            })
+            .collect()
+    }
+}
+
+/// `weather://forecast/<location>/<first date>?days=<n>`, with the location
+/// lowercased and non-alphanumeric runs turned into `-`.
+pub fn forecast_uri(location: &str, forecast: &ForecastResponse) -> String {
+    format!(
+        "weather://forecast/{}/{}?days={}",
+        slug(location),
+        first_date(&forecast.items),
+        forecast.items.len()
+    )
+}
+
+/// `weather://history/<location>/<first date>.<csv|parquet>?days=<n>`, with
+/// the location as in [`forecast_uri`].
+pub fn history_uri(location: &str, days: &[Forecast], format: ExportFormat) -> String {
+    format!(
+        "weather://history/{}/{}.{}?days={}",
+        slug(location),
+        first_date(days),
+        format.extension(),
+        days.len()
+    )
+}
+
+/// `location` lowercased, with non-alphanumeric runs turned into `-`.
+pub fn slug(location: &str) -> String {
+    let mut slug = String::new();
+    for character in location.trim().to_lowercase().chars() {
+        if character.is_alphanumeric() {
+            slug.push(character);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+fn first_date(days: &[Forecast]) -> &str {
+    days.first().map(|day| day.date.as_str()).unwrap_or("none")
+}
+
+/// One row per day, with a header. Text fields are quoted where needed.
+pub fn forecast_csv(forecast: &ForecastResponse) -> String {
+    let unit = match forecast.units {
+        Units::Metric => "c",
+        Units::Imperial => "f",
+    };
+    let mut csv =
+        format!("date,high_{unit},low_{unit},condition,precipitation_chance,sunrise,sunset\n");
+    for day in &forecast.items {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            day.date,
+            day.high,
+            day.low,
+            csv_field(&day.condition),
+            day.precipitation_chance,
+            day.sunrise.as_deref().unwrap_or(""),
+            day.sunset.as_deref().unwrap_or(""),
+        ));
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
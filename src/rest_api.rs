@@ -0,0 +1,292 @@
+use axum::extract::{Path, Query, State};
+use axum::http::header::{ACCEPT_LANGUAGE, CONTENT_DISPOSITION, CONTENT_LANGUAGE, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{middleware, Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::app_state::AppState;
+use crate::history_export::{self, ExportFormat};
+use crate::i18n::Lang;
+use crate::providers::ProviderError;
+use crate::request_id;
+use crate::request_signing::{self, RequestSigning};
+use crate::resources;
+use crate::trace_utils::{enrich_span, is_recorded, RequestAttribution};
+use crate::weather_tools::{
+    default_days, default_history_days, Forecast, ForecastResponse, Units, Weather,
+};
+use rmcp_otel::TraceParentContext;
+
+/// Plain REST access to the same service the MCP tools use, so both paths
+/// can be compared side by side in the trace backend.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Weather Assistant REST API"),
+    paths(get_weather, get_forecast, export_history),
+    components(schemas(Weather, Forecast, ForecastResponse, Units, ExportFormat, ApiErrorBody))
+)]
+pub struct ApiDoc;
+
+/// REST routes; with `signing`, the weather routes require a valid
+/// `x-signature` while the OpenAPI document stays public.
+pub fn router(state: AppState, signing: Option<Arc<RequestSigning>>) -> Router {
+    let mut weather = Router::new()
+        .route("/api/weather/:city", get(get_weather))
+        .route("/api/forecast/:city", get(get_forecast))
+        .route("/api/history/:city/export", get(export_history));
+    if let Some(signing) = signing {
+        weather = weather.route_layer(middleware::from_fn_with_state(
+            signing,
+            request_signing::verify_signature,
+        ));
+    }
+    weather
+        .route("/api/openapi.json", get(openapi_document))
+        .with_state(state)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ForecastQuery {
+    /// Number of days to forecast (1-7, default 3)
+    days: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct HistoryExportQuery {
+    /// Number of past days to export (1-30, default 7)
+    days: Option<u32>,
+    /// File format, csv by default
+    format: Option<ExportFormat>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiErrorBody {
+    error: String,
+    /// `x-request-id` of the failed request, to quote when reporting it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl ApiErrorBody {
+    fn new(error: String) -> Self {
+        Self {
+            error,
+            request_id: request_id::current(),
+        }
+    }
+}
+
+struct ApiError(ProviderError);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self.0 {
+            ProviderError::LocationNotFound(_) | ProviderError::NoData(_) => StatusCode::NOT_FOUND,
+            ProviderError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ProviderError::LocationTooLong => StatusCode::BAD_REQUEST,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+        let body = ApiErrorBody::new(self.0.to_string());
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Parent the handler span on the context extracted by `TracePropagationLayer`
+/// and record the caller's session and user.
+fn attach_parent(
+    parent: Option<Extension<TraceParentContext>>,
+    attribution: Option<Extension<RequestAttribution>>,
+) {
+    let span = tracing::Span::current();
+    if let Some(Extension(TraceParentContext(context))) = parent {
+        let _ = span.set_parent(context);
+    }
+    if let Some(Extension(attribution)) = attribution {
+        enrich_span(&span, &attribution);
+    }
+}
+
+/// JSON body with the `Content-Language` it was translated to.
+type Localized<T> = ([(HeaderName, &'static str); 1], Json<T>);
+
+/// Language negotiated from `Accept-Language`; English without one.
+fn requested_lang(headers: &HeaderMap) -> Lang {
+    headers
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Lang::negotiate)
+        .unwrap_or_default()
+}
+
+fn record_output<T: Serialize>(output: &T) {
+    let span = tracing::Span::current();
+    if is_recorded(&span) {
+        let json = serde_json::json!(output);
+        span.record("output", tracing::field::display(&json));
+    }
+}
+
+/// Get current weather for a city
+#[utoipa::path(
+    get,
+    path = "/api/weather/{city}",
+    params(
+        ("city" = String, Path, description = "City name to get weather for"),
+        ("Accept-Language" = Option<String>, Header, description = "Language of condition names: en (default), de, fr or es")
+    ),
+    responses(
+        (status = 200, description = "Current conditions", body = Weather),
+        (status = 404, description = "Unknown location", body = ApiErrorBody),
+        (status = 502, description = "Upstream provider failure", body = ApiErrorBody)
+    )
+)]
+#[instrument(name = "rest.get_weather", skip(state, parent, attribution, headers), fields(
+    output = tracing::field::Empty,
+    cache.state = tracing::field::Empty
+))]
+async fn get_weather(
+    State(state): State<AppState>,
+    parent: Option<Extension<TraceParentContext>>,
+    attribution: Option<Extension<RequestAttribution>>,
+    headers: HeaderMap,
+    Path(city): Path<String>,
+) -> Result<Localized<Weather>, ApiError> {
+    attach_parent(parent, attribution);
+
+    let lang = requested_lang(&headers);
+    let weather = state
+        .current_weather(&city)
+        .await
+        .map_err(ApiError)?
+        .localized(lang);
+    record_output(&weather);
+    Ok(([(CONTENT_LANGUAGE, lang.code())], Json(weather)))
+}
+
+/// Get a daily forecast for a city
+#[utoipa::path(
+    get,
+    path = "/api/forecast/{city}",
+    params(
+        ("city" = String, Path, description = "City name for forecast"),
+        ForecastQuery,
+        ("Accept-Language" = Option<String>, Header, description = "Language of condition names: en (default), de, fr or es")
+    ),
+    responses(
+        (status = 200, description = "Daily forecast", body = ForecastResponse),
+        (status = 404, description = "Unknown location", body = ApiErrorBody),
+        (status = 502, description = "Upstream provider failure", body = ApiErrorBody)
+    )
+)]
+#[instrument(name = "rest.get_forecast", skip(state, parent, attribution, headers, query), fields(
+    days = query.days,
+    output = tracing::field::Empty,
+    cache.state = tracing::field::Empty
+))]
+async fn get_forecast(
+    State(state): State<AppState>,
+    parent: Option<Extension<TraceParentContext>>,
+    attribution: Option<Extension<RequestAttribution>>,
+    headers: HeaderMap,
+    Path(city): Path<String>,
+    Query(query): Query<ForecastQuery>,
+) -> Result<Localized<ForecastResponse>, ApiError> {
+    attach_parent(parent, attribution);
+
+    let lang = requested_lang(&headers);
+    let days = query.days.unwrap_or_else(default_days);
+    let items = state.forecast(&city, days).await.map_err(ApiError)?;
+    let response = ForecastResponse::in_units(items, Units::Metric).localized(lang);
+    record_output(&response);
+    Ok(([(CONTENT_LANGUAGE, lang.code())], Json(response)))
+}
+
+/// Download the daily weather of the past days for a city as a file
+#[utoipa::path(
+    get,
+    path = "/api/history/{city}/export",
+    params(
+        ("city" = String, Path, description = "City name to export history for"),
+        HistoryExportQuery
+    ),
+    responses(
+        (status = 200, description = "CSV (text/csv) or Parquet (application/vnd.apache.parquet) attachment, one row per day, oldest first"),
+        (status = 404, description = "Unknown location", body = ApiErrorBody),
+        (status = 500, description = "The file could not be written", body = ApiErrorBody),
+        (status = 502, description = "Upstream provider failure", body = ApiErrorBody)
+    )
+)]
+#[instrument(name = "rest.export_history", skip(state, parent, attribution, query), fields(
+    days = query.days,
+    format = ?query.format,
+    output = tracing::field::Empty,
+    otel.status_code = tracing::field::Empty,
+    cache.state = tracing::field::Empty
+))]
+async fn export_history(
+    State(state): State<AppState>,
+    parent: Option<Extension<TraceParentContext>>,
+    attribution: Option<Extension<RequestAttribution>>,
+    Path(city): Path<String>,
+    Query(query): Query<HistoryExportQuery>,
+) -> Result<Response, ApiError> {
+    attach_parent(parent, attribution);
+
+    let format = query.format.unwrap_or_default();
+    let days = query.days.unwrap_or_else(default_history_days);
+    let history = state.history(&city, days).await.map_err(ApiError)?;
+    let filename = format!(
+        "{}-history-{}.{}",
+        resources::slug(&city),
+        history
+            .first()
+            .map(|day| day.date.as_str())
+            .unwrap_or("none"),
+        format.extension()
+    );
+    let days = history.len();
+    let file = match format {
+        ExportFormat::Csv => history_export::csv(history).into_bytes(),
+        ExportFormat::Parquet => {
+            let written = state
+                .cpu_pool
+                .run("history_export.parquet", move || {
+                    history_export::parquet(&history)
+                })
+                .await;
+            match written {
+                Ok(file) => file,
+                Err(error) => {
+                    tracing::Span::current().record("otel.status_code", "ERROR");
+                    let body =
+                        ApiErrorBody::new(format!("could not write the Parquet file: {error}"));
+                    return Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response());
+                }
+            }
+        }
+    };
+    record_output(&serde_json::json!({
+        "format": format,
+        "days": days,
+        "bytes": file.len(),
+    }));
+    let disposition = format!("attachment; filename=\"{filename}\"");
+    Ok((
+        [
+            (CONTENT_TYPE, format.mime_type().to_string()),
+            (CONTENT_DISPOSITION, disposition),
+        ],
+        file,
+    )
+        .into_response())
+}
+
+async fn openapi_document() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
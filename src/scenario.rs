@@ -0,0 +1,192 @@
+//! Scripted demo sessions: a YAML file of tool calls, pauses and injected
+//! provider faults, run by an in-process client so every run produces the
+//! same trace shape in Langfuse.
+//!
+//! ```yaml
+//! name: rainy-day
+//! tags: [demo]
+//! steps:
+//!   - call: get_weather
+//!     args: { location: Paris }
+//!   - sleep: 500ms
+//!   - call: get_forecast
+//!     args: { location: Paris, days: 3 }
+//!     fault: error   # latency, error or timeout
+//! ```
+
+use anyhow::{bail, Context, Result};
+use opentelemetry::trace::TraceContextExt;
+use rmcp::model::{CallToolRequestParam, JsonObject};
+use rmcp::ServiceExt;
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::app_state::AppState;
+use crate::config::{Config, LatencySpec};
+use crate::providers::chaos::{self, Fault};
+use crate::trace_utils::{self, enrich_span, RequestAttribution};
+use crate::weather_tools::WeatherService;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Langfuse trace name of the run.
+    pub name: String,
+    /// Trace tags, on top of `LANGFUSE_TRACE_TAGS`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Recorded as the trace's user.
+    #[serde(default)]
+    pub user: Option<String>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Step {
+    Call {
+        call: String,
+        #[serde(default)]
+        args: Option<JsonObject>,
+        /// Fault forced on every provider call the tool makes.
+        #[serde(default)]
+        fault: Option<Fault>,
+    },
+    Sleep {
+        /// Fixed pause such as `500ms` or `1.5s`.
+        sleep: String,
+    },
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read scenario {}", path.display()))?;
+        let scenario: Self = serde_yaml::from_str(&text)
+            .with_context(|| format!("invalid scenario {}", path.display()))?;
+        for (index, step) in scenario.steps.iter().enumerate() {
+            if let Step::Sleep { sleep } = step {
+                pause(sleep).with_context(|| format!("step {}", index + 1))?;
+            }
+        }
+        Ok(scenario)
+    }
+
+    fn has_faults(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| matches!(step, Step::Call { fault: Some(_), .. }))
+    }
+}
+
+fn pause(value: &str) -> Result<Duration> {
+    let spec: LatencySpec = value.parse()?;
+    if !spec.jitter.is_zero() {
+        bail!("sleep takes a fixed duration, got '{value}'");
+    }
+    Ok(spec.base)
+}
+
+/// Run `scenario` against an in-process server built from `config`, under
+/// one `scenario` root span. Tracing must already be initialized.
+///
+/// Returns one line per step and the trace id.
+pub async fn run(scenario: &Scenario, mut config: Config) -> Result<String> {
+    config.trace_labels.name = Some(scenario.name.clone());
+    for tag in &scenario.tags {
+        if !config.trace_labels.tags.contains(tag) {
+            config.trace_labels.tags.push(tag.clone());
+        }
+    }
+    trace_utils::set_trace_labels(config.trace_labels.clone());
+    // Faults need the chaos layer; random ones stay off unless configured
+    if scenario.has_faults() && !config.chaos.enabled {
+        config.chaos.enabled = true;
+        config.chaos.latency_rate = 0.0;
+        config.chaos.error_rate = 0.0;
+        config.chaos.timeout_rate = 0.0;
+    }
+
+    let root = tracing::info_span!(
+        "scenario",
+        scenario.name = %scenario.name,
+        scenario.steps = scenario.steps.len(),
+    );
+    enrich_span(
+        &root,
+        &RequestAttribution {
+            user_id: scenario.user.clone(),
+            ..RequestAttribution::default()
+        },
+    );
+    let trace_id = root.context().span().span_context().trace_id();
+
+    let service = WeatherService::new(AppState::new(config)?).with_trace_parent(root.context());
+    let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+    let server = tokio::spawn(
+        async move {
+            if let Ok(server) = service.serve(server_io).await {
+                let _ = server.waiting().await;
+            }
+        }
+        .instrument(root.clone()),
+    );
+
+    let mut out = String::new();
+    let outcome = async {
+        let client = ().serve(client_io).await.context("failed to connect to the server")?;
+        for (index, step) in scenario.steps.iter().enumerate() {
+            let number = index + 1;
+            match step {
+                Step::Sleep { sleep } => {
+                    let duration = pause(sleep)?;
+                    tokio::time::sleep(duration)
+                        .instrument(tracing::info_span!(
+                            "scenario.sleep",
+                            step = number,
+                            sleep_ms = duration.as_millis() as u64
+                        ))
+                        .await;
+                    let _ = writeln!(out, "{number:>3}. sleep {sleep}");
+                }
+                Step::Call { call, args, fault } => {
+                    chaos::force(*fault);
+                    let started = Instant::now();
+                    let result = client
+                        .call_tool(CallToolRequestParam {
+                            name: call.clone().into(),
+                            arguments: args.clone(),
+                        })
+                        .await;
+                    chaos::force(None);
+                    let outcome = match result {
+                        Ok(result) if result.is_error == Some(true) => "error".to_string(),
+                        Ok(_) => "ok".to_string(),
+                        Err(error) => format!("failed: {error}"),
+                    };
+                    let fault = fault.map(|fault| format!(" [fault: {}]", fault.as_str()));
+                    let _ = writeln!(
+                        out,
+                        "{number:>3}. {call}{} -> {outcome} ({:.1} ms)",
+                        fault.unwrap_or_default(),
+                        started.elapsed().as_secs_f64() * 1_000.0
+                    );
+                }
+            }
+        }
+        client.cancel().await?;
+        anyhow::Ok(())
+    }
+    .instrument(root)
+    .await;
+    // The session span ends when the server's service is dropped
+    let _ = server.await;
+    outcome?;
+
+    let _ = writeln!(out, "\ntrace id: {trace_id}");
+    Ok(out)
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use rmcp::model::Tool;
+use std::fmt::Write;
+
+use crate::cli::SchemaFormat;
+
+/// Render tool definitions for docs generation or client pre-registration.
+pub fn render(tools: &[Tool], format: SchemaFormat) -> Result<String> {
+    match format {
+        SchemaFormat::Json => Ok(serde_json::to_string_pretty(tools)?),
+        SchemaFormat::Markdown => render_markdown(tools),
+    }
+}
+
+/// Server instructions for `initialize`: one entry per tool with its
+/// description and arguments, so the text follows the registered tools.
+pub fn instructions(tools: &[Tool]) -> String {
+    let mut out = String::from("This server provides weather tools:\n");
+    for tool in tools {
+        out.push_str(&format!("\n- {}", tool.name));
+        if let Some(description) = &tool.description {
+            out.push_str(&format!(": {description}"));
+        }
+        let arguments = arguments(tool);
+        if !arguments.is_empty() {
+            out.push_str(&format!("\n  Arguments: {}", arguments.join("; ")));
+        }
+    }
+    out.push('\n');
+    out
+}
+
+/// `name (required|optional): description` for each input property.
+fn arguments(tool: &Tool) -> Vec<String> {
+    let required: Vec<&str> = tool
+        .input_schema
+        .get("required")
+        .and_then(|required| required.as_array())
+        .map(|names| names.iter().filter_map(|name| name.as_str()).collect())
+        .unwrap_or_default();
+    let Some(properties) = tool
+        .input_schema
+        .get("properties")
+        .and_then(|properties| properties.as_object())
+    else {
+        return Vec::new();
+    };
+    properties
+        .iter()
+        .map(|(name, property)| {
+            let presence = if required.contains(&name.as_str()) {
+                "required"
+            } else {
+                "optional"
+            };
+            match property.get("description").and_then(|value| value.as_str()) {
+                Some(description) => format!("{name} ({presence}): {description}"),
+                None => format!("{name} ({presence})"),
+            }
+        })
+        .collect()
+}
+
+fn render_markdown(tools: &[Tool]) -> Result<String> {
+    let mut out = String::from("# Tools\n");
+
+    for tool in tools {
+        writeln!(out, "\n## `{}`\n", tool.name)?;
+        if let Some(description) = &tool.description {
+            writeln!(out, "{description}\n")?;
+        }
+
+        writeln!(out, "### Input schema\n")?;
+        writeln!(
+            out,
+            "```json\n{}\n```",
+            serde_json::to_string_pretty(&tool.input_schema)?
+        )?;
+
+        if let Some(output_schema) = &tool.output_schema {
+            writeln!(out, "\n### Output schema\n")?;
+            writeln!(
+                out,
+                "```json\n{}\n```",
+                serde_json::to_string_pretty(output_schema)?
+            )?;
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,253 @@
+//! Credentials read from files (Docker secrets) or HashiCorp Vault instead of
+//! plain environment variables.
+//!
+//! Secrets are resolved once at startup, before the server's runtime starts,
+//! and placed in the process environment, so configuration and the Langfuse
+//! exporter read them like any other variable. A variable set directly is
+//! used as is (setting its `<NAME>_FILE` too is an error); otherwise
+//! `<NAME>_FILE` wins over Vault.
+
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future::BoxFuture;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+use crate::config::env_string;
+
+/// Variables holding credentials, resolved by [`load`].
+pub const SECRET_VARS: &[&str] = &[
+    "LANGFUSE_PUBLIC_KEY",
+    "LANGFUSE_SECRET_KEY",
+    "SENTRY_DSN",
+    "ADMIN_TOKEN",
+    "RBAC_JWT_SECRET",
+    "REST_SIGNING_SECRET",
+    "LANGFUSE_TENANTS",
+    "TENANT_API_KEYS",
+    "WEBHOOK_SIGNING_SECRET",
+];
+
+/// A source of credentials by variable name.
+pub trait SecretsProvider: Send + Sync {
+    /// Short identifier for error messages.
+    fn name(&self) -> &'static str;
+
+    /// The secret for `var`, or `None` when this source does not have it.
+    fn get<'a>(&'a self, var: &'a str) -> BoxFuture<'a, Result<Option<String>>>;
+}
+
+/// Reads `<NAME>_FILE`, e.g. `LANGFUSE_SECRET_KEY_FILE=/run/secrets/langfuse`.
+pub struct FileSecrets;
+
+impl SecretsProvider for FileSecrets {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn get<'a>(&'a self, var: &'a str) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            let Some(path) = env_string(&format!("{var}_FILE")) else {
+                return Ok(None);
+            };
+            let value = tokio::fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("failed to read {var}_FILE ({path})"))?;
+            // Secret files usually end with a newline
+            Ok(Some(value.trim_end_matches(['\r', '\n']).to_string()))
+        })
+    }
+}
+
+/// Reads one Vault secret (KV v1 or v2) whose keys are variable names.
+pub struct VaultSecrets {
+    client: reqwest::Client,
+    url: String,
+    token: String,
+    secret: OnceCell<HashMap<String, String>>,
+}
+
+impl VaultSecrets {
+    /// From `VAULT_ADDR`, `VAULT_SECRET_PATH` and `VAULT_TOKEN` (or
+    /// `VAULT_TOKEN_FILE`); `None` unless address and path are set.
+    pub async fn from_env() -> Result<Option<Self>> {
+        let (Some(addr), Some(path)) = (env_string("VAULT_ADDR"), env_string("VAULT_SECRET_PATH"))
+        else {
+            return Ok(None);
+        };
+        let token = match env_string("VAULT_TOKEN") {
+            Some(token) => token,
+            None => FileSecrets
+                .get("VAULT_TOKEN")
+                .await?
+                .ok_or_else(|| anyhow!("VAULT_SECRET_PATH is set but VAULT_TOKEN is not"))?,
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Some(Self {
+            client,
+            url: format!(
+                "{}/v1/{}",
+                addr.trim_end_matches('/'),
+                path.trim_start_matches('/')
+            ),
+            token,
+            secret: OnceCell::new(),
+        }))
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, String>> {
+        let response = self
+            .client
+            .get(&self.url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .with_context(|| format!("failed to reach Vault at {}", self.url))?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!("Vault returned {status} for {}", self.url);
+        }
+        let body: Value = response.json().await?;
+        // KV v2 nests the values one level deeper than KV v1
+        let data = body
+            .pointer("/data/data")
+            .or_else(|| body.get("data"))
+            .and_then(Value::as_object)
+            .ok_or_else(|| anyhow!("Vault response for {} has no data", self.url))?;
+        Ok(data
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+            .collect())
+    }
+}
+
+impl SecretsProvider for VaultSecrets {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    fn get<'a>(&'a self, var: &'a str) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            let secret = self.secret.get_or_try_init(|| self.fetch()).await?;
+            Ok(secret.get(var).cloned())
+        })
+    }
+}
+
+/// Resolve every unset [`SECRET_VARS`] entry from the configured providers,
+/// returning the variables to set before reading the configuration.
+pub async fn load() -> Result<Vec<(&'static str, String)>> {
+    let mut providers: Vec<Box<dyn SecretsProvider>> = vec![Box::new(FileSecrets)];
+    if let Some(vault) = VaultSecrets::from_env().await? {
+        providers.push(Box::new(vault));
+    }
+    resolve(&providers).await
+}
+
+/// Values of the unset [`SECRET_VARS`], each from the first of `providers`
+/// that has it.
+async fn resolve(providers: &[Box<dyn SecretsProvider>]) -> Result<Vec<(&'static str, String)>> {
+    let mut resolved = Vec::new();
+    for var in SECRET_VARS {
+        if env_string(var).is_some() {
+            if env_string(&format!("{var}_FILE")).is_some() {
+                bail!("set either {var} or {var}_FILE, not both");
+            }
+            continue;
+        }
+        for provider in providers {
+            let value = provider
+                .get(var)
+                .await
+                .with_context(|| format!("failed to load {var} from {}", provider.name()))?;
+            if let Some(value) = value {
+                resolved.push((*var, value));
+                break;
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::with_vars;
+
+    /// Has every variable, as `<provider>:<var>`.
+    struct Everything(&'static str);
+
+    impl SecretsProvider for Everything {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn get<'a>(&'a self, var: &'a str) -> BoxFuture<'a, Result<Option<String>>> {
+            Box::pin(async move { Ok(Some(format!("{}:{var}", self.0))) })
+        }
+    }
+
+    fn resolve_with(
+        vars: &[(&str, &str)],
+        providers: Vec<Box<dyn SecretsProvider>>,
+    ) -> Result<HashMap<&'static str, String>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        with_vars(vars.iter().copied(), || {
+            runtime.block_on(resolve(&providers))
+        })
+        .map(|resolved| resolved.into_iter().collect())
+    }
+
+    #[test]
+    fn direct_variables_win_over_files_and_vault() {
+        let file = std::env::temp_dir().join(format!("admin-token-{}", std::process::id()));
+        std::fs::write(&file, "from-file\r\n").unwrap();
+        let path = file.to_str().unwrap();
+
+        let resolved = resolve_with(
+            &[("SENTRY_DSN", "direct"), ("ADMIN_TOKEN_FILE", path)],
+            vec![Box::new(FileSecrets), Box::new(Everything("vault"))],
+        );
+        std::fs::remove_file(&file).unwrap();
+        let resolved = resolved.unwrap();
+
+        // Set directly, so left alone
+        assert!(!resolved.contains_key("SENTRY_DSN"));
+        // The file wins over Vault, without its line ending
+        assert_eq!(resolved["ADMIN_TOKEN"], "from-file");
+        assert_eq!(resolved["RBAC_JWT_SECRET"], "vault:RBAC_JWT_SECRET");
+        assert_eq!(resolved.len(), SECRET_VARS.len() - 1);
+    }
+
+    #[test]
+    fn variables_and_files_are_exclusive() {
+        let error = resolve_with(
+            &[
+                ("ADMIN_TOKEN", "direct"),
+                ("ADMIN_TOKEN_FILE", "/run/secrets/admin"),
+            ],
+            vec![Box::new(FileSecrets)],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "set either ADMIN_TOKEN or ADMIN_TOKEN_FILE, not both"
+        );
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let error = resolve_with(
+            &[("ADMIN_TOKEN_FILE", "/nonexistent/admin")],
+            vec![Box::new(FileSecrets)],
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "failed to load ADMIN_TOKEN from file");
+    }
+}
@@ -0,0 +1,258 @@
+//! Startup self-test (`SELF_TEST=warn|fail`): before the server accepts
+//! traffic, every registered tool is called once with canned arguments
+//! through an in-process client, against the mock provider.
+//!
+//! A tool passes when its result is not an error, its structured content
+//! matches its output schema, and its span ended in the self-test's trace.
+//! Those spans are collected by [`SpanProbe`], which is registered on the
+//! tracer provider when the self-test is enabled; a `RUST_LOG` filtering out
+//! the tool spans fails the test.
+
+use anyhow::{Context as _, Result};
+use opentelemetry::trace::{TraceContextExt, TraceId};
+use opentelemetry::Context;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{Span, SpanData, SpanProcessor};
+use rmcp::model::{CallToolRequestParam, CallToolResult, Tool};
+use rmcp::ServiceExt;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::app_state::AppState;
+use crate::config::{ChaosConfig, Config, ProviderKind};
+use crate::weather_tools::WeatherService;
+
+/// Location the weather tools are asked about.
+const LOCATION: &str = "London";
+
+/// Never contacted: the refresh loop that delivers alerts is not running.
+const WEBHOOK_URL: &str = "http://127.0.0.1:9/self-test";
+
+/// Trace being probed and the names of its ended spans.
+static PROBE: Mutex<Option<(TraceId, HashSet<String>)>> = Mutex::new(None);
+
+/// Span processor recording the names of the spans that end in the
+/// self-test's trace. Does nothing outside the self-test.
+#[derive(Debug)]
+pub struct SpanProbe;
+
+impl SpanProcessor for SpanProbe {
+    fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+    fn on_end(&self, span: SpanData) {
+        if let Some((trace_id, names)) = &mut *PROBE.lock().expect("span probe lock poisoned") {
+            if span.span_context.trace_id() == *trace_id {
+                names.insert(span.name.into_owned());
+            }
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+/// How the self-test calls a tool.
+enum Plan {
+    Call(Value),
+    Skip(&'static str),
+}
+
+/// Canned arguments by tool name. Tools are called in name order, so
+/// `add_favorite` and `register_webhook` run before the tools undoing them.
+fn plan(tool: &str) -> Plan {
+    Plan::Call(match tool {
+        "get_forecast" | "get_uv_index" | "get_pollen_forecast" => {
+            json!({ "location": LOCATION, "days": 3 })
+        }
+        "export_history" => json!({ "location": LOCATION, "days": 3 }),
+        "create_watch" => json!({
+            "location": LOCATION,
+            "condition": "temperature_above",
+            "threshold": 30,
+        }),
+        "register_webhook" | "unregister_webhook" => {
+            json!({ "url": WEBHOOK_URL, "location": LOCATION })
+        }
+        "set_preference" => json!({ "units": "metric" }),
+        "rate_response" => return Plan::Skip("would post a score to Langfuse"),
+        "get_favorites_weather"
+        | "get_preferences"
+        | "get_server_stats"
+        | "list_favorites"
+        | "list_watches" => json!({}),
+        _ => json!({ "location": LOCATION }),
+    })
+}
+
+/// Outcome of the self-test, one entry per tool.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub passed: Vec<String>,
+    pub skipped: Vec<(String, &'static str)>,
+    pub failed: Vec<(String, String)>,
+    pub duration: Duration,
+}
+
+impl Report {
+    /// Log the outcome: a line per skipped or failed tool, then a summary.
+    pub fn log(&self) {
+        for (tool, reason) in &self.skipped {
+            tracing::info!(tool, reason, "Self-test skipped tool");
+        }
+        for (tool, reason) in &self.failed {
+            tracing::warn!(tool, reason, "Self-test failed");
+        }
+        let summary = if self.failed.is_empty() {
+            "Self-test passed"
+        } else {
+            "Self-test found failing tools"
+        };
+        tracing::info!(
+            passed = self.passed.len(),
+            skipped = self.skipped.len(),
+            failed = self.failed.len(),
+            duration = ?self.duration,
+            "{summary}"
+        );
+    }
+}
+
+/// Call every tool of a server built from `config`, switched to the mock
+/// provider and without the layers that add faults, delays or side effects.
+/// Tracing must already be initialized.
+pub async fn run(config: &Config) -> Result<Report> {
+    let started = Instant::now();
+    let mut config = config.clone();
+    config.provider = ProviderKind::Mock;
+    config.chaos = ChaosConfig {
+        enabled: false,
+        ..config.chaos
+    };
+    config.tool_latency.clear();
+    config.concurrency = Default::default();
+    config.slo = Default::default();
+    config.rbac = Default::default();
+    config.audit = Default::default();
+    config.preferences_path = None;
+    config.recording_path = None;
+    config.feature_flags.path = None;
+    config.feature_flags.url = None;
+    config.webhooks.signing_secret = Some("self-test".to_string());
+
+    let tools = WeatherService::tool_definitions();
+    let root = tracing::info_span!(parent: None, "self_test", self_test.tools = tools.len());
+    let trace_id = root.context().span().span_context().trace_id();
+    *PROBE.lock().expect("span probe lock poisoned") = Some((trace_id, HashSet::new()));
+
+    let service = WeatherService::new(AppState::new(config)?).with_trace_parent(root.context());
+    let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        if let Ok(server) = service.serve(server_io).await {
+            let _ = server.waiting().await;
+        }
+    });
+    let client = ().serve(client_io).await.context("failed to connect to the server")?;
+
+    let mut report = Report::default();
+    for tool in &tools {
+        let name = tool.name.to_string();
+        let arguments = match plan(&name) {
+            Plan::Call(Value::Object(arguments)) => arguments,
+            Plan::Call(_) => unreachable!("canned arguments are objects"),
+            Plan::Skip(reason) => {
+                report.skipped.push((name, reason));
+                continue;
+            }
+        };
+        let result = client
+            .call_tool(CallToolRequestParam {
+                name: tool.name.clone(),
+                arguments: Some(arguments),
+            })
+            .await;
+        let outcome = match result {
+            Ok(result) => check(tool, &result, trace_id),
+            Err(error) => Err(error.to_string()),
+        };
+        match outcome {
+            Ok(()) => report.passed.push(name),
+            Err(reason) => report.failed.push((name, reason)),
+        }
+    }
+    let _ = client.cancel().await;
+    *PROBE.lock().expect("span probe lock poisoned") = None;
+
+    report.duration = started.elapsed();
+    Ok(report)
+}
+
+fn check(tool: &Tool, result: &CallToolResult, trace_id: TraceId) -> Result<(), String> {
+    if result.is_error == Some(true) {
+        let message = result
+            .content
+            .iter()
+            .find_map(|content| content.as_text())
+            .map(|text| text.text.clone())
+            .unwrap_or_else(|| "error result".to_string());
+        return Err(message);
+    }
+
+    if let Some(schema) = &tool.output_schema {
+        let content = result
+            .structured_content
+            .as_ref()
+            .ok_or("no structured content, though the tool has an output schema")?;
+        let validator = jsonschema::validator_for(&Value::Object(schema.as_ref().clone()))
+            .map_err(|error| format!("invalid output schema: {error}"))?;
+        if let Err(error) = validator.validate(content) {
+            return Err(format!(
+                "output does not match the schema at '{}': {error}",
+                error.instance_path
+            ));
+        }
+    }
+
+    if trace_id == TraceId::INVALID {
+        return Err("no span: is RUST_LOG filtering out info spans?".to_string());
+    }
+    let probe = PROBE.lock().expect("span probe lock poisoned");
+    let produced = probe
+        .as_ref()
+        .is_some_and(|(_, names)| names.contains(tool.name.as_ref()));
+    if !produced {
+        return Err(format!("no '{}' span was produced", tool.name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::handler::server::tool::cached_schema_for_type;
+    use rmcp::model::Content;
+
+    #[test]
+    fn output_not_matching_the_schema_fails_with_its_path() {
+        let mut tool = WeatherService::tool_definitions()
+            .into_iter()
+            .find(|tool| tool.name == "get_weather")
+            .unwrap();
+        tool.output_schema = Some(cached_schema_for_type::<crate::weather_tools::Weather>());
+        let mut result = CallToolResult::success(vec![Content::text("{}")]);
+        result.structured_content = Some(json!({ "location": "London", "temperature": "warm" }));
+
+        let error = check(&tool, &result, TraceId::INVALID).unwrap_err();
+        assert!(
+            error.starts_with("output does not match the schema at '"),
+            "{error}"
+        );
+    }
+}
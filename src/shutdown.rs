@@ -0,0 +1,161 @@
+//! Graceful shutdown: the signals that start it, and the hooks run once the
+//! HTTP server has stopped.
+//!
+//! SIGTERM is handled as well as Ctrl+C: it is what `docker stop` and
+//! Kubernetes send, and a process running as PID 1 in a container has no
+//! default handler for it, so without one it is only stopped by the SIGKILL
+//! that follows the grace period.
+//!
+//! Hooks run one after the other in registration order, each under its own
+//! timeout, in a `shutdown.hook` span under one `shutdown` root span. A hook
+//! that fails or times out is logged and the next one runs. The trace
+//! pipeline is flushed after the hooks so their spans are exported.
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{field::Empty, Instrument};
+
+/// Wait for Ctrl+C or, on Unix, SIGTERM. Returns the signal's name.
+pub async fn signal() -> &'static str {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            tracing::warn!("Failed to listen for Ctrl+C; forcing shutdown");
+        }
+        "SIGINT"
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(error) => {
+                tracing::warn!(%error, "Failed to listen for SIGTERM; only Ctrl+C stops the server");
+                std::future::pending::<()>().await;
+            }
+        }
+        "SIGTERM"
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<&'static str>();
+
+    tokio::select! {
+        signal = ctrl_c => signal,
+        signal = terminate => signal,
+    }
+}
+
+type HookFn = Box<dyn FnOnce() -> BoxFuture<'static, Result<()>> + Send>;
+
+struct Hook {
+    name: &'static str,
+    timeout: Duration,
+    run: HookFn,
+}
+
+/// Async tasks to run at shutdown, shared by every part of the server that
+/// holds buffered work.
+#[derive(Clone, Default)]
+pub struct ShutdownHooks {
+    hooks: Arc<Mutex<Vec<Hook>>>,
+}
+
+impl ShutdownHooks {
+    /// Run `hook` at shutdown, after the hooks registered before it, giving
+    /// up on it after `timeout`.
+    pub fn register<F, Fut>(&self, name: &'static str, timeout: Duration, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.hooks
+            .lock()
+            .expect("shutdown hooks lock poisoned")
+            .push(Hook {
+                name,
+                timeout,
+                run: Box::new(move || Box::pin(hook())),
+            });
+    }
+
+    /// Run the registered hooks in order. Each runs once; hooks registered
+    /// while they run are left for the next call.
+    pub async fn run(&self) {
+        let hooks = std::mem::take(&mut *self.hooks.lock().expect("shutdown hooks lock poisoned"));
+        if hooks.is_empty() {
+            return;
+        }
+        let root = tracing::info_span!(parent: None, "shutdown", hooks = hooks.len());
+        async {
+            for hook in hooks {
+                run_hook(hook).await;
+            }
+        }
+        .instrument(root)
+        .await;
+    }
+}
+
+async fn run_hook(hook: Hook) {
+    let span = tracing::info_span!(
+        "shutdown.hook",
+        hook.name = hook.name,
+        hook.timeout_ms = hook.timeout.as_millis() as u64,
+        otel.status_code = Empty,
+        error = Empty,
+    );
+    let started = Instant::now();
+    let outcome = tokio::time::timeout(hook.timeout, (hook.run)())
+        .instrument(span.clone())
+        .await;
+    let _entered = span.enter();
+    match outcome {
+        Ok(Ok(())) => {
+            tracing::info!(hook = hook.name, elapsed = ?started.elapsed(), "Shutdown hook done")
+        }
+        Ok(Err(error)) => {
+            span.record("otel.status_code", "ERROR");
+            span.record("error", tracing::field::display(&error));
+            tracing::warn!(hook = hook.name, error = %error, "Shutdown hook failed");
+        }
+        Err(_) => {
+            span.record("otel.status_code", "ERROR");
+            span.record("error", "timed out");
+            tracing::warn!(hook = hook.name, timeout = ?hook.timeout, "Shutdown hook timed out");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn hooks_run_in_order_past_failures_and_timeouts() {
+        let hooks = ShutdownHooks::default();
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let timeout = Duration::from_millis(50);
+        for name in ["first", "failing", "slow", "last"] {
+            let ran = ran.clone();
+            hooks.register(name, timeout, move || async move {
+                match name {
+                    "failing" => anyhow::bail!("broken"),
+                    "slow" => tokio::time::sleep(Duration::from_secs(5)).await,
+                    _ => {}
+                }
+                ran.lock().unwrap().push(name);
+                Ok(())
+            });
+        }
+
+        hooks.run().await;
+        hooks.run().await;
+
+        assert_eq!(*ran.lock().unwrap(), ["first", "last"]);
+    }
+}
@@ -0,0 +1,56 @@
+use futures::future::BoxFuture;
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::Instrument;
+
+use crate::config::LatencySpec;
+use crate::tool_middleware::{Next, ToolCall, ToolMiddleware, ToolResult};
+
+/// Delays calls to the tools configured in `TOOL_LATENCY`, so span
+/// waterfalls in Langfuse look like a real deployment's.
+///
+/// The wait is its own `tool.simulated_latency` span in the call's trace,
+/// ahead of the tool span, and is not mistaken for time spent in the tool.
+pub struct SimulatedLatency {
+    delays: HashMap<String, LatencySpec>,
+}
+
+impl SimulatedLatency {
+    /// `None` when no tool has a delay configured.
+    pub fn from_config(delays: &HashMap<String, LatencySpec>) -> Option<Self> {
+        if delays.is_empty() {
+            return None;
+        }
+        Some(Self {
+            delays: delays.clone(),
+        })
+    }
+}
+
+/// A delay within `base ± jitter`, never below zero.
+fn sample(spec: &LatencySpec) -> Duration {
+    if spec.jitter.is_zero() {
+        return spec.base;
+    }
+    let low = spec.base.saturating_sub(spec.jitter);
+    let high = spec.base + spec.jitter;
+    rand::thread_rng().gen_range(low..=high)
+}
+
+impl ToolMiddleware for SimulatedLatency {
+    fn around<'a>(&'a self, call: &'a ToolCall, next: Next<'a>) -> BoxFuture<'a, ToolResult> {
+        Box::pin(async move {
+            if let Some(spec) = self.delays.get(call.name()) {
+                let delay = sample(spec);
+                let span = tracing::info_span!(
+                    "tool.simulated_latency",
+                    tool = call.name(),
+                    latency_ms = delay.as_millis() as u64
+                );
+                tokio::time::sleep(delay).instrument(span).await;
+            }
+            next.run(call).await
+        })
+    }
+}
@@ -0,0 +1,147 @@
+use opentelemetry::trace::{SpanContext, TraceContextExt};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Deduplicates concurrent calls for the same key: the first caller (the
+/// leader) runs the work, later callers wait for and share its result.
+///
+/// Followers run inside a `singleflight.wait` span linked to the leader's span,
+/// so the fan-in is visible in the trace backend.
+pub struct Singleflight<K, V> {
+    flights: Mutex<HashMap<K, Arc<Flight<V>>>>,
+}
+
+struct Flight<V> {
+    result: OnceCell<V>,
+    leader: SpanContext,
+}
+
+impl<K, V> Default for Singleflight<K, V> {
+    fn default() -> Self {
+        Self {
+            flights: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K, V> Singleflight<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    /// Run `work` unless an identical call is already in flight.
+    ///
+    /// Returns the value and whether it was shared from another caller.
+    pub async fn run<F, Fut>(&self, key: K, work: F) -> (V, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        let (flight, is_leader) = {
+            let mut flights = self.flights.lock().expect("singleflight lock poisoned");
+            match flights.get(&key) {
+                Some(flight) => (flight.clone(), false),
+                None => {
+                    let flight = Arc::new(Flight {
+                        result: OnceCell::new(),
+                        leader: tracing::Span::current()
+                            .context()
+                            .span()
+                            .span_context()
+                            .clone(),
+                    });
+                    flights.insert(key.clone(), flight.clone());
+                    (flight, true)
+                }
+            }
+        };
+
+        if is_leader {
+            // Remove the entry even if the leader is cancelled mid-flight;
+            // followers already holding the flight take over the work.
+            let _guard = FlightGuard {
+                flights: &self.flights,
+                key,
+                flight: &flight,
+            };
+            let value = flight.result.get_or_init(work).await.clone();
+            return (value, false);
+        }
+
+        let span = tracing::info_span!("singleflight.wait", coalesced = true);
+        if flight.leader.is_valid() {
+            span.add_link(flight.leader.clone());
+        }
+        let value = flight
+            .result
+            .get_or_init(work)
+            .instrument(span)
+            .await
+            .clone();
+        (value, true)
+    }
+}
+
+struct FlightGuard<'a, K: Eq + Hash, V> {
+    flights: &'a Mutex<HashMap<K, Arc<Flight<V>>>>,
+    key: K,
+    flight: &'a Arc<Flight<V>>,
+}
+
+impl<K: Eq + Hash, V> Drop for FlightGuard<'_, K, V> {
+    fn drop(&mut self) {
+        if let Ok(mut flights) = self.flights.lock() {
+            if flights
+                .get(&self.key)
+                .is_some_and(|current| Arc::ptr_eq(current, self.flight))
+            {
+                flights.remove(&self.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn concurrent_calls_share_the_leaders_result() {
+        let flights = Singleflight::default();
+        let runs = AtomicU32::new(0);
+        let work = || async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            42
+        };
+
+        let (leader, follower) =
+            tokio::join!(flights.run("paris", work), flights.run("paris", work));
+        assert_eq!(leader, (42, false));
+        assert_eq!(follower, (42, true));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // A finished flight is forgotten, so the next call runs again
+        assert_eq!(flights.run("paris", work).await, (42, false));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn followers_take_over_from_a_cancelled_leader() {
+        let flights = Singleflight::default();
+        let mut leader = Box::pin(flights.run("paris", std::future::pending::<u32>));
+        assert!(futures::poll!(&mut leader).is_pending());
+        let mut follower = Box::pin(flights.run("paris", || async { 7 }));
+        assert!(futures::poll!(&mut follower).is_pending());
+
+        drop(leader);
+        assert_eq!(follower.await, (7, true));
+        assert!(flights.flights.lock().unwrap().is_empty());
+    }
+}
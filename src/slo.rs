@@ -0,0 +1,259 @@
+//! Latency objectives of tools (`TOOL_SLO`, e.g. `get_weather=p95<200ms`),
+//! tracked over the last `SLO_WINDOW_SECS`.
+//!
+//! Each call is counted as fast or slow against its tool's threshold, in one
+//! of 60 slices of the window, so old calls age out a slice at a time.
+//! Compliance is the share of fast calls. The burn rate divides the share of
+//! slow calls by the share the objective allows (5% for p95): at 1 the error
+//! budget lasts exactly the window, at 2 half of it. A tool whose burn rate
+//! exceeds `SLO_BURN_RATE_ALERT` is logged as burning once, and as recovered
+//! when it falls back. The status of every objective is served as the
+//! `slo://tools` resource and pushed with the other metrics.
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::{SloConfig, SloObjective};
+use crate::tool_middleware::{Next, ToolCall, ToolMiddleware, ToolResult};
+
+/// URI of the SLO status resource.
+pub const SLO_URI: &str = "slo://tools";
+
+/// Slices the window is divided into.
+const SLICES: u32 = 60;
+
+/// Objectives and their windows, shared by every session.
+pub struct SloTracker {
+    burn_rate_alert: f64,
+    tools: BTreeMap<String, Tracked>,
+}
+
+struct Tracked {
+    objective: SloObjective,
+    window: Mutex<Window>,
+    /// Whether the burn rate was over the alert level at the last call.
+    burning: AtomicBool,
+}
+
+/// Fast and slow call counts over a sliding window.
+struct Window {
+    length: Duration,
+    slices: VecDeque<Slice>,
+}
+
+struct Slice {
+    start: Instant,
+    calls: u64,
+    slow: u64,
+}
+
+impl Window {
+    fn new(length: Duration) -> Self {
+        Self {
+            length,
+            slices: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, now: Instant, slow: bool) {
+        self.expire(now);
+        let slice_length = self.length / SLICES;
+        match self.slices.back_mut() {
+            Some(slice) if now.duration_since(slice.start) < slice_length => {
+                slice.calls += 1;
+                slice.slow += u64::from(slow);
+            }
+            _ => self.slices.push_back(Slice {
+                start: now,
+                calls: 1,
+                slow: u64::from(slow),
+            }),
+        }
+    }
+
+    /// Calls and slow calls within the window.
+    fn counts(&mut self, now: Instant) -> (u64, u64) {
+        self.expire(now);
+        self.slices.iter().fold((0, 0), |(calls, slow), slice| {
+            (calls + slice.calls, slow + slice.slow)
+        })
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(slice) = self.slices.front() {
+            if now.duration_since(slice.start) < self.length {
+                break;
+            }
+            self.slices.pop_front();
+        }
+    }
+}
+
+/// Share of slow calls over the share `objective` allows.
+fn burn_rate(objective: &SloObjective, calls: u64, slow: u64) -> f64 {
+    if calls == 0 {
+        return 0.0;
+    }
+    (slow as f64 / calls as f64) / (1.0 - objective.quantile)
+}
+
+impl SloTracker {
+    /// `None` when no tool has an objective.
+    pub fn from_config(config: &SloConfig) -> Option<Arc<Self>> {
+        if config.objectives.is_empty() {
+            return None;
+        }
+        let tools = config
+            .objectives
+            .iter()
+            .map(|(tool, objective)| {
+                let tracked = Tracked {
+                    objective: *objective,
+                    window: Mutex::new(Window::new(config.window)),
+                    burning: AtomicBool::new(false),
+                };
+                (tool.clone(), tracked)
+            })
+            .collect();
+        Some(Arc::new(Self {
+            burn_rate_alert: config.burn_rate_alert,
+            tools,
+        }))
+    }
+
+    /// Current status of every objective, by tool name.
+    pub fn snapshot(&self) -> Vec<SloStatus> {
+        let now = Instant::now();
+        self.tools
+            .iter()
+            .map(|(tool, tracked)| {
+                let (calls, slow) = tracked
+                    .window
+                    .lock()
+                    .expect("slo window lock poisoned")
+                    .counts(now);
+                let compliance = if calls == 0 {
+                    1.0
+                } else {
+                    1.0 - slow as f64 / calls as f64
+                };
+                SloStatus {
+                    tool: tool.clone(),
+                    objective: tracked.objective.to_string(),
+                    calls,
+                    slow_calls: slow,
+                    compliance,
+                    met: compliance >= tracked.objective.quantile,
+                    burn_rate: burn_rate(&tracked.objective, calls, slow),
+                    burning: tracked.burning.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+
+    fn record(&self, tool: &str, tracked: &Tracked, elapsed: Duration) {
+        let now = Instant::now();
+        let (calls, slow) = {
+            let mut window = tracked.window.lock().expect("slo window lock poisoned");
+            window.record(now, elapsed > tracked.objective.threshold);
+            window.counts(now)
+        };
+        let burn_rate = burn_rate(&tracked.objective, calls, slow);
+        let burning = burn_rate > self.burn_rate_alert;
+        if tracked.burning.swap(burning, Ordering::Relaxed) == burning {
+            return;
+        }
+        let objective = tracked.objective.to_string();
+        if burning {
+            tracing::warn!(
+                tool,
+                %objective,
+                burn_rate,
+                calls,
+                slow_calls = slow,
+                "Tool latency SLO burning error budget"
+            );
+        } else {
+            tracing::info!(tool, %objective, burn_rate, "Tool latency SLO recovered");
+        }
+    }
+}
+
+impl ToolMiddleware for SloTracker {
+    fn around<'a>(&'a self, call: &'a ToolCall, next: Next<'a>) -> BoxFuture<'a, ToolResult> {
+        Box::pin(async move {
+            let tool = call.name();
+            let Some(tracked) = self.tools.get(tool) else {
+                return next.run(call).await;
+            };
+            let started = Instant::now();
+            match AssertUnwindSafe(next.run(call)).catch_unwind().await {
+                Ok(result) => {
+                    self.record(tool, tracked, started.elapsed());
+                    result
+                }
+                Err(payload) => {
+                    self.record(tool, tracked, started.elapsed());
+                    std::panic::resume_unwind(payload)
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct SloStatus {
+    /// Tool name
+    pub tool: String,
+    /// Latency objective, e.g. "p95<200ms"
+    pub objective: String,
+    /// Calls within the window
+    pub calls: u64,
+    /// Calls within the window slower than the objective's threshold
+    pub slow_calls: u64,
+    /// Share of calls within the threshold, from 0 to 1; 1 without calls
+    pub compliance: f64,
+    /// Whether the compliance reaches the objective's quantile
+    pub met: bool,
+    /// Share of slow calls over the share the objective allows; above 1
+    /// the error budget runs out before the window ends
+    pub burn_rate: f64,
+    /// Whether the burn rate is above SLO_BURN_RATE_ALERT
+    pub burning: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_calls_age_out_of_the_window_a_slice_at_a_time() {
+        let start = Instant::now();
+        let mut window = Window::new(Duration::from_secs(60));
+        window.record(start, true);
+        window.record(start + Duration::from_millis(500), false);
+        window.record(start + Duration::from_secs(30), false);
+
+        assert_eq!(window.counts(start + Duration::from_secs(59)), (3, 1));
+        assert_eq!(window.counts(start + Duration::from_secs(60)), (1, 0));
+        assert_eq!(window.counts(start + Duration::from_secs(90)), (0, 0));
+    }
+
+    #[test]
+    fn burn_rate_is_the_slow_share_over_the_allowed_share() {
+        let p95 = SloObjective {
+            quantile: 0.95,
+            threshold: Duration::from_millis(200),
+        };
+        assert!((burn_rate(&p95, 100, 5) - 1.0).abs() < 1e-9);
+        assert!((burn_rate(&p95, 100, 20) - 4.0).abs() < 1e-9);
+        assert_eq!(burn_rate(&p95, 0, 0), 0.0);
+    }
+}
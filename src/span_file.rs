@@ -0,0 +1,337 @@
+//! Finished spans written as JSON lines to a local file, and the summary
+//! printed by `trace-report`, for demos without a tracing backend.
+
+use anyhow::{Context, Result};
+use opentelemetry::trace::{SpanId, Status};
+use opentelemetry::{Array, KeyValue, Value};
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::usage_stats::percentile;
+
+/// One line of the span file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpanRecord {
+    pub trace_id: String,
+    pub span_id: String,
+    /// `None` for root spans.
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub kind: String,
+    /// RFC 3339 in UTC.
+    pub start_time: String,
+    pub duration_ms: f64,
+    /// `unset`, `ok` or `error`.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_message: Option<String>,
+    #[serde(default)]
+    pub attributes: Map<String, JsonValue>,
+    #[serde(default)]
+    pub events: Vec<SpanEventRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpanEventRecord {
+    pub name: String,
+    pub time: String,
+    #[serde(default)]
+    pub attributes: Map<String, JsonValue>,
+}
+
+impl From<&SpanData> for SpanRecord {
+    fn from(span: &SpanData) -> Self {
+        let (status, status_message) = match &span.status {
+            Status::Unset => ("unset", None),
+            Status::Ok => ("ok", None),
+            Status::Error { description } => ("error", Some(description.to_string())),
+        };
+        let duration = span
+            .end_time
+            .duration_since(span.start_time)
+            .unwrap_or_default();
+        Self {
+            trace_id: span.span_context.trace_id().to_string(),
+            span_id: span.span_context.span_id().to_string(),
+            parent_span_id: (span.parent_span_id != SpanId::INVALID)
+                .then(|| span.parent_span_id.to_string()),
+            name: span.name.to_string(),
+            kind: format!("{:?}", span.span_kind).to_lowercase(),
+            start_time: timestamp(span.start_time),
+            duration_ms: duration.as_secs_f64() * 1_000.0,
+            status: status.to_string(),
+            status_message: status_message.filter(|message| !message.is_empty()),
+            attributes: attributes(&span.attributes),
+            events: span
+                .events
+                .iter()
+                .map(|event| SpanEventRecord {
+                    name: event.name.to_string(),
+                    time: timestamp(event.timestamp),
+                    attributes: attributes(&event.attributes),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn timestamp(time: SystemTime) -> String {
+    OffsetDateTime::from(time)
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+fn attributes(attributes: &[KeyValue]) -> Map<String, JsonValue> {
+    attributes
+        .iter()
+        .map(|attribute| (attribute.key.to_string(), json(&attribute.value)))
+        .collect()
+}
+
+fn json(value: &Value) -> JsonValue {
+    match value {
+        Value::Bool(value) => JsonValue::from(*value),
+        Value::I64(value) => JsonValue::from(*value),
+        Value::F64(value) => JsonValue::from(*value),
+        Value::String(value) => JsonValue::from(value.as_str()),
+        Value::Array(Array::Bool(values)) => JsonValue::from(values.clone()),
+        Value::Array(Array::I64(values)) => JsonValue::from(values.clone()),
+        Value::Array(Array::F64(values)) => JsonValue::from(values.clone()),
+        Value::Array(Array::String(values)) => values.iter().map(|value| value.as_str()).collect(),
+        other => JsonValue::from(other.to_string()),
+    }
+}
+
+/// Exporter appending each finished span to a file as one JSON line.
+#[derive(Debug)]
+pub struct JsonlSpanExporter {
+    file: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl JsonlSpanExporter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open span file {}", path.display()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(BufWriter::new(file))),
+        })
+    }
+}
+
+impl SpanExporter for JsonlSpanExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut lines = Vec::new();
+        for span in &batch {
+            serde_json::to_writer(&mut lines, &SpanRecord::from(span))
+                .map_err(|error| OTelSdkError::InternalFailure(error.to_string()))?;
+            lines.push(b'\n');
+        }
+
+        let file = Arc::clone(&self.file);
+        let write = move || {
+            let mut file = file.lock().expect("span file lock poisoned");
+            file.write_all(&lines)?;
+            file.flush()
+        };
+        // The batch processor runs on a tokio worker, which must not block on the disk
+        let written = match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => runtime
+                .spawn_blocking(write)
+                .await
+                .unwrap_or_else(|error| Err(io::Error::other(error))),
+            Err(_) => write(),
+        };
+        written.map_err(|error| OTelSdkError::InternalFailure(error.to_string()))
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.file
+            .lock()
+            .expect("span file lock poisoned")
+            .flush()
+            .map_err(|error| OTelSdkError::InternalFailure(error.to_string()))
+    }
+}
+
+/// Per-name latency and error rate of the spans in a span file, as a text
+/// table. Only spans named in `tools` are listed unless `tools` is `None`.
+pub fn report(path: &Path, tools: Option<&[String]>) -> Result<String> {
+    let file =
+        File::open(path).with_context(|| format!("failed to open span file {}", path.display()))?;
+
+    let mut spans = 0;
+    let mut traces = HashSet::new();
+    let mut by_name: BTreeMap<String, (Vec<Duration>, u64)> = BTreeMap::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: SpanRecord = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: not a span record", path.display(), number + 1))?;
+        spans += 1;
+        traces.insert(record.trace_id);
+        if tools.is_some_and(|tools| !tools.contains(&record.name)) {
+            continue;
+        }
+        let (durations, errors) = by_name.entry(record.name).or_default();
+        durations.push(Duration::from_secs_f64(
+            record.duration_ms.max(0.0) / 1_000.0,
+        ));
+        *errors += u64::from(record.status == "error");
+    }
+
+    let mut out = format!("{spans} spans in {} traces\n\n", traces.len());
+    let width = by_name.keys().map(String::len).max().unwrap_or(4).max(4);
+    let _ = writeln!(
+        out,
+        "{:width$}  {:>6}  {:>6}  {:>7}  {:>8}  {:>8}  {:>8}  {:>8}",
+        if tools.is_some() { "tool" } else { "span" },
+        "calls",
+        "errors",
+        "error %",
+        "mean ms",
+        "p50 ms",
+        "p95 ms",
+        "max ms",
+    );
+    for (name, (mut durations, errors)) in by_name {
+        durations.sort_unstable();
+        let calls = durations.len();
+        let total: Duration = durations.iter().sum();
+        let _ = writeln!(
+            out,
+            "{name:width$}  {calls:>6}  {errors:>6}  {:>6.1}%  {:>8.1}  {:>8.1}  {:>8.1}  {:>8.1}",
+            errors as f64 * 100.0 / calls as f64,
+            milliseconds(total / calls as u32),
+            milliseconds(percentile(&durations, 0.50)),
+            milliseconds(percentile(&durations, 0.95)),
+            milliseconds(durations[calls - 1]),
+        );
+    }
+    Ok(out)
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+
+    fn span_file(name: &str, lines: &[&str]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.jsonl", std::process::id()));
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path
+    }
+
+    fn record(trace_id: &str, name: &str, duration_ms: f64, status: &str) -> String {
+        format!(
+            r#"{{"trace_id":"{trace_id}","span_id":"b7ad6b7169203331","parent_span_id":null,"name":"{name}","kind":"internal","start_time":"2024-01-01T00:00:00Z","duration_ms":{duration_ms},"status":"{status}"}}"#
+        )
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let samples: Vec<_> = (1..=10).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&[], 0.95), Duration::ZERO);
+        assert_eq!(percentile(&samples[..1], 0.50), Duration::from_millis(1));
+        assert_eq!(percentile(&samples[..1], 0.95), Duration::from_millis(1));
+        assert_eq!(percentile(&samples, 0.0), Duration::from_millis(1));
+        assert_eq!(percentile(&samples, 0.50), Duration::from_millis(5));
+        assert_eq!(percentile(&samples, 0.95), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn empty_files_report_no_spans() {
+        let path = span_file("span-file-empty", &[""]);
+        let report = report(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        let report = report.unwrap();
+        assert!(report.starts_with("0 spans in 0 traces\n"));
+        assert_eq!(report.lines().count(), 3);
+    }
+
+    #[test]
+    fn reports_group_spans_by_name() {
+        let trace = "0af7651916cd43dd8448eb211c80319c";
+        let path = span_file(
+            "span-file-report",
+            &[
+                &record(trace, "get_weather", 4.0, "ok"),
+                "",
+                &record(trace, "get_weather", 2.0, "error"),
+                &record(
+                    "4bf92f3577b34da6a3ce929d0e0e4736",
+                    "get_forecast",
+                    3.0,
+                    "unset",
+                ),
+                &record(trace, "http.request", 9.0, "ok"),
+            ],
+        );
+        let tools = ["get_weather".to_string(), "get_forecast".to_string()];
+        let report = report(&path, Some(&tools));
+        std::fs::remove_file(&path).unwrap();
+        let report = report.unwrap();
+
+        let rows: Vec<Vec<&str>> = report
+            .lines()
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        assert_eq!(rows[0], ["4", "spans", "in", "2", "traces"]);
+        assert_eq!(rows[2][0], "tool");
+        // A single sample is its own mean and every percentile
+        assert_eq!(
+            rows[3],
+            ["get_forecast", "1", "0", "0.0%", "3.0", "3.0", "3.0", "3.0"]
+        );
+        assert_eq!(
+            rows[4],
+            ["get_weather", "2", "1", "50.0%", "3.0", "2.0", "4.0", "4.0"]
+        );
+        assert_eq!(rows.len(), 5);
+    }
+
+    #[test]
+    fn malformed_lines_are_reported_with_their_number() {
+        let path = span_file("span-file-malformed", &[&record("1", "a", 1.0, "ok"), "{"]);
+        let error = report(&path, None).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.to_string().ends_with(":2: not a span record"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exported_spans_are_appended_as_lines() {
+        let path = span_file("span-file-export", &[]);
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(JsonlSpanExporter::create(&path).unwrap())
+            .build();
+        let tracer = provider.tracer("span-file-test");
+        tracer.in_span("get_weather", |_| {});
+        tracer.in_span("get_forecast", |_| {});
+
+        let report = report(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        let report = report.unwrap();
+        assert!(report.starts_with("2 spans in 2 traces\n"));
+        assert!(report.contains("get_forecast"));
+    }
+}
@@ -0,0 +1,146 @@
+//! Caps on exported span attributes, so a load test cannot flood the tracing
+//! backend with huge or high-cardinality values.
+
+use opentelemetry::{KeyValue, StringValue, Value};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::resource::Resource;
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+use std::time::Duration;
+
+use crate::config::SpanLimitsConfig;
+
+/// Exporter wrapper truncating long string attributes and replacing the
+/// configured unbounded ones with a hash bucket.
+#[derive(Debug)]
+pub struct AttributeGuardExporter<E> {
+    inner: E,
+    limits: SpanLimitsConfig,
+}
+
+impl<E> AttributeGuardExporter<E> {
+    pub fn new(inner: E, limits: SpanLimitsConfig) -> Self {
+        Self { inner, limits }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for AttributeGuardExporter<E> {
+    async fn export(&self, mut batch: Vec<SpanData>) -> OTelSdkResult {
+        for span in &mut batch {
+            for attribute in &mut span.attributes {
+                self.guard(attribute);
+            }
+        }
+        self.inner.export(batch).await
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+impl<E> AttributeGuardExporter<E> {
+    fn guard(&self, attribute: &mut KeyValue) {
+        let key = attribute.key.as_str();
+        if self
+            .limits
+            .hashed_attributes
+            .iter()
+            .any(|hashed| hashed == key)
+        {
+            let bucket = bucket(&attribute.value.as_str(), self.limits.hash_buckets);
+            *attribute = KeyValue::new(format!("{key}.bucket"), bucket as i64);
+            return;
+        }
+        let Some(max) = self.limits.max_attribute_length else {
+            return;
+        };
+        if let Value::String(value) = &attribute.value {
+            if let Some(truncated) = truncate(value.as_str(), max) {
+                attribute.value = Value::String(StringValue::from(truncated));
+            }
+        }
+    }
+}
+
+/// `value` cut to at most `max` characters with a trailing `…`, or `None`
+/// when it already fits.
+fn truncate(value: &str, max: usize) -> Option<String> {
+    value.char_indices().nth(max)?;
+    let (end, _) = value.char_indices().nth(max - 1)?;
+    Some(format!("{}…", &value[..end]))
+}
+
+/// Stable bucket of `value` in `0..buckets` (FNV-1a), the same across runs
+/// so buckets can be compared between load tests.
+fn bucket(value: &str, buckets: u64) -> u64 {
+    let hash = value.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    hash % buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_counts_characters_not_bytes() {
+        assert_eq!(truncate("Paris", 5), None);
+        assert_eq!(truncate("Paris", 4).as_deref(), Some("Par…"));
+        assert_eq!(truncate("Paris", 1).as_deref(), Some("…"));
+        assert_eq!(truncate("", 1), None);
+        // Multibyte characters are never split
+        assert_eq!(truncate("Zürich", 6), None);
+        assert_eq!(truncate("Zürich", 3).as_deref(), Some("Zü…"));
+        assert_eq!(truncate("🌧🌧🌧", 2).as_deref(), Some("🌧…"));
+    }
+
+    #[test]
+    fn buckets_are_stable_and_in_range() {
+        // FNV-1a offset basis, the hash of the empty string
+        assert_eq!(bucket("", u64::MAX), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(bucket("Paris", 64), bucket("Paris", 64));
+        assert_eq!(bucket("Paris", 1), 0);
+        assert!(["Paris", "Zürich", "Tokyo"]
+            .iter()
+            .all(|value| bucket(value, 8) < 8));
+    }
+
+    #[test]
+    fn guard_hashes_and_truncates_attributes() {
+        let exporter = AttributeGuardExporter::new(
+            (),
+            SpanLimitsConfig {
+                max_attribute_length: Some(3),
+                hashed_attributes: vec!["location".into()],
+                hash_buckets: 16,
+                ..SpanLimitsConfig::default()
+            },
+        );
+        let guarded = |attribute: KeyValue| {
+            let mut attribute = attribute;
+            exporter.guard(&mut attribute);
+            attribute
+        };
+
+        let hashed = guarded(KeyValue::new("location", "Paris"));
+        assert_eq!(hashed.key.as_str(), "location.bucket");
+        assert_eq!(hashed.value, Value::I64(bucket("Paris", 16) as i64));
+        assert_eq!(
+            guarded(KeyValue::new("output", "sunny")).value,
+            Value::from("su…")
+        );
+        assert_eq!(
+            guarded(KeyValue::new("days", 12345)).value,
+            Value::I64(12345)
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Moves rmcp's transport spans into the trace of the session they serve.
+//!
+//! `streamable_http_session` and `serve_inner` are opened by rmcp in tasks
+//! spawned before `initialize` is handled, so they start as roots of traces
+//! of their own. Their parent cannot be changed once started; instead their
+//! ids are rewritten at export, making them children of the session's
+//! `mcp.session` span.
+
+use once_cell::sync::Lazy;
+use opentelemetry::trace::{SpanContext, SpanId, TraceId, TraceState};
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::resource::Resource;
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// rmcp span name, and the attribute carrying its session id.
+const RMCP_SPANS: &[(&str, &str)] = &[
+    ("streamable_http_session", "id"),
+    ("serve_inner", "session.id"),
+];
+
+/// Exporters wrapped in [`SessionRepairExporter`]; each sees every span.
+static EXPORTERS: AtomicUsize = AtomicUsize::new(0);
+
+/// How long a session waits for its rmcp spans. A session open longer, or
+/// one whose span was lost, has its remaining spans exported as roots.
+const PENDING_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// A session whose rmcp spans are still to be re-parented.
+struct Pending {
+    session: SpanContext,
+    /// rmcp spans left to see, counting each exporter's copy.
+    remaining: usize,
+    registered: Instant,
+}
+
+/// Session span context by session id, until every exporter has seen both
+/// rmcp spans of the session.
+static SESSIONS: Lazy<Mutex<HashMap<String, Pending>>> = Lazy::new(Mutex::default);
+
+/// Make `session` the parent of the session's rmcp spans when they are
+/// exported. Nothing is kept when no exporter would repair them.
+pub fn register_session(session_id: &str, session: SpanContext) {
+    let exporters = EXPORTERS.load(Ordering::Relaxed);
+    if exporters == 0 {
+        return;
+    }
+    let mut sessions = SESSIONS.lock().expect("session span lock poisoned");
+    sessions.retain(|_, entry| entry.registered.elapsed() < PENDING_TTL);
+    sessions.insert(
+        session_id.to_string(),
+        Pending {
+            session,
+            remaining: RMCP_SPANS.len() * exporters,
+            registered: Instant::now(),
+        },
+    );
+}
+
+/// A registered session, as listed by [`pending`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingSession {
+    pub session_id: String,
+    /// Trace the session's rmcp spans will be moved into
+    pub trace_id: String,
+    /// rmcp spans not exported yet, counting each exporter's copy
+    pub remaining_spans: usize,
+    /// Seconds since the session was registered
+    pub age_secs: u64,
+}
+
+/// Sessions whose rmcp spans have not all been exported yet, oldest first.
+/// A session stuck here long after it started lost one of its spans.
+pub fn pending() -> Vec<PendingSession> {
+    let sessions = SESSIONS.lock().expect("session span lock poisoned");
+    let mut pending: Vec<_> = sessions
+        .iter()
+        .map(|(session_id, entry)| PendingSession {
+            session_id: session_id.clone(),
+            trace_id: entry.session.trace_id().to_string(),
+            remaining_spans: entry.remaining,
+            age_secs: entry.registered.elapsed().as_secs(),
+        })
+        .collect();
+    pending.sort_by_key(|entry| std::cmp::Reverse(entry.age_secs));
+    pending
+}
+
+/// Forget the session, or every session when `None`; their rmcp spans still
+/// to come are exported as roots. Returns how many were forgotten.
+pub fn forget(session_id: Option<&str>) -> usize {
+    let mut sessions = SESSIONS.lock().expect("session span lock poisoned");
+    match session_id {
+        Some(session_id) => usize::from(sessions.remove(session_id).is_some()),
+        None => {
+            let forgotten = sessions.len();
+            sessions.clear();
+            forgotten
+        }
+    }
+}
+
+/// Exporter wrapper that re-parents rmcp transport spans on their session
+/// span. Other spans pass through unchanged.
+#[derive(Debug)]
+pub struct SessionRepairExporter<E> {
+    inner: E,
+    /// Whether this exporter is counted in [`EXPORTERS`] until dropped.
+    counted: bool,
+}
+
+impl<E> SessionRepairExporter<E> {
+    pub fn new(inner: E) -> Self {
+        EXPORTERS.fetch_add(1, Ordering::Relaxed);
+        Self {
+            inner,
+            counted: true,
+        }
+    }
+
+    /// For one of several exporters that each see part of the spans, such as
+    /// per-tenant ones; the group is counted once with [`count_router`].
+    pub fn routed(inner: E) -> Self {
+        Self {
+            inner,
+            counted: false,
+        }
+    }
+}
+
+impl<E> Drop for SessionRepairExporter<E> {
+    fn drop(&mut self) {
+        if self.counted {
+            EXPORTERS.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Count a group of [`SessionRepairExporter::routed`] exporters between
+/// which every span is split, as one exporter.
+pub fn count_router() {
+    EXPORTERS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Trace `span` will be moved into at export, when it is a root rmcp span of
+/// a registered session.
+pub fn session_trace_id(span: &SpanData) -> Option<TraceId> {
+    let (_, key) = RMCP_SPANS.iter().find(|(name, _)| span.name == *name)?;
+    if span.parent_span_id != SpanId::INVALID {
+        return None;
+    }
+    let session_id = attribute(&span.attributes, key)?;
+    let sessions = SESSIONS.lock().expect("session span lock poisoned");
+    sessions
+        .get(&session_id)
+        .map(|entry| entry.session.trace_id())
+}
+
+impl<E: SpanExporter> SpanExporter for SessionRepairExporter<E> {
+    async fn export(&self, mut batch: Vec<SpanData>) -> OTelSdkResult {
+        for span in &mut batch {
+            repair(span);
+        }
+        self.inner.export(batch).await
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+fn repair(span: &mut SpanData) {
+    let Some(&(_, key)) = RMCP_SPANS.iter().find(|(name, _)| span.name == *name) else {
+        return;
+    };
+    if span.parent_span_id != SpanId::INVALID {
+        return;
+    }
+    let Some(session_id) = attribute(&span.attributes, key) else {
+        return;
+    };
+
+    let mut sessions = SESSIONS.lock().expect("session span lock poisoned");
+    let Some(Pending {
+        session, remaining, ..
+    }) = sessions.get_mut(&session_id)
+    else {
+        return;
+    };
+    span.span_context = SpanContext::new(
+        session.trace_id(),
+        span.span_context.span_id(),
+        session.trace_flags(),
+        false,
+        TraceState::default(),
+    );
+    span.parent_span_id = session.span_id();
+    *remaining = remaining.saturating_sub(1);
+    if *remaining == 0 {
+        sessions.remove(&session_id);
+    }
+}
+
+fn attribute(attributes: &[KeyValue], key: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.key.as_str() == key)
+        .map(|attribute| attribute.value.as_str().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TraceFlags;
+
+    fn session() -> SpanContext {
+        SpanContext::new(
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(),
+            SpanId::from_hex("b7ad6b7169203331").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        )
+    }
+
+    #[test]
+    fn registered_sessions_are_listed_until_forgotten() {
+        let _exporter = SessionRepairExporter::new(());
+        register_session("span-repair-test", session());
+
+        let listed = pending();
+        let entry = listed
+            .iter()
+            .find(|entry| entry.session_id == "span-repair-test")
+            .unwrap();
+        assert_eq!(entry.trace_id, "0af7651916cd43dd8448eb211c80319c");
+
+        assert_eq!(forget(Some("span-repair-test")), 1);
+        assert_eq!(forget(Some("span-repair-test")), 0);
+        assert!(pending()
+            .iter()
+            .all(|entry| entry.session_id != "span-repair-test"));
+    }
+
+    #[test]
+    fn expired_sessions_are_evicted_on_register() {
+        let _exporter = SessionRepairExporter::new(());
+        let Some(registered) = Instant::now().checked_sub(PENDING_TTL) else {
+            return;
+        };
+        SESSIONS.lock().unwrap().insert(
+            "span-repair-expired".to_string(),
+            Pending {
+                session: session(),
+                remaining: 1,
+                registered,
+            },
+        );
+        register_session("span-repair-fresh", session());
+
+        let sessions = SESSIONS.lock().unwrap();
+        assert!(!sessions.contains_key("span-repair-expired"));
+        assert!(sessions.contains_key("span-repair-fresh"));
+    }
+}
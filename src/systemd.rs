@@ -0,0 +1,135 @@
+//! Running as a systemd service: socket activation and `sd_notify`.
+//!
+//! With a `.socket` unit, systemd binds the port and passes the listening
+//! socket as file descriptor 3 (`LISTEN_FDS`, `LISTEN_PID`); the server
+//! serves on it instead of binding its own. With `Type=notify`, the server
+//! reports `READY=1` once it accepts connections and `STOPPING=1` when it
+//! starts shutting down, on the datagram socket in `NOTIFY_SOCKET`.
+//!
+//! Outside systemd neither variable is set and both are no-ops.
+
+use anyhow::{bail, Context, Result};
+use std::net::TcpListener;
+
+/// First file descriptor systemd passes (`SD_LISTEN_FDS_START`).
+#[cfg(unix)]
+const LISTEN_FDS_START: std::os::fd::RawFd = 3;
+
+/// The listening socket passed by systemd socket activation, if any.
+///
+/// The `LISTEN_*` variables are removed so child processes do not take the
+/// socket for theirs.
+pub fn listener() -> Result<Option<TcpListener>> {
+    let count = inherited_fds(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )?;
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(name);
+    }
+    if count == 0 {
+        return Ok(None);
+    }
+    if count > 1 {
+        tracing::warn!(
+            count,
+            "systemd passed several sockets; serving on the first only"
+        );
+    }
+    inherited_listener().map(Some)
+}
+
+#[cfg(unix)]
+fn inherited_listener() -> Result<TcpListener> {
+    use std::os::fd::BorrowedFd;
+
+    // SAFETY: the descriptor is only borrowed to duplicate it. A duplicate
+    // is taken, rather than ownership, so that a stale LISTEN_FDS pointing
+    // at a descriptor the runtime opened fails here instead of closing it.
+    let inherited = unsafe { BorrowedFd::borrow_raw(LISTEN_FDS_START) };
+    let listener = TcpListener::from(
+        inherited
+            .try_clone_to_owned()
+            .context("systemd passed no socket as file descriptor 3")?,
+    );
+    listener
+        .local_addr()
+        .context("the socket passed by systemd is not a TCP socket")?;
+    // Tokio needs a non-blocking socket
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+#[cfg(not(unix))]
+fn inherited_listener() -> Result<TcpListener> {
+    bail!("socket activation is only supported on Unix")
+}
+
+/// Sockets passed to this process, from `LISTEN_PID` and `LISTEN_FDS`.
+/// Variables meant for another process (a different `LISTEN_PID`) count
+/// as none.
+fn inherited_fds(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Result<usize> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(0);
+    };
+    let listen_pid: u32 = listen_pid
+        .trim()
+        .parse()
+        .with_context(|| format!("LISTEN_PID is not a process id: '{listen_pid}'"))?;
+    if listen_pid != pid {
+        return Ok(0);
+    }
+    match listen_fds.trim().parse() {
+        Ok(count) => Ok(count),
+        Err(_) => bail!("LISTEN_FDS is not a number: '{listen_fds}'"),
+    }
+}
+
+/// Send `state` (e.g. `READY=1`) to the service manager, when started with
+/// `NOTIFY_SOCKET`. Failures are logged, never fatal.
+pub fn notify(state: &str) {
+    let Ok(path) = std::env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+    match send(&path, state) {
+        Ok(()) => tracing::debug!(state, "Notified systemd"),
+        Err(error) => tracing::warn!(%error, state, socket = %path, "Failed to notify systemd"),
+    }
+}
+
+#[cfg(unix)]
+fn send(path: &str, state: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let socket = UnixDatagram::unbound()?;
+    // A leading '@' names a socket in the abstract namespace
+    #[cfg(target_os = "linux")]
+    if let Some(name) = path.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        socket.send_to_addr(state.as_bytes(), &address)?;
+        return Ok(());
+    }
+    socket.send_to(state.as_bytes(), path)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_path: &str, _state: &str) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sockets_count_only_when_passed_to_this_process() {
+        assert_eq!(inherited_fds(None, None, 42).unwrap(), 0);
+        assert_eq!(inherited_fds(Some("42"), Some("1"), 42).unwrap(), 1);
+        assert_eq!(inherited_fds(Some("41"), Some("1"), 42).unwrap(), 0);
+        assert!(inherited_fds(Some("42"), Some("one"), 42).is_err());
+        assert!(inherited_fds(Some("pid"), Some("1"), 42).is_err());
+    }
+}
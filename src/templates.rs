@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use minijinja::Environment;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::i18n::Lang;
+
+/// Templates shipped with the server, by name. A file with the same name in
+/// `TEMPLATE_DIR` replaces the built-in one. `<name>.<lang>.j2` is the
+/// translation of `<name>.j2`, which is in English.
+const BUILT_IN: &[(&str, &str)] = &[
+    (
+        "describe_weather.j2",
+        include_str!("../templates/describe_weather.j2"),
+    ),
+    (
+        "describe_weather.de.j2",
+        include_str!("../templates/describe_weather.de.j2"),
+    ),
+    (
+        "describe_weather.es.j2",
+        include_str!("../templates/describe_weather.es.j2"),
+    ),
+    (
+        "describe_weather.fr.j2",
+        include_str!("../templates/describe_weather.fr.j2"),
+    ),
+];
+
+/// Text templates for tools that render prose from structured data.
+pub struct Templates {
+    env: Environment<'static>,
+}
+
+impl Templates {
+    /// Compile the built-in templates and any overrides in `dir`, so a
+    /// broken override fails startup rather than a tool call.
+    pub fn load(dir: Option<&Path>) -> Result<Self> {
+        let mut env = Environment::new();
+        for &(name, source) in BUILT_IN {
+            let source = match dir.map(|dir| dir.join(name)) {
+                Some(path) if path.exists() => std::fs::read_to_string(&path)
+                    .with_context(|| format!("reading template {}", path.display()))?,
+                _ => source.to_string(),
+            };
+            env.add_template_owned(name, source)
+                .with_context(|| format!("compiling template {name}"))?;
+        }
+        Ok(Self { env })
+    }
+
+    /// Name of the `lang` translation of template `<base>.j2`, or of the
+    /// English one when there is no translation.
+    pub fn localized(&self, base: &str, lang: Lang) -> String {
+        let translated = format!("{base}.{}.j2", lang.code());
+        if self.env.get_template(&translated).is_ok() {
+            translated
+        } else {
+            format!("{base}.j2")
+        }
+    }
+
+    pub fn render(&self, name: &str, context: impl Serialize) -> Result<String, minijinja::Error> {
+        self.env.get_template(name)?.render(context)
+    }
+}
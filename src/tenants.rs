@@ -0,0 +1,382 @@
+//! Per-tenant Langfuse projects.
+//!
+//! A caller's tenant comes from its `x-api-key` header or a claim of its
+//! bearer JWT, and is recorded as `tenant.id` on the spans that open a trace
+//! (`http.request`, `mcp.session`). [`TenantRoutingProcessor`] remembers the
+//! tenant of each trace and sends all of the trace's spans to that tenant's
+//! exporter.
+
+use axum::http::{header, HeaderMap};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use once_cell::sync::OnceCell;
+use opentelemetry::trace::{Span as _, TraceId};
+use opentelemetry::Context;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::resource::Resource;
+use opentelemetry_sdk::trace::{Span, SpanData, SpanProcessor};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::TenantsConfig;
+use crate::span_repair;
+
+/// Span attribute naming the tenant a trace belongs to.
+pub const TENANT_ATTRIBUTE: &str = "tenant.id";
+
+/// Header carrying a tenant API key.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Traces whose tenant is remembered; the oldest are forgotten first.
+const MAX_TRACES: usize = 10_000;
+
+/// Finds a request's tenant from its headers.
+struct TenantResolver {
+    api_keys: HashMap<String, String>,
+    jwt: Option<(DecodingKey, Validation)>,
+    claim: String,
+    known: Vec<String>,
+}
+
+static RESOLVER: OnceCell<TenantResolver> = OnceCell::new();
+
+/// Enable tenant resolution, set once at startup. A no-op without tenants.
+pub fn install(config: &TenantsConfig, jwt_secret: Option<&str>) {
+    if config.credentials.is_empty() {
+        return;
+    }
+    let _ = RESOLVER.set(TenantResolver::new(config, jwt_secret));
+}
+
+/// The configured tenant of a request: by API key, else by JWT claim.
+/// `None` for unknown callers and when tenants are not configured.
+pub fn resolve(headers: &HeaderMap) -> Option<String> {
+    RESOLVER.get()?.resolve(headers)
+}
+
+impl TenantResolver {
+    fn new(config: &TenantsConfig, jwt_secret: Option<&str>) -> Self {
+        let jwt = jwt_secret.map(|secret| {
+            let mut validation = Validation::new(Algorithm::HS256);
+            validation.validate_aud = false;
+            (DecodingKey::from_secret(secret.as_bytes()), validation)
+        });
+        Self {
+            api_keys: config.api_keys.clone(),
+            jwt,
+            claim: config.jwt_claim.clone(),
+            known: config.credentials.keys().cloned().collect(),
+        }
+    }
+
+    fn resolve(&self, headers: &HeaderMap) -> Option<String> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        if let Some(key) = header(API_KEY_HEADER) {
+            return self.api_keys.get(key).cloned();
+        }
+        let (key, validation) = self.jwt.as_ref()?;
+        let token = header(header::AUTHORIZATION.as_str())?.strip_prefix("Bearer ")?;
+        let claims = jsonwebtoken::decode::<Map<String, Value>>(token, key, validation).ok()?;
+        let tenant = claims.claims.get(&self.claim)?.as_str()?;
+        self.known.iter().find(|known| *known == tenant).cloned()
+    }
+}
+
+/// Span processor sending each trace to the processor of the tenant recorded
+/// on one of its spans, and other traces to `default`.
+///
+/// The tenant must be set when a trace's first tagged span starts; spans of
+/// the trace that ended earlier go to `default`.
+pub struct TenantRoutingProcessor {
+    default: Option<Box<dyn SpanProcessor>>,
+    tenants: HashMap<String, Box<dyn SpanProcessor>>,
+    traces: Mutex<TraceTenants>,
+}
+
+#[derive(Default)]
+struct TraceTenants {
+    by_trace: HashMap<TraceId, String>,
+    order: VecDeque<TraceId>,
+}
+
+impl TraceTenants {
+    fn insert(&mut self, trace_id: TraceId, tenant: String) {
+        if self.by_trace.insert(trace_id, tenant).is_none() {
+            self.order.push_back(trace_id);
+        }
+        while self.order.len() > MAX_TRACES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.by_trace.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl TenantRoutingProcessor {
+    pub fn new(
+        default: Option<Box<dyn SpanProcessor>>,
+        tenants: HashMap<String, Box<dyn SpanProcessor>>,
+    ) -> Self {
+        Self {
+            default,
+            tenants,
+            traces: Mutex::default(),
+        }
+    }
+
+    fn processors(&self) -> impl Iterator<Item = &Box<dyn SpanProcessor>> {
+        self.default.iter().chain(self.tenants.values())
+    }
+}
+
+impl std::fmt::Debug for TenantRoutingProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TenantRoutingProcessor")
+            .field("tenants", &self.tenants.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SpanProcessor for TenantRoutingProcessor {
+    fn on_start(&self, span: &mut Span, _cx: &Context) {
+        let trace_id = span.span_context().trace_id();
+        let mut traces = self.traces.lock().expect("tenant trace lock poisoned");
+        if traces.by_trace.contains_key(&trace_id) {
+            return;
+        }
+        let tenant = span.exported_data().and_then(|data| {
+            data.attributes
+                .into_iter()
+                .find(|attribute| attribute.key.as_str() == TENANT_ATTRIBUTE)
+                .map(|attribute| attribute.value.as_str().into_owned())
+        });
+        if let Some(tenant) = tenant.filter(|tenant| self.tenants.contains_key(tenant)) {
+            traces.insert(trace_id, tenant);
+        }
+    }
+
+    fn on_end(&self, span: SpanData) {
+        // rmcp's transport spans join their session's trace at export
+        let trace_id = span_repair::session_trace_id(&span).unwrap_or(span.span_context.trace_id());
+        let tenant = self
+            .traces
+            .lock()
+            .expect("tenant trace lock poisoned")
+            .by_trace
+            .get(&trace_id)
+            .cloned();
+        let processor = match tenant {
+            Some(tenant) => self.tenants.get(&tenant),
+            None => self.default.as_ref(),
+        };
+        if let Some(processor) = processor {
+            processor.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        collect(self.processors().map(|processor| processor.force_flush()))
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        collect(
+            self.processors()
+                .map(|processor| processor.shutdown_with_timeout(timeout)),
+        )
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        for processor in self.default.iter_mut().chain(self.tenants.values_mut()) {
+            processor.set_resource(resource);
+        }
+    }
+}
+
+/// Every result, failing with the errors of those that failed.
+fn collect(results: impl Iterator<Item = OTelSdkResult>) -> OTelSdkResult {
+    let errors: Vec<String> = results
+        .filter_map(|result| result.err())
+        .map(|error| error.to_string())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(OTelSdkError::InternalFailure(errors.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{TraceContextExt as _, Tracer as _, TracerProvider as _};
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SimpleSpanProcessor};
+
+    fn tenants_config() -> TenantsConfig {
+        TenantsConfig {
+            credentials: HashMap::from([
+                ("acme".to_string(), ("pk".to_string(), "sk".to_string())),
+                ("globex".to_string(), ("pk".to_string(), "sk".to_string())),
+            ]),
+            api_keys: HashMap::from([("acme-key".to_string(), "acme".to_string())]),
+            jwt_claim: "tenant".to_string(),
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    header::HeaderName::from_static(name),
+                    value.parse().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn bearer(tenant: &str) -> String {
+        let claims = serde_json::json!({ "tenant": tenant, "exp": 4_102_444_800u64 });
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        format!("Bearer {token}")
+    }
+
+    #[test]
+    fn tenants_are_resolved_by_api_key_then_jwt_claim() {
+        let resolver = TenantResolver::new(&tenants_config(), Some("secret"));
+        let resolve = |pairs: &[(&'static str, String)]| resolver.resolve(&headers(pairs));
+
+        assert_eq!(
+            resolve(&[("x-api-key", "acme-key".into())]).as_deref(),
+            Some("acme")
+        );
+        assert_eq!(
+            resolve(&[("authorization", bearer("globex"))]).as_deref(),
+            Some("globex")
+        );
+        assert_eq!(resolve(&[("authorization", bearer("initech"))]), None);
+        assert_eq!(resolve(&[]), None);
+        // An unknown API key is not retried as a JWT
+        assert_eq!(
+            resolve(&[
+                ("x-api-key", "other".into()),
+                ("authorization", bearer("globex"))
+            ]),
+            None
+        );
+
+        let without_jwt = TenantResolver::new(&tenants_config(), None);
+        assert_eq!(
+            without_jwt.resolve(&headers(&[("authorization", bearer("globex"))])),
+            None
+        );
+    }
+
+    /// Names of the spans each processor was given.
+    struct Recorded {
+        provider: SdkTracerProvider,
+        acme: InMemorySpanExporter,
+        default: InMemorySpanExporter,
+    }
+
+    impl Recorded {
+        fn new() -> Self {
+            let acme = InMemorySpanExporter::default();
+            let default = InMemorySpanExporter::default();
+            let router = TenantRoutingProcessor::new(
+                Some(Box::new(SimpleSpanProcessor::new(default.clone()))),
+                HashMap::from([(
+                    "acme".to_string(),
+                    Box::new(SimpleSpanProcessor::new(acme.clone())) as Box<dyn SpanProcessor>,
+                )]),
+            );
+            let provider = SdkTracerProvider::builder()
+                .with_span_processor(router)
+                .build();
+            Self {
+                provider,
+                acme,
+                default,
+            }
+        }
+
+        fn names(exporter: &InMemorySpanExporter) -> Vec<String> {
+            let mut names: Vec<_> = exporter
+                .get_finished_spans()
+                .unwrap()
+                .into_iter()
+                .map(|span| span.name.into_owned())
+                .collect();
+            names.sort();
+            names
+        }
+    }
+
+    #[test]
+    fn traces_go_to_the_tenant_of_their_first_tagged_span() {
+        let recorded = Recorded::new();
+        let tracer = recorded.provider.tracer("tenants");
+        let tagged = |name: &'static str, tenant: &'static str| {
+            tracer
+                .span_builder(name)
+                .with_attributes([KeyValue::new(TENANT_ATTRIBUTE, tenant)])
+        };
+
+        tracer.in_span("untagged", |_| {});
+        let session = tracer.in_span("before", |cx| {
+            // Ended before the tenant is known
+            tracer.start_with_context("early", &cx).end();
+            let tagged = tracer.build_with_context(tagged("tagged", "acme"), &cx);
+            let session = tagged.span_context().clone();
+            let cx = cx.with_span(tagged);
+            tracer.start_with_context("child", &cx).end();
+            session
+        });
+        tracer.in_span("other", |cx| {
+            let _unknown = tracer.build_with_context(tagged("unknown", "initech"), &cx);
+        });
+
+        // rmcp's transport spans follow the trace of their session
+        let _exporter = span_repair::SessionRepairExporter::new(());
+        span_repair::register_session("tenants-test", session);
+        tracer
+            .span_builder("serve_inner")
+            .with_attributes([KeyValue::new("session.id", "tenants-test")])
+            .start(&tracer)
+            .end();
+        span_repair::forget(Some("tenants-test"));
+
+        assert_eq!(
+            Recorded::names(&recorded.acme),
+            ["before", "child", "serve_inner", "tagged"]
+        );
+        assert_eq!(
+            Recorded::names(&recorded.default),
+            ["early", "other", "unknown", "untagged"]
+        );
+    }
+
+    #[test]
+    fn the_oldest_traces_are_forgotten_first() {
+        let mut traces = TraceTenants::default();
+        let trace_id = |n: usize| TraceId::from_bytes((n as u128 + 1).to_be_bytes());
+        for n in 0..=MAX_TRACES {
+            traces.insert(trace_id(n), "acme".to_string());
+        }
+        // Seen again, so not counted twice
+        traces.insert(trace_id(MAX_TRACES), "acme".to_string());
+
+        assert_eq!(traces.by_trace.len(), MAX_TRACES);
+        assert_eq!(traces.order.len(), MAX_TRACES);
+        assert!(!traces.by_trace.contains_key(&trace_id(0)));
+        assert!(traces.by_trace.contains_key(&trace_id(1)));
+        assert!(traces.by_trace.contains_key(&trace_id(MAX_TRACES)));
+    }
+}
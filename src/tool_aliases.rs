@@ -0,0 +1,87 @@
+//! Deprecated tool names kept as aliases of their replacements, so clients
+//! written against an older tool list keep working while they migrate.
+
+use rmcp::model::{CallToolResult, Meta};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// An old tool name still accepted by `tools/call`.
+#[derive(Debug)]
+pub struct ToolAlias {
+    /// Deprecated name
+    pub alias: &'static str,
+    /// Tool the alias dispatches to
+    pub target: &'static str,
+    /// Server version the alias will be removed in
+    pub removal: &'static str,
+}
+
+/// Aliases are not listed by `tools/list`; only the replacements are.
+pub const ALIASES: &[ToolAlias] = &[
+    ToolAlias {
+        alias: "weather_current",
+        target: "get_weather",
+        removal: "2.0.0",
+    },
+    ToolAlias {
+        alias: "weather_forecast",
+        target: "get_forecast",
+        removal: "2.0.0",
+    },
+];
+
+pub fn resolve(name: &str) -> Option<&'static ToolAlias> {
+    ALIASES.iter().find(|alias| alias.alias == name)
+}
+
+impl ToolAlias {
+    /// Attach a `deprecation` notice to the result's `_meta`.
+    pub fn annotate(&self, result: &mut CallToolResult) {
+        let notice = DeprecationNotice {
+            tool: self.alias,
+            replacement: self.target,
+            removal: self.removal,
+            message: format!(
+                "'{}' is deprecated and will be removed in {}; call '{}' instead",
+                self.alias, self.removal, self.target
+            ),
+        };
+        let meta = result.meta.get_or_insert_with(Meta::new);
+        meta.0.insert(
+            "deprecation".to_string(),
+            serde_json::to_value(notice).expect("notice serializes"),
+        );
+    }
+}
+
+#[derive(Serialize)]
+struct DeprecationNotice {
+    tool: &'static str,
+    replacement: &'static str,
+    removal: &'static str,
+    message: String,
+}
+
+/// Calls made through each alias since startup.
+#[derive(Debug, Default)]
+pub struct DeprecationStats {
+    calls: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl DeprecationStats {
+    /// Count a call through `alias`, returning its new total.
+    pub fn record(&self, alias: &ToolAlias) -> u64 {
+        let mut calls = self.calls.lock().expect("deprecation stats lock poisoned");
+        let total = calls.entry(alias.alias).or_default();
+        *total += 1;
+        *total
+    }
+
+    pub fn totals(&self) -> BTreeMap<&'static str, u64> {
+        self.calls
+            .lock()
+            .expect("deprecation stats lock poisoned")
+            .clone()
+    }
+}
@@ -0,0 +1,89 @@
+//! Tool failures returned as results with `isError: true`.
+//!
+//! MCP separates protocol errors, a JSON-RPC `error` the client handles, from
+//! tool execution errors, which are results the model reads and can react to.
+//! Errors raised while running a tool (upstream failures, unknown locations,
+//! internal errors and panics) become results carrying a short text and a
+//! [`ToolErrorReport`] as structured content. Errors about the call itself
+//! (unknown tool, malformed or out-of-range arguments, denied access) stay
+//! protocol errors.
+
+use rmcp::model::{CallToolResult, Content, ErrorCode};
+use rmcp::{schemars, ErrorData as McpError};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Code of internal errors raised without a more specific one.
+pub const INTERNAL_ERROR: &str = "internal_error";
+
+/// Code of tool calls that panicked.
+pub const PANIC: &str = "panic";
+
+/// Structured content of a failed tool call, under `error`.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ToolErrorReport {
+    /// Stable identifier of the failure, e.g. `upstream_timeout`
+    pub code: String,
+    pub message: String,
+    /// Whether the same call may succeed if tried again later
+    pub retryable: bool,
+    /// Trace of the failed call, to look it up in Langfuse
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    /// `x-request-id` of the HTTP request that carried the call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// `error` with `code` and `retryable` attached, so it is returned as an
+/// `isError` result whatever its JSON-RPC code.
+pub fn with_code(mut error: McpError, code: &str, retryable: bool) -> McpError {
+    error.data = Some(json!({ "code": code, "retryable": retryable }));
+    error
+}
+
+/// The report for `error` if it is a tool execution error, `None` for
+/// protocol errors.
+pub fn report(error: &McpError, trace_id: Option<String>) -> Option<ToolErrorReport> {
+    let data = error.data.as_ref();
+    let code = data
+        .and_then(|data| data.get("code"))
+        .and_then(|code| code.as_str());
+    let code = match code {
+        Some(code) => code.to_string(),
+        None if error.code == ErrorCode::INTERNAL_ERROR => INTERNAL_ERROR.to_string(),
+        None => return None,
+    };
+    let retryable = data
+        .and_then(|data| data.get("retryable"))
+        .and_then(|retryable| retryable.as_bool())
+        .unwrap_or(false);
+    Some(ToolErrorReport {
+        code,
+        message: error.message.to_string(),
+        retryable,
+        trace_id,
+        request_id: None,
+    })
+}
+
+/// An `isError` result with a readable text and the report as
+/// `{"error": ...}` structured content.
+pub fn into_result(report: ToolErrorReport) -> CallToolResult {
+    let mut text = format!("{}: {}", report.code, report.message);
+    if report.retryable {
+        text.push_str(". This may be temporary; try again later");
+    }
+    if let Some(trace_id) = &report.trace_id {
+        text.push_str(&format!(". Trace id: {trace_id}"));
+    }
+    if let Some(request_id) = &report.request_id {
+        text.push_str(&format!(". Request id: {request_id}"));
+    }
+    CallToolResult {
+        content: vec![Content::text(text)],
+        structured_content: Some(json!({ "error": report })),
+        is_error: Some(true),
+        meta: None,
+    }
+}
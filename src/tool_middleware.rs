@@ -0,0 +1,76 @@
+use futures::future::BoxFuture;
+use rmcp::model::{CallToolRequestParam, CallToolResult, Tool};
+use rmcp::service::RequestContext;
+use rmcp::{ErrorData as McpError, RoleServer};
+use std::sync::Arc;
+
+use crate::weather_tools::WeatherService;
+
+pub type ToolResult = Result<CallToolResult, McpError>;
+
+/// A `tools/call` request on its way through the middleware chain.
+pub struct ToolCall {
+    pub request: CallToolRequestParam,
+    pub context: RequestContext<RoleServer>,
+}
+
+impl ToolCall {
+    pub fn name(&self) -> &str {
+        &self.request.name
+    }
+}
+
+/// Cross-cutting behaviour run around every tool call (auth, auditing, ...),
+/// registered on [`WeatherService`] instead of repeated in each tool body.
+///
+/// Implement `before`/`after` for simple checks and rewrites, or `around`
+/// to hold state across the call; overriding `around` replaces the default
+/// that calls the other two.
+pub trait ToolMiddleware: Send + Sync {
+    /// Runs before the tool; an error is returned without calling it.
+    fn before(&self, _call: &ToolCall) -> Result<(), McpError> {
+        Ok(())
+    }
+
+    /// Sees, and may replace, the tool's result.
+    fn after(&self, _call: &ToolCall, result: ToolResult) -> ToolResult {
+        result
+    }
+
+    /// Wraps the rest of the chain, which runs when `next` is awaited.
+    fn around<'a>(&'a self, call: &'a ToolCall, next: Next<'a>) -> BoxFuture<'a, ToolResult> {
+        Box::pin(async move {
+            self.before(call)?;
+            let result = next.run(call).await;
+            self.after(call, result)
+        })
+    }
+
+    /// Remove tools the caller should not see from a `tools/list` response.
+    fn filter_tools(
+        &self,
+        _tools: &mut Vec<Tool>,
+        _context: &RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        Ok(())
+    }
+}
+
+/// The middleware after the current one, ending at the tool router.
+pub struct Next<'a> {
+    service: &'a WeatherService,
+    chain: &'a [Arc<dyn ToolMiddleware>],
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(service: &'a WeatherService, chain: &'a [Arc<dyn ToolMiddleware>]) -> Self {
+        Self { service, chain }
+    }
+
+    pub fn run(self, call: &'a ToolCall) -> BoxFuture<'a, ToolResult> {
+        match self.chain.split_first() {
+            Some((middleware, rest)) => middleware.around(call, Next::new(self.service, rest)),
+            None => self.service.route(call),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Cursor pagination and name-prefix filtering for `tools/list`.
+//!
+//! Tools are listed by name. A cursor holds the name of the last tool of the
+//! previous page (and the prefix filter), so the next page starts after it
+//! whatever was registered or removed in between: no tool present for the
+//! whole listing is skipped or returned twice.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rmcp::model::{ListToolsResult, Meta, Tool};
+use rmcp::ErrorData as McpError;
+use serde::{Deserialize, Serialize};
+
+/// `_meta` key of a `tools/list` request restricting it to names with this prefix.
+pub const PREFIX_META_KEY: &str = "prefix";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    /// Name of the last tool already returned.
+    after: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("cursor serializes"))
+    }
+
+    fn decode(cursor: &str) -> Result<Self, McpError> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| McpError::invalid_params("invalid cursor", None))
+    }
+}
+
+/// The prefix filter from a request's `_meta`, if any.
+pub fn prefix(meta: &Meta) -> Result<Option<String>, McpError> {
+    match meta.0.get(PREFIX_META_KEY) {
+        None => Ok(None),
+        Some(prefix) => prefix
+            .as_str()
+            .map(|prefix| Some(prefix.to_string()))
+            .ok_or_else(|| McpError::invalid_params("_meta.prefix must be a string", None)),
+    }
+}
+
+/// One page of `tools`: at most `page_size` tools whose names start with
+/// `prefix`, after the `cursor` of the previous page. Later pages keep the
+/// first page's prefix; sending a different one is an error.
+pub fn page(
+    mut tools: Vec<Tool>,
+    cursor: Option<&str>,
+    prefix: Option<String>,
+    page_size: usize,
+) -> Result<ListToolsResult, McpError> {
+    let (after, prefix) = match cursor {
+        Some(cursor) => {
+            let cursor = Cursor::decode(cursor)?;
+            if prefix.is_some() && prefix != cursor.prefix {
+                return Err(McpError::invalid_params(
+                    "prefix differs from the one the cursor was issued for",
+                    None,
+                ));
+            }
+            (Some(cursor.after), cursor.prefix)
+        }
+        None => (None, prefix),
+    };
+
+    tools.retain(|tool| {
+        prefix
+            .as_deref()
+            .is_none_or(|prefix| tool.name.starts_with(prefix))
+            && after
+                .as_deref()
+                .is_none_or(|after| tool.name.as_ref() > after)
+    });
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let next_cursor = (tools.len() > page_size).then(|| {
+        tools.truncate(page_size);
+        Cursor {
+            after: tools[page_size - 1].name.to_string(),
+            prefix,
+        }
+        .encode()
+    });
+    Ok(ListToolsResult { next_cursor, tools })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Map;
+    use std::sync::Arc;
+
+    fn tool(name: &str) -> Tool {
+        Tool::new(name.to_string(), "", Arc::new(Map::new()))
+    }
+
+    fn tools(names: &[&str]) -> Vec<Tool> {
+        names.iter().map(|name| tool(name)).collect()
+    }
+
+    fn names(page: &ListToolsResult) -> Vec<&str> {
+        page.tools.iter().map(|tool| tool.name.as_ref()).collect()
+    }
+
+    #[test]
+    fn pages_follow_names_until_the_last() {
+        let registered = tools(&[
+            "get_weather",
+            "add_favorite",
+            "get_forecast",
+            "rate_response",
+        ]);
+        let first = page(registered.clone(), None, None, 2).unwrap();
+        assert_eq!(names(&first), ["add_favorite", "get_forecast"]);
+
+        let cursor = first.next_cursor.as_deref();
+        let second = page(registered, cursor, None, 2).unwrap();
+        assert_eq!(names(&second), ["get_weather", "rate_response"]);
+        assert_eq!(second.next_cursor, None);
+    }
+
+    #[test]
+    fn cursor_is_stable_while_tools_are_registered_and_removed() {
+        let first = page(tools(&["b", "d", "f", "h"]), None, None, 2).unwrap();
+        assert_eq!(names(&first), ["b", "d"]);
+
+        // "a" and "c" sort before the cursor, "e" after; "f" is removed
+        let registered = tools(&["a", "b", "c", "d", "e", "h"]);
+        let second = page(registered, first.next_cursor.as_deref(), None, 2).unwrap();
+        assert_eq!(names(&second), ["e", "h"]);
+        assert_eq!(second.next_cursor, None);
+    }
+
+    #[test]
+    fn cursor_survives_the_removal_of_its_own_tool() {
+        let first = page(tools(&["a", "b", "c"]), None, None, 1).unwrap();
+        let second = page(tools(&["b", "c"]), first.next_cursor.as_deref(), None, 1).unwrap();
+        assert_eq!(names(&second), ["b"]);
+    }
+
+    #[test]
+    fn prefix_filters_every_page() {
+        let registered = tools(&[
+            "get_forecast",
+            "get_uv_index",
+            "get_weather",
+            "list_watches",
+        ]);
+        let first = page(registered.clone(), None, Some("get_".to_string()), 2).unwrap();
+        assert_eq!(names(&first), ["get_forecast", "get_uv_index"]);
+
+        let second = page(registered.clone(), first.next_cursor.as_deref(), None, 2).unwrap();
+        assert_eq!(names(&second), ["get_weather"]);
+
+        let other = Some("list_".to_string());
+        assert!(page(registered, first.next_cursor.as_deref(), other, 2).is_err());
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        assert!(page(tools(&["a"]), Some("not a cursor"), None, 1).is_err());
+    }
+}
@@ -1,35 +1,371 @@
+use axum::http::{request::Parts, HeaderMap};
+use once_cell::sync::OnceCell;
+use opentelemetry::{
+    trace::{Status, TraceContextExt, TraceId},
+    Array, Context, KeyValue, StringValue, Value,
+};
+use rmcp::{service::RequestContext, RoleServer};
+use rmcp_otel::TraceParentContext;
 use serde::Serialize;
 use serde_json::json;
+use std::any::Any;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-/// Setup trace context and record input parameters for a tool function.
-/// Call this at the beginning of your tool function.
-pub async fn trace_setup_input<T: Serialize>(args: &T) {
-    // Try to get stored trace context and attach it
-    if let Some(ctx) = crate::trace_store::get_current_trace_context().await {
-        // Ignore potential failure if the span is already closed
-        let _ = tracing::Span::current().set_parent(ctx);
+use crate::config::TraceLabels;
+use crate::request_id::REQUEST_ID_HEADER;
+use crate::tenants;
+
+/// Header carrying the end user, recorded as `langfuse.user.id`.
+pub const USER_ID_HEADER: &str = "x-user-id";
+
+/// Span attribute carrying the request id.
+pub const REQUEST_ID_ATTRIBUTE: &str = "request.id";
+
+/// Which Langfuse session and user a request belongs to.
+///
+/// Built from the request headers by `TracePropagationLayer` and stored in the
+/// request extensions, where tools and REST handlers pick it up.
+#[derive(Clone, Debug, Default)]
+pub struct RequestAttribution {
+    /// The MCP session id, so each conversation groups into one Langfuse session.
+    pub session_id: Option<String>,
+    pub user_id: Option<String>,
+    /// Tenant whose Langfuse project receives the trace.
+    pub tenant: Option<String>,
+    /// The request's `x-request-id`, set by `request_id::assign`.
+    pub request_id: Option<String>,
+}
+
+impl RequestAttribution {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        Self {
+            session_id: header("mcp-session-id"),
+            user_id: header(USER_ID_HEADER),
+            tenant: tenants::resolve(headers),
+            request_id: header(REQUEST_ID_HEADER.as_str()),
+        }
+    }
+
+    /// Attribution of the HTTP request that carried an MCP call.
+    pub fn from_request_context(context: &RequestContext<RoleServer>) -> Self {
+        context
+            .extensions
+            .get::<Parts>()
+            .and_then(|parts| parts.extensions.get::<Self>())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// `TracePropagationLayer` hook: attribute each request to its session,
+/// user and tenant for the handlers, and enrich its `http.request` span like
+/// the tool spans before the span starts, so the trace is routed to the
+/// tenant's project.
+pub fn attribute_request(request: &mut axum::extract::Request, span: &tracing::Span) {
+    let attribution = RequestAttribution::from_headers(request.headers());
+    enrich_span(span, &attribution);
+    request.extensions_mut().insert(attribution);
+}
+
+/// Configured trace labels, set once at startup.
+static TRACE_LABELS: OnceCell<TraceLabels> = OnceCell::new();
+
+/// Install the trace name, tags and environment applied by [`enrich_span`].
+pub fn set_trace_labels(labels: TraceLabels) {
+    let _ = TRACE_LABELS.set(labels);
+}
+
+/// Record `langfuse.session.id`, `langfuse.user.id`, `tenant.id`,
+/// `request.id` and the configured trace labels on `span`.
+pub fn enrich_span(span: &tracing::Span, attribution: &RequestAttribution) {
+    if let Some(session_id) = &attribution.session_id {
+        span.set_attribute("langfuse.session.id", session_id.clone());
+    }
+    if let Some(user_id) = &attribution.user_id {
+        span.set_attribute("langfuse.user.id", user_id.clone());
+    }
+    if let Some(tenant) = &attribution.tenant {
+        span.set_attribute(tenants::TENANT_ATTRIBUTE, tenant.clone());
+    }
+    if let Some(request_id) = &attribution.request_id {
+        span.set_attribute(REQUEST_ID_ATTRIBUTE, request_id.clone());
+    }
+
+    let Some(labels) = TRACE_LABELS.get() else {
+        return;
+    };
+    if let Some(name) = &labels.name {
+        span.set_attribute("langfuse.trace.name", name.clone());
+    }
+    if let Some(environment) = &labels.environment {
+        span.set_attribute("langfuse.environment", environment.clone());
+    }
+    if !labels.tags.is_empty() {
+        span.set_attribute("langfuse.trace.tags", tags_value(&labels.tags, &[]));
+    }
+}
+
+/// Add per-call tags to the current span's trace, on top of the configured ones.
+///
+/// Usage:
+/// ```rust,ignore
+/// trace_tags(&["open-meteo"]);
+/// ```
+pub fn trace_tags(tags: &[&str]) {
+    let configured = TRACE_LABELS
+        .get()
+        .map(|labels| labels.tags.as_slice())
+        .unwrap_or_default();
+    tracing::Span::current().set_attribute("langfuse.trace.tags", tags_value(configured, tags));
+}
+
+fn tags_value(configured: &[String], extra: &[&str]) -> Value {
+    let mut tags: Vec<StringValue> = configured.iter().cloned().map(StringValue::from).collect();
+    for tag in extra {
+        if !configured.iter().any(|existing| existing == tag) {
+            tags.push(StringValue::from(tag.to_string()));
+        }
     }
+    Value::Array(Array::String(tags))
+}
+
+/// Context a tool call's spans open under, put in the call's
+/// `RequestContext` extensions by `WeatherService::call_tool`: the
+/// session's `mcp.session` span.
+#[derive(Clone, Debug)]
+pub struct CallTraceContext(pub Context);
+
+/// Tool spans by where [`call_trace_context`] found their parent, since
+/// startup.
+static SESSION_PARENTS: AtomicU64 = AtomicU64::new(0);
+static REQUEST_PARENTS: AtomicU64 = AtomicU64::new(0);
+static NO_PARENTS: AtomicU64 = AtomicU64::new(0);
+
+/// Point-in-time copy of the tool span parent counts.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceParentTotals {
+    /// Parented on the session's `mcp.session` span
+    pub session: u64,
+    /// Parented on the `http.request` span, for calls outside a session
+    pub request: u64,
+    /// Without a parent, each a root trace of its own
+    pub none: u64,
+}
+
+pub fn trace_parent_totals() -> TraceParentTotals {
+    TraceParentTotals {
+        session: SESSION_PARENTS.load(Ordering::Relaxed),
+        request: REQUEST_PARENTS.load(Ordering::Relaxed),
+        none: NO_PARENTS.load(Ordering::Relaxed),
+    }
+}
+
+/// Parent for the spans of a tool call: its [`CallTraceContext`], else the
+/// span of the HTTP request that carried it. Counted in
+/// [`trace_parent_totals`], so call it once per tool span.
+pub fn call_trace_context(context: &RequestContext<RoleServer>) -> Option<Context> {
+    let parent = call_parent(context);
+    let counter = match &parent {
+        Some(CallParent::Session(_)) => &SESSION_PARENTS,
+        Some(CallParent::Request(_)) => &REQUEST_PARENTS,
+        None => &NO_PARENTS,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+    parent.map(CallParent::into_context)
+}
+
+/// The context [`call_trace_context`] returns, without counting it, for
+/// work outliving the call such as watches.
+pub fn call_parent_context(context: &RequestContext<RoleServer>) -> Option<Context> {
+    call_parent(context).map(CallParent::into_context)
+}
+
+enum CallParent {
+    Session(Context),
+    Request(Context),
+}
+
+impl CallParent {
+    fn into_context(self) -> Context {
+        match self {
+            Self::Session(context) | Self::Request(context) => context,
+        }
+    }
+}
+
+fn call_parent(context: &RequestContext<RoleServer>) -> Option<CallParent> {
+    if let Some(CallTraceContext(session)) = context.extensions.get() {
+        return Some(CallParent::Session(session.clone()));
+    }
+    context
+        .extensions
+        .get::<Parts>()
+        .and_then(|parts| parts.extensions.get::<TraceParentContext>())
+        .map(|TraceParentContext(request)| CallParent::Request(request.clone()))
+}
+
+/// Parent the current tool span on the call's context, record session and
+/// user attribution, and link it to the `http.request` span of the request
+/// that carried the call. Call it first, before the span starts (its id is
+/// read by [`trace_rmcp_setup`]).
+///
+/// Usage:
+/// ```rust,ignore
+/// trace_rmcp_context(&request_context);
+/// ```
+pub fn trace_rmcp_context(context: &RequestContext<RoleServer>) {
+    let span = tracing::Span::current();
+    if let Some(parent) = call_trace_context(context) {
+        // Ignore failure if the span has already started
+        let _ = span.set_parent(parent);
+    }
+    enrich_span(&span, &RequestAttribution::from_request_context(context));
+    link_request_span(&span, context);
+}
 
+/// Tool spans are parented on the session span, not on the HTTP request
+/// that carried the call, which is its own trace. A link keeps the way from
+/// one to the other.
+fn link_request_span(span: &tracing::Span, context: &RequestContext<RoleServer>) {
+    let request = context
+        .extensions
+        .get::<Parts>()
+        .and_then(|parts| parts.extensions.get::<TraceParentContext>());
+    if let Some(TraceParentContext(request)) = request {
+        span.add_link_with_attributes(
+            request.span().span_context().clone(),
+            vec![KeyValue::new("link.kind", "http.request")],
+        );
+    }
+}
+
+tokio::task_local! {
+    /// Trace id slot of the tool call running on this task.
+    static TOOL_TRACE_ID: Arc<OnceLock<String>>;
+}
+
+/// Run a tool call so its span can report the trace id via [`note_trace_id`],
+/// read with [`tool_trace_id`] while the call runs.
+pub async fn scope_tool_trace<F: Future>(call: F) -> F::Output {
+    TOOL_TRACE_ID.scope(Arc::new(OnceLock::new()), call).await
+}
+
+/// Remember the trace of `span` (the tool's span, once parented) for the
+/// tool call in progress. A no-op outside [`scope_tool_trace`].
+pub fn note_trace_id(span: &tracing::Span) {
+    let _ = TOOL_TRACE_ID.try_with(|slot| {
+        let trace_id = span.context().span().span_context().trace_id();
+        if trace_id != TraceId::INVALID {
+            let _ = slot.set(trace_id.to_string());
+        }
+    });
+}
+
+/// Trace id of the tool call in progress, once its span has reported it.
+pub fn tool_trace_id() -> Option<String> {
+    TOOL_TRACE_ID
+        .try_with(|slot| slot.get().cloned())
+        .ok()
+        .flatten()
+}
+
+pub use rmcp_otel::{inject_headers, spawn_traced};
+
+/// Whether attributes recorded on `span` can end up in an exported trace.
+///
+/// False when the span is disabled by the log filter or the OTel sampler
+/// dropped its trace, so callers can skip serializing large payloads.
+pub fn is_recorded(span: &tracing::Span) -> bool {
+    !span.is_disabled() && span.context().span().span_context().is_sampled()
+}
+
+/// Recorded in the `input` attribute in place of a `#[sensitive]` field.
+pub const SENSITIVE_PLACEHOLDER: &str = "***";
+
+/// Argument types with fields that must not appear in recorded input,
+/// implemented by `#[derive(Sensitive)]` from `rmcp-trace-macro` for the
+/// fields marked `#[sensitive]`.
+pub trait Sensitive {
+    /// JSON keys of the sensitive fields, after serde renaming.
+    const SENSITIVE_FIELDS: &'static [&'static str];
+}
+
+/// Arguments as `#[trace_io]` records them. Method resolution picks
+/// [`MaskSensitive`] for `Sensitive` types and falls back to
+/// [`MaskNothing`] for the others, so arguments need no extra derive:
+///
+/// ```rust,ignore
+/// use crate::trace_utils::{MaskNothing as _, MaskSensitive as _};
+/// let input = (&InputArgs(&args)).to_input_json();
+/// ```
+pub struct InputArgs<'a, T>(pub &'a T);
+
+pub trait MaskSensitive {
+    fn to_input_json(&self) -> serde_json::Value;
+}
+
+impl<T: Serialize + Sensitive> MaskSensitive for InputArgs<'_, T> {
+    fn to_input_json(&self) -> serde_json::Value {
+        let mut input = json!(self.0);
+        if let serde_json::Value::Object(fields) = &mut input {
+            for name in T::SENSITIVE_FIELDS {
+                // Absent values stay visible as null
+                if let Some(value) = fields.get_mut(*name).filter(|value| !value.is_null()) {
+                    *value = json!(SENSITIVE_PLACEHOLDER);
+                }
+            }
+        }
+        input
+    }
+}
+
+pub trait MaskNothing {
+    fn to_input_json(&self) -> serde_json::Value;
+}
+
+impl<T: Serialize> MaskNothing for &InputArgs<'_, T> {
+    fn to_input_json(&self) -> serde_json::Value {
+        json!(self.0)
+    }
+}
+
+/// Record input parameters for a tool function.
+/// Call this at the beginning of your tool function.
+pub fn trace_setup_input<T: Serialize>(args: &T) {
     // Record input parameters as span attribute
-    let input_json = json!(args);
-    tracing::Span::current().record("input", tracing::field::display(&input_json.to_string()));
+    let span = tracing::Span::current();
+    note_trace_id(&span);
+    crate::feature_flags::record(&span);
+    if is_recorded(&span) {
+        let input_json = json!(args);
+        span.record("input", tracing::field::display(&input_json.to_string()));
+    }
 }
 
 /// Convenience function that combines all tracing setup for RMCP tools.
 /// Returns the extracted args after setting up tracing.
 ///
 /// Usage:
-/// ```rust
-/// let args = trace_rmcp_setup(params).await;
+/// ```rust,ignore
+/// let args = trace_rmcp_setup(params);
 /// ```
-pub async fn trace_rmcp_setup<T: for<'de> serde::Deserialize<'de> + Serialize>(
+pub fn trace_rmcp_setup<T: for<'de> serde::Deserialize<'de> + Serialize>(
     params: rmcp::handler::server::wrapper::Parameters<T>,
 ) -> T {
     let rmcp::handler::server::wrapper::Parameters(args) = params;
 
-    // Setup trace context and input
-    trace_setup_input(&args).await;
+    // Record the input
+    trace_setup_input(&args);
 
     args
 }
@@ -37,13 +373,121 @@ pub async fn trace_rmcp_setup<T: for<'de> serde::Deserialize<'de> + Serialize>(
 /// Convenience function for recording output and returning result.
 ///
 /// Usage:
-/// ```rust
+/// ```rust,ignore
 /// trace_rmcp_result(json!(&weather))
 /// ```
 pub fn trace_rmcp_result<T: Serialize>(
     output_data: T,
 ) -> Result<rmcp::model::CallToolResult, rmcp::ErrorData> {
     let json_value = json!(&output_data);
-    tracing::Span::current().record("output", tracing::field::display(&json_value.to_string()));
+    let span = tracing::Span::current();
+    // Again here, for the tools without arguments
+    crate::feature_flags::record(&span);
+    if is_recorded(&span) {
+        span.record("output", tracing::field::display(&json_value.to_string()));
+    }
     Ok(rmcp::model::CallToolResult::structured(json_value))
 }
+
+/// Add an OpenTelemetry event to the current span, with `key = value`
+/// attributes. Unlike a log line, it reaches the span whatever the log
+/// filter, so cache hits, retries and injected faults show up the same way
+/// in every trace. Values convert into [`Value`]: strings, `i64`, `f64`
+/// and `bool`.
+///
+/// Usage:
+/// ```rust,ignore
+/// trace_event!("cache.hit", cache.state = "stale", cache.revalidating = true);
+/// ```
+#[macro_export]
+macro_rules! trace_event {
+    ($name:expr $(, $($key:ident).+ = $value:expr)* $(,)?) => {
+        $crate::trace_utils::add_event(
+            $name,
+            vec![$(::opentelemetry::KeyValue::new(stringify!($($key).+), $value)),*],
+        )
+    };
+}
+
+/// Add the event `name` to the current span; see [`trace_event!`].
+pub fn add_event(name: &'static str, attributes: Vec<KeyValue>) {
+    tracing::Span::current().add_event(name, attributes);
+}
+
+/// Mark the span active when a thread panics as failed, with the panic message
+/// as an `exception` event, then run the previously installed hook.
+///
+/// Panics unwind out of the span before they can be caught, so this is the
+/// only point where the failing tool span is still current.
+pub fn record_panics_on_spans() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info.payload_as_str().unwrap_or("Box<dyn Any>").to_string();
+        let mut attributes = vec![
+            KeyValue::new("exception.type", "panic"),
+            KeyValue::new("exception.message", message.clone()),
+        ];
+        if let Some(location) = info.location() {
+            attributes.push(KeyValue::new("code.filepath", location.file().to_string()));
+            attributes.push(KeyValue::new("code.lineno", i64::from(location.line())));
+        }
+        let span = tracing::Span::current();
+        span.add_event("exception", attributes);
+        span.set_status(Status::error(message));
+        previous(info);
+    }));
+}
+
+/// Message of a payload caught with `catch_unwind`.
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Login {
+        user: String,
+        #[serde(rename = "apiKey")]
+        api_key: String,
+        token: Option<String>,
+    }
+
+    impl Sensitive for Login {
+        const SENSITIVE_FIELDS: &'static [&'static str] = &["apiKey", "token"];
+    }
+
+    #[derive(Serialize)]
+    struct Lookup {
+        location: String,
+    }
+
+    #[test]
+    // Borrowed as `#[trace_io]` does, for the fallback to resolve
+    #[allow(clippy::needless_borrow)]
+    fn only_sensitive_fields_are_masked_in_recorded_input() {
+        let login = Login {
+            user: "ada".to_string(),
+            api_key: "secret".to_string(),
+            token: None,
+        };
+        let lookup = Lookup {
+            location: "London".to_string(),
+        };
+
+        assert_eq!(
+            (&InputArgs(&login)).to_input_json(),
+            json!({ "user": "ada", "apiKey": "***", "token": null })
+        );
+        assert_eq!(
+            (&InputArgs(&lookup)).to_input_json(),
+            json!({ "location": "London" })
+        );
+    }
+}
@@ -1,71 +1,577 @@
-use anyhow::Result;
-use opentelemetry::{global, trace::TracerProvider as _, KeyValue};
+use anyhow::{anyhow, bail, Context, Result};
+use opentelemetry::{
+    global,
+    trace::{SpanId, TraceId, TracerProvider as _},
+    KeyValue,
+};
 use opentelemetry_langfuse::ExporterBuilder;
 use opentelemetry_sdk::{
-    propagation::TraceContextPropagator, resource::Resource, trace::SdkTracerProvider,
+    error::OTelSdkResult,
+    propagation::TraceContextPropagator,
+    resource::Resource,
+    runtime,
+    trace::{
+        span_processor_with_async_runtime::BatchSpanProcessor, BatchConfigBuilder,
+        SdkTracerProvider, Span, SpanData, SpanExporter, SpanProcessor,
+    },
 };
 use opentelemetry_semantic_conventions::resource::{SERVICE_NAME, SERVICE_VERSION};
-use std::env;
-use tracing::{Metadata, Subscriber};
+use sentry::ClientInitGuard;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tracing::{info, warn, Event, Subscriber};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
+use tracing_opentelemetry::OtelData;
 use tracing_subscriber::{
-    fmt::{self, format::FmtSpan, time::UtcTime},
-    layer::{Filter, SubscriberExt},
+    field::RecordFields,
+    fmt::{
+        self,
+        format::{DefaultFields, FmtSpan, Writer},
+        time::UtcTime,
+        FmtContext, FormatEvent, FormatFields,
+    },
+    layer::SubscriberExt,
+    registry::{LookupSpan, Scope},
+    reload,
     util::SubscriberInitExt,
-    EnvFilter, Layer,
+    EnvFilter, Layer, Registry,
 };
 
-/// Filter to exclude rmcp library internal spans that don't have proper parent context
-#[derive(Debug, Clone)]
-struct RmcpSpanFilter;
+use crate::build_info;
+use crate::config::{
+    env_string, BatchExportConfig, Config, LogRotation, LoggingConfig, SelfTestMode,
+    TelemetryConfig, TenantsConfig,
+};
+use crate::error_reporting;
+use crate::export_stats::{CountingExporter, EndedSpanCounter, ExportStats};
+use crate::resource_detection;
+use crate::self_test::SpanProbe;
+use crate::span_file::JsonlSpanExporter;
+use crate::span_limits::AttributeGuardExporter;
+use crate::span_repair::{self, SessionRepairExporter};
+use crate::tenants::TenantRoutingProcessor;
+
+/// Filter used when `RUST_LOG` is unset or invalid.
+const DEFAULT_LOG_FILTER: &str = "info,tokio=info";
+
+/// Telemetry handles that must live until shutdown so buffered data is flushed.
+pub struct Telemetry {
+    pub tracer_provider: SdkTracerProvider,
+    pub log_level: LogLevelHandle,
+    pub exporter: ExporterHandle,
+    /// Span counts for the Langfuse pipeline (all zero when export is disabled).
+    pub export_stats: Arc<ExportStats>,
+    /// Flushes the non-blocking log file writer when dropped.
+    _log_guard: Option<WorkerGuard>,
+    /// Sends queued Sentry reports when dropped.
+    _sentry_guard: Option<ClientInitGuard>,
+    /// Writes the `--profile-out` Chrome trace when dropped.
+    _profile_guard: Option<FlushGuard>,
+}
+
+/// Console formatter that prefixes each line with the OTel trace and span id
+/// of the innermost exported span, so ids can be pasted into Langfuse search.
+struct OtelIdsFormat<F> {
+    inner: F,
+}
 
-impl<S> Filter<S> for RmcpSpanFilter
+impl<S, N, F> FormatEvent<S, N> for OtelIdsFormat<F>
 where
-    S: Subscriber,
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    F: FormatEvent<S, N>,
 {
-    fn enabled(
+    fn format_event(
         &self,
-        meta: &Metadata<'_>,
-        _cx: &tracing_subscriber::layer::Context<'_, S>,
-    ) -> bool {
-        // Filter out rmcp internal spans that don't propagate trace context properly
-        let name = meta.name();
-        let target = meta.target();
-
-        // Exclude serve_inner and streamable_http_session spans from rmcp
-        if target.starts_with("rmcp")
-            && (name == "serve_inner" || name == "streamable_http_session")
-        {
-            return false;
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        if let Some((trace_id, span_id)) = ctx.event_scope().and_then(otel_ids) {
+            write!(writer, "trace_id={trace_id} span_id={span_id} ")?;
         }
+        self.inner.format_event(ctx, writer, event)
+    }
+}
+
+/// OTel trace and span id of the innermost exported span in `scope`.
+///
+/// Spans disabled for the OTel layer have no OTel data, so this walks up to
+/// the nearest ancestor that does.
+pub fn otel_ids<S>(scope: Scope<'_, S>) -> Option<(TraceId, SpanId)>
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    scope.into_iter().find_map(|span| {
+        let extensions = span.extensions();
+        let data = extensions.get::<OtelData>()?;
+        Some((data.trace_id()?, data.span_id()?))
+    })
+}
+
+/// Swaps the global log filter at runtime without restarting the server.
+#[derive(Clone)]
+pub struct LogLevelHandle(reload::Handle<EnvFilter, Registry>);
 
-        true
+impl LogLevelHandle {
+    /// Replace the active filter with `directives` (same syntax as `RUST_LOG`).
+    pub fn set(&self, directives: &str) -> Result<()> {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|error| anyhow!("invalid log filter '{directives}': {error}"))?;
+        self.0.reload(filter)?;
+        info!(filter = directives, "Log filter reloaded");
+        Ok(())
+    }
+
+    /// The active filter, in `RUST_LOG` syntax.
+    pub fn current(&self) -> String {
+        self.0
+            .with_current(|filter| filter.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Re-read `RUST_LOG` (from `.env` first, then the process environment)
+    /// every time the process receives SIGHUP.
+    #[cfg(unix)]
+    pub fn reload_on_sighup(self) -> Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangups = signal(SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangups.recv().await.is_some() {
+                let directives = rust_log_from_env();
+                if let Err(error) = self.set(&directives) {
+                    warn!(error = %format!("{error:#}"), "Ignoring SIGHUP log filter reload");
+                }
+            }
+        });
+        Ok(())
     }
 }
 
-/// Initialise tracing so that `tracing` spans (including Tokio runtime spans)
-/// are forwarded to the configured OpenTelemetry exporter and to stdout.
-pub fn init_tracing() -> Result<SdkTracerProvider> {
-    // Ensure trace context propagation (e.g. W3C traceparent headers).
-    global::set_text_map_propagator(TraceContextPropagator::new());
+/// `RUST_LOG` as currently written in `.env`, so edits are picked up on reload.
+#[cfg(unix)]
+fn rust_log_from_env() -> String {
+    std::fs::read_to_string(".env")
+        .ok()
+        .and_then(|contents| {
+            contents.lines().find_map(|line| {
+                let value = line.trim().strip_prefix("RUST_LOG=")?;
+                Some(value.trim().trim_matches('"').to_string())
+            })
+        })
+        .filter(|value| !value.is_empty())
+        .or_else(|| env_string("RUST_LOG"))
+        .unwrap_or_else(|| DEFAULT_LOG_FILTER.to_string())
+}
+
+/// Field formatter for the log file.
+///
+/// Formatted span fields are cached per formatter type, so the file layer needs
+/// its own type to avoid reusing the coloured output of the console layer.
+struct PlainFields(DefaultFields);
+
+impl<'writer> FormatFields<'writer> for PlainFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        writer: Writer<'writer>,
+        fields: R,
+    ) -> std::fmt::Result {
+        self.0.format_fields(writer, fields)
+    }
+}
+
+/// Langfuse base URL from `LANGFUSE_BASE_URL` or `LANGFUSE_HOST`.
+///
+/// The exporter crate only reads `LANGFUSE_HOST`, so resolve both here.
+pub fn langfuse_host() -> String {
+    env_string("LANGFUSE_BASE_URL")
+        .or_else(|| env_string("LANGFUSE_HOST"))
+        .unwrap_or_else(|| "https://cloud.langfuse.com".to_string())
+}
+
+/// Langfuse credential variables that are not set.
+pub fn missing_langfuse_credentials() -> Vec<&'static str> {
+    ["LANGFUSE_PUBLIC_KEY", "LANGFUSE_SECRET_KEY"]
+        .into_iter()
+        .filter(|key| env_string(key).is_none())
+        .collect()
+}
 
-    // Build the resource with service information
-    let resource = Resource::builder()
+/// Service, deployment, host, OS, process and container information attached
+/// to every exported span.
+fn resource(telemetry: &TelemetryConfig) -> Resource {
+    let mut builder = Resource::builder()
+        .with_detectors(&resource_detection::detectors())
         .with_attributes([
             KeyValue::new(SERVICE_NAME, "weather-assistant-rust"),
             KeyValue::new(SERVICE_VERSION, env!("CARGO_PKG_VERSION")),
         ])
-        .build();
+        .with_attributes(build_info::resource_attributes());
+    if let Some(environment) = &telemetry.deployment_environment {
+        builder =
+            builder.with_attribute(KeyValue::new("deployment.environment", environment.clone()));
+    }
+    builder.build()
+}
+
+/// Langfuse project the server's own spans are exported to, replaceable at
+/// runtime with [`ExporterHandle::reconfigure`].
+#[derive(Debug, Clone)]
+pub struct LangfuseTarget {
+    pub host: String,
+    pub public_key: Option<String>,
+    pub secret_key: Option<String>,
+}
+
+impl LangfuseTarget {
+    /// From `LANGFUSE_BASE_URL`/`LANGFUSE_HOST` and the `LANGFUSE_*` keys.
+    pub fn from_env() -> Self {
+        Self {
+            host: langfuse_host(),
+            public_key: env_string("LANGFUSE_PUBLIC_KEY"),
+            secret_key: env_string("LANGFUSE_SECRET_KEY"),
+        }
+    }
+
+    fn keys(&self) -> Option<(String, String)> {
+        Some((self.public_key.clone()?, self.secret_key.clone()?))
+    }
+}
+
+/// Batch processor exporting to the Langfuse project at `host` with `keys`
+/// (public, secret). `routed` when it sees only part of the spans.
+///
+/// The batch processor runs on the Tokio runtime because the OTLP exporter uses
+/// the async reqwest client, which needs a reactor. Must be called from within
+/// a Tokio runtime.
+fn langfuse_processor(
+    telemetry: &TelemetryConfig,
+    host: &str,
+    (public_key, secret_key): &(String, String),
+    routed: bool,
+    stats: Arc<ExportStats>,
+) -> Result<BatchSpanProcessor<runtime::Tokio>> {
+    let exporter = ExporterBuilder::new()
+        .with_basic_auth(public_key, secret_key)
+        .with_host(host)
+        .build()?;
+    let exporter = AttributeGuardExporter::new(exporter, telemetry.limits.clone());
+    let exporter = if routed {
+        SessionRepairExporter::routed(exporter)
+    } else {
+        SessionRepairExporter::new(exporter)
+    };
 
-    // Create the Langfuse exporter from environment configuration
-    // This automatically wires up credentials and endpoint via LANGFUSE_* vars
-    let exporter = ExporterBuilder::from_env()?.build()?;
+    Ok(batch_processor(
+        CountingExporter::new(exporter, stats),
+        &telemetry.batch,
+    ))
+}
+
+/// Processor sending each tenant's traces to its own Langfuse project, and
+/// the others to `target` when it has keys.
+fn tenant_processor(
+    telemetry: &TelemetryConfig,
+    tenants: &TenantsConfig,
+    target: &LangfuseTarget,
+    stats: Arc<ExportStats>,
+) -> Result<TenantRoutingProcessor> {
+    let default: Option<Box<dyn SpanProcessor>> = match target.keys() {
+        Some(keys) => Some(Box::new(langfuse_processor(
+            telemetry,
+            &target.host,
+            &keys,
+            true,
+            stats.clone(),
+        )?)),
+        None => None,
+    };
+    let mut processors: HashMap<String, Box<dyn SpanProcessor>> = HashMap::new();
+    for (tenant, keys) in &tenants.credentials {
+        let processor = langfuse_processor(telemetry, &target.host, keys, true, stats.clone())?;
+        processors.insert(tenant.clone(), Box::new(processor));
+    }
+    Ok(TenantRoutingProcessor::new(default, processors))
+}
+
+/// Everything exported to Langfuse: the tenant router when tenants are
+/// configured, else `target`'s project. `None` when there is nothing to
+/// export to.
+fn langfuse_pipeline(
+    telemetry: &TelemetryConfig,
+    tenants: &TenantsConfig,
+    target: &LangfuseTarget,
+    stats: Arc<ExportStats>,
+) -> Result<Option<Box<dyn SpanProcessor>>> {
+    if !tenants.credentials.is_empty() {
+        let processor = tenant_processor(telemetry, tenants, target, stats)?;
+        return Ok(Some(Box::new(processor)));
+    }
+    match target.keys() {
+        Some(keys) => {
+            let processor = langfuse_processor(telemetry, &target.host, &keys, true, stats)?;
+            Ok(Some(Box::new(processor)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Span processor forwarding to a Langfuse pipeline that can be replaced
+/// while spans are in flight. Spans ending after a swap go to the new one.
+#[derive(Debug)]
+struct SwappableProcessor {
+    pipeline: Arc<RwLock<Option<Box<dyn SpanProcessor>>>>,
+    ended: EndedSpanCounter,
+}
+
+impl SpanProcessor for SwappableProcessor {
+    fn on_start(&self, span: &mut Span, cx: &opentelemetry::Context) {
+        if let Some(processor) = &*self.pipeline.read().expect("exporter lock poisoned") {
+            processor.on_start(span, cx);
+        }
+    }
 
-    // Build the tracer provider with batch processing
-    let provider = SdkTracerProvider::builder()
-        .with_resource(resource)
-        .with_batch_exporter(exporter)
+    fn on_end(&self, span: SpanData) {
+        if let Some(processor) = &*self.pipeline.read().expect("exporter lock poisoned") {
+            self.ended.count(&span);
+            processor.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        match &*self.pipeline.read().expect("exporter lock poisoned") {
+            Some(processor) => processor.force_flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        match &*self.pipeline.read().expect("exporter lock poisoned") {
+            Some(processor) => processor.shutdown_with_timeout(timeout),
+            None => Ok(()),
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        if let Some(processor) = &mut *self.pipeline.write().expect("exporter lock poisoned") {
+            processor.set_resource(resource);
+        }
+    }
+}
+
+/// Replaces the Langfuse exporter at runtime, e.g. to rotate keys or point
+/// at another Langfuse instance, without restarting the server.
+///
+/// The tracing layer keeps the same tracer provider; only the processor
+/// behind it is rebuilt and swapped.
+#[derive(Clone)]
+pub struct ExporterHandle {
+    pipeline: Arc<RwLock<Option<Box<dyn SpanProcessor>>>>,
+    target: Arc<Mutex<LangfuseTarget>>,
+    /// Held for the whole swap so concurrent updates apply in order.
+    updating: Arc<tokio::sync::Mutex<()>>,
+    telemetry: TelemetryConfig,
+    tenants: TenantsConfig,
+    resource: Resource,
+    stats: Arc<ExportStats>,
+}
+
+impl ExporterHandle {
+    /// The Langfuse project spans are currently exported to.
+    pub fn target(&self) -> LangfuseTarget {
+        self.target.lock().expect("exporter lock poisoned").clone()
+    }
+
+    /// Tenants with their own Langfuse project; they keep their keys.
+    pub fn tenants(&self) -> Vec<String> {
+        let mut tenants: Vec<String> = self.tenants.credentials.keys().cloned().collect();
+        tenants.sort();
+        tenants
+    }
+
+    /// Build an exporter for `target` and swap it in. Spans queued for the
+    /// previous exporter are flushed to it before it shuts down.
+    pub async fn reconfigure(&self, target: LangfuseTarget) -> Result<()> {
+        let _updating = self.updating.lock().await;
+        reqwest::Url::parse(&target.host)
+            .with_context(|| format!("invalid Langfuse host '{}'", target.host))?;
+        let Some(mut pipeline) =
+            langfuse_pipeline(&self.telemetry, &self.tenants, &target, self.stats.clone())?
+        else {
+            bail!("both Langfuse keys are required");
+        };
+        pipeline.set_resource(&self.resource);
+
+        let previous = self
+            .pipeline
+            .write()
+            .expect("exporter lock poisoned")
+            .replace(pipeline);
+        if previous.is_none() {
+            span_repair::count_router();
+        }
+        *self.target.lock().expect("exporter lock poisoned") = target.clone();
+        info!(
+            host = %target.host,
+            public_key = target.public_key.as_deref().unwrap_or_default(),
+            "Langfuse exporter reconfigured"
+        );
+
+        // Shutting down blocks on the batch processor, which runs on this runtime
+        if let Some(previous) = previous {
+            let result = tokio::task::spawn_blocking(move || previous.shutdown()).await;
+            if let Ok(Err(error)) = result {
+                warn!(%error, "Previous Langfuse exporter did not shut down cleanly");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn batch_processor<E: SpanExporter + 'static>(
+    exporter: E,
+    batch: &BatchExportConfig,
+) -> BatchSpanProcessor<runtime::Tokio> {
+    let batch_config = BatchConfigBuilder::default()
+        .with_max_queue_size(batch.max_queue_size)
+        .with_max_export_batch_size(batch.max_export_batch_size)
+        .with_scheduled_delay(batch.scheduled_delay)
+        .with_max_export_timeout(batch.export_timeout)
         .build();
 
+    BatchSpanProcessor::builder(exporter, runtime::Tokio)
+        .with_batch_config(batch_config)
+        .build()
+}
+
+/// Build the tracer provider exporting to Langfuse.
+pub fn build_tracer_provider(telemetry: &TelemetryConfig) -> Result<SdkTracerProvider> {
+    Ok(SdkTracerProvider::builder()
+        .with_resource(resource(telemetry))
+        .with_max_attributes_per_span(telemetry.limits.max_attributes)
+        .with_span_processor(langfuse_processor(
+            telemetry,
+            &langfuse_host(),
+            &LangfuseTarget::from_env()
+                .keys()
+                .context("Langfuse keys are not set")?,
+            false,
+            Arc::default(),
+        )?)
+        .build())
+}
+
+/// Open the rolling log file, returning a non-blocking writer and the guard
+/// that flushes it on drop.
+fn file_writer(
+    logging: &LoggingConfig,
+) -> Result<Option<(tracing_appender::non_blocking::NonBlocking, WorkerGuard)>> {
+    let Some(dir) = &logging.dir else {
+        return Ok(None);
+    };
+
+    let rotation = match logging.rotation {
+        LogRotation::Minutely => Rotation::MINUTELY,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix("rmcp-demo")
+        .filename_suffix("log")
+        .build(dir)
+        .with_context(|| format!("failed to open log directory {}", dir.display()))?;
+
+    Ok(Some(tracing_appender::non_blocking(appender)))
+}
+
+/// Initialise tracing so that `tracing` spans (including Tokio runtime spans)
+/// are forwarded to the configured OpenTelemetry exporter and to stdout.
+///
+/// When `LOG_DIR` is set, the same lines are also written (without colours) to
+/// a rolling file in that directory.
+///
+/// Without Langfuse credentials, spans are still created (so trace ids appear in
+/// logs) but nothing is exported, unless `TRACING_STRICT` is set.
+///
+/// With `profile_out`, span timings are also recorded locally as a Chrome trace
+/// (viewable as a flame chart in Perfetto), no backend required.
+pub fn init_tracing(config: &Config, profile_out: Option<&Path>) -> Result<Telemetry> {
+    // Ensure trace context propagation (e.g. W3C traceparent headers).
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let mut builder = SdkTracerProvider::builder()
+        .with_resource(resource(&config.telemetry))
+        .with_max_attributes_per_span(config.telemetry.limits.max_attributes);
+    let export_stats = Arc::new(ExportStats::default());
+
+    let missing = missing_langfuse_credentials();
+    if !missing.is_empty() && config.telemetry.strict {
+        bail!(
+            "{} not set and TRACING_STRICT is enabled; set the Langfuse keys or unset TRACING_STRICT",
+            missing.join(", ")
+        );
+    }
+    // The Langfuse pipeline sits behind a swappable processor so the admin
+    // endpoint can replace it; the slot counts as one exporter once filled
+    let target = LangfuseTarget::from_env();
+    let pipeline = langfuse_pipeline(
+        &config.telemetry,
+        &config.tenants,
+        &target,
+        export_stats.clone(),
+    )?;
+    if pipeline.is_some() {
+        span_repair::count_router();
+    }
+    let pipeline = Arc::new(RwLock::new(pipeline));
+    let exporter = ExporterHandle {
+        pipeline: pipeline.clone(),
+        target: Arc::new(Mutex::new(target)),
+        updating: Arc::default(),
+        telemetry: config.telemetry.clone(),
+        tenants: config.tenants.clone(),
+        resource: resource(&config.telemetry),
+        stats: export_stats.clone(),
+    };
+    builder = builder.with_span_processor(SwappableProcessor {
+        pipeline,
+        ended: EndedSpanCounter::new(export_stats.clone()),
+    });
+
+    // Append spans to a local file, with or without Langfuse
+    if let Some(path) = &config.telemetry.span_file {
+        let exporter = SessionRepairExporter::new(AttributeGuardExporter::new(
+            JsonlSpanExporter::create(path)?,
+            config.telemetry.limits.clone(),
+        ));
+        builder = builder.with_span_processor(batch_processor(exporter, &config.telemetry.batch));
+    }
+
+    // Print each span as it ends, in addition to the batch export
+    if config.telemetry.debug_exporter {
+        builder =
+            builder.with_simple_exporter(SessionRepairExporter::new(AttributeGuardExporter::new(
+                opentelemetry_stdout::SpanExporter::default(),
+                config.telemetry.limits.clone(),
+            )));
+    }
+
+    // Collects the spans of the startup self-test's trace
+    if config.self_test != SelfTestMode::Off {
+        builder = builder.with_span_processor(SpanProbe);
+    }
+
+    let provider = builder.build();
+
     let tracer = provider.tracer("weather-assistant");
 
     // Install the provider as global so other crates use it
@@ -73,26 +579,120 @@ pub fn init_tracing() -> Result<SdkTracerProvider> {
 
     // Forward tracing events (including Tokio internal spans when enabled) to OTEL
     // and keep console logging with env-based filtering.
+    // The filter sits behind a reload layer so it can be changed at runtime.
     let env_filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info,tokio=info"));
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let (env_filter, log_level) = reload::Layer::new(env_filter);
 
-    // Apply the filter to the OpenTelemetry layer to exclude unwanted rmcp spans
-    let otel_layer = tracing_opentelemetry::layer()
-        .with_tracer(tracer)
-        .with_filter(RmcpSpanFilter);
+    // rmcp's transport spans are exported too; `SessionRepairExporter` moves
+    // them into their session's trace
+    let otel_layer = tracing_opentelemetry::layer().with_tracer(tracer);
 
-    let fmt_layer = fmt::layer()
+    let format = fmt::format()
         .with_timer(UtcTime::rfc_3339())
         .with_thread_ids(true)
         .with_thread_names(true)
-        .with_target(true)
-        .with_span_events(FmtSpan::ENTER | FmtSpan::EXIT | FmtSpan::CLOSE);
+        .with_target(true);
+
+    let fmt_layer = fmt::layer()
+        .with_span_events(FmtSpan::ENTER | FmtSpan::EXIT | FmtSpan::CLOSE)
+        .event_format(OtelIdsFormat {
+            inner: format.clone(),
+        });
+
+    let (file_layer, log_guard) = match file_writer(&config.logging)? {
+        Some((writer, guard)) => {
+            let layer = fmt::layer()
+                .with_writer(writer)
+                .with_ansi(false)
+                .fmt_fields(PlainFields(DefaultFields::new()))
+                .with_span_events(FmtSpan::ENTER | FmtSpan::EXIT | FmtSpan::CLOSE)
+                .event_format(OtelIdsFormat { inner: format });
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let (profile_layer, profile_guard) = match profile_out {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("failed to create profile output {}", path.display()))?;
+            let (layer, guard) = ChromeLayerBuilder::new()
+                .writer(file)
+                .include_args(true)
+                .build();
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let sentry_guard = error_reporting::init(&config.telemetry);
+    let sentry_layer = sentry_guard.as_ref().map(|_| error_reporting::layer());
 
-    tracing_subscriber::registry()
-        .with(env_filter)
-        .with(fmt_layer)
-        .with(otel_layer)
-        .init();
+    // The env filter applies to the output layers only, so tokio-console
+    // still sees the runtime's trace-level task events.
+    let layers = fmt_layer
+        .and_then(file_layer)
+        .and_then(otel_layer)
+        .and_then(sentry_layer)
+        .and_then(profile_layer)
+        .with_filter(env_filter);
+    let registry = tracing_subscriber::registry().with(layers);
+    #[cfg(feature = "tokio-console")]
+    let registry = registry.with(console_subscriber::spawn());
+    registry.init();
+
+    if missing.is_empty() {
+        let batch = &config.telemetry.batch;
+        info!(
+            max_queue_size = batch.max_queue_size,
+            max_export_batch_size = batch.max_export_batch_size,
+            scheduled_delay = ?batch.scheduled_delay,
+            export_timeout = ?batch.export_timeout,
+            "Exporting traces to {}",
+            langfuse_host()
+        );
+    } else if !config.tenants.credentials.is_empty() {
+        info!(
+            missing = %missing.join(", "),
+            "Langfuse credentials not set; only tenant traces are exported"
+        );
+    } else if config.telemetry.span_file.is_some() {
+        info!(
+            missing = %missing.join(", "),
+            "Langfuse credentials not set; spans are only written to the span file"
+        );
+    } else {
+        warn!(
+            missing = %missing.join(", "),
+            "Langfuse credentials not set; traces will not be exported. \
+             Set them in .env, or TRACING_STRICT=1 to fail instead"
+        );
+    }
+
+    if !config.tenants.credentials.is_empty() {
+        info!(
+            tenants = ?config.tenants.credentials.keys().collect::<Vec<_>>(),
+            "Routing tenant traces to their own Langfuse projects"
+        );
+    }
+    if let Some(path) = &config.telemetry.span_file {
+        info!(path = %path.display(), "Writing spans to a JSONL file");
+    }
+    if sentry_guard.is_some() {
+        info!("Reporting errors and panics to Sentry");
+    }
+    if let Some(path) = profile_out {
+        info!(path = %path.display(), "Writing a Chrome trace profile on shutdown");
+    }
 
-    Ok(provider)
+    Ok(Telemetry {
+        tracer_provider: provider,
+        log_level: LogLevelHandle(log_level),
+        exporter,
+        export_stats,
+        _log_guard: log_guard,
+        _sentry_guard: sentry_guard,
+        _profile_guard: profile_guard,
+    })
 }
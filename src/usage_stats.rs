@@ -0,0 +1,172 @@
+//! Per-tool call counts, errors and latency since startup, for
+//! `get_server_stats` and the `stats://server` resource.
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::panic::AssertUnwindSafe;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::concurrency::{ConcurrencyLimits, ToolConcurrency};
+use crate::keepalive::{KeepaliveStats, KeepaliveTotals};
+use crate::tool_aliases::DeprecationStats;
+use crate::tool_middleware::{Next, ToolCall, ToolMiddleware, ToolResult};
+
+/// URI of the stats resource.
+pub const STATS_URI: &str = "stats://server";
+
+/// Latest calls per tool kept for the latency percentiles.
+const RECENT_CALLS: usize = 256;
+
+/// Running totals by tool, recorded by the middleware of the same name.
+pub struct UsageStats {
+    started: Instant,
+    tools: Mutex<BTreeMap<String, ToolCounters>>,
+}
+
+#[derive(Default)]
+struct ToolCounters {
+    calls: u64,
+    errors: u64,
+    total: Duration,
+    max: Duration,
+    recent: VecDeque<Duration>,
+}
+
+impl Default for UsageStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            tools: Mutex::default(),
+        }
+    }
+}
+
+impl UsageStats {
+    fn record(&self, tool: &str, elapsed: Duration, failed: bool) {
+        let mut tools = self.tools.lock().expect("usage stats lock poisoned");
+        let counters = tools.entry(tool.to_string()).or_default();
+        counters.calls += 1;
+        counters.errors += u64::from(failed);
+        counters.total += elapsed;
+        counters.max = counters.max.max(elapsed);
+        if counters.recent.len() == RECENT_CALLS {
+            counters.recent.pop_front();
+        }
+        counters.recent.push_back(elapsed);
+    }
+
+    pub fn snapshot(
+        &self,
+        deprecations: &DeprecationStats,
+        keepalive: &KeepaliveStats,
+        concurrency: Option<&ConcurrencyLimits>,
+    ) -> ServerStats {
+        let tools = self.tools.lock().expect("usage stats lock poisoned");
+        let tools: Vec<ToolUsage> = tools
+            .iter()
+            .map(|(tool, counters)| {
+                let mut recent: Vec<Duration> = counters.recent.iter().copied().collect();
+                recent.sort_unstable();
+                ToolUsage {
+                    tool: tool.clone(),
+                    calls: counters.calls,
+                    errors: counters.errors,
+                    mean_latency_ms: milliseconds(counters.total.div_f64(counters.calls as f64)),
+                    p50_latency_ms: milliseconds(percentile(&recent, 0.50)),
+                    p95_latency_ms: milliseconds(percentile(&recent, 0.95)),
+                    max_latency_ms: milliseconds(counters.max),
+                }
+            })
+            .collect();
+        ServerStats {
+            uptime_seconds: self.started.elapsed().as_secs(),
+            calls: tools.iter().map(|tool| tool.calls).sum(),
+            errors: tools.iter().map(|tool| tool.errors).sum(),
+            tools,
+            deprecated_calls: deprecations
+                .totals()
+                .into_iter()
+                .map(|(alias, calls)| (alias.to_string(), calls))
+                .collect(),
+            keepalive: keepalive.totals(),
+            concurrency: concurrency
+                .map(ConcurrencyLimits::snapshot)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl ToolMiddleware for UsageStats {
+    fn around<'a>(&'a self, call: &'a ToolCall, next: Next<'a>) -> BoxFuture<'a, ToolResult> {
+        Box::pin(async move {
+            let started = Instant::now();
+            match AssertUnwindSafe(next.run(call)).catch_unwind().await {
+                Ok(result) => {
+                    let failed = match &result {
+                        Err(_) => true,
+                        Ok(result) => result.is_error == Some(true),
+                    };
+                    self.record(call.name(), started.elapsed(), failed);
+                    result
+                }
+                Err(payload) => {
+                    self.record(call.name(), started.elapsed(), true);
+                    std::panic::resume_unwind(payload)
+                }
+            }
+        })
+    }
+}
+
+/// Nearest-rank percentile of sorted `samples`.
+pub(crate) fn percentile(samples: &[Duration], quantile: f64) -> Duration {
+    if samples.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (quantile * samples.len() as f64).ceil() as usize;
+    samples[rank.clamp(1, samples.len()) - 1]
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 10_000.0).round() / 10.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ServerStats {
+    /// Seconds since the server started
+    pub uptime_seconds: u64,
+    /// Tool calls across all tools and sessions
+    pub calls: u64,
+    /// Calls that failed or returned an error result
+    pub errors: u64,
+    /// One entry per tool called at least once, by name
+    pub tools: Vec<ToolUsage>,
+    /// Calls made through each deprecated tool name
+    pub deprecated_calls: BTreeMap<String, u64>,
+    /// Keepalive pings sent to idle sessions
+    pub keepalive: KeepaliveTotals,
+    /// Occupancy of the tools with a concurrency limit
+    pub concurrency: Vec<ToolConcurrency>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ToolUsage {
+    /// Tool name
+    pub tool: String,
+    /// Calls since startup
+    pub calls: u64,
+    /// Calls that failed or returned an error result
+    pub errors: u64,
+    /// Mean call duration in milliseconds
+    pub mean_latency_ms: f64,
+    /// Median duration of the latest 256 calls, in milliseconds
+    pub p50_latency_ms: f64,
+    /// 95th percentile duration of the latest 256 calls, in milliseconds
+    pub p95_latency_ms: f64,
+    /// Longest call duration in milliseconds
+    pub max_latency_ms: f64,
+}
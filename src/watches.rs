@@ -0,0 +1,420 @@
+//! Threshold rules on a location's current weather, such as "temperature
+//! above 30 °C", created with the `create_watch` tool.
+//!
+//! The [`refresh`](crate::refresh) job evaluates every watch. When a watch's
+//! rule starts to hold, the watch is marked triggered and the session that
+//! created it is sent `notifications/resources/updated` for the watch's
+//! `weather://watch/<id>` resource. The watch re-arms once the rule no
+//! longer holds.
+
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::Context;
+use rmcp::model::{AnnotateAble, RawResource, Resource, ResourceUpdatedNotificationParam};
+use rmcp::service::Peer;
+use rmcp::{schemars, ErrorData as McpError, RoleServer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::app_state::AppState;
+use crate::providers::{check_location, normalize_location};
+use crate::weather_tools::Weather;
+
+/// Most watches one session can create.
+pub const MAX_WATCHES_PER_SESSION: usize = 20;
+
+const URI_PREFIX: &str = "weather://watch/";
+
+/// What a watch compares with its threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchCondition {
+    /// Temperature in °C above the threshold
+    TemperatureAbove,
+    /// Temperature in °C below the threshold
+    TemperatureBelow,
+    /// Wind speed in km/h above the threshold
+    WindSpeedAbove,
+    /// Relative humidity in percent above the threshold
+    HumidityAbove,
+    /// Relative humidity in percent below the threshold
+    HumidityBelow,
+}
+
+impl WatchCondition {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TemperatureAbove => "temperature_above",
+            Self::TemperatureBelow => "temperature_below",
+            Self::WindSpeedAbove => "wind_speed_above",
+            Self::HumidityAbove => "humidity_above",
+            Self::HumidityBelow => "humidity_below",
+        }
+    }
+
+    /// The compared value of `weather`, which is metric as providers return it.
+    fn value(self, weather: &Weather) -> f64 {
+        match self {
+            Self::TemperatureAbove | Self::TemperatureBelow => f64::from(weather.temperature),
+            Self::WindSpeedAbove => f64::from(weather.wind_speed),
+            Self::HumidityAbove | Self::HumidityBelow => f64::from(weather.humidity),
+        }
+    }
+
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::TemperatureAbove | Self::WindSpeedAbove | Self::HumidityAbove => {
+                value > threshold
+            }
+            Self::TemperatureBelow | Self::HumidityBelow => value < threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Watch {
+    /// Identifier of the watch
+    pub id: String,
+    /// Resource with the watch's current state; updated when it triggers
+    pub uri: String,
+    pub location: String,
+    pub condition: WatchCondition,
+    /// °C, km/h or percent, depending on the condition
+    pub threshold: f64,
+    /// Whether the rule held at the last check
+    pub triggered: bool,
+    /// Compared value at the last check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_value: Option<f64>,
+    /// Last check, RFC 3339 in UTC; absent until the first check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<String>,
+    /// When the rule last started to hold
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub triggered_at: Option<String>,
+}
+
+struct Entry {
+    watch: Watch,
+    /// MCP session that created the watch.
+    session_id: String,
+    /// Trace context of that session, parent of the watch's triggers.
+    session: Option<Context>,
+    peer: Peer<RoleServer>,
+}
+
+/// Watches of every session. A session's watches go away with it.
+#[derive(Clone, Default)]
+pub struct WatchRegistry {
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+impl WatchRegistry {
+    pub fn create(
+        &self,
+        session_id: &str,
+        session: Option<Context>,
+        peer: Peer<RoleServer>,
+        location: &str,
+        condition: WatchCondition,
+        threshold: f64,
+    ) -> Result<Watch, McpError> {
+        let mut entries = self.entries.lock().expect("watch lock poisoned");
+        let owned = entries
+            .iter()
+            .filter(|entry| entry.session_id == session_id)
+            .count();
+        let watch = new_watch(owned, location, condition, threshold)?;
+        entries.push(Entry {
+            watch: watch.clone(),
+            session_id: session_id.to_string(),
+            session,
+            peer,
+        });
+        Ok(watch)
+    }
+
+    /// The session's watches, oldest first.
+    pub fn list(&self, session_id: &str) -> Vec<Watch> {
+        self.entries
+            .lock()
+            .expect("watch lock poisoned")
+            .iter()
+            .filter(|entry| entry.session_id == session_id)
+            .map(|entry| entry.watch.clone())
+            .collect()
+    }
+
+    /// One of the session's watches by resource URI.
+    pub fn get(&self, session_id: &str, uri: &str) -> Option<Watch> {
+        if !uri.starts_with(URI_PREFIX) {
+            return None;
+        }
+        self.list(session_id)
+            .into_iter()
+            .find(|watch| watch.uri == uri)
+    }
+
+    /// The session's watches as resources.
+    pub fn resources(&self, session_id: &str) -> Vec<Resource> {
+        self.list(session_id)
+            .into_iter()
+            .map(|watch| {
+                let mut resource = RawResource::new(
+                    &watch.uri,
+                    format!(
+                        "Watch {} {} in {}",
+                        watch.condition.as_str(),
+                        watch.threshold,
+                        watch.location
+                    ),
+                );
+                resource.mime_type = Some("application/json".to_string());
+                resource.no_annotation()
+            })
+            .collect()
+    }
+
+    /// Normalized locations with at least one watch. Drops the watches of
+    /// closed sessions first.
+    pub fn locations(&self) -> BTreeSet<String> {
+        let mut entries = self.entries.lock().expect("watch lock poisoned");
+        entries.retain(|entry| !entry.peer.is_transport_closed());
+        entries
+            .iter()
+            .map(|entry| normalize_location(&entry.watch.location))
+            .collect()
+    }
+}
+
+/// A watch for a session that already has `owned` watches, if the session
+/// may create another and the rule is valid.
+fn new_watch(
+    owned: usize,
+    location: &str,
+    condition: WatchCondition,
+    threshold: f64,
+) -> Result<Watch, McpError> {
+    let location = location.trim();
+    if location.is_empty() {
+        return Err(McpError::invalid_params("location must not be empty", None));
+    }
+    check_location(location)?;
+    if !threshold.is_finite() {
+        return Err(McpError::invalid_params("threshold must be a number", None));
+    }
+    if owned >= MAX_WATCHES_PER_SESSION {
+        return Err(McpError::invalid_params(
+            format!("at most {MAX_WATCHES_PER_SESSION} watches per session"),
+            None,
+        ));
+    }
+    let id = format!("w_{:016x}", rand::random::<u64>());
+    Ok(Watch {
+        uri: format!("{URI_PREFIX}{id}"),
+        id,
+        location: location.to_string(),
+        condition,
+        threshold,
+        triggered: false,
+        last_value: None,
+        checked_at: None,
+        triggered_at: None,
+    })
+}
+
+/// Record a check of `watch` against `weather` at `now`. Returns whether
+/// the rule started to hold, which happens again only after it stopped.
+fn observe(watch: &mut Watch, weather: &Weather, now: &str) -> bool {
+    let value = watch.condition.value(weather);
+    let holds = watch.condition.holds(value, watch.threshold);
+    let started = holds && !watch.triggered;
+    if started {
+        watch.triggered_at = Some(now.to_string());
+    }
+    watch.triggered = holds;
+    watch.last_value = Some(value);
+    watch.checked_at = Some(now.to_string());
+    started
+}
+
+/// Evaluate the watches on `location` against `weather` and notify the
+/// sessions whose watches started to hold. Runs inside the refresh job's
+/// `weather.refresh` span.
+pub async fn evaluate(state: &AppState, location: &str, weather: &Weather) {
+    let key = normalize_location(location);
+    let now = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default();
+    let mut triggered = Vec::new();
+    {
+        let mut entries = state.watches.entries.lock().expect("watch lock poisoned");
+        for entry in entries
+            .iter_mut()
+            .filter(|entry| normalize_location(&entry.watch.location) == key)
+        {
+            if observe(&mut entry.watch, weather, &now) {
+                triggered.push((
+                    entry.watch.clone(),
+                    entry.session_id.clone(),
+                    entry.session.clone(),
+                    entry.peer.clone(),
+                ));
+            }
+        }
+    }
+
+    let refresh = tracing::Span::current();
+    refresh.record("watches_triggered", triggered.len());
+    let refresh = refresh.context().span().span_context().clone();
+    for (watch, session_id, session, peer) in triggered {
+        // In the session's trace, like its keepalive pings
+        let span = tracing::info_span!(
+            parent: None,
+            "watch.triggered",
+            watch.id = %watch.id,
+            watch.condition = watch.condition.as_str(),
+            watch.threshold = watch.threshold,
+            watch.value = watch.last_value,
+            location = %watch.location,
+            session.id = %session_id,
+        );
+        if let Some(parent) = session {
+            let _ = span.set_parent(parent);
+        }
+        if refresh.is_valid() {
+            span.add_link(refresh.clone());
+        }
+        async {
+            tracing::info!(watch.id = %watch.id, location = %watch.location, "Watch triggered");
+            if !state.config.resources.enabled {
+                return;
+            }
+            let notified = peer
+                .notify_resource_updated(ResourceUpdatedNotificationParam { uri: watch.uri })
+                .await;
+            if let Err(error) = notified {
+                tracing::debug!(%error, "Could not send resources/updated");
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather_tools::Units;
+
+    fn weather(temperature: i32, wind_speed: i32, humidity: i32) -> Weather {
+        Weather {
+            location: "Paris".to_string(),
+            temperature,
+            condition: "Sunny".to_string(),
+            humidity,
+            wind_speed,
+            units: Units::Metric,
+        }
+    }
+
+    fn watch(condition: WatchCondition, threshold: f64) -> Watch {
+        new_watch(0, "Paris", condition, threshold).unwrap()
+    }
+
+    #[test]
+    fn watches_trigger_once_until_the_rule_stops_holding() {
+        let mut watch = watch(WatchCondition::TemperatureAbove, 30.0);
+        assert!(!observe(&mut watch, &weather(29, 0, 50), "t1"));
+        assert_eq!(watch.checked_at.as_deref(), Some("t1"));
+        assert!(observe(&mut watch, &weather(31, 0, 50), "t2"));
+        assert!(!observe(&mut watch, &weather(35, 0, 50), "t3"));
+        assert!(watch.triggered);
+        assert_eq!(watch.triggered_at.as_deref(), Some("t2"));
+        assert_eq!(watch.last_value, Some(35.0));
+
+        // Re-armed once the rule no longer holds
+        assert!(!observe(&mut watch, &weather(30, 0, 50), "t4"));
+        assert!(!watch.triggered);
+        assert!(observe(&mut watch, &weather(31, 0, 50), "t5"));
+        assert_eq!(watch.triggered_at.as_deref(), Some("t5"));
+    }
+
+    #[test]
+    fn thresholds_are_compared_strictly() {
+        let cases = [
+            (
+                WatchCondition::TemperatureAbove,
+                weather(30, 0, 0),
+                30.0,
+                false,
+            ),
+            (
+                WatchCondition::TemperatureAbove,
+                weather(31, 0, 0),
+                30.0,
+                true,
+            ),
+            (
+                WatchCondition::TemperatureBelow,
+                weather(0, 0, 0),
+                0.0,
+                false,
+            ),
+            (
+                WatchCondition::TemperatureBelow,
+                weather(-1, 0, 0),
+                0.0,
+                true,
+            ),
+            (
+                WatchCondition::WindSpeedAbove,
+                weather(0, 50, 0),
+                50.0,
+                false,
+            ),
+            (
+                WatchCondition::WindSpeedAbove,
+                weather(0, 50, 0),
+                49.5,
+                true,
+            ),
+            (
+                WatchCondition::HumidityAbove,
+                weather(0, 0, 80),
+                80.0,
+                false,
+            ),
+            (WatchCondition::HumidityBelow, weather(0, 0, 20), 20.5, true),
+        ];
+        for (condition, weather, threshold, expected) in cases {
+            let mut watch = watch(condition, threshold);
+            assert_eq!(
+                observe(&mut watch, &weather, "now"),
+                expected,
+                "{} {threshold}",
+                condition.as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_watches_are_refused() {
+        let condition = WatchCondition::TemperatureAbove;
+        for threshold in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(new_watch(0, "Paris", condition, threshold).is_err());
+        }
+        assert!(new_watch(0, "  ", condition, 30.0).is_err());
+        assert!(new_watch(MAX_WATCHES_PER_SESSION - 1, "Paris", condition, 30.0).is_ok());
+        let refused = new_watch(MAX_WATCHES_PER_SESSION, "Paris", condition, 30.0).unwrap_err();
+        assert_eq!(refused.message, "at most 20 watches per session");
+
+        let watch = new_watch(0, " Paris ", condition, 30.0).unwrap();
+        assert_eq!(watch.location, "Paris");
+        assert_eq!(watch.uri, format!("weather://watch/{}", watch.id));
+    }
+}
@@ -0,0 +1,147 @@
+//! Forecast chart drawn as a PNG, returned to clients as MCP image content.
+
+use tiny_skia::{Color, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+use crate::weather_tools::Forecast;
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 320;
+const MARGIN: f32 = 24.0;
+/// Share of the plot height used by temperatures; precipitation gets the rest.
+const TEMPERATURE_SHARE: f32 = 0.7;
+
+/// One column per day: a bar from low to high coloured by condition, the
+/// chance of precipitation underneath, and a line joining the highs.
+/// Gridlines are every 5 degrees. There is no text, which would need fonts.
+pub fn render_forecast_png(forecast: &[Forecast]) -> Result<Vec<u8>, String> {
+    let mut pixmap = Pixmap::new(WIDTH, HEIGHT).ok_or("invalid image size")?;
+    pixmap.fill(Color::from_rgba8(250, 250, 252, 255));
+    if forecast.is_empty() {
+        return pixmap.encode_png().map_err(|error| error.to_string());
+    }
+
+    let plot_width = WIDTH as f32 - 2.0 * MARGIN;
+    let plot_height = HEIGHT as f32 - 2.0 * MARGIN;
+    let temperature_height = plot_height * TEMPERATURE_SHARE;
+    let precipitation_top = MARGIN + temperature_height + MARGIN / 2.0;
+    let precipitation_height = HEIGHT as f32 - MARGIN - precipitation_top;
+
+    let coldest = forecast.iter().map(|day| day.low).min().unwrap_or(0) - 2;
+    let warmest = forecast.iter().map(|day| day.high).max().unwrap_or(0) + 2;
+    let span = (warmest - coldest).max(1) as f32;
+    let y_for = |temperature: i32| {
+        MARGIN + temperature_height * (1.0 - (temperature - coldest) as f32 / span)
+    };
+
+    // Gridlines every 5 degrees
+    let grid = paint(225, 228, 235);
+    let mut degrees = coldest.div_euclid(5) * 5 + 5;
+    while degrees < warmest {
+        let y = y_for(degrees);
+        stroke_line(
+            &mut pixmap,
+            (MARGIN, y),
+            (WIDTH as f32 - MARGIN, y),
+            &grid,
+            1.0,
+        );
+        degrees += 5;
+    }
+
+    let slot = plot_width / forecast.len() as f32;
+    let bar_width = (slot * 0.5).min(48.0);
+    let mut highs = PathBuilder::new();
+    for (index, day) in forecast.iter().enumerate() {
+        let center = MARGIN + slot * (index as f32 + 0.5);
+        let left = center - bar_width / 2.0;
+
+        let (red, green, blue) = condition_color(&day.condition);
+        fill_rect(
+            &mut pixmap,
+            left,
+            y_for(day.high),
+            bar_width,
+            y_for(day.low) - y_for(day.high),
+            &paint(red, green, blue),
+        );
+
+        let rain_height =
+            precipitation_height * day.precipitation_chance.clamp(0, 100) as f32 / 100.0;
+        fill_rect(
+            &mut pixmap,
+            left,
+            precipitation_top,
+            bar_width,
+            precipitation_height,
+            &paint(232, 238, 248),
+        );
+        fill_rect(
+            &mut pixmap,
+            left,
+            precipitation_top + precipitation_height - rain_height,
+            bar_width,
+            rain_height,
+            &paint(70, 130, 200),
+        );
+
+        if index == 0 {
+            highs.move_to(center, y_for(day.high));
+        } else {
+            highs.line_to(center, y_for(day.high));
+        }
+    }
+    if let Some(path) = highs.finish() {
+        let stroke = Stroke {
+            width: 2.5,
+            ..Stroke::default()
+        };
+        pixmap.stroke_path(
+            &path,
+            &paint(200, 60, 50),
+            &stroke,
+            Transform::identity(),
+            None,
+        );
+    }
+
+    pixmap.encode_png().map_err(|error| error.to_string())
+}
+
+fn condition_color(condition: &str) -> (u8, u8, u8) {
+    match condition {
+        "Sunny" => (245, 180, 40),
+        "Partly Cloudy" => (230, 190, 110),
+        "Cloudy" | "Foggy" => (160, 165, 175),
+        "Rainy" => (90, 140, 200),
+        "Snowy" => (170, 210, 235),
+        "Stormy" => (90, 80, 130),
+        _ => (180, 180, 180),
+    }
+}
+
+fn paint(red: u8, green: u8, blue: u8) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(red, green, blue, 255);
+    paint.anti_alias = true;
+    paint
+}
+
+fn fill_rect(pixmap: &mut Pixmap, x: f32, y: f32, width: f32, height: f32, paint: &Paint) {
+    // Zero-height bars (no rain, equal high and low) are simply skipped
+    if let Some(rect) = Rect::from_xywh(x, y, width, height.max(0.0)) {
+        pixmap.fill_rect(rect, paint, Transform::identity(), None);
+    }
+}
+
+fn stroke_line(pixmap: &mut Pixmap, from: (f32, f32), to: (f32, f32), paint: &Paint, width: f32) {
+    let mut path = PathBuilder::new();
+    path.move_to(from.0, from.1);
+    path.line_to(to.0, to.1);
+    if let Some(path) = path.finish() {
+        let stroke = Stroke {
+            width,
+            ..Stroke::default()
+        };
+        pixmap.stroke_path(&path, paint, &stroke, Transform::identity(), None);
+    }
+}
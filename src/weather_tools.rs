@@ -1,95 +1,917 @@
-use rand::Rng;
+use axum::http::request::Parts;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use chrono::{NaiveDate, Utc};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use opentelemetry::trace::TraceContextExt;
 use rmcp::{
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
+    handler::server::{
+        router::tool::ToolRouter,
+        tool::{cached_schema_for_type, ToolCallContext},
+        wrapper::Parameters,
+    },
     model::*,
     schemars,
-    service::RequestContext,
-    tool, tool_handler, tool_router, ErrorData as McpError, RoleServer, ServerHandler,
+    service::{NotificationContext, RequestContext},
+    tool, tool_router, ErrorData as McpError, RoleServer, ServerHandler,
 };
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::sync::Arc;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use tokio::sync::Mutex;
-use tracing::{debug, info, instrument};
+use tracing::{debug, info, instrument, warn, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::app_state::{AppState, MAX_FORECAST_DAYS, MAX_HISTORY_DAYS, MAX_LONG_RANGE_DAYS};
+use crate::astronomy;
+use crate::build_info;
+use crate::client_capabilities::{self, ClientSupport};
+use crate::feature_flags;
+use crate::forecast_summary;
+use crate::forecast_time;
+use crate::history_export::{self, ExportFormat};
+use crate::i18n::{self, Lang};
+use crate::keepalive::{self, Activity};
+use crate::langfuse_client::CreateScore;
+use crate::post_processing;
+use crate::preferences::Preferences;
+use crate::providers::{check_location, normalize_location};
+use crate::resources::{self, SessionResources};
+use crate::schema_export;
+use crate::simulated_latency::SimulatedLatency;
+use crate::slo;
+use crate::span_repair;
+use crate::tool_aliases;
+use crate::tool_errors;
+use crate::tool_middleware::{Next, ToolCall, ToolMiddleware, ToolResult};
+use crate::tool_pagination;
+use crate::trace_utils::{enrich_span, RequestAttribution};
+use crate::usage_stats::{self, ServerStats};
+use crate::watches::{Watch, WatchCondition};
+use crate::weather_map;
+use crate::webhooks::Webhook;
+use rmcp_otel::TraceParentContext;
 
 #[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
 pub struct GetWeatherArgs {
-    /// City name to get weather for
-    pub location: String,
+    /// City name to get weather for; defaults to the session's default location
+    #[serde(default)]
+    pub location: Option<String>,
+    /// Language of condition names and text: "en" (default), "de", "fr" or "es"
+    #[serde(default)]
+    pub lang: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
 pub struct GetForecastArgs {
     /// City name for forecast
     pub location: String,
-    /// Number of days to forecast (1-7)
+    /// Number of days to forecast (1-7, or 1-16 with long_range)
+    #[serde(default = "default_days")]
+    pub days: u32,
+    /// Language of condition names: "en" (default), "de", "fr" or "es"
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// Allow up to 16 days; fails with long_range_unsupported when the
+    /// weather provider cannot forecast that far
+    #[serde(default)]
+    pub long_range: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct LocationArgs {
+    /// City name; defaults to the session's default location
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+/// Asked of the user via elicitation when a tool has no location to use.
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct LocationAnswer {
+    /// City name
+    pub location: String,
+}
+
+rmcp::elicit_safe!(LocationAnswer);
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct DailyArgs {
+    /// City name
+    pub location: String,
+    /// Number of days (1-7)
     #[serde(default = "default_days")]
     pub days: u32,
 }
 
-fn default_days() -> u32 {
+pub fn default_days() -> u32 {
     3
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+pub fn default_history_days() -> u32 {
+    7
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct ExportHistoryArgs {
+    /// City name; defaults to the session's default location
+    #[serde(default)]
+    pub location: Option<String>,
+    /// Number of past days to export (1-30, default 7)
+    #[serde(default = "default_history_days")]
+    pub days: u32,
+    /// File format: "csv" (default) or "parquet"
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Most favorite locations one user can save.
+pub const MAX_FAVORITES: usize = 20;
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct SummarizeWeatherArgs {
+    /// City name to summarize the weather for
+    pub location: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct GetAstronomyArgs {
+    /// City name; one of the bundled demo cities
+    pub location: String,
+    /// Calendar date (YYYY-MM-DD); defaults to today in the location's timezone
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct SetPreferenceArgs {
+    /// Location used when get_weather is called without one; an empty string clears it
+    #[serde(default)]
+    pub default_location: Option<String>,
+    /// Unit system for get_weather and get_forecast
+    #[serde(default)]
+    pub units: Option<Units>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct FavoriteArgs {
+    /// City name to add to or remove from the favorites
+    pub location: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct RegisterWebhookArgs {
+    /// http(s) URL that receives a signed JSON POST for each new alert
+    pub url: String,
+    /// City name to watch for alerts
+    pub location: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct UnregisterWebhookArgs {
+    /// URL given to register_webhook
+    pub url: String,
+    /// Only remove the webhook for this location; all of the URL's when omitted
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct CreateWatchArgs {
+    /// City name to watch
+    pub location: String,
+    /// What to compare: temperature_above, temperature_below, wind_speed_above, humidity_above or humidity_below
+    pub condition: WatchCondition,
+    /// Threshold in °C, km/h or percent, depending on the condition
+    pub threshold: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct RateResponseArgs {
+    /// Rating of the previous answer, from 0 (unhelpful) to 1 (helpful)
+    pub score: f64,
+    /// Optional free-text feedback
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// "metric" (°C, km/h) or "imperial" (°F, mph).
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    schemars::JsonSchema,
+    utoipa::ToSchema,
+    async_graphql::Enum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl Units {
+    /// A temperature given in °C.
+    fn temperature(self, celsius: i32) -> i32 {
+        self.precise_temperature(celsius as f64).round() as i32
+    }
+
+    /// A temperature given in °C, unrounded.
+    pub fn precise_temperature(self, celsius: f64) -> f64 {
+        match self {
+            Units::Metric => celsius,
+            Units::Imperial => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    /// Unit symbols for temperatures and speeds.
+    pub fn symbols(self) -> (&'static str, &'static str) {
+        match self {
+            Units::Metric => ("°C", "km/h"),
+            Units::Imperial => ("°F", "mph"),
+        }
+    }
+
+    /// A speed given in km/h.
+    fn speed(self, km_per_hour: i32) -> i32 {
+        match self {
+            Units::Metric => km_per_hour,
+            Units::Imperial => (km_per_hour as f64 / 1.609_344).round() as i32,
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    schemars::JsonSchema,
+    utoipa::ToSchema,
+    async_graphql::SimpleObject,
+)]
 pub struct Weather {
+    /// Resolved location name
     pub location: String,
+    /// Temperature in °C, or °F in imperial units
     pub temperature: i32,
+    /// Sky condition, e.g. "Sunny"
     pub condition: String,
+    /// Relative humidity in percent
     pub humidity: i32,
+    /// Wind speed in km/h, or mph in imperial units
     pub wind_speed: i32,
+    /// Unit system of `temperature` and `wind_speed`
+    #[serde(default)]
+    pub units: Units,
+}
+
+impl Weather {
+    /// Convert from metric, as providers return it.
+    pub fn in_units(self, units: Units) -> Self {
+        Self {
+            temperature: units.temperature(self.temperature),
+            wind_speed: units.speed(self.wind_speed),
+            units,
+            ..self
+        }
+    }
+
+    /// Condition name in `lang`.
+    pub fn localized(self, lang: Lang) -> Self {
+        Self {
+            condition: i18n::condition(lang, &self.condition),
+            ..self
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    schemars::JsonSchema,
+    utoipa::ToSchema,
+    async_graphql::SimpleObject,
+)]
 pub struct Forecast {
+    /// Day offset, starting at 1 for today
     pub day: i32,
+    /// Calendar date in the location's timezone (ISO-8601, e.g. "2026-03-29")
+    pub date: String,
+    /// Local sunrise with UTC offset; absent during polar day or night
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sunrise: Option<String>,
+    /// Local sunset with UTC offset; absent during polar day or night
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sunset: Option<String>,
+    /// Daily high in °C, or °F in imperial units
     pub high: i32,
+    /// Daily low in °C, or °F in imperial units
     pub low: i32,
+    /// Sky condition, e.g. "Rainy"
+    pub condition: String,
+    /// Chance of precipitation in percent
+    pub precipitation_chance: i32,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    schemars::JsonSchema,
+    utoipa::ToSchema,
+    async_graphql::SimpleObject,
+)]
+pub struct ForecastResponse {
+    /// One entry per forecast day
+    pub items: Vec<Forecast>,
+    /// Unit system of the temperatures
+    #[serde(default)]
+    pub units: Units,
+}
+
+impl ForecastResponse {
+    /// Convert metric provider results to `units`.
+    pub fn in_units(items: Vec<Forecast>, units: Units) -> Self {
+        let items = items
+            .into_iter()
+            .map(|day| Forecast {
+                high: units.temperature(day.high),
+                low: units.temperature(day.low),
+                ..day
+            })
+            .collect();
+        Self { items, units }
+    }
+
+    /// Condition names in `lang`.
+    pub fn localized(mut self, lang: Lang) -> Self {
+        for day in &mut self.items {
+            day.condition = i18n::condition(lang, &day.condition);
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct HistoryExport {
+    /// URI of the embedded file, readable again with resources/read
+    pub uri: String,
+    /// File format
+    pub format: ExportFormat,
+    /// Days in the file, oldest first
+    pub days: usize,
+    /// First day covered (ISO-8601)
+    pub first_date: String,
+    /// Last day covered (ISO-8601), yesterday
+    pub last_date: String,
+    /// File size in bytes
+    pub bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct FavoritesList {
+    /// Favorite locations, in the order they were added
+    pub favorites: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct RemovedWebhooks {
+    /// Webhooks that no longer receive alerts
+    pub removed: Vec<Webhook>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct WatchList {
+    /// This session's watches, oldest first
+    pub watches: Vec<Watch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct FavoriteWeather {
+    /// Favorite location as saved
+    pub location: String,
+    /// Current conditions; absent when the lookup failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weather: Option<Weather>,
+    /// Why the lookup failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct FavoritesWeatherResponse {
+    /// One entry per favorite, in the same order as list_favorites
+    pub items: Vec<FavoriteWeather>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema)]
+pub struct UvIndexDay {
+    /// Day offset, starting at 1 for today
+    pub day: i32,
+    /// Calendar date in the location's timezone (ISO-8601)
+    pub date: String,
+    /// Daily maximum UV index
+    pub uv_index: f64,
+    /// WHO exposure category: "Low", "Moderate", "High", "Very High" or "Extreme"
+    pub risk: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema)]
+pub struct UvIndexResponse {
+    /// One entry per forecast day
+    pub items: Vec<UvIndexDay>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema)]
+pub struct PollenCount {
+    /// Daily peak concentration in grains/m³
+    pub grains_per_m3: i32,
+    /// "None", "Low", "Moderate", "High" or "Very High"
+    pub level: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema)]
+pub struct PollenDay {
+    /// Day offset, starting at 1 for today
+    pub day: i32,
+    /// Calendar date in the location's timezone (ISO-8601)
+    pub date: String,
+    /// Tree pollen (alder, birch, olive)
+    pub tree: PollenCount,
+    /// Grass pollen
+    pub grass: PollenCount,
+    /// Weed pollen (mugwort, ragweed)
+    pub weed: PollenCount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema)]
+pub struct PollenResponse {
+    /// One entry per forecast day
+    pub items: Vec<PollenDay>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema)]
+pub struct MarineConditions {
+    /// Resolved location name
+    pub location: String,
+    /// Significant wave height in metres
+    pub wave_height: f64,
+    /// Wave period in seconds
+    pub wave_period: f64,
+    /// Direction the waves come from
+    pub wave_direction: CompassDirection,
+    /// Sea state on the Douglas scale, from the wave height
+    pub sea_state: SeaState,
+    /// Sea surface temperature in °C
+    pub water_temperature: f64,
+}
+
+/// Douglas sea scale.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SeaState {
+    Calm,
+    Smooth,
+    Slight,
+    Moderate,
+    Rough,
+    VeryRough,
+    High,
+    VeryHigh,
+    Phenomenal,
+}
+
+impl SeaState {
+    pub fn from_wave_height(metres: f64) -> Self {
+        match metres {
+            value if value < 0.1 => SeaState::Calm,
+            value if value < 0.5 => SeaState::Smooth,
+            value if value < 1.25 => SeaState::Slight,
+            value if value < 2.5 => SeaState::Moderate,
+            value if value < 4.0 => SeaState::Rough,
+            value if value < 6.0 => SeaState::VeryRough,
+            value if value < 9.0 => SeaState::High,
+            value if value < 14.0 => SeaState::VeryHigh,
+            _ => SeaState::Phenomenal,
+        }
+    }
+}
+
+/// Eight-point compass direction.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema,
+)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CompassDirection {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl CompassDirection {
+    pub fn from_degrees(degrees: f64) -> Self {
+        const POINTS: [CompassDirection; 8] = [
+            CompassDirection::N,
+            CompassDirection::NE,
+            CompassDirection::E,
+            CompassDirection::SE,
+            CompassDirection::S,
+            CompassDirection::SW,
+            CompassDirection::W,
+            CompassDirection::NW,
+        ];
+        POINTS[(degrees.rem_euclid(360.0) / 45.0).round() as usize % POINTS.len()]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema)]
+pub struct SnowReport {
+    /// Resolved location name
+    pub location: String,
+    /// Snow depth on the ground in cm
+    pub base_depth: i32,
+    /// Snowfall over the last 24 hours in cm
+    pub fresh_snow: i32,
+    /// Air temperature in °C
+    pub temperature: i32,
+    /// Surface conditions, from the depth, fresh snow and temperature
+    pub conditions: SnowConditions,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SnowConditions {
+    /// No snow on the ground
+    NoSnow,
+    /// At least 10 cm of fresh snow
+    Powder,
+    /// Settled snow below freezing
+    Packed,
+    /// Wet, softening snow above freezing
+    Spring,
+}
+
+impl SnowConditions {
+    pub fn classify(base_depth_cm: f64, fresh_snow_cm: f64, temperature: f64) -> Self {
+        if base_depth_cm < 1.0 {
+            SnowConditions::NoSnow
+        } else if fresh_snow_cm >= 10.0 {
+            SnowConditions::Powder
+        } else if temperature > 1.0 {
+            SnowConditions::Spring
+        } else {
+            SnowConditions::Packed
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct WeatherDescription {
+    /// Resolved location name
+    pub location: String,
+    /// Paragraph rendered from the current weather and the next days
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ForecastSummary {
+    /// Location as requested
+    pub location: String,
+    /// Number of days summarized
+    pub days: u32,
+    /// Mean daily high, one decimal
+    pub mean_high: f64,
+    /// Mean daily low, one decimal
+    pub mean_low: f64,
+    /// Unit system of the temperatures
+    pub units: Units,
+    /// Day with the highest chance of precipitation
+    pub rainiest_day: SummaryDay,
+    /// Day with the best outdoor score
+    pub best_outdoor_day: SummaryDay,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct SummaryDay {
+    /// Calendar date in the location's timezone (ISO-8601)
+    pub date: String,
+    /// Sky condition, e.g. "Sunny"
     pub condition: String,
+    /// Chance of precipitation in percent
     pub precipitation_chance: i32,
+    /// Outdoor score from 0 (stay in) to 100 (ideal): dry, clear and near 22 °C
+    pub outdoor_score: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, utoipa::ToSchema)]
+pub struct Astronomy {
+    /// Location as requested
+    pub location: String,
+    /// Calendar date in the location's timezone (ISO-8601)
+    pub date: String,
+    /// IANA timezone name, e.g. "Europe/Paris"
+    pub timezone: String,
+    /// Local sunrise with UTC offset; absent during polar day or night
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sunrise: Option<String>,
+    /// Local sunset with UTC offset; absent during polar day or night
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sunset: Option<String>,
+    /// Minutes between sunrise and sunset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day_length_minutes: Option<i64>,
+    /// Moon phase at local noon, e.g. "Waxing Gibbous"
+    pub moon_phase: String,
+    /// Illuminated part of the moon's disc in percent
+    pub moon_illumination: i32,
+    /// Days since the last new moon
+    pub moon_age_days: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct WeatherSummary {
+    /// Resolved location name
+    pub location: String,
+    /// Short natural-language summary written by the client's model
+    pub summary: String,
+    /// Model that wrote the summary, as reported by the client
+    pub model: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ScoreReceipt {
+    /// Langfuse id of the created score
+    pub score_id: String,
+    /// Trace the score was attached to
+    pub trace_id: String,
 }
 
 #[derive(Clone)]
 pub struct WeatherService {
     tool_router: ToolRouter<WeatherService>,
-    // We could add state here if needed, e.g., for caching
-    _state: Arc<Mutex<()>>,
+    app: AppState,
+    /// Run around every tool call, outermost first.
+    middleware: Vec<Arc<dyn ToolMiddleware>>,
+    /// This session's preferences; `None` until first used, then seeded
+    /// from the caller's stored ones.
+    preferences: Arc<Mutex<Option<Preferences>>>,
+    /// Resources embedded in this session's tool results.
+    resources: Arc<SessionResources>,
+    /// Last client request, so keepalive pings only go to idle sessions.
+    activity: Arc<Activity>,
+    /// Least severe `notifications/message` the client wants, from
+    /// `logging/setLevel`.
+    log_level: Arc<std::sync::Mutex<LoggingLevel>>,
+    /// `mcp.session` span opened by `initialize`; it ends when the session's
+    /// service is dropped, and every tool span of the session is its child.
+    session_span: Arc<OnceLock<tracing::Span>>,
+    /// Set once the client confirms initialization.
+    session_id: Arc<OnceLock<String>>,
+    /// Whether rmcp's `serve_inner` span has been given the session id.
+    transport_tagged: Arc<AtomicBool>,
+    /// Parent of the session span when `initialize` did not arrive over
+    /// HTTP with a trace context (in-process clients).
+    trace_parent: Option<opentelemetry::Context>,
 }
 
 #[tool_router]
 impl WeatherService {
-    pub fn new() -> Self {
+    pub fn new(app: AppState) -> Self {
+        // Audit first, so calls refused by later middleware are recorded too
+        let mut middleware: Vec<Arc<dyn ToolMiddleware>> = Vec::new();
+        if let Some(audit) = &app.audit {
+            middleware.push(Arc::new(audit.clone()));
+        }
+        if let Some(recorder) = &app.recorder {
+            middleware.push(Arc::new(recorder.clone()));
+        }
+        middleware.push(app.usage.clone());
+        if let Some(slo) = &app.slo {
+            middleware.push(slo.clone());
+        }
+        if let Some(rbac) = &app.rbac {
+            middleware.push(rbac.clone());
+        }
+        if let Some(concurrency) = &app.concurrency {
+            middleware.push(concurrency.clone());
+        }
+        if let Some(priority) = &app.priority {
+            middleware.push(priority.clone());
+        }
+        if let Some(latency) = SimulatedLatency::from_config(&app.config.tool_latency) {
+            middleware.push(Arc::new(latency));
+        }
         Self {
             tool_router: Self::tool_router(),
-            _state: Arc::new(Mutex::new(())),
+            app,
+            middleware,
+            preferences: Arc::default(),
+            resources: Arc::default(),
+            activity: Arc::default(),
+            log_level: Arc::new(std::sync::Mutex::new(LoggingLevel::Debug)),
+            session_span: Arc::default(),
+            session_id: Arc::default(),
+            transport_tagged: Arc::default(),
+            trace_parent: None,
+        }
+    }
+
+    /// Add `middleware` inside the configured ones (audit, recording, RBAC,
+    /// concurrency, priority, latency).
+    pub fn with_middleware(mut self, middleware: impl ToolMiddleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Parent the session span on `parent` unless the client's HTTP request
+    /// carries a trace context, so an in-process client's calls join its trace.
+    pub fn with_trace_parent(mut self, parent: opentelemetry::Context) -> Self {
+        self.trace_parent = Some(parent);
+        self
+    }
+
+    /// Convert and localize `forecast` a chunk of days at a time. Each chunk
+    /// is recorded as an event on the tool span and, when the request has a
+    /// progress token, sent to the client as a progress notification whose
+    /// message is the chunk's `ForecastResponse` JSON. The returned response
+    /// holds every chunk's days.
+    async fn stream_forecast(
+        &self,
+        context: &RequestContext<RoleServer>,
+        forecast: Vec<Forecast>,
+        units: Units,
+        lang: Lang,
+    ) -> ForecastResponse {
+        let progress_token = context
+            .meta
+            .get_progress_token()
+            .filter(|_| feature_flags::current().streaming_results);
+        let total = forecast.len();
+        let mut items = Vec::with_capacity(total);
+        for (index, days) in forecast
+            .chunks(self.app.config.forecast_chunk_days)
+            .enumerate()
+        {
+            let chunk = ForecastResponse::in_units(days.to_vec(), units).localized(lang);
+            info!(
+                chunk = index,
+                days = chunk.items.len(),
+                first = chunk.items.first().map(|day| day.date.as_str()),
+                last = chunk.items.last().map(|day| day.date.as_str()),
+                "Forecast chunk computed"
+            );
+            items.extend(chunk.items.iter().cloned());
+
+            let Some(progress_token) = progress_token.clone() else {
+                continue;
+            };
+            let progress = ProgressNotificationParam {
+                progress_token,
+                progress: items.len() as f64,
+                total: Some(total as f64),
+                message: serde_json::to_string(&chunk).ok(),
+            };
+            if let Err(error) = context.peer.notify_progress(progress).await {
+                debug!(%error, "Could not send forecast chunk");
+            }
+        }
+        ForecastResponse { items, units }
+    }
+
+    /// Key of this session's watches; empty for transports without session ids.
+    fn session_key(&self) -> String {
+        self.session_id.get().cloned().unwrap_or_default()
+    }
+
+    /// This session's preferences, seeded from the caller's stored ones on
+    /// first use.
+    async fn session_preferences(&self, context: &RequestContext<RoleServer>) -> Preferences {
+        let user_id = self.preference_user(context);
+        let mut session = self.preferences.lock().await;
+        session
+            .get_or_insert_with(|| {
+                user_id
+                    .and_then(|user_id| self.app.preferences.get(&user_id))
+                    .unwrap_or_default()
+            })
+            .clone()
+    }
+
+    /// User whose stored preferences the caller shares: its `x-user-id`, but
+    /// only when `RBAC_TRUST_USER_HEADER` says a proxy sets that header, as
+    /// any caller could otherwise read and change anyone's preferences.
+    fn preference_user(&self, context: &RequestContext<RoleServer>) -> Option<String> {
+        RequestAttribution::from_request_context(context)
+            .user_id
+            .filter(|_| self.app.config.rbac.trust_user_header)
+    }
+
+    /// Apply `change` to this session's preferences, and to the caller's
+    /// stored ones so their later sessions start with it; nothing changes if
+    /// it fails. The stored preferences are changed in place rather than
+    /// replaced by this session's copy, which would undo changes the user
+    /// made meanwhile in another session.
+    async fn update_preferences(
+        &self,
+        context: &RequestContext<RoleServer>,
+        change: impl FnOnce(&mut Preferences) -> Result<(), McpError>,
+    ) -> Result<Preferences, McpError> {
+        let user_id = self.preference_user(context);
+        let mut session = self.preferences.lock().await;
+        let preferences = match user_id {
+            Some(user_id) => self.app.preferences.update(&user_id, change).await?,
+            None => {
+                let mut preferences = session.clone().unwrap_or_default();
+                change(&mut preferences)?;
+                preferences
+            }
+        };
+        *session = Some(preferences.clone());
+        Ok(preferences)
+    }
+
+    /// `location`, or the session's default when it is absent or blank.
+    async fn location_or_default(
+        &self,
+        context: &RequestContext<RoleServer>,
+        location: Option<String>,
+    ) -> Result<String, McpError> {
+        if let Some(location) = location.filter(|location| !location.trim().is_empty()) {
+            return Ok(location);
+        }
+        if let Some(location) = self.session_preferences(context).await.default_location {
+            return Ok(location);
         }
+        // Ask the user, when the client can show them the question
+        if feature_flags::current().elicitation && ClientSupport::of(&context.peer).elicitation {
+            let answer = context
+                .peer
+                .elicit::<LocationAnswer>("Which location do you want the weather for?")
+                .await;
+            match answer {
+                Ok(Some(answer)) if !answer.location.trim().is_empty() => {
+                    return Ok(answer.location)
+                }
+                Ok(_) => {}
+                Err(error) => debug!(%error, "Location elicitation ended without an answer"),
+            }
+        }
+        Err(McpError::invalid_params(
+            "location is required: no default location is set (see set_preference)",
+            None,
+        ))
     }
 
-    #[tool(description = "Get current weather for a specified location")]
-    #[instrument(skip(self, _request_context, params), fields(
+    #[tool(
+        description = "Get current weather for a specified location",
+        output_schema = cached_schema_for_type::<Weather>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
         input = tracing::field::Empty,
-        output = tracing::field::Empty
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
     ))]
     async fn get_weather(
         &self,
-        _request_context: RequestContext<RoleServer>,
+        request_context: RequestContext<RoleServer>,
         params: Parameters<GetWeatherArgs>,
     ) -> Result<CallToolResult, McpError> {
-        // One line: extract args and setup tracing
-        let args = crate::trace_utils::trace_rmcp_setup(params).await;
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
 
-        info!(location = %args.location, "Handling get_weather request");
+        let location = self
+            .location_or_default(&request_context, args.location)
+            .await?;
+        let units = self.session_preferences(&request_context).await.units;
 
-        let mut rng = rand::thread_rng();
-        let weather_conditions = ["Sunny", "Cloudy", "Rainy", "Partly Cloudy"];
+        info!(%location, "Handling get_weather request");
 
-        let weather = Weather {
-            location: args.location.clone(),
-            temperature: rng.gen_range(15..=30),
-            condition: weather_conditions[rng.gen_range(0..weather_conditions.len())].to_string(),
-            humidity: rng.gen_range(40..=80),
-            wind_speed: rng.gen_range(5..=25),
-        };
+        let weather = self
+            .app
+            .current_weather(&location)
+            .await?
+            .in_units(units)
+            .localized(Lang::resolve(args.lang.as_deref()));
 
         debug!(?weather, "Generated weather response");
 
@@ -97,38 +919,45 @@ impl WeatherService {
         crate::trace_utils::trace_rmcp_result(weather)
     }
 
-    #[tool(description = "Get weather forecast for the specified location and number of days")]
-    #[instrument(skip(self, _request_context, params), fields(
+    #[tool(
+        description = "Get weather forecast for the specified location and number of days, with the days also embedded as a CSV resource (weather://forecast/...)",
+        output_schema = cached_schema_for_type::<ForecastResponse>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
         input = tracing::field::Empty,
-        output = tracing::field::Empty
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
     ))]
     async fn get_forecast(
         &self,
-        _request_context: RequestContext<RoleServer>,
+        request_context: RequestContext<RoleServer>,
         params: Parameters<GetForecastArgs>,
     ) -> Result<CallToolResult, McpError> {
-        // One line: extract args and setup tracing
-        let args = crate::trace_utils::trace_rmcp_setup(params).await;
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
 
         info!(
             location = %args.location,
             requested_days = args.days,
+            long_range = args.long_range,
             "Handling get_forecast request"
         );
 
-        let mut rng = rand::thread_rng();
-        let conditions = ["Sunny", "Cloudy", "Rainy", "Stormy"];
-        let days = args.days.min(7);
-
-        let forecast: Vec<Forecast> = (1..=days)
-            .map(|day| Forecast {
-                day: day as i32,
-                high: rng.gen_range(20..=35),
-                low: rng.gen_range(10..=20),
-                condition: conditions[rng.gen_range(0..conditions.len())].to_string(),
-                precipitation_chance: rng.gen_range(0..=100),
-            })
-            .collect();
+        let forecast = if args.long_range {
+            if !(1..=MAX_LONG_RANGE_DAYS).contains(&args.days) {
+                return Err(McpError::invalid_params(
+                    format!("days must be between 1 and {MAX_LONG_RANGE_DAYS} with long_range"),
+                    None,
+                ));
+            }
+            self.app
+                .long_range_forecast(&args.location, args.days)
+                .await?
+        } else {
+            self.app.forecast(&args.location, args.days).await?
+        };
+        let units = self.session_preferences(&request_context).await.units;
 
         debug!(
             forecast_len = forecast.len(),
@@ -136,27 +965,1410 @@ impl WeatherService {
             "Generated forecast response"
         );
 
-        // One line: record output and return
-        crate::trace_utils::trace_rmcp_result(json!({ "items": forecast }))
+        let response = self
+            .stream_forecast(
+                &request_context,
+                forecast,
+                units,
+                Lang::resolve(args.lang.as_deref()),
+            )
+            .await;
+        let resources_config = &self.app.config.resources;
+        if !resources_config.enabled {
+            return crate::trace_utils::trace_rmcp_result(response);
+        }
+
+        let uri = resources::forecast_uri(&args.location, &response);
+        let is_new = self.resources.get(&uri).is_none();
+        let csv = self.resources.insert(
+            &uri,
+            &format!("Forecast for {}", args.location),
+            "text/csv",
+            resources::forecast_csv(&response),
+        );
+        if is_new && resources_config.list_changed {
+            if let Err(error) = request_context.peer.notify_resource_list_changed().await {
+                debug!(%error, "Could not send resources/list_changed");
+            }
+        }
+
+        // The same days as CSV, also readable later with resources/read
+        let mut result = crate::trace_utils::trace_rmcp_result(response)?;
+        result.content.push(Content::resource(csv));
+        Ok(result)
     }
-}
 
-#[tool_handler]
-impl ServerHandler for WeatherService {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder()
-                .enable_tools()
-                .build(),
-            server_info: Implementation {
-                name: "weather-assistant-rust".to_string(),
-                version: "1.0.0".to_string(),
-                title: None,
-                website_url: None,
-                icons: None,
-            },
-            instructions: Some("This server provides weather tools. Tools: get_weather (get current weather for a location), get_forecast (get weather forecast for multiple days).".to_string()),
+    #[tool(
+        description = "Summarize current weather and the next days for a location in plain language, written by the client's model via MCP sampling",
+        output_schema = cached_schema_for_type::<WeatherSummary>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn summarize_weather(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<SummarizeWeatherArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name(), "sampling"]);
+
+        info!(location = %args.location, "Handling summarize_weather request");
+
+        let weather = self.app.current_weather(&args.location).await?;
+        let forecast = self.app.forecast(&args.location, default_days()).await?;
+
+        // Without sampling, fall back to the server-side template
+        if !ClientSupport::of(&request_context.peer).sampling {
+            tracing::Span::current().set_attribute("mcp.sampling.skipped", true);
+            info!("Client does not support sampling; using the describe_weather template");
+            let forecast = ForecastResponse::in_units(forecast, Units::Metric).items;
+            let summary = self.render_description(&weather, &forecast, Units::Metric, Lang::En)?;
+            return crate::trace_utils::trace_rmcp_result(WeatherSummary {
+                location: weather.location,
+                summary,
+                model: "template".to_string(),
+            });
+        }
+
+        let data = serde_json::json!({ "current": weather, "forecast": forecast });
+        let request = CreateMessageRequestParam {
+            messages: vec![SamplingMessage {
+                role: Role::User,
+                content: Content::text(format!(
+                    "Summarize this weather data for {} in two sentences:\n{data}",
+                    weather.location
+                )),
+            }],
+            model_preferences: None,
+            system_prompt: Some(
+                "You are a concise weather presenter. Use only the data provided.".to_string(),
+            ),
+            include_context: Some(ContextInclusion::None),
+            temperature: Some(0.3),
+            max_tokens: 200,
+            stop_sequences: None,
+            metadata: None,
+        };
+
+        let result = crate::generation::sample(&request_context.peer, request)
+            .await
+            .map_err(|error| {
+                tool_errors::with_code(
+                    McpError::internal_error(format!("sampling failed: {error}"), None),
+                    "sampling_failed",
+                    false,
+                )
+            })?;
+        let summary = result
+            .message
+            .content
+            .as_text()
+            .map(|text| text.text.clone())
+            .unwrap_or_default();
+
+        crate::trace_utils::trace_rmcp_result(WeatherSummary {
+            location: weather.location,
+            summary,
+            model: result.model,
+        })
+    }
+
+    #[tool(
+        description = "Describe current weather and the next three days for a location in a short paragraph, rendered from a server-side template (no model involved)",
+        output_schema = cached_schema_for_type::<WeatherDescription>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn describe_weather(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<GetWeatherArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
+
+        let location = self
+            .location_or_default(&request_context, args.location)
+            .await?;
+        let units = self.session_preferences(&request_context).await.units;
+
+        info!(%location, "Handling describe_weather request");
+
+        let lang = Lang::resolve(args.lang.as_deref());
+        let weather = self
+            .app
+            .current_weather(&location)
+            .await?
+            .in_units(units)
+            .localized(lang);
+        let forecast = self.app.forecast(&location, default_days()).await?;
+        let forecast = ForecastResponse::in_units(forecast, units)
+            .localized(lang)
+            .items;
+
+        let text = self.render_description(&weather, &forecast, units, lang)?;
+
+        crate::trace_utils::trace_rmcp_result(WeatherDescription {
+            location: weather.location,
+            text,
+        })
+    }
+
+    #[tool(
+        description = "Get a PNG chart of the 7-day forecast for a location: daily low-to-high bars coloured by condition, a line through the highs, and the chance of precipitation underneath"
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn get_weather_map(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<LocationArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
+
+        let location = self
+            .location_or_default(&request_context, args.location)
+            .await?;
+
+        info!(%location, "Handling get_weather_map request");
+
+        let forecast = Arc::new(self.app.forecast(&location, MAX_FORECAST_DAYS).await?);
+        let png = self
+            .app
+            .cpu_pool
+            .run("weather_map.render", {
+                let forecast = Arc::clone(&forecast);
+                move || {
+                    let _span =
+                        tracing::info_span!("weather_map.render", days = forecast.len()).entered();
+                    weather_map::render_forecast_png(&forecast)
+                }
+            })
+            .await
+            .map_err(|error| McpError::internal_error(error, None))?;
+
+        // The image itself would swamp the trace; record what was sent instead
+        let span = tracing::Span::current();
+        if crate::trace_utils::is_recorded(&span) {
+            let output = serde_json::json!({
+                "mime_type": "image/png",
+                "bytes": png.len(),
+                "days": forecast.len(),
+            });
+            span.record("output", tracing::field::display(&output));
+        }
+
+        let first = forecast.first().map(|day| day.date.as_str()).unwrap_or("");
+        let last = forecast.last().map(|day| day.date.as_str()).unwrap_or("");
+        Ok(CallToolResult::success(vec![
+            Content::image(BASE64_STANDARD.encode(&png), "image/png"),
+            Content::text(format!(
+                "{}-day forecast chart for {location}, {first} to {last}",
+                forecast.len()
+            )),
+        ]))
+    }
+
+    #[tool(
+        description = "Export the daily weather of the past days (up to 30) for a location as a CSV or Parquet file, embedded as a resource (weather://history/...)",
+        output_schema = cached_schema_for_type::<HistoryExport>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn export_history(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<ExportHistoryArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
+
+        let location = self
+            .location_or_default(&request_context, args.location)
+            .await?;
+        if !(1..=MAX_HISTORY_DAYS).contains(&args.days) {
+            return Err(McpError::invalid_params(
+                format!("days must be between 1 and {MAX_HISTORY_DAYS}"),
+                None,
+            ));
+        }
+
+        info!(%location, days = args.days, format = ?args.format, "Handling export_history request");
+
+        let history = self.app.history(&location, args.days).await?;
+        let uri = resources::history_uri(&location, &history, args.format);
+        let name = format!("Weather history for {location}");
+        let days = history.len();
+        let first_date = history
+            .first()
+            .map(|day| day.date.clone())
+            .unwrap_or_default();
+        let last_date = history
+            .last()
+            .map(|day| day.date.clone())
+            .unwrap_or_default();
+        let (file, bytes) = match args.format {
+            ExportFormat::Csv => {
+                let csv = history_export::csv(history);
+                let bytes = csv.len();
+                (
+                    self.resources
+                        .insert(&uri, &name, args.format.mime_type(), csv),
+                    bytes,
+                )
+            }
+            ExportFormat::Parquet => {
+                let parquet = self
+                    .app
+                    .cpu_pool
+                    .run("history_export.parquet", move || {
+                        history_export::parquet(&history)
+                    })
+                    .await
+                    .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+                let file =
+                    self.resources
+                        .insert_blob(&uri, &name, args.format.mime_type(), &parquet);
+                (file, parquet.len())
+            }
+        };
+        let resources_config = &self.app.config.resources;
+        if resources_config.enabled && resources_config.list_changed {
+            if let Err(error) = request_context.peer.notify_resource_list_changed().await {
+                debug!(%error, "Could not send resources/list_changed");
+            }
+        }
+
+        let mut result = crate::trace_utils::trace_rmcp_result(HistoryExport {
+            uri,
+            format: args.format,
+            days,
+            first_date,
+            last_date,
+            bytes,
+        })?;
+        result.content.push(Content::resource(file));
+        Ok(result)
+    }
+
+    #[tool(
+        description = "Summarize the coming week for a location: mean high and low, the rainiest day and the best day to be outdoors",
+        output_schema = cached_schema_for_type::<ForecastSummary>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn get_forecast_summary(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<GetWeatherArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
+
+        let location = self
+            .location_or_default(&request_context, args.location)
+            .await?;
+        let units = self.session_preferences(&request_context).await.units;
+
+        info!(%location, "Handling get_forecast_summary request");
+
+        let forecast = self.app.forecast(&location, MAX_FORECAST_DAYS).await?;
+        let mut summary = self
+            .app
+            .cpu_pool
+            .run("forecast_summary.summarize", move || {
+                forecast_summary::summarize(&location, &forecast, units)
+            })
+            .await
+            .ok_or_else(|| McpError::internal_error("provider returned an empty forecast", None))?;
+        let lang = Lang::resolve(args.lang.as_deref());
+        for day in [&mut summary.rainiest_day, &mut summary.best_outdoor_day] {
+            day.condition = i18n::condition(lang, &day.condition);
+        }
+
+        crate::trace_utils::trace_rmcp_result(summary)
+    }
+
+    #[tool(
+        description = "Get the daily maximum UV index and its exposure risk for the specified location and number of days",
+        output_schema = cached_schema_for_type::<UvIndexResponse>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn get_uv_index(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<DailyArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
+
+        info!(
+            location = %args.location,
+            requested_days = args.days,
+            "Handling get_uv_index request"
+        );
+
+        let items = self.app.uv_index(&args.location, args.days).await?;
+
+        crate::trace_utils::trace_rmcp_result(UvIndexResponse { items })
+    }
+
+    #[tool(
+        description = "Get the daily pollen forecast (tree, grass and weed levels) for the specified location and number of days",
+        output_schema = cached_schema_for_type::<PollenResponse>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn get_pollen_forecast(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<DailyArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
+
+        info!(
+            location = %args.location,
+            requested_days = args.days,
+            "Handling get_pollen_forecast request"
+        );
+
+        let items = self.app.pollen_forecast(&args.location, args.days).await?;
+
+        crate::trace_utils::trace_rmcp_result(PollenResponse { items })
+    }
+
+    #[tool(
+        description = "Get current marine conditions (wave height, period and direction, sea state, water temperature) for a coastal location",
+        output_schema = cached_schema_for_type::<MarineConditions>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn get_marine_conditions(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<LocationArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
+
+        let location = self
+            .location_or_default(&request_context, args.location)
+            .await?;
+
+        info!(%location, "Handling get_marine_conditions request");
+
+        let marine = self.app.marine_conditions(&location).await?;
+
+        crate::trace_utils::trace_rmcp_result(marine)
+    }
+
+    #[tool(
+        description = "Get a snow report (base depth, fresh snow over the last 24 hours, surface conditions) for a location, e.g. a ski resort",
+        output_schema = cached_schema_for_type::<SnowReport>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty,
+        cache.state = tracing::field::Empty
+    ))]
+    async fn get_snow_report(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<LocationArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
+
+        let location = self
+            .location_or_default(&request_context, args.location)
+            .await?;
+
+        info!(%location, "Handling get_snow_report request");
+
+        let report = self.app.snow_report(&location).await?;
+
+        crate::trace_utils::trace_rmcp_result(report)
+    }
+
+    #[tool(
+        description = "Get sunrise, sunset and moon phase for a location on a given date. Computed locally, no weather provider involved",
+        output_schema = cached_schema_for_type::<Astronomy>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty
+    ))]
+    async fn get_astronomy(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<GetAstronomyArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+
+        let place =
+            forecast_time::bundled_place(&normalize_location(&args.location)).ok_or_else(|| {
+                let known: Vec<_> = forecast_time::bundled_place_names().collect();
+                McpError::invalid_params(
+                    format!(
+                        "unknown location '{}'; known locations: {}",
+                        args.location,
+                        known.join(", ")
+                    ),
+                    None,
+                )
+            })?;
+        let date = match &args.date {
+            Some(date) => date.parse::<NaiveDate>().map_err(|error| {
+                McpError::invalid_params(
+                    format!("date must be YYYY-MM-DD, got '{date}': {error}"),
+                    None,
+                )
+            })?,
+            None => Utc::now().with_timezone(&place.timezone).date_naive(),
+        };
+
+        info!(location = %args.location, %date, "Handling get_astronomy request");
+
+        crate::trace_utils::trace_rmcp_result(astronomy::astronomy(&args.location, &place, date))
+    }
+
+    #[tool(
+        description = "Set this session's preferences: a default location for tools called without one, and metric or imperial units. Only the given fields change",
+        output_schema = cached_schema_for_type::<Preferences>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty
+    ))]
+    async fn set_preference(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<SetPreferenceArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+
+        let preferences = self
+            .update_preferences(&request_context, |preferences| {
+                if let Some(location) = args.default_location {
+                    let location = location.trim();
+                    preferences.default_location =
+                        (!location.is_empty()).then(|| location.to_string());
+                }
+                if let Some(units) = args.units {
+                    preferences.units = units;
+                }
+                Ok(())
+            })
+            .await?;
+
+        info!(?preferences, "Updated session preferences");
+
+        crate::trace_utils::trace_rmcp_result(preferences)
+    }
+
+    #[tool(
+        description = "Get this session's preferences (default location and units)",
+        output_schema = cached_schema_for_type::<Preferences>()
+    )]
+    #[instrument(skip(self, request_context), err(Display), fields(
+        output = tracing::field::Empty
+    ))]
+    async fn get_preferences(
+        &self,
+        request_context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+
+        let preferences = self.session_preferences(&request_context).await;
+
+        crate::trace_utils::trace_rmcp_result(preferences)
+    }
+
+    #[tool(
+        description = "Get server load since startup: calls, errors and latency per tool, across all sessions",
+        output_schema = cached_schema_for_type::<ServerStats>()
+    )]
+    #[instrument(skip(self, request_context), err(Display), fields(
+        output = tracing::field::Empty
+    ))]
+    async fn get_server_stats(
+        &self,
+        request_context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+
+        let stats = self.app.usage.snapshot(
+            &self.app.deprecations,
+            &self.app.keepalive,
+            self.app.concurrency.as_deref(),
+        );
+
+        crate::trace_utils::trace_rmcp_result(stats)
+    }
+
+    #[tool(
+        description = "Add a location to the favorites. Favorites are kept per user across sessions when x-user-id is trusted",
+        output_schema = cached_schema_for_type::<FavoritesList>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty
+    ))]
+    async fn add_favorite(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<FavoriteArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+
+        let location = args.location.trim().to_string();
+        if location.is_empty() {
+            return Err(McpError::invalid_params("location must not be empty", None));
+        }
+        check_location(&location)?;
+        let preferences = self
+            .update_preferences(&request_context, |preferences| {
+                let key = normalize_location(&location);
+                if preferences
+                    .favorites
+                    .iter()
+                    .any(|favorite| normalize_location(favorite) == key)
+                {
+                    return Ok(());
+                }
+                if preferences.favorites.len() >= MAX_FAVORITES {
+                    return Err(McpError::invalid_params(
+                        format!("at most {MAX_FAVORITES} favorites can be saved"),
+                        None,
+                    ));
+                }
+                preferences.favorites.push(location.clone());
+                Ok(())
+            })
+            .await?;
+
+        info!(%location, "Added favorite");
+
+        crate::trace_utils::trace_rmcp_result(FavoritesList {
+            favorites: preferences.favorites,
+        })
+    }
+
+    #[tool(
+        description = "Remove a location from the favorites",
+        output_schema = cached_schema_for_type::<FavoritesList>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty
+    ))]
+    async fn remove_favorite(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<FavoriteArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+
+        let key = normalize_location(&args.location);
+        let preferences = self
+            .update_preferences(&request_context, |preferences| {
+                let before = preferences.favorites.len();
+                preferences
+                    .favorites
+                    .retain(|favorite| normalize_location(favorite) != key);
+                if preferences.favorites.len() == before {
+                    return Err(McpError::invalid_params(
+                        format!("'{}' is not a favorite", args.location),
+                        None,
+                    ));
+                }
+                Ok(())
+            })
+            .await?;
+
+        info!(location = %args.location, "Removed favorite");
+
+        crate::trace_utils::trace_rmcp_result(FavoritesList {
+            favorites: preferences.favorites,
+        })
+    }
+
+    #[tool(
+        description = "List the favorite locations",
+        output_schema = cached_schema_for_type::<FavoritesList>()
+    )]
+    #[instrument(skip(self, request_context), err(Display), fields(
+        output = tracing::field::Empty
+    ))]
+    async fn list_favorites(
+        &self,
+        request_context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+
+        let preferences = self.session_preferences(&request_context).await;
+
+        crate::trace_utils::trace_rmcp_result(FavoritesList {
+            favorites: preferences.favorites,
+        })
+    }
+
+    #[tool(
+        description = "Get current weather for every favorite location in one call. A location that fails reports its error without failing the others",
+        output_schema = cached_schema_for_type::<FavoritesWeatherResponse>()
+    )]
+    #[instrument(skip(self, request_context), err(Display), fields(
+        output = tracing::field::Empty,
+        favorites = tracing::field::Empty
+    ))]
+    async fn get_favorites_weather(
+        &self,
+        request_context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        crate::trace_utils::trace_tags(&[self.app.provider.name()]);
+
+        let preferences = self.session_preferences(&request_context).await;
+        tracing::Span::current().record("favorites", preferences.favorites.len());
+
+        // Concurrently, each under this tool's span
+        let lookups = preferences
+            .favorites
+            .into_iter()
+            .map(|location| async move {
+                match self.app.current_weather(&location).await {
+                    Ok(weather) => FavoriteWeather {
+                        location,
+                        weather: Some(weather.in_units(preferences.units)),
+                        error: None,
+                    },
+                    Err(error) => FavoriteWeather {
+                        location,
+                        weather: None,
+                        error: Some(error.to_string()),
+                    },
+                }
+            });
+        let items = futures::future::join_all(lookups).await;
+
+        crate::trace_utils::trace_rmcp_result(FavoritesWeatherResponse { items })
+    }
+
+    #[tool(
+        description = "Register a URL to receive weather alerts for a location: heat, frost, strong wind, storm or snow. Each new alert is POSTed as JSON signed with HMAC-SHA256 (x-webhook-signature)",
+        output_schema = cached_schema_for_type::<Webhook>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty
+    ))]
+    async fn register_webhook(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<RegisterWebhookArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+
+        if self.app.config.webhooks.signing_secret.is_none() {
+            return Err(McpError::invalid_request(
+                "webhooks are disabled on this server (WEBHOOK_SIGNING_SECRET is not set)",
+                None,
+            ));
+        }
+        let webhook = self
+            .app
+            .webhooks
+            .register(
+                &self.session_key(),
+                &args.url,
+                &args.location,
+                &self.app.config.webhooks,
+            )
+            .await?;
+
+        info!(webhook.id = %webhook.id, location = %webhook.location, "Registered webhook");
+
+        crate::trace_utils::trace_rmcp_result(webhook)
+    }
+
+    #[tool(
+        description = "Stop sending weather alerts to a URL this session registered with register_webhook",
+        output_schema = cached_schema_for_type::<RemovedWebhooks>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty
+    ))]
+    async fn unregister_webhook(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<UnregisterWebhookArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+
+        let removed =
+            self.app
+                .webhooks
+                .unregister(&self.session_key(), &args.url, args.location.as_deref());
+        if removed.is_empty() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "no webhook is registered for '{}' by this session",
+                    args.url
+                ),
+                None,
+            ));
+        }
+
+        info!(url = %args.url, removed = removed.len(), "Unregistered webhooks");
+
+        crate::trace_utils::trace_rmcp_result(RemovedWebhooks { removed })
+    }
+
+    #[tool(
+        description = "Watch a location for a threshold, e.g. temperature_above 30. Checked in the background; when the rule starts to hold, the watch's weather://watch/<id> resource is updated and the session notified",
+        output_schema = cached_schema_for_type::<Watch>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty
+    ))]
+    async fn create_watch(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<CreateWatchArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+
+        let watch = self.app.watches.create(
+            &self.session_key(),
+            crate::trace_utils::call_parent_context(&request_context),
+            request_context.peer.clone(),
+            &args.location,
+            args.condition,
+            args.threshold,
+        )?;
+
+        info!(watch.id = %watch.id, location = %watch.location, "Created watch");
+
+        crate::trace_utils::trace_rmcp_result(watch)
+    }
+
+    #[tool(
+        description = "List this session's watches with their state at the last check",
+        output_schema = cached_schema_for_type::<WatchList>()
+    )]
+    #[instrument(skip(self, request_context), err(Display), fields(
+        output = tracing::field::Empty
+    ))]
+    async fn list_watches(
+        &self,
+        request_context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+
+        crate::trace_utils::trace_rmcp_result(WatchList {
+            watches: self.app.watches.list(&self.session_key()),
+        })
+    }
+
+    #[tool(
+        description = "Rate the previous answer from 0 (unhelpful) to 1 (helpful), with an optional comment. The rating is recorded as a Langfuse score on the current trace",
+        output_schema = cached_schema_for_type::<ScoreReceipt>()
+    )]
+    #[instrument(skip(self, request_context, params), err(Display), fields(
+        input = tracing::field::Empty,
+        output = tracing::field::Empty
+    ))]
+    async fn rate_response(
+        &self,
+        request_context: RequestContext<RoleServer>,
+        params: Parameters<RateResponseArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        crate::trace_utils::trace_rmcp_context(&request_context);
+        let args = crate::trace_utils::trace_rmcp_setup(params);
+
+        if !(0.0..=1.0).contains(&args.score) {
+            return Err(McpError::invalid_params(
+                format!("score must be between 0 and 1, got {}", args.score),
+                None,
+            ));
+        }
+        let langfuse = self.app.langfuse.as_ref().ok_or_else(|| {
+            McpError::internal_error("Langfuse credentials are not configured", None)
+        })?;
+
+        // Score the trace this call belongs to (the client's conversation)
+        let trace_id = tracing::Span::current()
+            .context()
+            .span()
+            .span_context()
+            .trace_id()
+            .to_string();
+        let score = CreateScore {
+            trace_id: trace_id.clone(),
+            name: "user-feedback".to_string(),
+            value: args.score,
+            comment: args.comment,
+        };
+
+        let created = langfuse.create_score(&score).await.map_err(|error| {
+            tool_errors::with_code(
+                McpError::internal_error(error.to_string(), None),
+                "langfuse_unavailable",
+                true,
+            )
+        })?;
+
+        info!(score_id = %created.id, %trace_id, "Recorded feedback score");
+
+        crate::trace_utils::trace_rmcp_result(ScoreReceipt {
+            score_id: created.id,
+            trace_id,
+        })
+    }
+}
+
+impl WeatherService {
+    /// Definitions of every registered tool, sorted by name.
+    pub fn tool_definitions() -> Vec<Tool> {
+        let mut tools = Self::tool_router().list_all();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        tools
+    }
+
+    /// The `describe_weather` paragraph for `weather` and `forecast`, which
+    /// are already in `units` and `lang`.
+    fn render_description(
+        &self,
+        weather: &Weather,
+        forecast: &[Forecast],
+        units: Units,
+        lang: Lang,
+    ) -> Result<String, McpError> {
+        let (temperature, speed) = units.symbols();
+        let context = serde_json::json!({
+            "location": weather.location,
+            "weather": weather,
+            "forecast": forecast,
+            "units": { "name": units, "temperature": temperature, "speed": speed },
+        });
+        let template = self.app.templates.localized("describe_weather", lang);
+        let _span = tracing::info_span!("template.render", %template).entered();
+        self.app
+            .templates
+            .render(&template, context)
+            .map_err(|error| McpError::internal_error(format!("{error:#}"), None))
+    }
+
+    /// What this server supports, as configured. Resource subscriptions and
+    /// prompts are not implemented, so they are never advertised. Logging is
+    /// only advertised when idle sessions are warned before being closed.
+    fn capabilities(&self) -> ServerCapabilities {
+        let mut capabilities = ServerCapabilities::builder().enable_tools().build();
+        if self.app.config.keepalive.idle_timeout.is_some() {
+            capabilities.logging = Some(JsonObject::new());
+        }
+        let config = &self.app.config.resources;
+        if config.enabled {
+            capabilities.resources = Some(ResourcesCapability {
+                subscribe: None,
+                list_changed: config.list_changed.then_some(true),
+            });
+        }
+        capabilities
+    }
+
+    /// Note a client request. The first one also tags rmcp's `serve_inner`
+    /// span, which runs request handlers, with the session id so
+    /// [`span_repair`] can move it into the session's trace; it only opens
+    /// after `initialize` has been handled.
+    fn touch(&self) {
+        self.activity.touch();
+        let Some(session_id) = self.session_id.get() else {
+            return;
+        };
+        if self.transport_tagged.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let transport = tracing::Span::current();
+        if transport.metadata().map(|meta| meta.name()) == Some("serve_inner") {
+            transport.set_attribute("session.id", session_id.clone());
+        }
+    }
+
+    /// Server instructions generated from [`Self::tool_definitions`] once,
+    /// then shared by every session.
+    pub fn instructions() -> &'static str {
+        static INSTRUCTIONS: OnceLock<String> = OnceLock::new();
+        INSTRUCTIONS.get_or_init(|| schema_export::instructions(&Self::tool_definitions()))
+    }
+}
+
+impl WeatherService {
+    /// End of the middleware chain: dispatch to the tool.
+    pub(crate) fn route<'a>(&'a self, call: &'a ToolCall) -> BoxFuture<'a, ToolResult> {
+        let context = ToolCallContext::new(self, call.request.clone(), call.context.clone());
+        Box::pin(self.tool_router.call(context))
+    }
+}
+
+impl ServerHandler for WeatherService {
+    /// Keep the client's declared capabilities for later calls, and open the
+    /// session's `mcp.session` span under the request's HTTP span, with an
+    /// `mcp.initialize` span recording what was negotiated.
+    async fn initialize(
+        &self,
+        request: InitializeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<InitializeResult, McpError> {
+        let session = tracing::info_span!(
+            parent: None,
+            "mcp.session",
+            session.id = tracing::field::Empty,
+            session.idle_timeout_secs = tracing::field::Empty,
+        );
+        let parent = context
+            .extensions
+            .get::<Parts>()
+            .and_then(|parts| parts.extensions.get::<TraceParentContext>())
+            .map(|TraceParentContext(parent)| parent)
+            .or(self.trace_parent.as_ref());
+        if let Some(parent) = parent {
+            let _ = session.set_parent(parent.clone());
+        }
+        if let Some(timeout) = self.app.config.keepalive.idle_timeout {
+            session.record("session.idle_timeout_secs", timeout.as_secs());
+        }
+        client_capabilities::record_negotiated(&session, &request);
+        enrich_span(
+            &session,
+            &RequestAttribution::from_request_context(&context),
+        );
+
+        let span = tracing::info_span!(parent: &session, "mcp.initialize");
+        client_capabilities::record_negotiated(&span, &request);
+        span.in_scope(|| {
+            info!(
+                client = %request.client_info.name,
+                support = ?ClientSupport::from_params(&request),
+                "Client initialized"
+            )
+        });
+        let _ = self.session_span.set(session);
+
+        if context.peer.peer_info().is_none() {
+            context.peer.set_peer_info(request);
+        }
+        Ok(self.get_info())
+    }
+
+    /// Name the session span after the session id, make it the parent of
+    /// rmcp's transport spans, and start keepalive pings and the idle timeout
+    /// under it when configured.
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        let session_id = context
+            .extensions
+            .get::<Parts>()
+            .and_then(|parts| parts.headers.get("mcp-session-id"))
+            .and_then(|value| value.to_str().ok());
+        if let Some(session_id) = session_id {
+            let _ = self.session_id.set(session_id.to_string());
+            if let Some(session) = self.session_span.get() {
+                session.record("session.id", session_id);
+                enrich_span(
+                    session,
+                    &RequestAttribution {
+                        session_id: Some(session_id.to_string()),
+                        ..RequestAttribution::default()
+                    },
+                );
+                span_repair::register_session(
+                    session_id,
+                    session.context().span().span_context().clone(),
+                );
+            }
+            let session = keepalive::Session {
+                peer: context.peer.clone(),
+                id: session_id.to_string(),
+                context: self.session_span.get().map(|session| session.context()),
+            };
+            keepalive::spawn(
+                session.clone(),
+                self.activity.clone(),
+                self.app.config.keepalive.clone(),
+                self.app.keepalive.clone(),
+                self.app.sessions.clone(),
+            );
+            keepalive::spawn_idle_timeout(
+                session,
+                self.activity.clone(),
+                self.app.config.keepalive.clone(),
+                self.log_level.clone(),
+                self.app.keepalive.clone(),
+                self.app.sessions.clone(),
+            );
+        }
+    }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.touch();
+        *self.log_level.lock().expect("log level lock poisoned") = request.level;
+        Ok(())
+    }
+
+    /// Run the call through the middleware chain, turning a panicking tool
+    /// into an `internal_error` response instead of tearing down the session.
+    ///
+    /// Deprecated names are resolved first, so middleware (permissions,
+    /// auditing) only ever sees the current tool names.
+    async fn call_tool(
+        &self,
+        mut request: CallToolRequestParam,
+        mut context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.touch();
+        let alias = tool_aliases::resolve(&request.name);
+        if let Some(alias) = alias {
+            let calls = self.app.deprecations.record(alias);
+            warn!(
+                alias = alias.alias,
+                tool = alias.target,
+                calls,
+                "Deprecated tool name called"
+            );
+            request.name = alias.target.into();
+        }
+
+        // Refused before the middleware, which keys stats and limits by name
+        let Some(route) = self.tool_router.map.get(request.name.as_ref()) else {
+            return Err(McpError::invalid_params(
+                format!("tool '{}' not found", request.name),
+                None,
+            ));
+        };
+        let input_schema = route.attr.input_schema.clone();
+
+        let post_processing = post_processing::Pipeline::from_arguments(
+            &mut request.arguments,
+            Some(input_schema.as_ref()),
+        )?;
+
+        if let Some(session) = self.session_span.get() {
+            context
+                .extensions
+                .insert(crate::trace_utils::CallTraceContext(session.context()));
+        }
+        let call = ToolCall { request, context };
+        let chain = Next::new(self, &self.middleware).run(&call);
+        // Tool spans open under the session span
+        let chain = match self.session_span.get() {
+            Some(session) => chain.instrument(session.clone()).boxed(),
+            None => chain,
+        };
+        let (outcome, trace_id) =
+            feature_flags::scope(crate::trace_utils::scope_tool_trace(async {
+                let outcome = AssertUnwindSafe(chain).catch_unwind().await;
+                (outcome, crate::trace_utils::tool_trace_id())
+            }))
+            .await;
+        let result = match outcome {
+            Ok(result) => result,
+            Err(payload) => {
+                let tool = call.name();
+                let message = crate::trace_utils::panic_message(payload.as_ref());
+                warn!(%tool, panic = message, "Tool panicked");
+                Err(tool_errors::with_code(
+                    McpError::internal_error(format!("tool '{tool}' panicked: {message}"), None),
+                    tool_errors::PANIC,
+                    false,
+                ))
+            }
+        };
+        // Failures of the tool itself go back to the model as results
+        let mut result = match result {
+            Ok(result) => result,
+            Err(error) => match tool_errors::report(&error, trace_id) {
+                Some(mut report) => {
+                    report.request_id =
+                        RequestAttribution::from_request_context(&call.context).request_id;
+                    tool_errors::into_result(report)
+                }
+                None => return Err(error),
+            },
+        };
+        if let Some(alias) = alias {
+            alias.annotate(&mut result);
+        }
+        if !post_processing.is_empty() {
+            let _span = tracing::info_span!(
+                "tool.post_process",
+                tool = %call.name(),
+                stages = %post_processing.names().join(","),
+            )
+            .entered();
+            post_processing.apply(&mut result);
+        }
+        Ok(result)
+    }
+
+    async fn list_tools(
+        &self,
+        request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        self.touch();
+        let mut tools = self.tool_router.list_all();
+        tools.iter_mut().for_each(post_processing::advertise);
+        if !ClientSupport::of(&context.peer).sampling {
+            tools.retain(|tool| tool.name != "summarize_weather");
+        }
+        for middleware in &self.middleware {
+            middleware.filter_tools(&mut tools, &context)?;
+        }
+        tool_pagination::page(
+            tools,
+            request
+                .as_ref()
+                .and_then(|request| request.cursor.as_deref()),
+            tool_pagination::prefix(&context.meta)?,
+            self.app.config.tools_page_size,
+        )
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        self.touch();
+        if !self.app.config.resources.enabled {
+            return Ok(ListResourcesResult::with_all_items(Vec::new()));
+        }
+        let mut stats = RawResource::new(usage_stats::STATS_URI, "Server statistics");
+        stats.description = Some("Calls, errors and latency per tool since startup".to_string());
+        stats.mime_type = Some("application/json".to_string());
+
+        let mut build = RawResource::new(build_info::BUILD_URI, "Build info");
+        build.description = Some(
+            "Version, git commit, build time, compiler and features of the server".to_string(),
+        );
+        build.mime_type = Some("application/json".to_string());
+
+        let mut resources = vec![stats.no_annotation(), build.no_annotation()];
+        if self.app.slo.is_some() {
+            let mut slo = RawResource::new(slo::SLO_URI, "Tool latency SLOs");
+            slo.description =
+                Some("Compliance and burn rate of each tool's latency objective".to_string());
+            slo.mime_type = Some("application/json".to_string());
+            resources.push(slo.no_annotation());
+        }
+        resources.extend(self.app.watches.resources(&self.session_key()));
+        resources.extend(self.resources.list());
+        Ok(ListResourcesResult::with_all_items(resources))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        self.touch();
+        if !self.app.config.resources.enabled {
+            return Err(McpError::resource_not_found(
+                "resources are disabled (MCP_RESOURCES=false)",
+                None,
+            ));
+        }
+        if request.uri == usage_stats::STATS_URI {
+            let stats = self.app.usage.snapshot(
+                &self.app.deprecations,
+                &self.app.keepalive,
+                self.app.concurrency.as_deref(),
+            );
+            let text = serde_json::to_string_pretty(&stats)
+                .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+            return Ok(ReadResourceResult {
+                contents: vec![ResourceContents::TextResourceContents {
+                    uri: request.uri,
+                    mime_type: Some("application/json".to_string()),
+                    text,
+                    meta: None,
+                }],
+            });
+        }
+        if request.uri == build_info::BUILD_URI {
+            let text = serde_json::to_string_pretty(build_info::build_info())
+                .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+            return Ok(ReadResourceResult {
+                contents: vec![ResourceContents::TextResourceContents {
+                    uri: request.uri,
+                    mime_type: Some("application/json".to_string()),
+                    text,
+                    meta: None,
+                }],
+            });
+        }
+        if let (slo::SLO_URI, Some(slo)) = (request.uri.as_str(), &self.app.slo) {
+            let text = serde_json::to_string_pretty(&slo.snapshot())
+                .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+            return Ok(ReadResourceResult {
+                contents: vec![ResourceContents::TextResourceContents {
+                    uri: request.uri,
+                    mime_type: Some("application/json".to_string()),
+                    text,
+                    meta: None,
+                }],
+            });
+        }
+        if let Some(watch) = self.app.watches.get(&self.session_key(), &request.uri) {
+            let text = serde_json::to_string_pretty(&watch)
+                .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+            return Ok(ReadResourceResult {
+                contents: vec![ResourceContents::TextResourceContents {
+                    uri: request.uri,
+                    mime_type: Some("application/json".to_string()),
+                    text,
+                    meta: None,
+                }],
+            });
+        }
+        match self.resources.get(&request.uri) {
+            Some(contents) => Ok(ReadResourceResult {
+                contents: vec![contents],
+            }),
+            None => Err(McpError::resource_not_found(
+                format!("no resource '{}' in this session", request.uri),
+                None,
+            )),
+        }
+    }
+
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities: self.capabilities(),
+            server_info: Implementation {
+                name: "weather-assistant-rust".to_string(),
+                version: "1.0.0".to_string(),
+                title: None,
+                website_url: None,
+                icons: None,
+            },
+            instructions: Some(Self::instructions().to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sea_states_start_at_their_lower_bound() {
+        let cases = [
+            (0.0, SeaState::Calm),
+            (0.1, SeaState::Smooth),
+            (0.49, SeaState::Smooth),
+            (0.5, SeaState::Slight),
+            (1.25, SeaState::Moderate),
+            (2.5, SeaState::Rough),
+            (4.0, SeaState::VeryRough),
+            (6.0, SeaState::High),
+            (9.0, SeaState::VeryHigh),
+            (13.9, SeaState::VeryHigh),
+            (14.0, SeaState::Phenomenal),
+        ];
+        for (metres, expected) in cases {
+            assert_eq!(SeaState::from_wave_height(metres), expected, "{metres} m");
+        }
+    }
+
+    #[test]
+    fn compass_directions_round_to_the_nearest_point() {
+        let cases = [
+            (0.0, CompassDirection::N),
+            (22.4, CompassDirection::N),
+            (22.5, CompassDirection::NE),
+            (90.0, CompassDirection::E),
+            (180.0, CompassDirection::S),
+            (292.5, CompassDirection::NW),
+            (337.6, CompassDirection::N),
+            (360.0, CompassDirection::N),
+            (-45.0, CompassDirection::NW),
+            (765.0, CompassDirection::NE),
+        ];
+        for (degrees, expected) in cases {
+            assert_eq!(
+                CompassDirection::from_degrees(degrees),
+                expected,
+                "{degrees}°"
+            );
+        }
+    }
+
+    #[test]
+    fn snow_conditions_check_depth_then_fresh_snow_then_temperature() {
+        let cases = [
+            (0.9, 20.0, -5.0, SnowConditions::NoSnow),
+            (1.0, 10.0, 5.0, SnowConditions::Powder),
+            (50.0, 9.9, 1.1, SnowConditions::Spring),
+            (50.0, 9.9, 1.0, SnowConditions::Packed),
+            (50.0, 0.0, -10.0, SnowConditions::Packed),
+        ];
+        for (base, fresh, temperature, expected) in cases {
+            assert_eq!(
+                SnowConditions::classify(base, fresh, temperature),
+                expected,
+                "{base} cm base, {fresh} cm fresh, {temperature}°C"
+            );
         }
     }
 }
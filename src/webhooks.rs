@@ -0,0 +1,643 @@
+//! Weather alerts pushed to URLs registered with the `register_webhook` tool.
+//!
+//! The [`refresh`](crate::refresh) job looks up the current weather of every
+//! registered location. When a location enters an alert (a threshold
+//! crossed, or storm or snow conditions), each webhook for it receives one
+//! signed JSON `POST`. The alert fires again only after it has cleared.
+//!
+//! Each delivery is the root of its own trace and links to the refresh that
+//! triggered it.
+
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use opentelemetry::trace::TraceContextExt;
+use rmcp::{schemars, ErrorData as McpError};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tracing::{field::Empty, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::app_state::AppState;
+use crate::config::WebhookConfig;
+use crate::providers::{check_location, normalize_location};
+use crate::weather_tools::Weather;
+
+/// `sha256=<hex HMAC>` of `<timestamp>.<body>`, keyed with `WEBHOOK_SIGNING_SECRET`.
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+/// Unix time in seconds at which the delivery was signed.
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+
+/// Most webhooks registered at once, across all sessions.
+pub const MAX_WEBHOOKS: usize = 50;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A URL notified of alerts for one location.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Webhook {
+    /// Identifier sent with every delivery
+    pub id: String,
+    pub url: String,
+    pub location: String,
+    /// Session that registered the webhook; only it may remove it.
+    #[serde(skip)]
+    owner: String,
+}
+
+/// What a delivery reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// Temperature at or above `WEBHOOK_HEAT_CELSIUS`.
+    Heat,
+    /// Temperature at or below `WEBHOOK_FROST_CELSIUS`.
+    Frost,
+    /// Wind at or above `WEBHOOK_WIND_KMH`.
+    Wind,
+    Storm,
+    Snow,
+}
+
+impl AlertKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Heat => "heat",
+            Self::Frost => "frost",
+            Self::Wind => "wind",
+            Self::Storm => "storm",
+            Self::Snow => "snow",
+        }
+    }
+}
+
+/// Alerts `weather` (metric, as providers return it) is in, with a message each.
+pub fn alerts(weather: &Weather, config: &WebhookConfig) -> Vec<(AlertKind, String)> {
+    let mut alerts = Vec::new();
+    if weather.temperature >= config.heat_celsius {
+        alerts.push((
+            AlertKind::Heat,
+            format!(
+                "Temperature {} °C is at or above {} °C",
+                weather.temperature, config.heat_celsius
+            ),
+        ));
+    }
+    if weather.temperature <= config.frost_celsius {
+        alerts.push((
+            AlertKind::Frost,
+            format!(
+                "Temperature {} °C is at or below {} °C",
+                weather.temperature, config.frost_celsius
+            ),
+        ));
+    }
+    if weather.wind_speed >= config.wind_kmh {
+        alerts.push((
+            AlertKind::Wind,
+            format!(
+                "Wind {} km/h is at or above {} km/h",
+                weather.wind_speed, config.wind_kmh
+            ),
+        ));
+    }
+    match weather.condition.as_str() {
+        "Stormy" => alerts.push((AlertKind::Storm, "Storm conditions".to_string())),
+        "Snowy" => alerts.push((AlertKind::Snow, "Snowfall".to_string())),
+        _ => {}
+    }
+    alerts
+}
+
+/// Body of a delivery.
+#[derive(Debug, Serialize)]
+struct AlertPayload<'a> {
+    webhook_id: &'a str,
+    alert: AlertKind,
+    message: &'a str,
+    location: &'a str,
+    weather: &'a Weather,
+    /// When the alert was detected, RFC 3339 in UTC.
+    timestamp: &'a str,
+}
+
+/// Registered webhooks, shared by every session, with the alerts each has
+/// already been sent.
+#[derive(Clone, Default)]
+pub struct WebhookRegistry {
+    inner: Arc<Mutex<Registry>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    webhooks: Vec<Webhook>,
+    /// Alerts delivered to each webhook that have not cleared since.
+    active: HashMap<String, HashSet<AlertKind>>,
+}
+
+impl WebhookRegistry {
+    /// Register `url` for alerts at `location` on behalf of the session
+    /// `owner`; registering the same pair again returns the existing webhook.
+    /// URLs resolving to loopback, private or link-local addresses are
+    /// refused unless their host is in `WEBHOOK_ALLOWED_HOSTS`.
+    pub async fn register(
+        &self,
+        owner: &str,
+        url: &str,
+        location: &str,
+        config: &WebhookConfig,
+    ) -> Result<Webhook, McpError> {
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|error| McpError::invalid_params(format!("invalid url: {error}"), None))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(McpError::invalid_params("url must be http or https", None));
+        }
+        let location = location.trim();
+        if location.is_empty() {
+            return Err(McpError::invalid_params("location must not be empty", None));
+        }
+        check_location(location)?;
+        check_destination(&parsed, &config.allowed_hosts)
+            .await
+            .map_err(|error| McpError::invalid_params(error, None))?;
+
+        let key = normalize_location(location);
+        let mut registry = self.inner.lock().expect("webhook lock poisoned");
+        if let Some(existing) = registry.webhooks.iter().find(|webhook| {
+            webhook.owner == owner
+                && webhook.url == url
+                && normalize_location(&webhook.location) == key
+        }) {
+            return Ok(existing.clone());
+        }
+        if registry.webhooks.len() >= MAX_WEBHOOKS {
+            return Err(McpError::invalid_params(
+                format!("at most {MAX_WEBHOOKS} webhooks can be registered"),
+                None,
+            ));
+        }
+        let webhook = Webhook {
+            id: format!("wh_{:016x}", rand::random::<u64>()),
+            url: url.to_string(),
+            location: location.to_string(),
+            owner: owner.to_string(),
+        };
+        registry.webhooks.push(webhook.clone());
+        Ok(webhook)
+    }
+
+    /// Remove the webhooks `owner` registered for `url`, only the one for
+    /// `location` when given. Returns the removed webhooks.
+    pub fn unregister(&self, owner: &str, url: &str, location: Option<&str>) -> Vec<Webhook> {
+        let key = location.map(normalize_location);
+        let mut registry = self.inner.lock().expect("webhook lock poisoned");
+        let (removed, kept) = std::mem::take(&mut registry.webhooks)
+            .into_iter()
+            .partition(|webhook: &Webhook| {
+                webhook.owner == owner
+                    && webhook.url == url
+                    && key
+                        .as_ref()
+                        .is_none_or(|key| normalize_location(&webhook.location) == *key)
+            });
+        registry.webhooks = kept;
+        for webhook in &removed {
+            registry.active.remove(&webhook.id);
+        }
+        removed
+    }
+
+    pub fn list(&self) -> Vec<Webhook> {
+        self.inner
+            .lock()
+            .expect("webhook lock poisoned")
+            .webhooks
+            .clone()
+    }
+
+    /// Normalized locations with at least one webhook.
+    pub fn locations(&self) -> BTreeSet<String> {
+        self.list()
+            .iter()
+            .map(|webhook| normalize_location(&webhook.location))
+            .collect()
+    }
+
+    fn for_location(&self, location: &str) -> Vec<Webhook> {
+        let key = normalize_location(location);
+        self.list()
+            .into_iter()
+            .filter(|webhook| normalize_location(&webhook.location) == key)
+            .collect()
+    }
+
+    /// Record `current` as the webhook's alerts and return those it has not
+    /// been sent yet. Alerts no longer present may fire again later.
+    fn newly_active(&self, webhook_id: &str, current: &[AlertKind]) -> Vec<AlertKind> {
+        let mut registry = self.inner.lock().expect("webhook lock poisoned");
+        // Skip webhooks unregistered while their location was checked
+        if !registry
+            .webhooks
+            .iter()
+            .any(|webhook| webhook.id == webhook_id)
+        {
+            return Vec::new();
+        }
+        let active = registry.active.entry(webhook_id.to_string()).or_default();
+        let new = current
+            .iter()
+            .copied()
+            .filter(|kind| !active.contains(kind))
+            .collect();
+        *active = current.iter().copied().collect();
+        new
+    }
+}
+
+/// Deliver the alerts `weather` is in to the webhooks for `location` that
+/// have not been sent them yet. Runs inside the refresh job's
+/// `weather.refresh` span, which deliveries link to.
+pub async fn notify(state: &AppState, location: &str, weather: &Weather) {
+    let Some(secret) = &state.config.webhooks.signing_secret else {
+        return;
+    };
+    let webhooks = state.webhooks.for_location(location);
+    if webhooks.is_empty() {
+        return;
+    }
+    let alerts = alerts(weather, &state.config.webhooks);
+    let refresh = tracing::Span::current();
+    refresh.record(
+        "alerts",
+        tracing::field::display(
+            alerts
+                .iter()
+                .map(|(kind, _)| kind.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+    );
+    let kinds: Vec<AlertKind> = alerts.iter().map(|(kind, _)| *kind).collect();
+    let detected = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default();
+    let check = refresh.context().span().span_context().clone();
+
+    let mut deliveries = Vec::new();
+    for webhook in &webhooks {
+        let new = state.webhooks.newly_active(&webhook.id, &kinds);
+        for (kind, message) in alerts.iter().filter(|(kind, _)| new.contains(kind)) {
+            let payload = AlertPayload {
+                webhook_id: &webhook.id,
+                alert: *kind,
+                message,
+                location: &webhook.location,
+                weather,
+                timestamp: &detected,
+            };
+            deliveries.push(deliver(
+                state,
+                secret.as_bytes(),
+                webhook,
+                payload,
+                check.clone(),
+            ));
+        }
+    }
+    join_all(deliveries).await;
+}
+
+/// `POST` one signed alert, in a `webhook.delivery` root span linked to
+/// the refresh that found it. The request carries the delivery's
+/// `traceparent`, so the receiver can continue its trace.
+async fn deliver(
+    state: &AppState,
+    secret: &[u8],
+    webhook: &Webhook,
+    payload: AlertPayload<'_>,
+    check: opentelemetry::trace::SpanContext,
+) {
+    let span = tracing::info_span!(
+        parent: None,
+        "webhook.delivery",
+        otel.kind = "producer",
+        webhook.id = %webhook.id,
+        webhook.alert = payload.alert.as_str(),
+        location = %webhook.location,
+        url.full = %webhook.url,
+        http.response.status_code = Empty,
+        otel.status_code = Empty,
+    );
+    if check.is_valid() {
+        span.add_link(check);
+    }
+    async {
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(error) => {
+                tracing::warn!(%error, "Failed to encode webhook payload");
+                return;
+            }
+        };
+        let span = tracing::Span::current();
+        match post(state, secret, &webhook.url, body).await {
+            Ok(status) if status.is_success() => {
+                span.record("http.response.status_code", status.as_u16());
+                tracing::info!(webhook.id = %webhook.id, alert = payload.alert.as_str(), "Webhook delivered");
+            }
+            Ok(status) => {
+                span.record("http.response.status_code", status.as_u16());
+                span.record("otel.status_code", "ERROR");
+                tracing::warn!(webhook.id = %webhook.id, %status, "Webhook delivery rejected");
+            }
+            Err(error) => {
+                span.record("otel.status_code", "ERROR");
+                tracing::warn!(webhook.id = %webhook.id, %error, "Webhook delivery failed");
+            }
+        }
+    }
+    .instrument(span)
+    .await;
+}
+
+/// Sign `body` and `POST` it to `url`, returning the response status.
+///
+/// The destination is checked again, as the host may resolve elsewhere
+/// since registration, and the request goes through a client of its own
+/// that only connects to the addresses just checked and never follows
+/// redirects, so neither DNS rebinding nor a redirect can point the signed
+/// request at an internal address. The request carries the current span's
+/// `traceparent`.
+async fn post(
+    state: &AppState,
+    secret: &[u8],
+    url: &str,
+    body: Vec<u8>,
+) -> Result<reqwest::StatusCode, String> {
+    let url = reqwest::Url::parse(url).map_err(|error| error.to_string())?;
+    let addresses = check_destination(&url, &state.config.webhooks.allowed_hosts).await?;
+    let mut client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .connect_timeout(state.config.http_client.connect_timeout)
+        .timeout(state.config.http_client.request_timeout)
+        .user_agent(concat!("rmcp-demo/", env!("CARGO_PKG_VERSION")));
+    if let Some(domain) = url.domain().filter(|_| !addresses.is_empty()) {
+        client = client.resolve_to_addrs(domain, &addresses);
+    }
+    let client = client.build().map_err(|error| error.to_string())?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut request = client
+        .post(url)
+        .header("content-type", "application/json")
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(
+            SIGNATURE_HEADER,
+            format!("sha256={}", signature(secret, timestamp, &body)),
+        )
+        .body(body)
+        .build()
+        .map_err(|error| error.to_string())?;
+    crate::trace_utils::inject_headers(request.headers_mut());
+    let response = client
+        .execute(request)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(response.status())
+}
+
+/// Refuse `url` when its host resolves to an address that is not public,
+/// unless the host is in `allowed_hosts`. Returns the checked addresses,
+/// none for allowed hosts.
+async fn check_destination(
+    url: &reqwest::Url,
+    allowed_hosts: &[String],
+) -> Result<Vec<SocketAddr>, String> {
+    let host = url.host_str().ok_or("url must have a host")?;
+    if allowed_hosts
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    {
+        return Ok(Vec::new());
+    }
+    let port = url.port_or_known_default().unwrap_or(80);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|error| format!("cannot resolve '{host}': {error}"))?
+        .collect();
+    if let Some(address) = addresses.iter().find(|address| !is_public(address.ip())) {
+        return Err(format!(
+            "url must not point to a loopback, private or link-local address ({})",
+            address.ip()
+        ));
+    }
+    Ok(addresses)
+}
+
+/// Whether `ip` is reachable on the public internet, rather than the
+/// server's own host or network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || first == 0
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (first == 100 && second & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local, fc00::/7, and link-local, fe80::/10
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<body>`.
+pub fn signature(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather_tools::Units;
+
+    fn config(allowed_hosts: &[&str]) -> WebhookConfig {
+        WebhookConfig {
+            signing_secret: Some("secret".to_string()),
+            heat_celsius: 30,
+            frost_celsius: 0,
+            wind_kmh: 50,
+            allowed_hosts: allowed_hosts.iter().map(|host| host.to_string()).collect(),
+        }
+    }
+
+    fn weather(temperature: i32, wind_speed: i32, condition: &str) -> Weather {
+        Weather {
+            location: "Paris".to_string(),
+            temperature,
+            condition: condition.to_string(),
+            humidity: 50,
+            wind_speed,
+            units: Units::Metric,
+        }
+    }
+
+    fn kinds(weather: &Weather) -> Vec<AlertKind> {
+        alerts(weather, &config(&[]))
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .collect()
+    }
+
+    #[test]
+    fn alerts_fire_at_their_thresholds() {
+        assert_eq!(kinds(&weather(29, 49, "Sunny")), []);
+        assert_eq!(kinds(&weather(30, 49, "Sunny")), [AlertKind::Heat]);
+        assert_eq!(kinds(&weather(1, 0, "Cloudy")), []);
+        assert_eq!(kinds(&weather(0, 0, "Cloudy")), [AlertKind::Frost]);
+        assert_eq!(
+            kinds(&weather(10, 50, "Stormy")),
+            [AlertKind::Wind, AlertKind::Storm]
+        );
+        assert_eq!(
+            kinds(&weather(-5, 10, "Snowy")),
+            [AlertKind::Frost, AlertKind::Snow]
+        );
+    }
+
+    #[tokio::test]
+    async fn alerts_are_sent_once_until_they_clear() {
+        let registry = WebhookRegistry::default();
+        let webhook = registry
+            .register(
+                "session",
+                "http://93.184.216.34/hook",
+                "Paris",
+                &config(&[]),
+            )
+            .await
+            .unwrap();
+
+        let heat = [AlertKind::Heat];
+        assert_eq!(registry.newly_active(&webhook.id, &heat), heat);
+        assert_eq!(registry.newly_active(&webhook.id, &heat), []);
+        let both = [AlertKind::Heat, AlertKind::Wind];
+        assert_eq!(registry.newly_active(&webhook.id, &both), [AlertKind::Wind]);
+        assert_eq!(registry.newly_active(&webhook.id, &[]), []);
+        assert_eq!(registry.newly_active(&webhook.id, &heat), heat);
+        assert_eq!(registry.newly_active("wh_unknown", &heat), []);
+    }
+
+    #[tokio::test]
+    async fn webhooks_are_only_removed_by_their_session() {
+        let registry = WebhookRegistry::default();
+        let url = "http://93.184.216.34/hook";
+        registry
+            .register("owner", url, "Paris", &config(&[]))
+            .await
+            .unwrap();
+
+        assert!(registry.unregister("other", url, None).is_empty());
+        assert_eq!(registry.list().len(), 1);
+        assert_eq!(registry.unregister("owner", url, None).len(), 1);
+        assert!(registry.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn internal_addresses_are_refused_unless_allowed() {
+        let registry = WebhookRegistry::default();
+        for url in [
+            "http://127.0.0.1:9000/hook",
+            "http://localhost/hook",
+            "http://10.0.0.5/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[::ffff:192.168.1.1]/hook",
+        ] {
+            let refused = registry
+                .register("session", url, "Paris", &config(&[]))
+                .await;
+            assert!(refused.is_err(), "{url} was accepted");
+        }
+        registry
+            .register(
+                "session",
+                "http://localhost/hook",
+                "Paris",
+                &config(&["localhost"]),
+            )
+            .await
+            .unwrap();
+    }
+
+    /// Serve `router` on a free loopback port.
+    async fn serve(router: axum::Router) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        address
+    }
+
+    #[tokio::test]
+    async fn redirects_are_not_followed() {
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counted = hits.clone();
+        let internal = serve(axum::Router::new().fallback(move || async move {
+            counted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            "ok"
+        }))
+        .await;
+        let target = format!("http://localhost:{}/admin", internal.port());
+        let receiver = serve(
+            axum::Router::new()
+                .fallback(move || async move { axum::response::Redirect::temporary(&target) }),
+        )
+        .await;
+
+        let config = crate::config::Config::from_vars([("WEBHOOK_ALLOWED_HOSTS", "127.0.0.1")]);
+        let state = AppState::new(config.unwrap()).unwrap();
+        let url = format!("http://127.0.0.1:{}/hook", receiver.port());
+        let status = post(&state, b"secret", &url, b"{}".to_vec()).await;
+
+        assert_eq!(status, Ok(reqwest::StatusCode::TEMPORARY_REDIRECT));
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+        // Only allowed hosts may be internal
+        let internal = format!("http://localhost:{}/admin", internal.port());
+        assert!(post(&state, b"secret", &internal, b"{}".to_vec())
+            .await
+            .is_err());
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn signature_is_the_hmac_of_timestamp_and_body() {
+        let expected = "49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686";
+        assert_eq!(signature(b"secret", 1_700_000_000, br#"{"a":1}"#), expected);
+        assert_ne!(signature(b"secret", 1_700_000_001, br#"{"a":1}"#), expected);
+        assert_ne!(signature(b"other", 1_700_000_000, br#"{"a":1}"#), expected);
+    }
+}
@@ -0,0 +1,248 @@
+//! Trace propagation on the MCP endpoint, end to end: each test opens a
+//! session with different trace headers, calls a tool, and checks where the
+//! exported tool span landed.
+//!
+//! The server only speaks W3C Trace Context. Whatever the headers, a tool
+//! span is a child of its session's `mcp.session` span; the session span
+//! continues the caller's trace when `initialize` carried a `traceparent`
+//! and starts a new one otherwise. A gRPC call's span continues the trace of
+//! its `traceparent` metadata.
+
+use axum::body::Body;
+use axum::http::{HeaderMap, Request};
+use axum::Router;
+use http_body_util::BodyExt;
+use once_cell::sync::Lazy;
+use opentelemetry::trace::{SpanId, TraceId, TracerProvider as _};
+use opentelemetry::Value;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use rmcp::transport::streamable_http_server::StreamableHttpService;
+use rmcp_demo::app_state::AppState;
+use rmcp_demo::config::Config;
+use rmcp_demo::weather_tools::WeatherService;
+use rmcp_demo::{grpc, trace_utils};
+use rmcp_otel::TracePropagationLayer;
+use std::time::Duration;
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+/// Spans of every test; each test picks its own by session id.
+static SPANS: Lazy<InMemorySpanExporter> = Lazy::new(|| {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("propagation")));
+    tracing::subscriber::set_global_default(subscriber).expect("no other subscriber");
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    exporter
+});
+
+fn server() -> Router {
+    let state = AppState::new(Config::from_env().expect("default config")).expect("app state");
+    let sessions = state.sessions.clone();
+    let service = StreamableHttpService::new(
+        move || Ok(WeatherService::new(state.clone())),
+        sessions,
+        Default::default(),
+    );
+    Router::new()
+        .nest_service("/weather", service)
+        .layer(TracePropagationLayer::new().on_request(trace_utils::attribute_request))
+}
+
+async fn post(router: &Router, headers: &[(&str, &str)], body: &str) -> HeaderMap {
+    let mut request = Request::post("/weather")
+        .header("content-type", "application/json")
+        .header("accept", "application/json, text/event-stream");
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = router
+        .clone()
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{}", response.status());
+    let headers = response.headers().clone();
+    response.into_body().collect().await.unwrap();
+    headers
+}
+
+/// Open a session and call `get_weather` in it, sending `headers` with every
+/// request. Returns the session id.
+async fn call_tool(headers: &[(&str, &str)]) -> String {
+    let router = server();
+    let initialized = post(
+        &router,
+        headers,
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05","capabilities":{},"clientInfo":{"name":"propagation","version":"0"}}}"#,
+    )
+    .await;
+    let session_id = initialized["mcp-session-id"].to_str().unwrap().to_string();
+    let headers: Vec<_> = headers
+        .iter()
+        .copied()
+        .chain([("mcp-session-id", session_id.as_str())])
+        .collect();
+    post(
+        &router,
+        &headers,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+    )
+    .await;
+    post(
+        &router,
+        &headers,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"get_weather","arguments":{"location":"London"}}}"#,
+    )
+    .await;
+    session_id
+}
+
+fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+    span.attributes
+        .iter()
+        .find(|attribute| attribute.key.as_str() == key)
+        .map(|attribute| attribute.value.clone())
+}
+
+/// The session's `mcp.session` and `get_weather` spans. The session span
+/// ends with the session, so its id is read off the `mcp.initialize` child.
+async fn session_spans(session_id: &str) -> (SpanData, SpanData) {
+    let session = Value::from(session_id.to_string());
+    for _ in 0..50 {
+        let spans = SPANS.get_finished_spans().unwrap();
+        let tool = spans.iter().find(|span| {
+            span.name == "get_weather"
+                && attribute(span, "langfuse.session.id").as_ref() == Some(&session)
+        });
+        let initialize = tool.and_then(|tool| {
+            spans.iter().find(|span| {
+                span.name == "mcp.initialize"
+                    && span.span_context.trace_id() == tool.span_context.trace_id()
+            })
+        });
+        if let (Some(initialize), Some(tool)) = (initialize, tool) {
+            return (initialize.clone(), tool.clone());
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("no get_weather span for session {session_id}");
+}
+
+#[tokio::test]
+async fn w3c_traceparent_is_continued() {
+    Lazy::force(&SPANS);
+    let session_id = call_tool(&[("traceparent", TRACEPARENT)]).await;
+    let (initialize, tool) = session_spans(&session_id).await;
+
+    assert_eq!(
+        tool.span_context.trace_id(),
+        TraceId::from_hex(TRACE_ID).unwrap()
+    );
+    assert_eq!(tool.parent_span_id, initialize.parent_span_id);
+
+    // The HTTP spans carry the same attribution as the tool spans
+    let session = Value::from(session_id);
+    assert!(SPANS.get_finished_spans().unwrap().iter().any(|span| {
+        span.name == "POST /weather"
+            && attribute(span, "langfuse.session.id").as_ref() == Some(&session)
+    }));
+}
+
+#[tokio::test]
+async fn b3_headers_are_not_honoured() {
+    Lazy::force(&SPANS);
+    let session_id = call_tool(&[
+        ("x-b3-traceid", TRACE_ID),
+        ("x-b3-spanid", "00f067aa0ba902b7"),
+        ("x-b3-sampled", "1"),
+    ])
+    .await;
+    let (initialize, tool) = session_spans(&session_id).await;
+
+    assert_ne!(
+        tool.span_context.trace_id(),
+        TraceId::from_hex(TRACE_ID).unwrap()
+    );
+    assert_eq!(tool.parent_span_id, initialize.parent_span_id);
+}
+
+#[tokio::test]
+async fn without_headers_the_session_starts_a_trace() {
+    Lazy::force(&SPANS);
+    let session_id = call_tool(&[]).await;
+    let (initialize, tool) = session_spans(&session_id).await;
+
+    assert_ne!(tool.span_context.trace_id(), TraceId::INVALID);
+    assert_ne!(tool.parent_span_id, SpanId::INVALID);
+    assert_eq!(tool.parent_span_id, initialize.parent_span_id);
+}
+
+#[tokio::test]
+async fn grpc_traceparent_is_continued() {
+    use prost::Message;
+
+    Lazy::force(&SPANS);
+    let state = AppState::new(Config::from_env().expect("default config")).expect("app state");
+    let router = grpc::router(state)
+        .layer(TracePropagationLayer::new().on_request(trace_utils::attribute_request));
+
+    let message = grpc::GetWeatherRequest {
+        location: "London".to_string(),
+        lang: String::new(),
+    }
+    .encode_to_vec();
+    // Uncompressed frame: flag byte, big-endian length, message
+    let mut frame = vec![0];
+    frame.extend((message.len() as u32).to_be_bytes());
+    frame.extend(message);
+    let request = Request::post(format!("/{}/GetWeather", grpc::SERVICE))
+        .header("content-type", "application/grpc")
+        .header("te", "trailers")
+        .header("traceparent", TRACEPARENT)
+        .header("x-user-id", "grpc-user")
+        .body(Body::from(frame))
+        .unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    let body = response.into_body().collect().await.unwrap();
+    let status = body
+        .trailers()
+        .and_then(|trailers| trailers.get("grpc-status"))
+        .cloned();
+    assert_eq!(
+        status.as_ref().map(|status| status.as_bytes()),
+        Some(&b"0"[..])
+    );
+    let reply = grpc::WeatherReply::decode(&body.to_bytes()[5..]).unwrap();
+    assert_eq!(reply.location, "London");
+
+    let spans = SPANS.get_finished_spans().unwrap();
+    let call = spans
+        .iter()
+        .find(|span| span.name == "grpc.GetWeather")
+        .expect("no grpc.GetWeather span");
+    assert_eq!(
+        call.span_context.trace_id(),
+        TraceId::from_hex(TRACE_ID).unwrap()
+    );
+    let request = spans
+        .iter()
+        .find(|span| span.span_context.span_id() == call.parent_span_id)
+        .expect("the gRPC span's parent was not exported");
+    assert_eq!(request.name, format!("POST /{}/GetWeather", grpc::SERVICE));
+    assert_eq!(
+        request.parent_span_id,
+        SpanId::from_hex("00f067aa0ba902b7").unwrap()
+    );
+    assert_eq!(
+        attribute(request, "langfuse.user.id"),
+        Some(Value::from("grpc-user"))
+    );
+}
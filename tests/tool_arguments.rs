@@ -0,0 +1,161 @@
+//! Generated arguments for the location and forecast tools, called through
+//! an in-process client against the mock provider: odd unicode, huge
+//! locations, day counts far outside 1-16 and values of the wrong type.
+//!
+//! Whatever the arguments, a call must not panic. It either succeeds with
+//! output matching the tool's schema, fails as a tool error result with a
+//! code, or is refused as an MCP error; and its result stays under
+//! [`MAX_RESULT_BYTES`].
+
+use once_cell::sync::Lazy;
+use proptest::prelude::*;
+use rmcp::model::{CallToolRequestParam, CallToolResult, Tool};
+use rmcp::service::{RunningService, ServiceError};
+use rmcp::{RoleClient, ServiceExt};
+use rmcp_demo::app_state::AppState;
+use rmcp_demo::config::Config;
+use rmcp_demo::providers::{ProviderError, MAX_LOCATION_CHARS};
+use rmcp_demo::weather_tools::WeatherService;
+use serde_json::{json, Map, Value};
+use tokio::runtime::Runtime;
+
+/// Largest serialized result, whatever the input.
+const MAX_RESULT_BYTES: usize = 64 * 1024;
+
+/// Longest generated location; results must not grow with it.
+const LONGEST_LOCATION: usize = 16 * 1024;
+
+struct Server {
+    runtime: Runtime,
+    client: RunningService<RoleClient, ()>,
+    tools: Vec<Tool>,
+}
+
+static SERVER: Lazy<Server> = Lazy::new(|| {
+    let runtime = Runtime::new().unwrap();
+    let client = runtime.block_on(async {
+        let state = AppState::new(Config::from_env().expect("default config")).unwrap();
+        let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            if let Ok(server) = WeatherService::new(state).serve(server_io).await {
+                let _ = server.waiting().await;
+            }
+        });
+        ().serve(client_io).await.unwrap()
+    });
+    Server {
+        runtime,
+        client,
+        tools: WeatherService::tool_definitions(),
+    }
+});
+
+fn call(tool: &str, arguments: Map<String, Value>) -> Result<CallToolResult, ServiceError> {
+    SERVER
+        .runtime
+        .block_on(SERVER.client.call_tool(CallToolRequestParam {
+            name: tool.to_string().into(),
+            arguments: Some(arguments),
+        }))
+}
+
+fn check(tool: &str, outcome: Result<CallToolResult, ServiceError>) -> Result<(), TestCaseError> {
+    let result = match outcome {
+        Ok(result) => result,
+        Err(ServiceError::McpError(error)) => {
+            prop_assert!(!error.message.contains("panicked"), "{}", error.message);
+            return Ok(());
+        }
+        Err(error) => return Err(TestCaseError::fail(format!("{tool}: {error}"))),
+    };
+
+    let size = serde_json::to_vec(&result).unwrap().len();
+    prop_assert!(size <= MAX_RESULT_BYTES, "{tool}: {size} byte result");
+
+    let structured = result.structured_content.as_ref();
+    if result.is_error == Some(true) {
+        let code = structured.and_then(|content| content["error"]["code"].as_str());
+        prop_assert!(code.is_some(), "{tool}: error result without a code");
+        prop_assert_ne!(code, Some("panic"));
+        return Ok(());
+    }
+    let definition = SERVER.tools.iter().find(|t| t.name == tool).unwrap();
+    if let Some(schema) = &definition.output_schema {
+        let validator = jsonschema::validator_for(&Value::Object(schema.as_ref().clone())).unwrap();
+        let content = structured.cloned().unwrap_or_default();
+        if let Err(error) = validator.validate(&content) {
+            return Err(TestCaseError::fail(format!(
+                "{tool}: output does not match the schema at '{}': {error}",
+                error.instance_path
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn location() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "\\PC{0,64}",
+        any::<String>(),
+        ("\\PC{1,4}", 1..LONGEST_LOCATION / 4).prop_map(|(part, times)| part.repeat(times)),
+    ]
+}
+
+fn days() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        (0u32..20).prop_map(Value::from),
+        any::<u32>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<f64>().prop_map(|days| json!(days)),
+        "\\PC{0,8}".prop_map(Value::from),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn get_weather_handles_any_location(location in location()) {
+        let arguments = json!({ "location": location });
+        check("get_weather", call("get_weather", arguments.as_object().unwrap().clone()))?;
+    }
+
+    #[test]
+    fn get_forecast_handles_any_arguments(
+        location in location(),
+        days in days(),
+        lang in proptest::option::of("\\PC{0,8}"),
+        long_range in any::<bool>(),
+    ) {
+        let arguments = json!({
+            "location": location,
+            "days": days,
+            "lang": lang,
+            "long_range": long_range,
+        });
+        check("get_forecast", call("get_forecast", arguments.as_object().unwrap().clone()))?;
+    }
+}
+
+#[test]
+fn overlong_locations_are_refused_on_every_facade() {
+    let location = "x".repeat(MAX_LOCATION_CHARS + 1);
+    for (tool, arguments) in [
+        ("get_weather", json!({ "location": location })),
+        ("get_forecast", json!({ "location": location, "days": 3 })),
+        ("add_favorite", json!({ "location": location })),
+    ] {
+        let result = call(tool, arguments.as_object().unwrap().clone()).unwrap();
+        assert_eq!(result.is_error, Some(true), "{tool} accepted the location");
+        let code = &result.structured_content.unwrap()["error"]["code"];
+        assert_eq!(code, "location_too_long", "{tool}");
+    }
+
+    // REST, gRPC and GraphQL look locations up through the same state
+    let state = AppState::new(Config::from_env().expect("default config")).unwrap();
+    let refused = SERVER.runtime.block_on(state.current_weather(&location));
+    assert!(matches!(refused, Err(ProviderError::LocationTooLong)));
+    let accepted = "x".repeat(MAX_LOCATION_CHARS);
+    let lookup = SERVER.runtime.block_on(state.current_weather(&accepted));
+    assert!(!matches!(lookup, Err(ProviderError::LocationTooLong)));
+}
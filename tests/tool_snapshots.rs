@@ -0,0 +1,134 @@
+//! Golden `CallToolResult`s: each tool is called through an in-process
+//! client against the mock provider seeded with [`SEED`], and its result
+//! compared with the snapshot in `tests/snapshots/`. The configuration is
+//! the default one whatever the environment holds.
+//!
+//! A change in a tool's output, schema or serialization fails here first.
+//! Review it with `cargo insta review`, or accept it with
+//! `INSTA_UPDATE=always cargo test --test tool_snapshots`.
+//!
+//! Dates and times depend on the day the test runs and are redacted, as are
+//! rendered images.
+
+use rmcp::model::CallToolRequestParam;
+use rmcp::ServiceExt;
+use rmcp_demo::app_state::AppState;
+use rmcp_demo::config::Config;
+use rmcp_demo::weather_tools::WeatherService;
+use serde_json::{json, Value};
+
+const SEED: u64 = 42;
+
+const LOCATION: &str = "London";
+
+/// Tools left out, and why.
+const SKIPPED: &[(&str, &str)] = &[
+    (
+        "summarize_weather",
+        "asks the client for a sampling completion",
+    ),
+    ("get_astronomy", "moon phase and day length change daily"),
+    ("get_server_stats", "reports latencies and uptime"),
+    ("register_webhook", "returns a random id"),
+    ("unregister_webhook", "needs a registered webhook's id"),
+    ("create_watch", "returns a random id"),
+    ("list_watches", "lists watches by random id"),
+    ("rate_response", "posts a score to Langfuse"),
+];
+
+/// Fields whose values depend on the current time.
+const TIME_FIELDS: &[&str] = &["date", "sunrise", "sunset"];
+
+/// Dates and timestamps inside other values (URIs, CSV, text).
+const DATE_PATTERN: &str = r"\d{4}-\d{2}-\d{2}(T\d{2}:\d{2}:\d{2}[+-]\d{2}:\d{2})?";
+
+/// Calls in order; later ones see the preferences and favorites set before.
+fn calls() -> Vec<(&'static str, Value)> {
+    vec![
+        ("get_weather", json!({ "location": LOCATION })),
+        ("get_forecast", json!({ "location": LOCATION, "days": 3 })),
+        ("describe_weather", json!({ "location": LOCATION })),
+        ("get_weather_map", json!({ "location": LOCATION })),
+        ("export_history", json!({ "location": LOCATION, "days": 3 })),
+        ("get_forecast_summary", json!({ "location": LOCATION })),
+        ("get_uv_index", json!({ "location": LOCATION, "days": 3 })),
+        (
+            "get_pollen_forecast",
+            json!({ "location": LOCATION, "days": 3 }),
+        ),
+        ("get_marine_conditions", json!({ "location": LOCATION })),
+        ("get_snow_report", json!({ "location": LOCATION })),
+        ("set_preference", json!({ "units": "imperial" })),
+        ("get_preferences", json!({})),
+        ("add_favorite", json!({ "location": LOCATION })),
+        ("list_favorites", json!({})),
+        ("get_favorites_weather", json!({})),
+        ("remove_favorite", json!({ "location": LOCATION })),
+    ]
+}
+
+/// `value` with time-dependent fields and image data redacted, and text
+/// content that is JSON parsed so it is redacted too.
+fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(mut object) if object.get("type") == Some(&json!("image")) => {
+            object.insert("data".to_string(), json!("[image]"));
+            Value::Object(object)
+        }
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        _ if TIME_FIELDS.contains(&key.as_str()) => json!("[time]"),
+                        Value::String(text) if key == "text" => {
+                            match serde_json::from_str::<Value>(&text) {
+                                Ok(parsed @ (Value::Object(_) | Value::Array(_))) => redact(parsed),
+                                _ => Value::String(text),
+                            }
+                        }
+                        value => redact(value),
+                    };
+                    (key, value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        value => value,
+    }
+}
+
+#[tokio::test]
+async fn tool_results_match_their_snapshots() {
+    let config = Config::from_vars([("MOCK_SEED", SEED.to_string())]).expect("default config");
+    let state = AppState::new(config).unwrap();
+    let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        if let Ok(server) = WeatherService::new(state).serve(server_io).await {
+            let _ = server.waiting().await;
+        }
+    });
+    let client = ().serve(client_io).await.unwrap();
+
+    let calls = calls();
+    for tool in WeatherService::tool_definitions() {
+        let covered = calls.iter().any(|(name, _)| tool.name == *name)
+            || SKIPPED.iter().any(|(name, _)| tool.name == *name);
+        assert!(covered, "no snapshot or reason to skip for {}", tool.name);
+    }
+
+    for (tool, arguments) in calls {
+        let result = client
+            .call_tool(CallToolRequestParam {
+                name: tool.into(),
+                arguments: arguments.as_object().cloned(),
+            })
+            .await
+            .unwrap();
+        let result = redact(serde_json::to_value(result).unwrap());
+        insta::with_settings!({ filters => vec![(DATE_PATTERN, "[date]")] }, {
+            insta::assert_json_snapshot!(tool, result);
+        });
+    }
+    let _ = client.cancel().await;
+}
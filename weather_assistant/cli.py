@@ -192,13 +192,19 @@ def main(argv: list[str] | None = None) -> int:
             low = entry.get("low")
             condition = entry.get("condition")
             precip = entry.get("precipitation_chance")
-            print(f"  Day {day}:")
+            date = entry.get("date")
+            sunrise = entry.get("sunrise")
+            sunset = entry.get("sunset")
+            print(f"  Day {day} ({date}):" if date else f"  Day {day}:")
             if high is not None and low is not None:
                 print(f"    Temps: high {high}°C / low {low}°C")
             if condition is not None:
                 print(f"    Condition: {condition}")
             if precip is not None:
                 print(f"    Chance of rain: {precip}%")
+            if sunrise and sunset:
+                # Local times, e.g. 2026-03-29T07:18:00+02:00
+                print(f"    Sun: rises {sunrise[11:16]}, sets {sunset[11:16]}")
 
     return 0
 